@@ -10,7 +10,7 @@ use core::{
     bitcoin::{
         Block, BlockchainStatus, BlockchainStatusPayload, SignedTransactionOutput, Transaction,
     },
-    db::postgres::PgExecutorAddr,
+    db::{postgres::PgExecutorAddr, redis::RedisExecutorAddr},
     payment::{Payment, PaymentPayload},
     payout::Payout,
 };
@@ -21,6 +21,7 @@ pub type ProcessorAddr = Addr<Processor>;
 pub struct Processor {
     pub network: Network,
     pub postgres: PgExecutorAddr,
+    pub redis: RedisExecutorAddr,
 }
 
 impl Actor for Processor {
@@ -51,6 +52,7 @@ impl Handler<ProcessMempoolTransactions> for Processor {
         _: &mut Self::Context,
     ) -> Self::Result {
         let postgres = self.postgres.clone();
+        let redis = self.redis.clone();
 
         let process = stream::iter_ok(pooled_transactions.clone())
             .and_then(move |transaction| {
@@ -126,7 +128,11 @@ impl Handler<ProcessMempoolTransactions> for Processor {
                             _ => (),
                         };
 
-                        Payment::update(payment.id, payment_payload, &postgres).from_err()
+                        let redis = redis.clone();
+
+                        Payment::update(payment.id, payment_payload, &postgres)
+                            .from_err()
+                            .map(move |payment| payment.notify(&redis))
                     })
                     .for_each(move |_| future::ok(()))
             });
@@ -145,6 +151,7 @@ impl Handler<ProcessBlock> for Processor {
     fn handle(&mut self, ProcessBlock(block): ProcessBlock, _: &mut Self::Context) -> Self::Result {
         info!("Processing block: {}", block.height.unwrap());
         let postgres = self.postgres.clone();
+        let redis = self.redis.clone();
         let network = self.network;
 
         let process = stream::iter_ok(block.transactions.clone().unwrap())
@@ -190,6 +197,7 @@ impl Handler<ProcessBlock> for Processor {
                 let block_number = block.height.unwrap();
                 let transactions = block.transactions.unwrap();
                 let _postgres = postgres.clone();
+                let _redis = redis.clone();
 
                 Payment::find_all_by_address(addresses, Crypto::Btc, &postgres)
                     .from_err()
@@ -210,6 +218,9 @@ impl Handler<ProcessBlock> for Processor {
                             + U128::from(payment.confirmations_required)
                             - U128::from(1);
 
+                        let postgres = postgres.clone();
+                        let redis = redis.clone();
+
                         Payout::insert_btc_payout(
                             amount_paid,
                             block_height_required,
@@ -217,7 +228,9 @@ impl Handler<ProcessBlock> for Processor {
                             transaction.to_owned(),
                             &postgres,
                         )
+                        .and_then(move |payout| payout.payment(&postgres))
                         .from_err()
+                        .map(move |payment| payment.notify(&redis))
                     })
                     .for_each(move |_| future::ok(()))
                     .and_then(move |_| {
@@ -228,7 +241,7 @@ impl Handler<ProcessBlock> for Processor {
 
                         BlockchainStatus::update(network, payload, &_postgres).from_err()
                     })
-                    .map(|_| ())
+                    .map(move |status| status.notify(&_redis))
             });
 
         Box::new(process)
@@ -6,11 +6,12 @@ use bitcoin::{
     processor::Processor,
 };
 use blockchain_api_client::bitcoin::BlockchainApiClientAddr;
-use core::db::postgres;
+use core::db::{postgres, redis::RedisExecutorAddr};
 use types::bitcoin::Network;
 
 pub fn run(
     postgres: postgres::PgExecutorAddr,
+    redis: RedisExecutorAddr,
     blockchain_api_client: BlockchainApiClientAddr,
     network: Network,
     skip_missed_blocks: bool,
@@ -19,6 +20,7 @@ pub fn run(
     let block_processor = Arbiter::start(move |_| Processor {
         network,
         postgres: pg,
+        redis,
     });
 
     let _block_processor = block_processor.clone();
@@ -5,7 +5,7 @@ use bigdecimal::BigDecimal;
 use futures::{future, stream, Future, Stream};
 
 use core::{
-    db::postgres::PgExecutorAddr,
+    db::{postgres::PgExecutorAddr, redis::RedisExecutorAddr},
     ethereum::{Block, BlockchainStatus, BlockchainStatusPayload, Transaction},
     payment::{Payment, PaymentPayload},
     payout::Payout,
@@ -18,6 +18,7 @@ pub type ProcessorAddr = Addr<Processor>;
 pub struct Processor {
     pub network: Network,
     pub postgres: PgExecutorAddr,
+    pub redis: RedisExecutorAddr,
 }
 
 impl Actor for Processor {
@@ -36,9 +37,11 @@ impl Handler<ProcessBlock> for Processor {
     fn handle(&mut self, ProcessBlock(block): ProcessBlock, _: &mut Self::Context) -> Self::Result {
         info!("Processing block: {}", block.number.unwrap());
         let postgres = self.postgres.clone();
+        let redis = self.redis.clone();
         let network = self.network;
         let block_number = block.number;
         let _postgres = postgres.clone();
+        let _redis = redis.clone();
 
         let process = stream::iter_ok(block.transactions.clone())
             .filter(|transaction| transaction.to_address.is_some())
@@ -81,6 +84,9 @@ impl Handler<ProcessBlock> for Processor {
                             + U128::from(payment.confirmations_required)
                             - U128::from(1);
 
+                        let postgres = postgres.clone();
+                        let redis = redis.clone();
+
                         Payout::insert_eth_payout(
                             amount_paid,
                             block_height_required,
@@ -88,7 +94,9 @@ impl Handler<ProcessBlock> for Processor {
                             transaction.to_owned(),
                             &postgres,
                         )
+                        .and_then(move |payout| payout.payment(&postgres))
                         .from_err()
+                        .map(move |payment| payment.notify(&redis))
                     })
                     .for_each(move |_| future::ok(()))
                     .and_then(move |_| {
@@ -99,7 +107,7 @@ impl Handler<ProcessBlock> for Processor {
 
                         BlockchainStatus::update(network, payload, &_postgres).from_err()
                     })
-                    .map(|_| ())
+                    .map(move |status| status.notify(&_redis))
             });
 
         Box::new(process)
@@ -119,6 +127,7 @@ impl Handler<ProcessPendingTransactions> for Processor {
         _: &mut Self::Context,
     ) -> Self::Result {
         let postgres = self.postgres.clone();
+        let redis = self.redis.clone();
 
         let process = stream::iter_ok(pending_transactions.clone())
             .filter(|transaction| transaction.to_address.is_some())
@@ -176,7 +185,11 @@ impl Handler<ProcessPendingTransactions> for Processor {
                             _ => (),
                         };
 
-                        Payment::update(payment.id, payment_payload, &postgres).from_err()
+                        let redis = redis.clone();
+
+                        Payment::update(payment.id, payment_payload, &postgres)
+                            .from_err()
+                            .map(move |payment| payment.notify(&redis))
                     })
                     .for_each(move |_| future::ok(()))
             });
@@ -1,7 +1,7 @@
 use actix::prelude::*;
 
 use blockchain_api_client::ethereum::BlockchainApiClientAddr;
-use core::db::postgres;
+use core::db::{postgres, redis::RedisExecutorAddr};
 use ethereum::{
     pb_poller::{Poller as PendingBlocksPoller, StartPolling as StartPollingPendings},
     poller::{Poller, StartPolling},
@@ -11,6 +11,7 @@ use types::ethereum::Network;
 
 pub fn run(
     postgres: postgres::PgExecutorAddr,
+    redis: RedisExecutorAddr,
     blockchain_api_client: BlockchainApiClientAddr,
     network: Network,
     skip_missed_blocks: bool,
@@ -19,6 +20,7 @@ pub fn run(
     let block_processor = Arbiter::start(move |_| Processor {
         network,
         postgres: pg,
+        redis,
     });
 
     let _block_processor = block_processor.clone();
@@ -338,6 +338,86 @@ impl BlockchainApiClient {
             })
         }))
     }
+
+    /// Looks up the unspent outputs paying to `addresses` in the node's UTXO set, without needing
+    /// the addresses to be in a wallet. Returns the outpoints as `(txid, vout, script_pubkey)`,
+    /// the script pubkey in hex.
+    pub fn scan_tx_out_set(
+        &self,
+        addresses: Vec<String>,
+    ) -> Box<Future<Item = Vec<(H256, u32, String)>, Error = Error>> {
+        let descriptors: Vec<String> = addresses
+            .iter()
+            .map(|address| format!("addr({})", address))
+            .collect();
+
+        let req = match client::ClientRequest::post(&self.url)
+            .header("Authorization", format!("{}", self.basic_auth))
+            .content_type("application/json")
+            .timeout(Duration::from_secs(600))
+            .json(json!({
+                "jsonrpc": "1.0",
+                "method": "scantxoutset",
+                "params": ["start", descriptors],
+                "id": "1"
+            })) {
+            Ok(req) => req,
+            Err(e) => return Box::new(err(Error::CustomError(format!("{}", e)))),
+        };
+
+        Box::new(req.send().from_err().and_then(move |resp| {
+            resp.body().limit(4194304).from_err().and_then(move |body| {
+                let body: Value = match serde_json::from_slice(&body) {
+                    Ok(body) => body,
+                    Err(e) => return err(Error::from(e)),
+                };
+
+                if let Some(unspents) = body.get("result").and_then(|r| r.get("unspents")) {
+                    if let Some(unspents) = unspents.as_array() {
+                        let mut outpoints = Vec::new();
+
+                        for unspent in unspents {
+                            let txid = match unspent
+                                .get("txid")
+                                .map(|txid| serde_json::from_value::<H256>(txid.clone()))
+                            {
+                                Some(Ok(txid)) => txid,
+                                Some(Err(e)) => return err(Error::from(e)),
+                                None => return err(Error::EmptyResponseError),
+                            };
+
+                            let vout = match unspent.get("vout").and_then(|vout| vout.as_u64()) {
+                                Some(vout) => vout as u32,
+                                None => return err(Error::EmptyResponseError),
+                            };
+
+                            match unspent
+                                .get("scriptPubKey")
+                                .and_then(|script_pubkey| script_pubkey.as_str())
+                            {
+                                Some(script_pubkey) => {
+                                    outpoints.push((txid, vout, script_pubkey.to_owned()))
+                                }
+                                None => return err(Error::EmptyResponseError),
+                            }
+                        }
+
+                        return ok(outpoints);
+                    }
+                };
+
+                err(Error::CustomError(format!(
+                    "{}",
+                    body.get("error")
+                        .unwrap()
+                        .get("message")
+                        .unwrap()
+                        .as_str()
+                        .unwrap()
+                )))
+            })
+        }))
+    }
 }
 
 #[derive(Message)]
@@ -461,3 +541,19 @@ impl Handler<GetRawMempool> for BlockchainApiClient {
         self.get_raw_mempool()
     }
 }
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<(H256, u32, String)>, Error>")]
+pub struct ScanTxOutSet(pub Vec<String>);
+
+impl Handler<ScanTxOutSet> for BlockchainApiClient {
+    type Result = Box<Future<Item = Vec<(H256, u32, String)>, Error = Error>>;
+
+    fn handle(
+        &mut self,
+        ScanTxOutSet(addresses): ScanTxOutSet,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.scan_tx_out_set(addresses)
+    }
+}
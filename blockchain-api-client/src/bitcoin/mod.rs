@@ -3,6 +3,7 @@ mod transaction;
 
 pub use self::api_client::{
     EstimateSmartFee, GetBlock, GetBlockByNumber, GetBlockCount, GetBlockHash, GetRawMempool,
-    GetRawTransaction, BlockchainApiClient, BlockchainApiClientAddr, SendRawTransaction,
+    GetRawTransaction, BlockchainApiClient, BlockchainApiClientAddr, ScanTxOutSet,
+    SendRawTransaction,
 };
 pub use self::transaction::UnsignedTransaction;
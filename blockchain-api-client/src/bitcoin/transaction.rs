@@ -5,7 +5,7 @@ use rust_base58::FromBase58;
 use rustc_hex::FromHex;
 use secp256k1::{
     key::{PublicKey, SecretKey},
-    Message, Secp256k1, Signature,
+    Message, RecoverableSignature, Secp256k1, Signature,
 };
 
 use core::bitcoin::Transaction;
@@ -17,23 +17,32 @@ pub struct Script(pub Vec<u8>);
 impl Script {
     pub fn p2pkh(to: String) -> Self {
         let decoded = to.from_base58().unwrap();
-        let mut pkh = decoded[1..21].to_vec();
+        Script::p2pkh_from_hash(&decoded[1..21])
+    }
 
+    fn p2pkh_from_hash(pkh: &[u8]) -> Self {
         let mut script = Vec::new();
         script.push(OP_DUP);
         script.push(OP_HASH160);
         script.push(OP_PUSHBYTES_20);
-        script.append(&mut pkh);
+        script.extend_from_slice(pkh);
         script.push(OP_EQUALVERIFY);
         script.push(OP_CHECKSIG);
         Script(script)
     }
 
-    pub fn script_sig(sig: Signature, pkey: PublicKey) -> Self {
-        let secp = Secp256k1::new();
+    /// Version 0 witness program of a 20 byte key hash, i.e. a native segwit output.
+    pub fn is_p2wpkh(&self) -> bool {
+        self.len() == 22 && self[0] == OP_0 && self[1] == OP_PUSHBYTES_20
+    }
 
-        let mut der_sig = sig.serialize_der(&secp);
-        der_sig.push(0x01); //SIGHASH ALL
+    /// The script a P2WPKH input signs in place of its script pubkey, see BIP143.
+    fn p2wpkh_script_code(&self) -> Self {
+        Script::p2pkh_from_hash(&self[2..22])
+    }
+
+    pub fn script_sig(sig: Signature, pkey: PublicKey) -> Self {
+        let der_sig = der_signature(sig);
 
         let mut script = Vec::new();
         script.write_u8(der_sig.len() as u8).unwrap();
@@ -56,6 +65,16 @@ impl Deref for Script {
         &self.0
     }
 }
+
+fn der_signature(sig: Signature) -> Vec<u8> {
+    let secp = Secp256k1::new();
+
+    let mut der_sig = sig.serialize_der(&secp);
+    der_sig.push(0x01); //SIGHASH ALL
+    der_sig
+}
+
+const OP_0: u8 = 0x00;
 const OP_DUP: u8 = 0x76;
 const OP_HASH160: u8 = 0xa9;
 const OP_EQUALVERIFY: u8 = 0x88;
@@ -68,12 +87,30 @@ pub struct OutPoint {
     pub index: u32,
 }
 
+impl OutPoint {
+    fn serialize(&self, stream: &mut Vec<u8>) {
+        let mut hash = self.hash.clone();
+        hash.reverse();
+        stream.extend_from_slice(&hash);
+        stream.write_u32::<LittleEndian>(self.index).unwrap();
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Input {
     pub outpoint: OutPoint,
     pub script_sig: Script,
     pub sequence: u32,
     pub script_witness: Vec<Vec<u8>>,
+    /// Script pubkey and value in satoshi of the output being spent.
+    pub previous_script_pubkey: Script,
+    pub value: u64,
+}
+
+impl Input {
+    pub fn is_segwit(&self) -> bool {
+        self.previous_script_pubkey.is_p2wpkh()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -82,6 +119,14 @@ pub struct Output {
     pub script_pubkey: Script,
 }
 
+impl Output {
+    fn serialize(&self, stream: &mut Vec<u8>) {
+        stream.write_u64::<LittleEndian>(self.value).unwrap();
+        VarInt::from(self.script_pubkey.len()).serialize(stream);
+        stream.extend_from_slice(&self.script_pubkey);
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UnsignedTransaction {
     pub version: i32,
@@ -100,17 +145,26 @@ impl UnsignedTransaction {
         };
 
         for (utxo, index) in inputs {
-            let hex_script = utxo.vout[index as usize].script.hex.clone();
-            let previous_script_pubkey = Script::from_hex(hex_script);
+            let output = &utxo.vout[index as usize];
+            let previous_script_pubkey = Script::from_hex(output.script.hex.clone());
+
+            // Segwit inputs keep an empty script sig, the signature goes in the witness.
+            let script_sig = if previous_script_pubkey.is_p2wpkh() {
+                Script::default()
+            } else {
+                previous_script_pubkey.clone()
+            };
 
             let input = Input {
                 outpoint: OutPoint {
                     hash: utxo.txid,
                     index: index,
                 },
-                script_sig: previous_script_pubkey,
+                script_sig,
                 sequence: 0xFFFFFFFF,
                 script_witness: Vec::new(),
+                previous_script_pubkey,
+                value: (output.value * (100_000_000 as f64)).round() as u64,
             };
 
             tx.inputs.push(input);
@@ -133,11 +187,43 @@ impl UnsignedTransaction {
 
             let secp = Secp256k1::new();
             let signature = secp.sign(&Message::from(hash.0), &skey);
-            self.inputs[idx].script_sig = Script::script_sig(signature, pkey);
+            self.set_signature(idx, signature, pkey);
+        }
+    }
+
+    /// Signs every input with a signature made elsewhere, e.g. by an external signer. `sign` gets
+    /// the signature hash of the input and `public_key` has to be the key it signs with.
+    pub fn sign_with<F, E>(&mut self, public_key: PublicKey, mut sign: F) -> Result<(), E>
+    where
+        F: FnMut(H256) -> Result<RecoverableSignature, E>,
+    {
+        let secp = Secp256k1::new();
+
+        for idx in 0..self.inputs.len() {
+            let hash = self.signature_hash(idx);
+
+            let signature = sign(hash)?.to_standard(&secp);
+            self.set_signature(idx, signature, public_key);
+        }
+
+        Ok(())
+    }
+
+    fn set_signature(&mut self, idx: usize, signature: Signature, public_key: PublicKey) {
+        let input = &mut self.inputs[idx];
+
+        if input.is_segwit() {
+            input.script_witness = vec![der_signature(signature), public_key.serialize().to_vec()];
+        } else {
+            input.script_sig = Script::script_sig(signature, public_key);
         }
     }
 
-    pub fn signature_hash(&self, _: usize) -> H256 {
+    pub fn signature_hash(&self, idx: usize) -> H256 {
+        if self.inputs[idx].is_segwit() {
+            return self.segwit_signature_hash(idx);
+        }
+
         let tx = self.clone();
 
         let mut serialized = Vec::new();
@@ -146,19 +232,64 @@ impl UnsignedTransaction {
         H256::from_data(&serialized)
     }
 
+    /// Signature hash of a P2WPKH input, see BIP143.
+    fn segwit_signature_hash(&self, idx: usize) -> H256 {
+        let input = &self.inputs[idx];
+
+        let mut prevouts = Vec::new();
+        let mut sequences = Vec::new();
+        for input in self.inputs.iter() {
+            input.outpoint.serialize(&mut prevouts);
+            sequences.write_u32::<LittleEndian>(input.sequence).unwrap();
+        }
+
+        let mut outputs = Vec::new();
+        for output in self.outputs.iter() {
+            output.serialize(&mut outputs);
+        }
+
+        let script_code = input.previous_script_pubkey.p2wpkh_script_code();
+
+        let mut serialized = Vec::new();
+        serialized
+            .write_u32::<LittleEndian>(self.version as u32)
+            .unwrap();
+        serialized.extend_from_slice(&H256::from_data(&prevouts));
+        serialized.extend_from_slice(&H256::from_data(&sequences));
+        input.outpoint.serialize(&mut serialized);
+        VarInt::from(script_code.len()).serialize(&mut serialized);
+        serialized.extend_from_slice(&script_code);
+        serialized.write_u64::<LittleEndian>(input.value).unwrap();
+        serialized
+            .write_u32::<LittleEndian>(input.sequence)
+            .unwrap();
+        serialized.extend_from_slice(&H256::from_data(&outputs));
+        serialized
+            .write_u32::<LittleEndian>(self.lock_time)
+            .unwrap();
+        serialized.write_u32::<LittleEndian>(1).unwrap(); // SIGHASH ALL
+        H256::from_data(&serialized)
+    }
+
+    /// Serializes without witnesses, as legacy signature hashes and transaction ids need.
     pub fn serialize(&self, stream: &mut Vec<u8>) {
+        self.serialize_inner(stream, false);
+    }
+
+    fn serialize_inner(&self, stream: &mut Vec<u8>, with_witness: bool) {
         stream
             .write_u32::<LittleEndian>(self.version as u32)
             .unwrap();
 
+        if with_witness {
+            stream.push(0x00); // Marker
+            stream.push(0x01); // Flag
+        }
+
         VarInt::from(self.inputs.len()).serialize(stream);
 
-        for mut input in self.inputs.clone() {
-            input.outpoint.hash.reverse();
-            stream.extend_from_slice(&input.outpoint.hash);
-            stream
-                .write_u32::<LittleEndian>(input.outpoint.index)
-                .unwrap();
+        for input in self.inputs.iter() {
+            input.outpoint.serialize(stream);
 
             let script_length = VarInt::from(input.script_sig.len());
             script_length.serialize(stream);
@@ -169,19 +300,30 @@ impl UnsignedTransaction {
 
         VarInt::from(self.outputs.len()).serialize(stream);
 
-        for output in self.outputs.clone() {
-            stream.write_u64::<LittleEndian>(output.value).unwrap();
-            let script_length = VarInt::from(output.script_pubkey.len());
-            script_length.serialize(stream);
-            stream.extend_from_slice(&output.script_pubkey);
+        for output in self.outputs.iter() {
+            output.serialize(stream);
+        }
+
+        if with_witness {
+            for input in self.inputs.iter() {
+                VarInt::from(input.script_witness.len()).serialize(stream);
+
+                for item in input.script_witness.iter() {
+                    VarInt::from(item.len()).serialize(stream);
+                    stream.extend_from_slice(item);
+                }
+            }
         }
 
         stream.write_u32::<LittleEndian>(self.lock_time).unwrap();
     }
 
+    /// Segwit transactions are serialized with their witnesses, see BIP144.
     pub fn into_raw_transaction(&self) -> Vec<u8> {
+        let with_witness = self.inputs.iter().any(|input| input.is_segwit());
+
         let mut s = Vec::new();
-        self.serialize(&mut s);
+        self.serialize_inner(&mut s, with_witness);
         s
     }
 }
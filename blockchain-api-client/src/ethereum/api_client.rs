@@ -297,12 +297,7 @@ impl BlockchainApiClient {
         }))
     }
 
-    pub fn send_raw_transaction(
-        &self,
-        signed_transaction: SignedTransaction,
-    ) -> Box<Future<Item = H256, Error = Error>> {
-        let rlp = signed_transaction.rlp_encode();
-
+    pub fn send_raw_transaction(&self, rlp: String) -> Box<Future<Item = H256, Error = Error>> {
         let req = match client::ClientRequest::post(&self.url).json(json!({
             "jsonrpc": "2.0",
             "method": "eth_sendRawTransaction",
@@ -437,6 +432,23 @@ impl Handler<SendRawTransaction> for BlockchainApiClient {
         SendRawTransaction(signed_transaction): SendRawTransaction,
         _: &mut Self::Context,
     ) -> Self::Result {
-        self.send_raw_transaction(signed_transaction)
+        self.send_raw_transaction(signed_transaction.rlp_encode())
+    }
+}
+
+/// Broadcasts a transaction signed earlier, given as returned by `SignedTransaction::rlp_encode`.
+#[derive(Message)]
+#[rtype(result = "Result<H256, Error>")]
+pub struct ResendRawTransaction(pub String);
+
+impl Handler<ResendRawTransaction> for BlockchainApiClient {
+    type Result = Box<Future<Item = H256, Error = Error>>;
+
+    fn handle(
+        &mut self,
+        ResendRawTransaction(rlp): ResendRawTransaction,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.send_raw_transaction(rlp)
     }
 }
@@ -4,7 +4,8 @@ mod transaction;
 
 pub use self::api_client::{
     GetBalance, GetBlockByNumber, GetBlockNumber, GetGasPrice, GetPendingBlock,
-    GetTransactionCount, BlockchainApiClient, BlockchainApiClientAddr, SendRawTransaction,
+    GetTransactionCount, BlockchainApiClient, BlockchainApiClientAddr, ResendRawTransaction,
+    SendRawTransaction,
 };
 pub use self::signature::Signature;
 pub use self::transaction::{SignedTransaction, UnsignedTransaction};
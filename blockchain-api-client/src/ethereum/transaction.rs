@@ -1,6 +1,6 @@
 use rlp::RlpStream;
 use rustc_hex::ToHex;
-use secp256k1::{Message, Secp256k1, key::SecretKey};
+use secp256k1::{Message, RecoverableSignature, Secp256k1, key::SecretKey};
 use tiny_keccak::keccak256;
 
 use errors::Error;
@@ -19,7 +19,8 @@ pub struct UnsignedTransaction {
 }
 
 impl UnsignedTransaction {
-    pub fn sign(self, secret_key: SecretKey, chain_id: u64) -> Result<SignedTransaction, Error> {
+    /// The EIP-155 hash to sign.
+    pub fn hash(&self, chain_id: u64) -> H256 {
         let mut stream = RlpStream::new();
 
         stream.begin_list(9);
@@ -33,10 +34,21 @@ impl UnsignedTransaction {
         stream.append(&0u8);
         stream.append(&0u8);
 
-        let hash = H256::from_hash(keccak256(stream.as_raw()));
+        H256::from_hash(keccak256(stream.as_raw()))
+    }
+
+    pub fn sign(self, secret_key: SecretKey, chain_id: u64) -> Result<SignedTransaction, Error> {
+        let hash = self.hash(chain_id);
 
         let secp = Secp256k1::new();
         let s = secp.sign_recoverable(&Message::from_slice(&hash[..])?, &secret_key);
+
+        Ok(self.with_signature(s, chain_id))
+    }
+
+    /// Attaches a signature of `hash(chain_id)` made elsewhere, e.g. by an external signer.
+    pub fn with_signature(self, s: RecoverableSignature, chain_id: u64) -> SignedTransaction {
+        let secp = Secp256k1::new();
         let (rec_id, data) = s.serialize_compact(&secp);
         let mut data_arr = [0; 65];
 
@@ -44,12 +56,12 @@ impl UnsignedTransaction {
         data_arr[64] = rec_id.to_i32() as u8;
         let signature = Signature::new(data_arr);
 
-        Ok(SignedTransaction {
+        SignedTransaction {
             transaction: self,
             v: signature.v() as u64 + (35 + chain_id * 2),
             r: signature.r().into(),
             s: signature.s().into(),
-        })
+        }
     }
 }
 
@@ -63,6 +75,15 @@ pub struct SignedTransaction {
 
 impl SignedTransaction {
     pub fn rlp_encode(&self) -> String {
+        self.rlp().to_hex()
+    }
+
+    /// The hash the transaction is known by once broadcast.
+    pub fn hash(&self) -> H256 {
+        H256::from_hash(keccak256(&self.rlp()))
+    }
+
+    fn rlp(&self) -> Vec<u8> {
         let mut s = RlpStream::new();
         s.begin_list(9);
         s.append(&self.transaction.nonce);
@@ -75,6 +96,6 @@ impl SignedTransaction {
         s.append(&self.r);
         s.append(&self.s);
 
-        s.drain().into_vec().to_hex()
+        s.drain().into_vec()
     }
 }
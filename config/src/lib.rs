@@ -5,16 +5,20 @@ extern crate bigdecimal;
 extern crate currency_api_client;
 extern crate types;
 
+use std::{env, fs, io, net::IpAddr};
+
 use currency_api_client::Api as CurrencyApi;
 use types::{bitcoin::Network as BtcNetwork, ethereum::Network as EthNetwork};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub postgres: String,
+    pub redis: String,
     pub server: ServerConfig,
     pub smtp: SmtpConfig,
     pub bitcoin: Option<BtcConfig>,
     pub ethereum: Option<EthConfig>,
+    pub signer: Option<SignerConfig>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -27,6 +31,73 @@ pub struct ServerConfig {
     pub web_client_url: String,
     pub currency_api: CurrencyApi,
     pub currency_api_key: String,
+    pub master_keys: Vec<MasterKeyConfig>,
+    /// Hours a confirmed payout address change waits before it takes effect, 24 by default.
+    pub payout_address_change_delay: Option<i64>,
+    pub rate_limits: Option<RateLimitsConfig>,
+    /// Addresses of the proxies in front of the server. Only their `X-Forwarded-For` is trusted,
+    /// without any requests are attributed to the address they come from.
+    pub trusted_proxies: Option<Vec<IpAddr>>,
+}
+
+/// A token bucket: `burst` requests at once, then `per_minute` as the bucket refills.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RateLimitConfig {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+/// Limits for the endpoints anyone can call and for second factor checks. Unset ones fall back
+/// to the server's defaults.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RateLimitsConfig {
+    /// Per IP and per account.
+    pub login: Option<RateLimitConfig>,
+    /// Per IP.
+    pub registration: Option<RateLimitConfig>,
+    /// Per IP and per account.
+    pub reset_password: Option<RateLimitConfig>,
+    /// Per IP and per client token, shared with payments for invoices.
+    pub payments: Option<RateLimitConfig>,
+    /// Per IP and per client token.
+    pub invoices: Option<RateLimitConfig>,
+    /// Per IP.
+    pub refresh: Option<RateLimitConfig>,
+    /// Per account, for every check of a second factor, signed in or not.
+    pub two_factor: Option<RateLimitConfig>,
+}
+
+/// A hex encoded 32 byte key read from `path` or from the `env` variable. The highest version is
+/// used for encrypting, older ones are kept around to decrypt until rows are re-wrapped.
+#[derive(Debug, Deserialize, Clone)]
+pub struct MasterKeyConfig {
+    pub version: i32,
+    pub path: Option<String>,
+    pub env: Option<String>,
+}
+
+impl MasterKeyConfig {
+    pub fn read(&self) -> io::Result<String> {
+        match (&self.path, &self.env) {
+            (Some(path), _) => fs::read_to_string(path),
+            (None, Some(name)) => {
+                env::var(name).map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))
+            }
+            (None, None) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "either path or env has to be set",
+            )),
+        }
+    }
+}
+
+/// A signing daemon listening on the Unix socket at `socket`. When set, payouts are signed by the
+/// daemon with the store id as key id, instead of in-process with the store's mnemonic.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SignerConfig {
+    pub socket: String,
+    /// In seconds.
+    pub timeout: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -0,0 +1,133 @@
+//! Envelope encryption for store secrets. Every store gets its own random data key which
+//! encrypts the store's columns. The data key itself is stored wrapped by a master key, so
+//! rotating the master key only means re-wrapping data keys.
+
+use hex;
+use ring::{aead, rand::SecureRandom, rand::SystemRandom};
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "master key {} must be 32 hex encoded bytes", _0)]
+    InvalidMasterKey(i32),
+    #[fail(display = "no master key configured")]
+    NoMasterKey,
+    #[fail(display = "unknown master key version: {}", _0)]
+    UnknownKeyVersion(i32),
+    #[fail(display = "failed to encrypt")]
+    EncryptionFailed,
+    #[fail(display = "failed to decrypt")]
+    DecryptionFailed,
+}
+
+/// The configured master keys. New data keys are always wrapped with the highest version, older
+/// versions are only kept to unwrap data keys that haven't been re-wrapped yet.
+#[derive(Clone)]
+pub struct Keychain {
+    keys: Vec<(i32, Vec<u8>)>,
+}
+
+impl Keychain {
+    /// Takes `(version, hex encoded key)` pairs.
+    pub fn new(keys: Vec<(i32, String)>) -> Result<Self, Error> {
+        let mut decoded = Vec::new();
+
+        for (version, key) in keys {
+            match hex::decode(key.trim()) {
+                Ok(ref key) if key.len() == KEY_LEN => decoded.push((version, key.to_owned())),
+                _ => return Err(Error::InvalidMasterKey(version)),
+            }
+        }
+
+        if decoded.is_empty() {
+            return Err(Error::NoMasterKey);
+        }
+
+        decoded.sort_by_key(|&(version, _)| version);
+
+        Ok(Keychain { keys: decoded })
+    }
+
+    fn current(&self) -> (i32, &[u8]) {
+        let &(version, ref key) = self.keys.last().unwrap();
+
+        (version, key)
+    }
+
+    fn get(&self, version: i32) -> Result<&[u8], Error> {
+        self.keys
+            .iter()
+            .find(|&&(v, _)| v == version)
+            .map(|&(_, ref key)| key.as_slice())
+            .ok_or(Error::UnknownKeyVersion(version))
+    }
+
+    pub fn current_version(&self) -> i32 {
+        self.current().0
+    }
+
+    /// Returns a fresh data key along with its wrapped form and the master key version used.
+    pub fn generate_data_key(&self) -> Result<(Vec<u8>, Vec<u8>, i32), Error> {
+        let mut data_key = vec![0u8; KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut data_key)
+            .map_err(|_| Error::EncryptionFailed)?;
+
+        let (wrapped, version) = self.wrap(&data_key)?;
+
+        Ok((data_key, wrapped, version))
+    }
+
+    pub fn wrap(&self, data_key: &[u8]) -> Result<(Vec<u8>, i32), Error> {
+        let (version, master_key) = self.current();
+
+        Ok((seal(master_key, b"data_key", data_key)?, version))
+    }
+
+    pub fn unwrap(&self, version: i32, wrapped: &[u8]) -> Result<Vec<u8>, Error> {
+        open(self.get(version)?, b"data_key", wrapped)
+    }
+}
+
+/// Encrypts with AES-256-GCM. The output is the random nonce followed by the ciphertext and tag.
+/// `ad` names what's being encrypted so ciphertexts can't be swapped between columns.
+pub fn seal(key: &[u8], ad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let sealing_key =
+        aead::SealingKey::new(&aead::AES_256_GCM, key).map_err(|_| Error::EncryptionFailed)?;
+    let tag_len = aead::AES_256_GCM.tag_len();
+
+    let mut nonce = vec![0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| Error::EncryptionFailed)?;
+
+    let mut in_out = plaintext.to_vec();
+    in_out.extend(vec![0u8; tag_len]);
+
+    let len = aead::seal_in_place(&sealing_key, &nonce, ad, &mut in_out, tag_len)
+        .map_err(|_| Error::EncryptionFailed)?;
+
+    let mut sealed = nonce;
+    sealed.extend_from_slice(&in_out[..len]);
+
+    Ok(sealed)
+}
+
+pub fn open(key: &[u8], ad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    if sealed.len() < NONCE_LEN {
+        return Err(Error::DecryptionFailed);
+    }
+
+    let opening_key =
+        aead::OpeningKey::new(&aead::AES_256_GCM, key).map_err(|_| Error::DecryptionFailed)?;
+
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let mut in_out = ciphertext.to_vec();
+
+    let plaintext = aead::open_in_place(&opening_key, nonce, ad, 0, &mut in_out)
+        .map_err(|_| Error::DecryptionFailed)?;
+
+    Ok(plaintext.to_vec())
+}
@@ -0,0 +1,120 @@
+use actix::prelude::*;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use db::{
+    postgres::{PgExecutor, PooledConnection},
+    Error,
+};
+use models::audit_log::{AuditLog, AuditLogPayload};
+
+pub fn insert(payload: AuditLogPayload, conn: &PooledConnection) -> Result<AuditLog, Error> {
+    use diesel::insert_into;
+    use schema::audit_logs::dsl;
+
+    insert_into(dsl::audit_logs)
+        .values(&payload)
+        .get_result(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_by_store(
+    store_id: Uuid,
+    limit: i64,
+    offset: i64,
+    conn: &PooledConnection,
+) -> Result<Vec<AuditLog>, Error> {
+    use schema::audit_logs::dsl;
+
+    dsl::audit_logs
+        .filter(dsl::store_id.eq(store_id))
+        .order(dsl::created_at.desc())
+        .limit(limit)
+        .offset(offset)
+        .load::<AuditLog>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+/// The user's own entries: changes to their account rather than to a store.
+pub fn find_by_user(
+    user_id: Uuid,
+    limit: i64,
+    offset: i64,
+    conn: &PooledConnection,
+) -> Result<Vec<AuditLog>, Error> {
+    use schema::audit_logs::dsl;
+
+    dsl::audit_logs
+        .filter(dsl::store_id.is_null().and(dsl::subject_id.eq(user_id)))
+        .order(dsl::created_at.desc())
+        .limit(limit)
+        .offset(offset)
+        .load::<AuditLog>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<AuditLog, Error>")]
+pub struct Insert(pub AuditLogPayload);
+
+impl Handler<Insert> for PgExecutor {
+    type Result = Result<AuditLog, Error>;
+
+    fn handle(&mut self, Insert(payload): Insert, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        insert(payload, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<AuditLog>, Error>")]
+pub struct FindByStore {
+    pub store_id: Uuid,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Handler<FindByStore> for PgExecutor {
+    type Result = Result<Vec<AuditLog>, Error>;
+
+    fn handle(
+        &mut self,
+        FindByStore {
+            store_id,
+            limit,
+            offset,
+        }: FindByStore,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_store(store_id, limit, offset, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<AuditLog>, Error>")]
+pub struct FindByUser {
+    pub user_id: Uuid,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Handler<FindByUser> for PgExecutor {
+    type Result = Result<Vec<AuditLog>, Error>;
+
+    fn handle(
+        &mut self,
+        FindByUser {
+            user_id,
+            limit,
+            offset,
+        }: FindByUser,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_user(user_id, limit, offset, &conn)
+    }
+}
@@ -27,15 +27,11 @@ pub fn find_by_id(id: Uuid, conn: &PooledConnection) -> Result<ClientToken, Erro
         .map_err(|e| Error::from(e))
 }
 
-pub fn find_by_token_and_domain(
-    token: Uuid,
-    domain: String,
-    conn: &PooledConnection,
-) -> Result<ClientToken, Error> {
+pub fn find_by_token(token: Uuid, conn: &PooledConnection) -> Result<ClientToken, Error> {
     use schema::client_tokens::dsl;
 
     dsl::client_tokens
-        .filter(dsl::token.eq(token).and(dsl::domain.eq(domain)))
+        .filter(dsl::token.eq(token))
         .first::<ClientToken>(conn)
         .map_err(|e| Error::from(e))
 }
@@ -104,22 +100,15 @@ impl Handler<FindById> for PgExecutor {
 
 #[derive(Message)]
 #[rtype(result = "Result<ClientToken, Error>")]
-pub struct FindByTokenAndDomain {
-    pub token: Uuid,
-    pub domain: String,
-}
+pub struct FindByToken(pub Uuid);
 
-impl Handler<FindByTokenAndDomain> for PgExecutor {
+impl Handler<FindByToken> for PgExecutor {
     type Result = Result<ClientToken, Error>;
 
-    fn handle(
-        &mut self,
-        FindByTokenAndDomain { token, domain }: FindByTokenAndDomain,
-        _: &mut Self::Context,
-    ) -> Self::Result {
+    fn handle(&mut self, FindByToken(token): FindByToken, _: &mut Self::Context) -> Self::Result {
         let conn = &self.get()?;
 
-        find_by_token_and_domain(token, domain, &conn)
+        find_by_token(token, &conn)
     }
 }
 
@@ -0,0 +1,138 @@
+use actix::prelude::*;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use db::{
+    postgres::{PgExecutor, PooledConnection},
+    Error,
+};
+use models::email_template::{EmailTemplate, EmailTemplatePayload};
+use types::NotificationKind;
+
+pub fn upsert(
+    payload: EmailTemplatePayload,
+    conn: &PooledConnection,
+) -> Result<EmailTemplate, Error> {
+    use diesel::{insert_into, pg::upsert::excluded};
+    use schema::email_templates::dsl;
+
+    insert_into(dsl::email_templates)
+        .values(&payload)
+        .on_conflict((dsl::store_id, dsl::kind))
+        .do_update()
+        .set((
+            dsl::subject.eq(excluded(dsl::subject)),
+            dsl::html.eq(excluded(dsl::html)),
+            dsl::text.eq(excluded(dsl::text)),
+            dsl::updated_at.eq(excluded(dsl::updated_at)),
+        ))
+        .get_result(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_by_store(store_id: Uuid, conn: &PooledConnection) -> Result<Vec<EmailTemplate>, Error> {
+    use schema::email_templates::dsl;
+
+    dsl::email_templates
+        .filter(dsl::store_id.eq(store_id))
+        .order(dsl::kind.asc())
+        .load::<EmailTemplate>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_by_store_and_kind(
+    store_id: Uuid,
+    kind: NotificationKind,
+    conn: &PooledConnection,
+) -> Result<Option<EmailTemplate>, Error> {
+    use schema::email_templates::dsl;
+
+    dsl::email_templates
+        .filter(dsl::store_id.eq(store_id).and(dsl::kind.eq(kind)))
+        .first::<EmailTemplate>(conn)
+        .optional()
+        .map_err(|e| Error::from(e))
+}
+
+pub fn delete(
+    store_id: Uuid,
+    kind: NotificationKind,
+    conn: &PooledConnection,
+) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::email_templates::dsl;
+
+    delete(dsl::email_templates.filter(dsl::store_id.eq(store_id).and(dsl::kind.eq(kind))))
+        .execute(conn)
+        .map_err(|e| Error::from(e))
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<EmailTemplate, Error>")]
+pub struct Upsert(pub EmailTemplatePayload);
+
+impl Handler<Upsert> for PgExecutor {
+    type Result = Result<EmailTemplate, Error>;
+
+    fn handle(&mut self, Upsert(payload): Upsert, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        upsert(payload, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<EmailTemplate>, Error>")]
+pub struct FindByStore(pub Uuid);
+
+impl Handler<FindByStore> for PgExecutor {
+    type Result = Result<Vec<EmailTemplate>, Error>;
+
+    fn handle(
+        &mut self,
+        FindByStore(store_id): FindByStore,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_store(store_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Option<EmailTemplate>, Error>")]
+pub struct FindByStoreAndKind {
+    pub store_id: Uuid,
+    pub kind: NotificationKind,
+}
+
+impl Handler<FindByStoreAndKind> for PgExecutor {
+    type Result = Result<Option<EmailTemplate>, Error>;
+
+    fn handle(
+        &mut self,
+        FindByStoreAndKind { store_id, kind }: FindByStoreAndKind,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_store_and_kind(store_id, kind, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, Error>")]
+pub struct Delete {
+    pub store_id: Uuid,
+    pub kind: NotificationKind,
+}
+
+impl Handler<Delete> for PgExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(&mut self, Delete { store_id, kind }: Delete, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        delete(store_id, kind, &conn)
+    }
+}
@@ -1,7 +1,10 @@
+use _redis::RedisError;
 use diesel::result::Error as DieselError;
 use r2d2::Error as PoolError;
 use serde_json::Error as SerdeJsonError;
 
+use crypto::Error as CryptoError;
+
 #[derive(Debug, Fail)]
 pub enum Error {
     #[fail(display = "{}", _0)]
@@ -10,6 +13,10 @@ pub enum Error {
     PoolError(#[cause] PoolError),
     #[fail(display = "{}", _0)]
     SerdeJsonError(#[cause] SerdeJsonError),
+    #[fail(display = "{}", _0)]
+    RedisError(#[cause] RedisError),
+    #[fail(display = "{}", _0)]
+    CryptoError(#[cause] CryptoError),
 }
 
 impl From<DieselError> for Error {
@@ -29,3 +36,15 @@ impl From<SerdeJsonError> for Error {
         Error::SerdeJsonError(e)
     }
 }
+
+impl From<RedisError> for Error {
+    fn from(e: RedisError) -> Error {
+        Error::RedisError(e)
+    }
+}
+
+impl From<CryptoError> for Error {
+    fn from(e: CryptoError) -> Error {
+        Error::CryptoError(e)
+    }
+}
@@ -0,0 +1,173 @@
+use actix::prelude::*;
+use chrono::{prelude::*, Duration};
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde_json::Value;
+use uuid::Uuid;
+
+use db::{
+    postgres::{PgExecutor, PooledConnection},
+    Error,
+};
+use models::idempotency_key::{
+    IdempotencyKey, IdempotencyKeyPayload, IDEMPOTENCY_KEY_HOURS, IDEMPOTENCY_KEY_LEASE_MINUTES,
+};
+
+/// Stores the key unless it was used already. Returns the stored key along with whether the
+/// request got it; keys older than `IDEMPOTENCY_KEY_HOURS` are replaced as if they never existed,
+/// and a key still without a response whose lease ran out is taken over by a request with the
+/// same fingerprint.
+pub fn claim(
+    payload: IdempotencyKeyPayload,
+    conn: &PooledConnection,
+) -> Result<(IdempotencyKey, bool), Error> {
+    use diesel::{delete, insert_into, update};
+    use schema::idempotency_keys::dsl;
+
+    let key = dsl::idempotency_keys.filter(
+        dsl::scope
+            .eq(payload.scope.clone())
+            .and(dsl::key.eq(payload.key.clone())),
+    );
+
+    delete(
+        key.clone()
+            .filter(dsl::created_at.lt(Utc::now() - Duration::hours(IDEMPOTENCY_KEY_HOURS))),
+    )
+    .execute(conn)?;
+
+    match insert_into(dsl::idempotency_keys)
+        .values(&payload)
+        .get_result(conn)
+    {
+        Ok(idempotency_key) => return Ok((idempotency_key, true)),
+        Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => (),
+        Err(e) => return Err(Error::from(e)),
+    }
+
+    let abandoned = key.clone().filter(
+        dsl::fingerprint
+            .eq(payload.fingerprint.clone())
+            .and(dsl::response.is_null())
+            .and(dsl::locked_at.lt(Utc::now() - Duration::minutes(IDEMPOTENCY_KEY_LEASE_MINUTES))),
+    );
+
+    if let Some(idempotency_key) = update(abandoned)
+        .set(dsl::locked_at.eq(payload.locked_at))
+        .get_result::<IdempotencyKey>(conn)
+        .optional()?
+    {
+        return Ok((idempotency_key, true));
+    }
+
+    key.first::<IdempotencyKey>(conn)
+        .map(|idempotency_key| (idempotency_key, false))
+        .map_err(|e| Error::from(e))
+}
+
+pub fn complete(
+    id: Uuid,
+    locked_at: DateTime<Utc>,
+    response: Value,
+    conn: &PooledConnection,
+) -> Result<usize, Error> {
+    use diesel::update;
+    use schema::idempotency_keys::dsl;
+
+    update(dsl::idempotency_keys.filter(dsl::id.eq(id).and(dsl::locked_at.eq(locked_at))))
+        .set(dsl::response.eq(response))
+        .execute(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn delete(id: Uuid, locked_at: DateTime<Utc>, conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::idempotency_keys::dsl;
+
+    delete(dsl::idempotency_keys.filter(dsl::id.eq(id).and(dsl::locked_at.eq(locked_at))))
+        .execute(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn delete_expired(conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::idempotency_keys::dsl;
+
+    delete(
+        dsl::idempotency_keys
+            .filter(dsl::created_at.lt(Utc::now() - Duration::hours(IDEMPOTENCY_KEY_HOURS))),
+    )
+    .execute(conn)
+    .map_err(|e| Error::from(e))
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<(IdempotencyKey, bool), Error>")]
+pub struct Claim(pub IdempotencyKeyPayload);
+
+impl Handler<Claim> for PgExecutor {
+    type Result = Result<(IdempotencyKey, bool), Error>;
+
+    fn handle(&mut self, Claim(payload): Claim, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        claim(payload, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, Error>")]
+pub struct Complete {
+    pub id: Uuid,
+    pub locked_at: DateTime<Utc>,
+    pub response: Value,
+}
+
+impl Handler<Complete> for PgExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(
+        &mut self,
+        Complete {
+            id,
+            locked_at,
+            response,
+        }: Complete,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        complete(id, locked_at, response, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, Error>")]
+pub struct Delete {
+    pub id: Uuid,
+    pub locked_at: DateTime<Utc>,
+}
+
+impl Handler<Delete> for PgExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(&mut self, Delete { id, locked_at }: Delete, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        delete(id, locked_at, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, Error>")]
+pub struct DeleteExpired;
+
+impl Handler<DeleteExpired> for PgExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(&mut self, _: DeleteExpired, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        delete_expired(&conn)
+    }
+}
@@ -0,0 +1,56 @@
+use actix::prelude::*;
+use diesel::prelude::*;
+
+use db::{
+    postgres::{PgExecutor, PooledConnection},
+    Error,
+};
+use models::invoice::{Invoice, InvoicePayload};
+use uuid::Uuid;
+
+pub fn insert(payload: InvoicePayload, conn: &PooledConnection) -> Result<Invoice, Error> {
+    use diesel::insert_into;
+    use schema::invoices::dsl;
+
+    insert_into(dsl::invoices)
+        .values(&payload)
+        .get_result(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_by_id(id: Uuid, conn: &PooledConnection) -> Result<Invoice, Error> {
+    use schema::invoices::dsl;
+
+    dsl::invoices
+        .filter(dsl::id.eq(id))
+        .first::<Invoice>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Invoice, Error>")]
+pub struct Insert(pub InvoicePayload);
+
+impl Handler<Insert> for PgExecutor {
+    type Result = Result<Invoice, Error>;
+
+    fn handle(&mut self, Insert(payload): Insert, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        insert(payload, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Invoice, Error>")]
+pub struct FindById(pub Uuid);
+
+impl Handler<FindById> for PgExecutor {
+    type Result = Result<Invoice, Error>;
+
+    fn handle(&mut self, FindById(id): FindById, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_id(id, &conn)
+    }
+}
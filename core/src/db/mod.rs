@@ -4,10 +4,20 @@ pub use self::errors::Error;
 pub mod postgres;
 pub mod redis;
 
+pub mod audit_logs;
 pub mod client_tokens;
+pub mod email_templates;
 pub mod ethereum;
+pub mod idempotency_keys;
+pub mod invoices;
+pub mod notifications;
 pub mod payments;
+pub mod payout_address_changes;
+pub mod payout_legs;
 pub mod payouts;
+pub mod retired_store_keys;
+pub mod sessions;
+pub mod store_memberships;
 pub mod stores;
 pub mod bitcoin;
 pub mod users;
@@ -0,0 +1,139 @@
+use actix::prelude::*;
+use chrono::{prelude::*, Duration};
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use uuid::Uuid;
+
+use db::{
+    postgres::{PgExecutor, PooledConnection},
+    Error,
+};
+use models::notification::{Notification, NotificationPayload, NOTIFICATION_DAYS};
+use types::NotificationKind;
+
+/// Records the notification unless it was sent already, in which case `None` is returned.
+pub fn claim(
+    payload: NotificationPayload,
+    conn: &PooledConnection,
+) -> Result<Option<Notification>, Error> {
+    use diesel::insert_into;
+    use schema::notifications::dsl;
+
+    match insert_into(dsl::notifications)
+        .values(&payload)
+        .get_result(conn)
+    {
+        Ok(notification) => Ok(Some(notification)),
+        Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => Ok(None),
+        Err(e) => Err(Error::from(e)),
+    }
+}
+
+pub fn delete(id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::notifications::dsl;
+
+    delete(dsl::notifications.filter(dsl::id.eq(id)))
+        .execute(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_notified(
+    kind: NotificationKind,
+    subject_ids: Vec<Uuid>,
+    period: Option<NaiveDate>,
+    conn: &PooledConnection,
+) -> Result<Vec<Uuid>, Error> {
+    use schema::notifications::dsl;
+
+    let query = dsl::notifications
+        .select(dsl::subject_id)
+        .filter(dsl::kind.eq(kind).and(dsl::subject_id.eq_any(subject_ids)));
+
+    let notified = match period {
+        Some(period) => query.filter(dsl::period.eq(period)).load::<Uuid>(conn),
+        None => query.filter(dsl::period.is_null()).load::<Uuid>(conn),
+    };
+
+    notified.map_err(|e| Error::from(e))
+}
+
+pub fn delete_expired(conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::notifications::dsl;
+
+    delete(
+        dsl::notifications
+            .filter(dsl::created_at.lt(Utc::now() - Duration::days(NOTIFICATION_DAYS))),
+    )
+    .execute(conn)
+    .map_err(|e| Error::from(e))
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Option<Notification>, Error>")]
+pub struct Claim(pub NotificationPayload);
+
+impl Handler<Claim> for PgExecutor {
+    type Result = Result<Option<Notification>, Error>;
+
+    fn handle(&mut self, Claim(payload): Claim, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        claim(payload, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, Error>")]
+pub struct Delete(pub Uuid);
+
+impl Handler<Delete> for PgExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(&mut self, Delete(id): Delete, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        delete(id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Uuid>, Error>")]
+pub struct FindNotified {
+    pub kind: NotificationKind,
+    pub subject_ids: Vec<Uuid>,
+    pub period: Option<NaiveDate>,
+}
+
+impl Handler<FindNotified> for PgExecutor {
+    type Result = Result<Vec<Uuid>, Error>;
+
+    fn handle(
+        &mut self,
+        FindNotified {
+            kind,
+            subject_ids,
+            period,
+        }: FindNotified,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_notified(kind, subject_ids, period, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, Error>")]
+pub struct DeleteExpired;
+
+impl Handler<DeleteExpired> for PgExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(&mut self, _: DeleteExpired, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        delete_expired(&conn)
+    }
+}
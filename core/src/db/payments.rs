@@ -1,14 +1,15 @@
 use actix::prelude::*;
+use chrono::prelude::*;
 use diesel::prelude::*;
 
 use db::{
     postgres::{PgExecutor, PooledConnection},
     Error,
 };
-use models::payment::{Payment, PaymentPayload};
+use models::payment::{Payment, PaymentExport, PaymentPayload, PaymentStats};
 use uuid::Uuid;
 
-use types::currency::Crypto;
+use types::{currency::Crypto, NotificationKind, PaymentStatus};
 
 pub fn insert(payload: PaymentPayload, conn: &PooledConnection) -> Result<Payment, Error> {
     use diesel::insert_into;
@@ -57,6 +58,221 @@ pub fn find_all_by_addresses(
         .map_err(|e| Error::from(e))
 }
 
+pub fn find_all_by_invoice(
+    invoice_id: Uuid,
+    conn: &PooledConnection,
+) -> Result<Vec<Payment>, Error> {
+    use schema::payments::dsl;
+
+    dsl::payments
+        .filter(dsl::invoice_id.eq(invoice_id))
+        .order(dsl::created_at.asc())
+        .load::<Payment>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_all_by_store(store_id: Uuid, conn: &PooledConnection) -> Result<Vec<Payment>, Error> {
+    use schema::payments::dsl;
+
+    dsl::payments
+        .filter(dsl::store_id.eq(store_id))
+        .order(dsl::created_at.asc())
+        .load::<Payment>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_by_store(
+    store_id: Uuid,
+    limit: i64,
+    offset: i64,
+    conn: &PooledConnection,
+) -> Result<Vec<Payment>, Error> {
+    use schema::payments::dsl;
+
+    dsl::payments
+        .filter(dsl::store_id.eq(store_id))
+        .order(dsl::created_at.desc())
+        .limit(limit)
+        .offset(offset)
+        .load::<Payment>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+/// Confirmed payments since `since` with a customer email that didn't get a receipt yet.
+pub fn find_receipts_due(
+    since: DateTime<Utc>,
+    conn: &PooledConnection,
+) -> Result<Vec<Payment>, Error> {
+    use diesel::{
+        sql_query,
+        sql_types::{Text, Timestamptz},
+    };
+
+    sql_query(
+        "SELECT payments.* FROM payments
+        WHERE payments.status IN ('confirmed', 'completed')
+            AND payments.paid_at >= $1
+            AND payments.customer_email IS NOT NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM notifications
+                WHERE notifications.kind = $2 AND notifications.subject_id = payments.id
+                    AND notifications.period IS NULL
+            )
+        ORDER BY payments.paid_at",
+    )
+    .bind::<Timestamptz, _>(since)
+    .bind::<Text, _>(NotificationKind::Receipt)
+    .load::<Payment>(conn)
+    .map_err(|e| Error::from(e))
+}
+
+/// Confirmed payments since `since` of at least their store's large payment threshold, in its
+/// fiat, that the owner wasn't told about yet.
+pub fn find_large_due(
+    since: DateTime<Utc>,
+    conn: &PooledConnection,
+) -> Result<Vec<Payment>, Error> {
+    use diesel::{
+        sql_query,
+        sql_types::{Text, Timestamptz},
+    };
+
+    sql_query(
+        "SELECT payments.* FROM payments
+        JOIN stores ON stores.id = payments.store_id AND stores.deleted_at IS NULL
+        WHERE payments.status IN ('confirmed', 'completed')
+            AND payments.paid_at >= $1
+            AND payments.fiat = stores.large_payment_threshold_fiat
+            AND payments.price >= stores.large_payment_threshold
+            AND NOT EXISTS (
+                SELECT 1 FROM notifications
+                WHERE notifications.kind = $2 AND notifications.subject_id = payments.id
+                    AND notifications.period IS NULL
+            )
+        ORDER BY payments.paid_at",
+    )
+    .bind::<Timestamptz, _>(since)
+    .bind::<Text, _>(NotificationKind::LargePayment)
+    .load::<Payment>(conn)
+    .map_err(|e| Error::from(e))
+}
+
+/// Payments since `since` that fell short of their charge and the owner wasn't told about yet.
+pub fn find_insufficient_amounts_due(
+    since: DateTime<Utc>,
+    conn: &PooledConnection,
+) -> Result<Vec<Payment>, Error> {
+    use diesel::{
+        sql_query,
+        sql_types::{Text, Timestamptz},
+    };
+
+    sql_query(
+        "SELECT payments.* FROM payments
+        WHERE payments.status = 'insufficient_amount'
+            AND payments.paid_at >= $1
+            AND NOT EXISTS (
+                SELECT 1 FROM notifications
+                WHERE notifications.kind = $2 AND notifications.subject_id = payments.id
+                    AND notifications.period IS NULL
+            )
+        ORDER BY payments.paid_at",
+    )
+    .bind::<Timestamptz, _>(since)
+    .bind::<Text, _>(NotificationKind::InsufficientAmount)
+    .load::<Payment>(conn)
+    .map_err(|e| Error::from(e))
+}
+
+/// Payments of the store created in `from..to`, summed up per `bucket` (a `date_trunc` field,
+/// in UTC), status and currencies. `fiat_paid` is what was paid converted at the payment's own
+/// rate.
+pub fn stats(
+    store_id: Uuid,
+    bucket: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    conn: &PooledConnection,
+) -> Result<Vec<PaymentStats>, Error> {
+    use diesel::{
+        sql_query,
+        sql_types::{Text, Timestamptz, Uuid as SqlUuid},
+    };
+
+    sql_query(
+        "SELECT date_trunc($1, created_at AT TIME ZONE 'UTC') AS bucket, status, crypto, fiat,
+            COUNT(*) AS count,
+            COALESCE(SUM(amount_paid), 0) AS amount_paid,
+            COALESCE(SUM(amount_paid * price / NULLIF(charge, 0)), 0) AS fiat_paid,
+            COUNT(paid_at) AS paid_count,
+            COALESCE(SUM(EXTRACT(EPOCH FROM paid_at - created_at)), 0)::FLOAT8 AS seconds_to_paid
+        FROM payments
+        WHERE store_id = $2 AND created_at >= $3 AND created_at < $4
+        GROUP BY 1, 2, 3, 4
+        ORDER BY 1",
+    )
+    .bind::<Text, _>(bucket)
+    .bind::<SqlUuid, _>(store_id)
+    .bind::<Timestamptz, _>(from)
+    .bind::<Timestamptz, _>(to)
+    .load::<PaymentStats>(conn)
+    .map_err(|e| Error::from(e))
+}
+
+/// A page of the store's payments created before `to`, along with their payouts, oldest first.
+/// Pages are keyed by the creation time and id of the last payment of the previous page, so the
+/// export can go through any number of payments a page at a time.
+pub fn find_for_export(
+    store_id: Uuid,
+    status: Option<PaymentStatus>,
+    to: DateTime<Utc>,
+    after: (DateTime<Utc>, Uuid),
+    limit: i64,
+    conn: &PooledConnection,
+) -> Result<Vec<PaymentExport>, Error> {
+    use diesel::{
+        sql_query,
+        sql_types::{BigInt, Nullable, Text, Timestamptz, Uuid as SqlUuid},
+    };
+
+    // Payout legs are in satoshi or wei, whatever the payment's amount doesn't account for went
+    // to fees.
+    sql_query(
+        "SELECT p.id, p.created_at, p.paid_at, p.status, p.identifier, p.fiat, p.price, p.crypto,
+            p.charge, p.amount_paid, p.transaction_hash,
+            po.status AS payout_status, po.action AS payout_action,
+            po.created_at AS payout_created_at,
+            legs.value / units.unit AS paid_out,
+            p.amount_paid - legs.value / units.unit AS fees,
+            COALESCE(legs.transaction_hashes, po.transaction_hash) AS payout_transaction_hashes
+        FROM payments p
+        CROSS JOIN LATERAL (
+            SELECT CASE p.crypto WHEN 'btc' THEN 1e8 ELSE 1e18 END AS unit
+        ) units
+        LEFT JOIN payouts po ON po.payment_id = p.id
+        LEFT JOIN LATERAL (
+            SELECT SUM(value) AS value,
+                string_agg(transaction_hash, ' ' ORDER BY leg) AS transaction_hashes
+            FROM payout_legs
+            WHERE payout_id = po.id
+        ) legs ON TRUE
+        WHERE p.store_id = $1
+            AND ($2::VARCHAR IS NULL OR p.status = $2)
+            AND p.created_at < $3
+            AND (p.created_at, p.id) > ($4, $5)
+        ORDER BY p.created_at, p.id
+        LIMIT $6",
+    )
+    .bind::<SqlUuid, _>(store_id)
+    .bind::<Nullable<Text>, _>(status)
+    .bind::<Timestamptz, _>(to)
+    .bind::<Timestamptz, _>(after.0)
+    .bind::<SqlUuid, _>(after.1)
+    .bind::<BigInt, _>(limit)
+    .load::<PaymentExport>(conn)
+    .map_err(|e| Error::from(e))
+}
+
 #[derive(Message)]
 #[rtype(result = "Result<Payment, Error>")]
 pub struct Insert(pub PaymentPayload);
@@ -119,3 +335,173 @@ impl Handler<FindAllByAddress> for PgExecutor {
         find_all_by_addresses(addresses, crypto, &conn)
     }
 }
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Payment>, Error>")]
+pub struct FindAllByInvoice(pub Uuid);
+
+impl Handler<FindAllByInvoice> for PgExecutor {
+    type Result = Result<Vec<Payment>, Error>;
+
+    fn handle(
+        &mut self,
+        FindAllByInvoice(invoice_id): FindAllByInvoice,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_all_by_invoice(invoice_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Payment>, Error>")]
+pub struct FindAllByStore(pub Uuid);
+
+impl Handler<FindAllByStore> for PgExecutor {
+    type Result = Result<Vec<Payment>, Error>;
+
+    fn handle(
+        &mut self,
+        FindAllByStore(store_id): FindAllByStore,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_all_by_store(store_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Payment>, Error>")]
+pub struct FindByStore {
+    pub store_id: Uuid,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Handler<FindByStore> for PgExecutor {
+    type Result = Result<Vec<Payment>, Error>;
+
+    fn handle(
+        &mut self,
+        FindByStore {
+            store_id,
+            limit,
+            offset,
+        }: FindByStore,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_store(store_id, limit, offset, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<PaymentStats>, Error>")]
+pub struct Stats {
+    pub store_id: Uuid,
+    pub bucket: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+impl Handler<Stats> for PgExecutor {
+    type Result = Result<Vec<PaymentStats>, Error>;
+
+    fn handle(
+        &mut self,
+        Stats {
+            store_id,
+            bucket,
+            from,
+            to,
+        }: Stats,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        stats(store_id, bucket, from, to, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<PaymentExport>, Error>")]
+pub struct FindForExport {
+    pub store_id: Uuid,
+    pub status: Option<PaymentStatus>,
+    pub to: DateTime<Utc>,
+    pub after: (DateTime<Utc>, Uuid),
+    pub limit: i64,
+}
+
+impl Handler<FindForExport> for PgExecutor {
+    type Result = Result<Vec<PaymentExport>, Error>;
+
+    fn handle(
+        &mut self,
+        FindForExport {
+            store_id,
+            status,
+            to,
+            after,
+            limit,
+        }: FindForExport,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_for_export(store_id, status, to, after, limit, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Payment>, Error>")]
+pub struct FindReceiptsDue(pub DateTime<Utc>);
+
+impl Handler<FindReceiptsDue> for PgExecutor {
+    type Result = Result<Vec<Payment>, Error>;
+
+    fn handle(
+        &mut self,
+        FindReceiptsDue(since): FindReceiptsDue,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_receipts_due(since, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Payment>, Error>")]
+pub struct FindLargeDue(pub DateTime<Utc>);
+
+impl Handler<FindLargeDue> for PgExecutor {
+    type Result = Result<Vec<Payment>, Error>;
+
+    fn handle(&mut self, FindLargeDue(since): FindLargeDue, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_large_due(since, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Payment>, Error>")]
+pub struct FindInsufficientAmountsDue(pub DateTime<Utc>);
+
+impl Handler<FindInsufficientAmountsDue> for PgExecutor {
+    type Result = Result<Vec<Payment>, Error>;
+
+    fn handle(
+        &mut self,
+        FindInsufficientAmountsDue(since): FindInsufficientAmountsDue,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_insufficient_amounts_due(since, &conn)
+    }
+}
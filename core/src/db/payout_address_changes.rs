@@ -0,0 +1,261 @@
+use actix::prelude::*;
+use chrono::{prelude::*, Duration};
+use diesel::prelude::*;
+
+use db::{
+    stores,
+    {
+        postgres::{PgExecutor, PooledConnection},
+        Error,
+    },
+};
+use models::{
+    payout_address_change::{PayoutAddressChange, PayoutAddressChangePayload},
+    store::{Store, StorePayload},
+};
+use uuid::Uuid;
+
+pub fn insert(
+    payload: PayoutAddressChangePayload,
+    conn: &PooledConnection,
+) -> Result<PayoutAddressChange, Error> {
+    use diesel::insert_into;
+    use schema::payout_address_changes::dsl;
+
+    insert_into(dsl::payout_address_changes)
+        .values(&payload)
+        .get_result(conn)
+        .map_err(|e| Error::from(e))
+}
+
+/// Returns the store's change that is still waiting for confirmation or for its cool-down to end.
+pub fn find_pending_by_store(
+    store_id: Uuid,
+    conn: &PooledConnection,
+) -> Result<Option<PayoutAddressChange>, Error> {
+    use schema::payout_address_changes::dsl;
+
+    dsl::payout_address_changes
+        .filter(
+            dsl::store_id
+                .eq(store_id)
+                .and(dsl::applied_at.is_null())
+                .and(dsl::cancelled_at.is_null())
+                .and(
+                    dsl::confirmed_at
+                        .is_not_null()
+                        .or(dsl::confirmation_token_expires_at.gt(Utc::now())),
+                ),
+        )
+        .order(dsl::created_at.desc())
+        .first::<PayoutAddressChange>(conn)
+        .optional()
+        .map_err(|e| Error::from(e))
+}
+
+pub fn cancel_pending_by_store(store_id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::update;
+    use schema::payout_address_changes::dsl;
+
+    update(
+        dsl::payout_address_changes.filter(
+            dsl::store_id
+                .eq(store_id)
+                .and(dsl::applied_at.is_null())
+                .and(dsl::cancelled_at.is_null()),
+        ),
+    )
+    .set(dsl::cancelled_at.eq(Utc::now()))
+    .execute(conn)
+    .map_err(|e| Error::from(e))
+}
+
+/// Confirms the change and starts its cool-down of `delay`.
+pub fn confirm(
+    token: Uuid,
+    delay: Duration,
+    conn: &PooledConnection,
+) -> Result<PayoutAddressChange, Error> {
+    use diesel::update;
+    use schema::payout_address_changes::dsl;
+
+    let now = Utc::now();
+
+    update(
+        dsl::payout_address_changes.filter(
+            dsl::confirmation_token
+                .eq(token)
+                .and(dsl::confirmation_token_expires_at.gt(now))
+                .and(dsl::confirmed_at.is_null())
+                .and(dsl::cancelled_at.is_null()),
+        ),
+    )
+    .set((dsl::confirmed_at.eq(now), dsl::effective_at.eq(now + delay)))
+    .get_result(conn)
+    .map_err(|e| Error::from(e))
+}
+
+pub fn cancel(token: Uuid, conn: &PooledConnection) -> Result<PayoutAddressChange, Error> {
+    use diesel::update;
+    use schema::payout_address_changes::dsl;
+
+    update(
+        dsl::payout_address_changes.filter(
+            dsl::cancellation_token
+                .eq(token)
+                .and(dsl::applied_at.is_null())
+                .and(dsl::cancelled_at.is_null()),
+        ),
+    )
+    .set(dsl::cancelled_at.eq(Utc::now()))
+    .get_result(conn)
+    .map_err(|e| Error::from(e))
+}
+
+/// Returns the confirmed changes whose cool-down is over.
+pub fn find_due(conn: &PooledConnection) -> Result<Vec<PayoutAddressChange>, Error> {
+    use schema::payout_address_changes::dsl;
+
+    dsl::payout_address_changes
+        .filter(
+            dsl::effective_at
+                .le(Utc::now())
+                .and(dsl::applied_at.is_null())
+                .and(dsl::cancelled_at.is_null()),
+        )
+        .load::<PayoutAddressChange>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+/// Writes the new addresses to the store. The change row is locked first so a cancel racing with
+/// this either wins or finds the change already applied.
+pub fn apply(id: Uuid, conn: &PooledConnection) -> Result<(PayoutAddressChange, Store), Error> {
+    use diesel::update;
+    use schema::payout_address_changes::dsl;
+
+    let change = dsl::payout_address_changes
+        .filter(
+            dsl::id
+                .eq(id)
+                .and(dsl::applied_at.is_null())
+                .and(dsl::cancelled_at.is_null()),
+        )
+        .for_update()
+        .first::<PayoutAddressChange>(conn)?;
+
+    let mut payload = StorePayload::new();
+    payload.set_updated_at();
+
+    if let Some(ref eth_payout_addresses) = change.eth_payout_addresses {
+        payload.eth_payout_addresses = Some(Some(eth_payout_addresses.clone()));
+        payload.eth_payout_splits = Some(change.eth_payout_splits.clone());
+    }
+
+    if let Some(ref btc_payout_addresses) = change.btc_payout_addresses {
+        payload.btc_payout_addresses = Some(Some(btc_payout_addresses.clone()));
+        payload.btc_payout_splits = Some(change.btc_payout_splits.clone());
+    }
+
+    let store = stores::update(change.store_id, payload, conn)?;
+
+    let change = update(dsl::payout_address_changes.filter(dsl::id.eq(id)))
+        .set(dsl::applied_at.eq(Utc::now()))
+        .get_result(conn)?;
+
+    Ok((change, store))
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<PayoutAddressChange, Error>")]
+pub struct Insert(pub PayoutAddressChangePayload);
+
+impl Handler<Insert> for PgExecutor {
+    type Result = Result<PayoutAddressChange, Error>;
+
+    fn handle(&mut self, Insert(payload): Insert, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        // A new request supersedes whatever change was still pending.
+        conn.transaction::<_, Error, _>(|| {
+            cancel_pending_by_store(payload.store_id, &conn)?;
+            insert(payload, &conn)
+        })
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Option<PayoutAddressChange>, Error>")]
+pub struct FindPendingByStore(pub Uuid);
+
+impl Handler<FindPendingByStore> for PgExecutor {
+    type Result = Result<Option<PayoutAddressChange>, Error>;
+
+    fn handle(
+        &mut self,
+        FindPendingByStore(store_id): FindPendingByStore,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_pending_by_store(store_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<PayoutAddressChange, Error>")]
+pub struct Confirm {
+    pub token: Uuid,
+    pub delay: Duration,
+}
+
+impl Handler<Confirm> for PgExecutor {
+    type Result = Result<PayoutAddressChange, Error>;
+
+    fn handle(&mut self, Confirm { token, delay }: Confirm, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        confirm(token, delay, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<PayoutAddressChange, Error>")]
+pub struct Cancel(pub Uuid);
+
+impl Handler<Cancel> for PgExecutor {
+    type Result = Result<PayoutAddressChange, Error>;
+
+    fn handle(&mut self, Cancel(token): Cancel, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        cancel(token, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<PayoutAddressChange>, Error>")]
+pub struct FindDue;
+
+impl Handler<FindDue> for PgExecutor {
+    type Result = Result<Vec<PayoutAddressChange>, Error>;
+
+    fn handle(&mut self, _: FindDue, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_due(&conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<(PayoutAddressChange, Store), Error>")]
+pub struct Apply(pub Uuid);
+
+impl Handler<Apply> for PgExecutor {
+    type Result = Result<(PayoutAddressChange, Store), Error>;
+
+    fn handle(&mut self, Apply(id): Apply, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        conn.transaction::<_, Error, _>(|| apply(id, &conn))
+    }
+}
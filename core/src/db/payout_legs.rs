@@ -0,0 +1,61 @@
+use actix::prelude::*;
+use diesel::prelude::*;
+
+use db::{
+    postgres::{PgExecutor, PooledConnection},
+    Error,
+};
+use models::payout_leg::{PayoutLeg, PayoutLegPayload};
+use uuid::Uuid;
+
+pub fn insert(payload: PayoutLegPayload, conn: &PooledConnection) -> Result<PayoutLeg, Error> {
+    use diesel::insert_into;
+    use schema::payout_legs::dsl;
+
+    insert_into(dsl::payout_legs)
+        .values(&payload)
+        .get_result(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_by_payout(payout_id: Uuid, conn: &PooledConnection) -> Result<Vec<PayoutLeg>, Error> {
+    use schema::payout_legs::dsl;
+
+    dsl::payout_legs
+        .filter(dsl::payout_id.eq(payout_id))
+        .order(dsl::leg.asc())
+        .load::<PayoutLeg>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<PayoutLeg, Error>")]
+pub struct Insert(pub PayoutLegPayload);
+
+impl Handler<Insert> for PgExecutor {
+    type Result = Result<PayoutLeg, Error>;
+
+    fn handle(&mut self, Insert(payload): Insert, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        insert(payload, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<PayoutLeg>, Error>")]
+pub struct FindByPayout(pub Uuid);
+
+impl Handler<FindByPayout> for PgExecutor {
+    type Result = Result<Vec<PayoutLeg>, Error>;
+
+    fn handle(
+        &mut self,
+        FindByPayout(payout_id): FindByPayout,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_payout(payout_id, &conn)
+    }
+}
@@ -1,4 +1,5 @@
 use actix::prelude::*;
+use chrono::prelude::*;
 use diesel::prelude::*;
 use uuid::Uuid;
 
@@ -12,10 +13,10 @@ use db::{
 use models::{
     bitcoin::Transaction as BtcTransaction,
     ethereum::Transaction as EthTransaction,
-    payment::PaymentPayload,
-    payout::{Payout, PayoutPayload},
+    payment::{Payment, PaymentPayload},
+    payout::{Payout, PayoutPayload, PayoutStats},
 };
-use types::{currency::Crypto, PayoutStatus, U128};
+use types::{currency::Crypto, NotificationKind, PayoutAction, PayoutStatus, U128};
 
 pub fn insert_btc(
     payout_payload: PayoutPayload,
@@ -80,15 +81,16 @@ pub fn update_with_payment(
     payout_payload: PayoutPayload,
     payment_payload: PaymentPayload,
     conn: &PooledConnection,
-) -> Result<Payout, Error> {
+) -> Result<(Payout, Payment), Error> {
     use diesel::update;
     use schema::payouts::dsl;
 
-    payments::update(payout_payload.payment_id.unwrap(), payment_payload, conn)?;
+    let payment = payments::update(payout_payload.payment_id.unwrap(), payment_payload, conn)?;
 
     update(dsl::payouts.filter(dsl::id.eq(id)))
         .set(&payout_payload)
         .get_result(conn)
+        .map(|payout| (payout, payment))
         .map_err(|e| Error::from(e))
 }
 
@@ -111,6 +113,79 @@ pub fn find_all_confirmed(
         .map_err(|e| Error::from(e))
 }
 
+/// Turns the ETH payout of a payment into a refund, as long as it hasn't been made yet.
+pub fn refund_pending(payment_id: Uuid, conn: &PooledConnection) -> Result<Option<Payout>, Error> {
+    use diesel::update;
+    use schema::payouts::dsl;
+
+    update(
+        dsl::payouts.filter(
+            dsl::payment_id
+                .eq(payment_id)
+                .and(dsl::status.eq(PayoutStatus::Pending))
+                .and(dsl::action.eq(PayoutAction::Payout))
+                .and(dsl::typ.eq(Crypto::Eth)),
+        ),
+    )
+    .set(dsl::action.eq(PayoutAction::Refund))
+    .get_result(conn)
+    .optional()
+    .map_err(|e| Error::from(e))
+}
+
+/// Payouts created since `since` that ran out of funds and the owner wasn't told about yet.
+pub fn find_failed_due(
+    since: DateTime<Utc>,
+    conn: &PooledConnection,
+) -> Result<Vec<Payout>, Error> {
+    use diesel::{
+        sql_query,
+        sql_types::{Text, Timestamptz},
+    };
+
+    sql_query(
+        "SELECT payouts.* FROM payouts
+        WHERE payouts.status = $1
+            AND payouts.created_at >= $2
+            AND NOT EXISTS (
+                SELECT 1 FROM notifications
+                WHERE notifications.kind = $3 AND notifications.subject_id = payouts.id
+                    AND notifications.period IS NULL
+            )
+        ORDER BY payouts.created_at",
+    )
+    .bind::<Text, _>(PayoutStatus::InsufficientFunds)
+    .bind::<Timestamptz, _>(since)
+    .bind::<Text, _>(NotificationKind::PayoutFailed)
+    .load::<Payout>(conn)
+    .map_err(|e| Error::from(e))
+}
+
+/// Payouts and refunds of the store created in `from..to`, counted per status, action and crypto.
+pub fn stats(
+    store_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    conn: &PooledConnection,
+) -> Result<Vec<PayoutStats>, Error> {
+    use diesel::{
+        sql_query,
+        sql_types::{Timestamptz, Uuid as SqlUuid},
+    };
+
+    sql_query(
+        "SELECT status, action, typ, COUNT(*) AS count
+        FROM payouts
+        WHERE store_id = $1 AND created_at >= $2 AND created_at < $3
+        GROUP BY 1, 2, 3",
+    )
+    .bind::<SqlUuid, _>(store_id)
+    .bind::<Timestamptz, _>(from)
+    .bind::<Timestamptz, _>(to)
+    .load::<PayoutStats>(conn)
+    .map_err(|e| Error::from(e))
+}
+
 #[derive(Message)]
 #[rtype(result = "Result<Payout, Error>")]
 pub struct InsertBtc {
@@ -182,7 +257,7 @@ impl Handler<Update> for PgExecutor {
 }
 
 #[derive(Message)]
-#[rtype(result = "Result<Payout, Error>")]
+#[rtype(result = "Result<(Payout, Payment), Error>")]
 pub struct UpdateWithPayment {
     pub id: Uuid,
     pub payout_payload: PayoutPayload,
@@ -190,7 +265,7 @@ pub struct UpdateWithPayment {
 }
 
 impl Handler<UpdateWithPayment> for PgExecutor {
-    type Result = Result<Payout, Error>;
+    type Result = Result<(Payout, Payment), Error>;
 
     fn handle(
         &mut self,
@@ -229,3 +304,61 @@ impl Handler<FindAllConfirmed> for PgExecutor {
         find_all_confirmed(block_height, typ, &conn)
     }
 }
+
+#[derive(Message)]
+#[rtype(result = "Result<Option<Payout>, Error>")]
+pub struct RefundPending(pub Uuid);
+
+impl Handler<RefundPending> for PgExecutor {
+    type Result = Result<Option<Payout>, Error>;
+
+    fn handle(
+        &mut self,
+        RefundPending(payment_id): RefundPending,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        refund_pending(payment_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<PayoutStats>, Error>")]
+pub struct Stats {
+    pub store_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+impl Handler<Stats> for PgExecutor {
+    type Result = Result<Vec<PayoutStats>, Error>;
+
+    fn handle(
+        &mut self,
+        Stats { store_id, from, to }: Stats,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        stats(store_id, from, to, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Payout>, Error>")]
+pub struct FindFailedDue(pub DateTime<Utc>);
+
+impl Handler<FindFailedDue> for PgExecutor {
+    type Result = Result<Vec<Payout>, Error>;
+
+    fn handle(
+        &mut self,
+        FindFailedDue(since): FindFailedDue,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_failed_due(since, &conn)
+    }
+}
@@ -1,4 +1,6 @@
 use std::ops::Deref;
+use std::thread;
+use std::time::Duration;
 
 use _redis;
 use actix::prelude::*;
@@ -46,6 +48,55 @@ impl Handler<Publish> for RedisExecutor {
     }
 }
 
+/// Refills the bucket for the time passed since it was last used and takes a token if there is
+/// one. Returns 0 when a token was taken, otherwise the milliseconds until the next one.
+const TAKE_TOKEN: &str = r#"
+local capacity = tonumber(ARGV[1])
+local refill_ms = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'updated_at')
+local tokens = tonumber(bucket[1]) or capacity
+local updated_at = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - updated_at) / refill_ms)
+local wait = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+else
+    wait = math.ceil((1 - tokens) * refill_ms)
+end
+redis.call('HMSET', KEYS[1], 'tokens', tokens, 'updated_at', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity * refill_ms))
+return wait
+"#;
+
+/// Takes a token from the bucket at `key`, which holds up to `capacity` tokens and gains one
+/// every `refill_ms` milliseconds. Resolves to the milliseconds to wait, 0 if a token was taken.
+#[derive(Message)]
+#[rtype(result = "Result<u64, Error>")]
+pub struct TakeToken {
+    pub key: String,
+    pub capacity: u32,
+    pub refill_ms: u64,
+    /// Milliseconds since the epoch.
+    pub now: i64,
+}
+
+impl Handler<TakeToken> for RedisExecutor {
+    type Result = Result<u64, Error>;
+
+    fn handle(&mut self, msg: TakeToken, _: &mut Self::Context) -> Self::Result {
+        let redis_conn = &self.get()?;
+
+        _redis::Script::new(TAKE_TOKEN)
+            .key(&msg.key)
+            .arg(msg.capacity)
+            .arg(msg.refill_ms)
+            .arg(msg.now)
+            .invoke(&**redis_conn)
+            .map_err(|e| Error::from(e))
+    }
+}
+
 impl Deref for RedisExecutor {
     type Target = RedisPool;
     fn deref(&self) -> &Self::Target {
@@ -55,16 +106,16 @@ impl Deref for RedisExecutor {
 
 pub type RedisSubscriberAddr = Addr<RedisSubscriber>;
 
+// Reconnect delays double from the first up to the second.
+const RECONNECT_MS: u64 = 500;
+const MAX_RECONNECT_MS: u64 = 30_000;
+
 pub struct RedisSubscriber {
     client: _redis::Client,
 }
 
 impl Actor for RedisSubscriber {
-    type Context = Context<Self>;
-
-    fn started(&mut self, _: &mut Self::Context) {
-        // TODO: Subscribe
-    }
+    type Context = SyncContext<Self>;
 }
 
 impl RedisSubscriber {
@@ -72,49 +123,72 @@ impl RedisSubscriber {
         let client = _redis::Client::open(url).unwrap();
         RedisSubscriber { client }
     }
+
+    fn connect(&self, patterns: &[String]) -> Result<_redis::PubSub, Error> {
+        let mut pubsub = self.client.get_pubsub()?;
+
+        for pattern in patterns.iter() {
+            pubsub.psubscribe(pattern)?;
+        }
+
+        Ok(pubsub)
+    }
+
+    /// Forwards messages until the connection fails, which is returned as an error, or the
+    /// recipient is gone.
+    fn listen(pubsub: &_redis::PubSub, recipient: &Recipient<Event>) -> Result<(), Error> {
+        loop {
+            let message = pubsub.get_message()?;
+            let value: String = match message.get_payload() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+
+            if let Err(_) = recipient.do_send(Event {
+                channel: message.get_channel_name().to_owned(),
+                value,
+            }) {
+                return Ok(());
+            }
+        }
+    }
 }
 
-#[derive(Message)]
+#[derive(Message, Debug, Clone)]
 #[rtype(result = "()")]
-pub struct Event<'a> {
-    pub key: &'a str,
+pub struct Event {
+    pub channel: String,
     pub value: String,
 }
 
 #[derive(Message)]
-#[rtype(result = "()")]
-pub struct Subscribe<'a> {
-    pub key: &'a str,
-    pub recipient: Recipient<Event<'a>>,
+#[rtype(result = "Result<(), Error>")]
+pub struct Subscribe {
+    pub patterns: Vec<String>,
+    pub recipient: Recipient<Event>,
 }
 
-impl<'a> Handler<Subscribe<'a>> for RedisSubscriber {
-    type Result = ();
+impl Handler<Subscribe> for RedisSubscriber {
+    type Result = Result<(), Error>;
 
+    // Blocks the arbiter thread for as long as there's a recipient, so the subscriber has to run
+    // in its own SyncArbiter. Messages published while reconnecting are missed.
     fn handle(&mut self, msg: Subscribe, _: &mut Self::Context) -> Self::Result {
-        let mut pubsub = self.client.get_pubsub().unwrap();
-
-        pubsub.subscribe(msg.key).unwrap();
+        let mut delay = RECONNECT_MS;
 
         loop {
-            let payload: String = match pubsub.get_message() {
-                Ok(msg) => match msg.get_payload() {
-                    Ok(payload) => payload,
-                    _ => return (),
-                },
-                _ => return (),
-            };
-
-            match msg.recipient.try_send(Event {
-                key: msg.key,
-                value: payload,
-            }) {
-                Ok(_) => (),
-                Err(_) => {
-                    // TODO: Handle error.
-                    ()
-                }
+            let res = self.connect(&msg.patterns).and_then(|pubsub| {
+                delay = RECONNECT_MS;
+                RedisSubscriber::listen(&pubsub, &msg.recipient)
+            });
+
+            match res {
+                Ok(()) => return Ok(()),
+                Err(e) => error!("Redis subscription failed, reconnecting: {:?}", e),
             }
+
+            thread::sleep(Duration::from_millis(delay));
+            delay = (delay * 2).min(MAX_RECONNECT_MS);
         }
     }
 }
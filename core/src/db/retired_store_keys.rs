@@ -0,0 +1,88 @@
+use actix::prelude::*;
+use chrono::prelude::*;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use db::{
+    postgres::{PgExecutor, PooledConnection},
+    Error,
+};
+use models::retired_store_key::{RetiredStoreKey, RetiredStoreKeyPayload};
+
+pub fn insert(
+    payload: RetiredStoreKeyPayload,
+    conn: &PooledConnection,
+) -> Result<RetiredStoreKey, Error> {
+    use diesel::insert_into;
+    use schema::retired_store_keys::dsl;
+
+    insert_into(dsl::retired_store_keys)
+        .values(&payload)
+        .get_result(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_by_id(id: Uuid, conn: &PooledConnection) -> Result<RetiredStoreKey, Error> {
+    use schema::retired_store_keys::dsl;
+
+    dsl::retired_store_keys
+        .filter(dsl::id.eq(id))
+        .first::<RetiredStoreKey>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_by_store(
+    store_id: Uuid,
+    retired_since: DateTime<Utc>,
+    conn: &PooledConnection,
+) -> Result<Vec<RetiredStoreKey>, Error> {
+    use schema::retired_store_keys::dsl;
+
+    dsl::retired_store_keys
+        .filter(
+            dsl::store_id
+                .eq(store_id)
+                .and(dsl::retired_at.gt(retired_since)),
+        )
+        .order(dsl::retired_at.desc())
+        .load::<RetiredStoreKey>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<RetiredStoreKey, Error>")]
+pub struct FindById(pub Uuid);
+
+impl Handler<FindById> for PgExecutor {
+    type Result = Result<RetiredStoreKey, Error>;
+
+    fn handle(&mut self, FindById(id): FindById, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_id(id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<RetiredStoreKey>, Error>")]
+pub struct FindByStore {
+    pub store_id: Uuid,
+    pub retired_since: DateTime<Utc>,
+}
+
+impl Handler<FindByStore> for PgExecutor {
+    type Result = Result<Vec<RetiredStoreKey>, Error>;
+
+    fn handle(
+        &mut self,
+        FindByStore {
+            store_id,
+            retired_since,
+        }: FindByStore,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_store(store_id, retired_since, &conn)
+    }
+}
@@ -0,0 +1,286 @@
+use actix::prelude::*;
+use chrono::{prelude::*, Duration};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use db::{
+    postgres::{PgExecutor, PooledConnection},
+    Error,
+};
+use models::session::{Session, SessionPayload, REFRESH_TOKEN_DAYS};
+
+pub fn insert(payload: SessionPayload, conn: &PooledConnection) -> Result<Session, Error> {
+    use diesel::insert_into;
+    use schema::sessions::dsl;
+
+    insert_into(dsl::sessions)
+        .values(&payload)
+        .get_result(conn)
+        .map_err(|e| Error::from(e))
+}
+
+/// Returns the session unless it has been revoked or its refresh token has expired.
+pub fn find_active(
+    id: Uuid,
+    user_id: Uuid,
+    conn: &PooledConnection,
+) -> Result<Option<Session>, Error> {
+    use schema::sessions::dsl;
+
+    dsl::sessions
+        .filter(
+            dsl::id
+                .eq(id)
+                .and(dsl::user_id.eq(user_id))
+                .and(dsl::revoked_at.is_null())
+                .and(dsl::refresh_token_expires_at.gt(Utc::now())),
+        )
+        .first::<Session>(conn)
+        .optional()
+        .map_err(|e| Error::from(e))
+}
+
+/// The user's active sessions, most recently used first.
+pub fn find_by_user(user_id: Uuid, conn: &PooledConnection) -> Result<Vec<Session>, Error> {
+    use schema::sessions::dsl;
+
+    dsl::sessions
+        .filter(
+            dsl::user_id
+                .eq(user_id)
+                .and(dsl::revoked_at.is_null())
+                .and(dsl::refresh_token_expires_at.gt(Utc::now())),
+        )
+        .order(dsl::last_used_at.desc())
+        .load::<Session>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+/// Swaps the refresh token for a new one, so each can only be used once. Returns `None` for a
+/// token that was revoked, has expired or isn't known. A token that was swapped already has been
+/// copied, its session is revoked.
+pub fn refresh(
+    token_hash: &str,
+    next_token_hash: &str,
+    conn: &PooledConnection,
+) -> Result<Option<Session>, Error> {
+    use diesel::{insert_into, update};
+    use schema::{sessions::dsl, used_refresh_tokens};
+
+    let now = Utc::now();
+
+    let session = update(
+        dsl::sessions.filter(
+            dsl::refresh_token_hash
+                .eq(token_hash)
+                .and(dsl::revoked_at.is_null())
+                .and(dsl::refresh_token_expires_at.gt(now)),
+        ),
+    )
+    .set((
+        dsl::refresh_token_hash.eq(next_token_hash),
+        dsl::refresh_token_expires_at.eq(now + Duration::days(REFRESH_TOKEN_DAYS)),
+        dsl::last_used_at.eq(now),
+    ))
+    .get_result::<Session>(conn)
+    .optional()?;
+
+    if let Some(session) = session {
+        insert_into(used_refresh_tokens::table)
+            .values((
+                used_refresh_tokens::token_hash.eq(token_hash),
+                used_refresh_tokens::session_id.eq(session.id),
+                used_refresh_tokens::used_at.eq(now),
+            ))
+            .execute(conn)?;
+
+        return Ok(Some(session));
+    }
+
+    let reused = used_refresh_tokens::table
+        .select(used_refresh_tokens::session_id)
+        .filter(used_refresh_tokens::token_hash.eq(token_hash))
+        .first::<Uuid>(conn)
+        .optional()?;
+
+    if let Some(session_id) = reused {
+        warn!("Refresh token reused, revoking session {}", session_id);
+
+        update(dsl::sessions.filter(dsl::id.eq(session_id).and(dsl::revoked_at.is_null())))
+            .set(dsl::revoked_at.eq(now))
+            .execute(conn)?;
+    }
+
+    Ok(None)
+}
+
+/// Forgets used refresh tokens old enough to have expired anyway.
+pub fn delete_used_refresh_tokens(conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::used_refresh_tokens::dsl;
+
+    delete(
+        dsl::used_refresh_tokens
+            .filter(dsl::used_at.lt(Utc::now() - Duration::days(REFRESH_TOKEN_DAYS))),
+    )
+    .execute(conn)
+    .map_err(|e| Error::from(e))
+}
+
+pub fn revoke(id: Uuid, user_id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::update;
+    use schema::sessions::dsl;
+
+    update(
+        dsl::sessions.filter(
+            dsl::id
+                .eq(id)
+                .and(dsl::user_id.eq(user_id))
+                .and(dsl::revoked_at.is_null()),
+        ),
+    )
+    .set(dsl::revoked_at.eq(Utc::now()))
+    .execute(conn)
+    .map_err(|e| Error::from(e))
+}
+
+pub fn revoke_by_user(user_id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::update;
+    use schema::sessions::dsl;
+
+    update(dsl::sessions.filter(dsl::user_id.eq(user_id).and(dsl::revoked_at.is_null())))
+        .set(dsl::revoked_at.eq(Utc::now()))
+        .execute(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn delete_by_user_id(user_id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::sessions::dsl;
+
+    delete(dsl::sessions.filter(dsl::user_id.eq(user_id)))
+        .execute(conn)
+        .map_err(|e| Error::from(e))
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Session, Error>")]
+pub struct Insert(pub SessionPayload);
+
+impl Handler<Insert> for PgExecutor {
+    type Result = Result<Session, Error>;
+
+    fn handle(&mut self, Insert(payload): Insert, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        insert(payload, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Option<Session>, Error>")]
+pub struct FindActive {
+    pub id: Uuid,
+    pub user_id: Uuid,
+}
+
+impl Handler<FindActive> for PgExecutor {
+    type Result = Result<Option<Session>, Error>;
+
+    fn handle(
+        &mut self,
+        FindActive { id, user_id }: FindActive,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_active(id, user_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Session>, Error>")]
+pub struct FindByUser(pub Uuid);
+
+impl Handler<FindByUser> for PgExecutor {
+    type Result = Result<Vec<Session>, Error>;
+
+    fn handle(&mut self, FindByUser(user_id): FindByUser, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_user(user_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Option<Session>, Error>")]
+pub struct Refresh {
+    pub token_hash: String,
+    pub next_token_hash: String,
+}
+
+impl Handler<Refresh> for PgExecutor {
+    type Result = Result<Option<Session>, Error>;
+
+    fn handle(
+        &mut self,
+        Refresh {
+            token_hash,
+            next_token_hash,
+        }: Refresh,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        conn.transaction::<_, Error, _>(|| refresh(&token_hash, &next_token_hash, &conn))
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, Error>")]
+pub struct DeleteUsedRefreshTokens;
+
+impl Handler<DeleteUsedRefreshTokens> for PgExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(&mut self, _: DeleteUsedRefreshTokens, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        delete_used_refresh_tokens(&conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, Error>")]
+pub struct Revoke {
+    pub id: Uuid,
+    pub user_id: Uuid,
+}
+
+impl Handler<Revoke> for PgExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(&mut self, Revoke { id, user_id }: Revoke, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        revoke(id, user_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, Error>")]
+pub struct RevokeByUser(pub Uuid);
+
+impl Handler<RevokeByUser> for PgExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(
+        &mut self,
+        RevokeByUser(user_id): RevokeByUser,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        revoke_by_user(user_id, &conn)
+    }
+}
@@ -0,0 +1,302 @@
+use actix::prelude::*;
+use chrono::prelude::*;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use db::{
+    postgres::{PgExecutor, PooledConnection},
+    Error,
+};
+use models::store_membership::{StoreMembership, StoreMembershipPayload};
+use types::Role;
+
+pub fn insert(
+    payload: StoreMembershipPayload,
+    conn: &PooledConnection,
+) -> Result<StoreMembership, Error> {
+    use diesel::insert_into;
+    use schema::store_memberships::dsl;
+
+    insert_into(dsl::store_memberships)
+        .values(&payload)
+        .get_result(conn)
+        .map_err(|e| Error::from(e))
+}
+
+/// Drops an invitation to `email` that hasn't been accepted yet.
+pub fn delete_invitation(
+    store_id: Uuid,
+    email: &str,
+    conn: &PooledConnection,
+) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::store_memberships::dsl;
+
+    delete(
+        dsl::store_memberships.filter(
+            dsl::store_id
+                .eq(store_id)
+                .and(dsl::email.eq(email))
+                .and(dsl::accepted_at.is_null()),
+        ),
+    )
+    .execute(conn)
+    .map_err(|e| Error::from(e))
+}
+
+pub fn find_by_id(
+    id: Uuid,
+    store_id: Uuid,
+    conn: &PooledConnection,
+) -> Result<StoreMembership, Error> {
+    use schema::store_memberships::dsl;
+
+    dsl::store_memberships
+        .filter(dsl::id.eq(id).and(dsl::store_id.eq(store_id)))
+        .first::<StoreMembership>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn find_by_store(
+    store_id: Uuid,
+    conn: &PooledConnection,
+) -> Result<Vec<StoreMembership>, Error> {
+    use schema::store_memberships::dsl;
+
+    dsl::store_memberships
+        .filter(dsl::store_id.eq(store_id))
+        .order(dsl::created_at.asc())
+        .load::<StoreMembership>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+/// Returns the user's membership of the store, once they've accepted the invitation.
+pub fn find_by_store_and_user(
+    store_id: Uuid,
+    user_id: Uuid,
+    conn: &PooledConnection,
+) -> Result<Option<StoreMembership>, Error> {
+    use schema::store_memberships::dsl;
+
+    dsl::store_memberships
+        .filter(
+            dsl::store_id
+                .eq(store_id)
+                .and(dsl::user_id.eq(user_id))
+                .and(dsl::accepted_at.is_not_null()),
+        )
+        .first::<StoreMembership>(conn)
+        .optional()
+        .map_err(|e| Error::from(e))
+}
+
+/// Accepts the invitation for the user it was sent to.
+pub fn accept(
+    token: Uuid,
+    user_id: Uuid,
+    email: &str,
+    conn: &PooledConnection,
+) -> Result<StoreMembership, Error> {
+    use diesel::update;
+    use schema::store_memberships::dsl;
+
+    let now = Utc::now();
+
+    update(
+        dsl::store_memberships.filter(
+            dsl::invitation_token
+                .eq(token)
+                .and(dsl::email.eq(email))
+                .and(dsl::invitation_expires_at.gt(now))
+                .and(dsl::accepted_at.is_null()),
+        ),
+    )
+    .set((dsl::user_id.eq(user_id), dsl::accepted_at.eq(now)))
+    .get_result(conn)
+    .map_err(|e| Error::from(e))
+}
+
+pub fn update_role(
+    id: Uuid,
+    store_id: Uuid,
+    role: Role,
+    conn: &PooledConnection,
+) -> Result<StoreMembership, Error> {
+    use diesel::update;
+    use schema::store_memberships::dsl;
+
+    update(dsl::store_memberships.filter(dsl::id.eq(id).and(dsl::store_id.eq(store_id))))
+        .set(dsl::role.eq(role))
+        .get_result(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn delete(id: Uuid, store_id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::store_memberships::dsl;
+
+    delete(dsl::store_memberships.filter(dsl::id.eq(id).and(dsl::store_id.eq(store_id))))
+        .execute(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn delete_by_store_id(store_id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::store_memberships::dsl;
+
+    delete(dsl::store_memberships.filter(dsl::store_id.eq(store_id)))
+        .execute(conn)
+        .map_err(|e| Error::from(e))
+}
+
+pub fn delete_by_user_id(user_id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::delete;
+    use schema::store_memberships::dsl;
+
+    delete(dsl::store_memberships.filter(dsl::user_id.eq(user_id)))
+        .execute(conn)
+        .map_err(|e| Error::from(e))
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<StoreMembership, Error>")]
+pub struct Insert(pub StoreMembershipPayload);
+
+impl Handler<Insert> for PgExecutor {
+    type Result = Result<StoreMembership, Error>;
+
+    fn handle(&mut self, Insert(payload): Insert, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        // Inviting someone again replaces their pending invitation, and with it the token.
+        conn.transaction::<_, Error, _>(|| {
+            delete_invitation(payload.store_id, &payload.email, &conn)?;
+            insert(payload, &conn)
+        })
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<StoreMembership, Error>")]
+pub struct FindById {
+    pub id: Uuid,
+    pub store_id: Uuid,
+}
+
+impl Handler<FindById> for PgExecutor {
+    type Result = Result<StoreMembership, Error>;
+
+    fn handle(
+        &mut self,
+        FindById { id, store_id }: FindById,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_id(id, store_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<StoreMembership>, Error>")]
+pub struct FindByStore(pub Uuid);
+
+impl Handler<FindByStore> for PgExecutor {
+    type Result = Result<Vec<StoreMembership>, Error>;
+
+    fn handle(
+        &mut self,
+        FindByStore(store_id): FindByStore,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_store(store_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Option<StoreMembership>, Error>")]
+pub struct FindByStoreAndUser {
+    pub store_id: Uuid,
+    pub user_id: Uuid,
+}
+
+impl Handler<FindByStoreAndUser> for PgExecutor {
+    type Result = Result<Option<StoreMembership>, Error>;
+
+    fn handle(
+        &mut self,
+        FindByStoreAndUser { store_id, user_id }: FindByStoreAndUser,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_store_and_user(store_id, user_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<StoreMembership, Error>")]
+pub struct Accept {
+    pub token: Uuid,
+    pub user_id: Uuid,
+    pub email: String,
+}
+
+impl Handler<Accept> for PgExecutor {
+    type Result = Result<StoreMembership, Error>;
+
+    fn handle(
+        &mut self,
+        Accept {
+            token,
+            user_id,
+            email,
+        }: Accept,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        accept(token, user_id, &email, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<StoreMembership, Error>")]
+pub struct UpdateRole {
+    pub id: Uuid,
+    pub store_id: Uuid,
+    pub role: Role,
+}
+
+impl Handler<UpdateRole> for PgExecutor {
+    type Result = Result<StoreMembership, Error>;
+
+    fn handle(
+        &mut self,
+        UpdateRole { id, store_id, role }: UpdateRole,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        update_role(id, store_id, role, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, Error>")]
+pub struct Delete {
+    pub id: Uuid,
+    pub store_id: Uuid,
+}
+
+impl Handler<Delete> for PgExecutor {
+    type Result = Result<usize, Error>;
+
+    fn handle(&mut self, Delete { id, store_id }: Delete, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        delete(id, store_id, &conn)
+    }
+}
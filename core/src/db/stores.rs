@@ -1,14 +1,19 @@
 use actix::prelude::*;
 use diesel::prelude::*;
 
+use crypto::Keychain;
 use db::{
-    client_tokens,
+    client_tokens, payout_address_changes, retired_store_keys, store_memberships,
     {
         postgres::{PgExecutor, PooledConnection},
         Error,
     },
 };
-use models::store::{Store, StorePayload};
+use models::{
+    retired_store_key::RetiredStoreKeyPayload,
+    store::{Store, StorePayload},
+};
+use types::currency::Crypto;
 use uuid::Uuid;
 
 pub fn insert(payload: StorePayload, conn: &PooledConnection) -> Result<Store, Error> {
@@ -49,22 +54,154 @@ pub fn find_by_id_with_deleted(id: Uuid, conn: &PooledConnection) -> Result<Stor
         .map_err(|e| Error::from(e))
 }
 
-pub fn find_by_owner(
-    owner_id: Uuid,
+pub fn find_by_key_id(key_id: Uuid, conn: &PooledConnection) -> Result<Store, Error> {
+    use schema::stores::dsl;
+
+    dsl::stores
+        .filter(dsl::key_id.eq(key_id))
+        .first::<Store>(conn)
+        .map_err(|e| Error::from(e))
+}
+
+/// Swaps in a new signing key, keeping the old public key around so vouchers it signed can
+/// still be verified.
+pub fn rotate_key(
+    id: Uuid,
+    payload: StorePayload,
+    conn: &PooledConnection,
+) -> Result<Store, Error> {
+    let store = find_by_id(id, conn)?;
+
+    retired_store_keys::insert(RetiredStoreKeyPayload::from_store(store), conn)?;
+
+    update(id, payload, conn)
+}
+
+/// Encrypts the secrets of stores still holding them in plaintext and re-wraps data keys wrapped
+/// with an older master key. Deleted stores are included, their addresses may still hold funds.
+pub fn encrypt_secrets(keychain: &Keychain, conn: &PooledConnection) -> Result<usize, Error> {
+    use diesel::update;
+    use schema::stores::dsl;
+
+    let stores = dsl::stores
+        .filter(
+            dsl::key_version
+                .is_null()
+                .or(dsl::key_version.lt(keychain.current_version())),
+        )
+        .load::<Store>(conn)?;
+
+    for store in stores.iter() {
+        let mut payload = StorePayload::new();
+
+        match (store.key_version, store.data_key.as_ref()) {
+            (Some(key_version), Some(data_key)) => {
+                let (data_key, key_version) =
+                    keychain.wrap(&keychain.unwrap(key_version, data_key)?)?;
+
+                payload.data_key = Some(data_key);
+                payload.key_version = Some(key_version);
+            }
+            _ => payload.set_secrets(
+                store.mnemonic.as_ref().map(|mnemonic| mnemonic.as_str()),
+                None,
+                None,
+                &store.private_key,
+                keychain,
+            )?,
+        }
+
+        update(dsl::stores.filter(dsl::id.eq(store.id)))
+            .set(&payload)
+            .execute(conn)?;
+    }
+
+    Ok(stores.len())
+}
+
+/// Increments the store's address counter for `crypto` and returns the new value. Done in a single
+/// statement so concurrent payments never get the same address.
+pub fn next_address_index(id: Uuid, crypto: Crypto, conn: &PooledConnection) -> Result<i32, Error> {
+    use diesel::update;
+    use schema::stores::dsl;
+
+    let query = update(dsl::stores.filter(dsl::id.eq(id).and(dsl::deleted_at.is_null())));
+
+    match crypto {
+        Crypto::Btc => query
+            .set(dsl::btc_address_index.eq(dsl::btc_address_index + 1))
+            .returning(dsl::btc_address_index)
+            .get_result(conn),
+        Crypto::Eth => query
+            .set(dsl::eth_address_index.eq(dsl::eth_address_index + 1))
+            .returning(dsl::eth_address_index)
+            .get_result(conn),
+    }
+    .map_err(|e| Error::from(e))
+}
+
+/// Increments the store's payout rotation counter for `crypto` and returns the new value. Deleted
+/// stores are included, their last payments still get paid out.
+pub fn next_payout_index(id: Uuid, crypto: Crypto, conn: &PooledConnection) -> Result<i32, Error> {
+    use diesel::update;
+    use schema::stores::dsl;
+
+    let query = update(dsl::stores.filter(dsl::id.eq(id)));
+
+    match crypto {
+        Crypto::Btc => query
+            .set(dsl::btc_payout_index.eq(dsl::btc_payout_index + 1))
+            .returning(dsl::btc_payout_index)
+            .get_result(conn),
+        Crypto::Eth => query
+            .set(dsl::eth_payout_index.eq(dsl::eth_payout_index + 1))
+            .returning(dsl::eth_payout_index)
+            .get_result(conn),
+    }
+    .map_err(|e| Error::from(e))
+}
+
+/// Returns the stores the user owns or has accepted an invitation to.
+pub fn find_by_member(
+    user_id: Uuid,
     limit: i64,
     offset: i64,
     conn: &PooledConnection,
 ) -> Result<Vec<Store>, Error> {
-    use schema::stores::dsl;
+    use schema::{store_memberships, stores::dsl};
+
+    let memberships = store_memberships::table
+        .select(store_memberships::store_id)
+        .filter(
+            store_memberships::user_id
+                .eq(user_id)
+                .and(store_memberships::accepted_at.is_not_null()),
+        );
 
     dsl::stores
-        .filter(dsl::owner_id.eq(owner_id).and(dsl::deleted_at.is_null()))
+        .filter(
+            dsl::owner_id
+                .eq(user_id)
+                .or(dsl::id.eq_any(memberships))
+                .and(dsl::deleted_at.is_null()),
+        )
+        .order(dsl::created_at.asc())
         .limit(limit)
         .offset(offset)
         .load::<Store>(conn)
         .map_err(|e| Error::from(e))
 }
 
+/// Stores whose owner gets a daily summary.
+pub fn find_all_with_daily_summary(conn: &PooledConnection) -> Result<Vec<Store>, Error> {
+    use schema::stores::dsl;
+
+    dsl::stores
+        .filter(dsl::daily_summary.eq(true).and(dsl::deleted_at.is_null()))
+        .load::<Store>(conn)
+        .map_err(|e| Error::from(e))
+}
+
 pub fn delete(id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
     use diesel::delete;
     use schema::stores::dsl;
@@ -87,6 +224,8 @@ pub fn soft_delete(id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
         .map_err(|e| Error::from(e))?;
 
     client_tokens::delete_by_store_id(id, conn)?;
+    payout_address_changes::cancel_pending_by_store(id, conn)?;
+    store_memberships::delete_by_store_id(id, conn)?;
 
     Ok(1)
 }
@@ -105,6 +244,8 @@ pub fn soft_delete_by_owner_id(owner_id: Uuid, conn: &PooledConnection) -> Resul
 
     for store in deleted_stores {
         client_tokens::delete_by_store_id(store.id, conn)?;
+        payout_address_changes::cancel_pending_by_store(store.id, conn)?;
+        store_memberships::delete_by_store_id(store.id, conn)?;
     }
 
     Ok(1)
@@ -175,27 +316,41 @@ impl Handler<FindByIdWithDeleted> for PgExecutor {
 
 #[derive(Message)]
 #[rtype(result = "Result<Vec<Store>, Error>")]
-pub struct FindByOwner {
-    pub owner_id: Uuid,
+pub struct FindByMember {
+    pub user_id: Uuid,
     pub limit: i64,
     pub offset: i64,
 }
 
-impl Handler<FindByOwner> for PgExecutor {
+impl Handler<FindByMember> for PgExecutor {
     type Result = Result<Vec<Store>, Error>;
 
     fn handle(
         &mut self,
-        FindByOwner {
-            owner_id,
+        FindByMember {
+            user_id,
             limit,
             offset,
-        }: FindByOwner,
+        }: FindByMember,
         _: &mut Self::Context,
     ) -> Self::Result {
         let conn = &self.get()?;
 
-        find_by_owner(owner_id, limit, offset, &conn)
+        find_by_member(user_id, limit, offset, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<Store>, Error>")]
+pub struct FindAllWithDailySummary;
+
+impl Handler<FindAllWithDailySummary> for PgExecutor {
+    type Result = Result<Vec<Store>, Error>;
+
+    fn handle(&mut self, _: FindAllWithDailySummary, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_all_with_daily_summary(&conn)
     }
 }
 
@@ -212,3 +367,80 @@ impl Handler<SoftDelete> for PgExecutor {
         conn.transaction::<_, Error, _>(|| soft_delete(id, &conn))
     }
 }
+
+#[derive(Message)]
+#[rtype(result = "Result<Store, Error>")]
+pub struct FindByKeyId(pub Uuid);
+
+impl Handler<FindByKeyId> for PgExecutor {
+    type Result = Result<Store, Error>;
+
+    fn handle(&mut self, FindByKeyId(key_id): FindByKeyId, _: &mut Self::Context) -> Self::Result {
+        let conn = &self.get()?;
+
+        find_by_key_id(key_id, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Store, Error>")]
+pub struct RotateKey {
+    pub id: Uuid,
+    pub payload: StorePayload,
+}
+
+impl Handler<RotateKey> for PgExecutor {
+    type Result = Result<Store, Error>;
+
+    fn handle(
+        &mut self,
+        RotateKey { id, payload }: RotateKey,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        conn.transaction::<_, Error, _>(|| rotate_key(id, payload, &conn))
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<i32, Error>")]
+pub struct NextAddressIndex {
+    pub id: Uuid,
+    pub crypto: Crypto,
+}
+
+impl Handler<NextAddressIndex> for PgExecutor {
+    type Result = Result<i32, Error>;
+
+    fn handle(
+        &mut self,
+        NextAddressIndex { id, crypto }: NextAddressIndex,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        next_address_index(id, crypto, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<i32, Error>")]
+pub struct NextPayoutIndex {
+    pub id: Uuid,
+    pub crypto: Crypto,
+}
+
+impl Handler<NextPayoutIndex> for PgExecutor {
+    type Result = Result<i32, Error>;
+
+    fn handle(
+        &mut self,
+        NextPayoutIndex { id, crypto }: NextPayoutIndex,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        next_payout_index(id, crypto, &conn)
+    }
+}
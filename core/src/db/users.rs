@@ -3,7 +3,7 @@ use chrono::prelude::*;
 use diesel::prelude::*;
 use uuid::Uuid;
 
-use db::{Error, stores, postgres::{PgExecutor, PooledConnection}};
+use db::{Error, sessions, stores, store_memberships, postgres::{PgExecutor, PooledConnection}};
 use models::user::{User, UserPayload};
 
 pub fn insert(payload: UserPayload, conn: &PooledConnection) -> Result<User, Error> {
@@ -26,6 +26,50 @@ pub fn update(id: Uuid, payload: UserPayload, conn: &PooledConnection) -> Result
         .map_err(|e| Error::from(e))
 }
 
+/// Updates the user with `payload`, which sets `step` as the last accepted TOTP step, unless the
+/// same or a later step was accepted in the meantime. `None` then, the code was used already.
+pub fn use_totp_step(
+    id: Uuid,
+    step: i64,
+    payload: UserPayload,
+    conn: &PooledConnection,
+) -> Result<Option<User>, Error> {
+    use diesel::update;
+    use schema::users::dsl;
+
+    update(
+        dsl::users.filter(
+            dsl::id.eq(id).and(
+                dsl::totp_last_step
+                    .is_null()
+                    .or(dsl::totp_last_step.lt(step)),
+            ),
+        ),
+    )
+    .set(&payload)
+    .get_result(conn)
+    .optional()
+    .map_err(|e| Error::from(e))
+}
+
+/// Updates the user with `payload`, which removes a recovery code, unless the recovery codes are
+/// no longer `recovery_codes`. `None` then, another request used one up in the meantime.
+pub fn use_recovery_code(
+    id: Uuid,
+    recovery_codes: Vec<String>,
+    payload: UserPayload,
+    conn: &PooledConnection,
+) -> Result<Option<User>, Error> {
+    use diesel::update;
+    use schema::users::dsl;
+
+    update(dsl::users.filter(dsl::id.eq(id).and(dsl::recovery_codes.eq(recovery_codes))))
+        .set(&payload)
+        .get_result(conn)
+        .optional()
+        .map_err(|e| Error::from(e))
+}
+
 pub fn find_by_email(email: String, conn: &PooledConnection) -> Result<User, Error> {
     use schema::users::dsl;
 
@@ -86,6 +130,8 @@ pub fn delete(id: Uuid, conn: &PooledConnection) -> Result<usize, Error> {
         .map_err(|e| Error::from(e))?;
 
     stores::soft_delete_by_owner_id(id, &conn)?;
+    store_memberships::delete_by_user_id(id, &conn)?;
+    sessions::delete_by_user_id(id, &conn)?;
 
     Ok(1)
 }
@@ -137,6 +183,54 @@ impl Handler<Update> for PgExecutor {
     }
 }
 
+#[derive(Message)]
+#[rtype(result = "Result<Option<User>, Error>")]
+pub struct UseTotpStep {
+    pub id: Uuid,
+    pub step: i64,
+    pub payload: UserPayload,
+}
+
+impl Handler<UseTotpStep> for PgExecutor {
+    type Result = Result<Option<User>, Error>;
+
+    fn handle(
+        &mut self,
+        UseTotpStep { id, step, payload }: UseTotpStep,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        use_totp_step(id, step, payload, &conn)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Option<User>, Error>")]
+pub struct UseRecoveryCode {
+    pub id: Uuid,
+    pub recovery_codes: Vec<String>,
+    pub payload: UserPayload,
+}
+
+impl Handler<UseRecoveryCode> for PgExecutor {
+    type Result = Result<Option<User>, Error>;
+
+    fn handle(
+        &mut self,
+        UseRecoveryCode {
+            id,
+            recovery_codes,
+            payload,
+        }: UseRecoveryCode,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let conn = &self.get()?;
+
+        use_recovery_code(id, recovery_codes, payload, &conn)
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "Result<User, Error>")]
 pub struct FindByEmail(pub String);
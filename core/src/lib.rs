@@ -13,9 +13,12 @@ extern crate failure;
 extern crate futures;
 extern crate hex;
 extern crate jsonwebtoken as jwt;
+#[macro_use]
+extern crate log;
 extern crate r2d2;
 extern crate r2d2_redis;
 extern crate redis as _redis;
+extern crate ring;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -28,9 +31,12 @@ extern crate types;
 
 mod schema;
 
+pub mod crypto;
 pub mod db;
 mod models;
 
 pub use models::{
-    bitcoin, client_token, ethereum, payment, payout, store, user, voucher, Error as ModelError,
+    audit_log, bitcoin, client_token, email_template, ethereum, idempotency_key, invoice,
+    notification, payment, payout, payout_address_change, payout_leg, retired_store_key, session,
+    store, store_membership, user, voucher, Error as ModelError,
 };
@@ -0,0 +1,113 @@
+use chrono::prelude::*;
+use futures::Future;
+use serde_json::Value;
+use uuid::Uuid;
+
+use db::{
+    audit_logs::{FindByStore, FindByUser, Insert},
+    postgres::PgExecutorAddr,
+};
+use models::Error;
+use schema::audit_logs;
+use types::AuditAction;
+
+#[derive(Debug, Insertable)]
+#[table_name = "audit_logs"]
+pub struct AuditLogPayload {
+    pub action: AuditAction,
+    pub store_id: Option<Uuid>,
+    pub user_id: Option<Uuid>,
+    pub subject_id: Option<Uuid>,
+    pub ip: Option<String>,
+    pub changes: Option<Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditLogPayload {
+    pub fn new(action: AuditAction) -> Self {
+        AuditLogPayload {
+            action,
+            store_id: None,
+            user_id: None,
+            subject_id: None,
+            ip: None,
+            changes: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// One entry of the audit log. `user_id` is who acted, it's empty for changes made by the server
+/// itself or through a link from an email. `subject_id` is the record acted on, like a client
+/// token or a payment, and `changes` holds the fields that changed with their values before and
+/// after.
+#[derive(Debug, Identifiable, Queryable)]
+pub struct AuditLog {
+    pub id: Uuid,
+    pub action: AuditAction,
+    pub store_id: Option<Uuid>,
+    pub user_id: Option<Uuid>,
+    pub subject_id: Option<Uuid>,
+    pub ip: Option<String>,
+    pub changes: Option<Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditLog {
+    pub fn insert(
+        payload: AuditLogPayload,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = AuditLog, Error = Error> {
+        (*postgres)
+            .send(Insert(payload))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Newest first.
+    pub fn find_by_store(
+        store_id: Uuid,
+        limit: i64,
+        offset: i64,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<AuditLog>, Error = Error> {
+        (*postgres)
+            .send(FindByStore {
+                store_id,
+                limit,
+                offset,
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Entries about the user's account, newest first.
+    pub fn find_by_user(
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<AuditLog>, Error = Error> {
+        (*postgres)
+            .send(FindByUser {
+                user_id,
+                limit,
+                offset,
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn export(&self) -> Value {
+        json!({
+            "id": self.id,
+            "action": self.action,
+            "store_id": self.store_id,
+            "user_id": self.user_id,
+            "subject_id": self.subject_id,
+            "ip": self.ip,
+            "changes": self.changes,
+            "created_at": self.created_at.timestamp(),
+        })
+    }
+}
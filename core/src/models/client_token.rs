@@ -1,16 +1,20 @@
 use base64::encode;
+use bigdecimal::BigDecimal;
 use chrono::prelude::*;
 use futures::Future;
 use serde_json::Value;
 use uuid::Uuid;
 
 use db::{
-    client_tokens::{Delete, FindById, FindByStore, FindByTokenAndDomain, Insert},
+    client_tokens::{Delete, FindById, FindByStore, FindByToken, Insert},
     postgres::PgExecutorAddr,
 };
 use models::{store::Store, Error};
 use schema::client_tokens;
-use types::Client;
+use types::{
+    currency::{Crypto, Fiat},
+    Client,
+};
 
 #[derive(Debug, Insertable, AsChangeset, Deserialize)]
 #[table_name = "client_tokens"]
@@ -19,9 +23,13 @@ pub struct ClientTokenPayload {
     pub name: String,
     pub token: Option<Uuid>,
     pub store_id: Uuid,
-    pub domain: String,
     pub created_at: Option<DateTime<Utc>>,
     pub typ: Client,
+    pub allowed_origins: Vec<String>,
+    pub allowed_cryptos: Option<Vec<Crypto>>,
+    pub allowed_fiats: Option<Vec<Fiat>>,
+    pub max_price: Option<BigDecimal>,
+    pub max_price_fiat: Option<Fiat>,
 }
 
 impl ClientTokenPayload {
@@ -37,9 +45,13 @@ pub struct ClientToken {
     pub name: String,
     pub token: Uuid,
     pub store_id: Uuid,
-    pub domain: String,
     pub created_at: DateTime<Utc>,
     pub typ: Client,
+    pub allowed_origins: Vec<String>,
+    pub allowed_cryptos: Option<Vec<Crypto>>,
+    pub allowed_fiats: Option<Vec<Fiat>>,
+    pub max_price: Option<BigDecimal>,
+    pub max_price_fiat: Option<Fiat>,
 }
 
 impl ClientToken {
@@ -81,13 +93,12 @@ impl ClientToken {
             .and_then(|res| res.map_err(|e| Error::from(e)))
     }
 
-    pub fn find_by_token_and_domain(
+    pub fn find_by_token(
         token: Uuid,
-        domain: String,
         postgres: &PgExecutorAddr,
     ) -> impl Future<Item = ClientToken, Error = Error> {
         (*postgres)
-            .send(FindByTokenAndDomain { token, domain })
+            .send(FindByToken(token))
             .from_err()
             .and_then(|res| res.map_err(|e| Error::from(e)))
     }
@@ -99,6 +110,49 @@ impl ClientToken {
             .and_then(|res| res.map_err(|e| Error::from(e)))
     }
 
+    /// Whether a request from `origin`, the host and port of the `Origin` header, may use the
+    /// token. Native tokens may also be used without an `Origin` header.
+    pub fn allows_origin(&self, origin: Option<&str>) -> bool {
+        let origin = match origin {
+            Some(origin) => origin.to_lowercase(),
+            None => return self.typ == Client::Native,
+        };
+
+        self.allowed_origins.iter().any(|pattern| {
+            let pattern = pattern.to_lowercase();
+
+            if pattern.starts_with("*.") {
+                origin.ends_with(&pattern[1..])
+            } else {
+                origin == pattern
+            }
+        })
+    }
+
+    pub fn allows_crypto(&self, crypto: &Crypto) -> bool {
+        match self.allowed_cryptos {
+            Some(ref cryptos) => cryptos.contains(crypto),
+            None => true,
+        }
+    }
+
+    pub fn allows_fiat(&self, fiat: &Fiat) -> bool {
+        match self.allowed_fiats {
+            Some(ref fiats) => fiats.contains(fiat),
+            None => true,
+        }
+    }
+
+    /// Prices are only comparable in the maximum price's fiat currency, so a token with a
+    /// maximum allows no other.
+    pub fn allows_price(&self, price: &BigDecimal, fiat: &Fiat) -> bool {
+        match (&self.max_price, &self.max_price_fiat) {
+            (Some(max_price), Some(max_price_fiat)) => fiat == max_price_fiat && price <= max_price,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+
     pub fn export(&self) -> Value {
         let token = encode(self.token.as_bytes());
         json!({
@@ -106,9 +160,92 @@ impl ClientToken {
             "name": self.name,
             "token": token,
             "store_id": self.store_id,
-            "domain": self.domain,
             "created_at": self.created_at.timestamp(),
             "typ": self.typ,
+            "allowed_origins": self.allowed_origins,
+            "allowed_cryptos": self.allowed_cryptos,
+            "allowed_fiats": self.allowed_fiats,
+            "max_price": self.max_price,
+            "max_price_fiat": self.max_price_fiat,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_token(typ: Client, allowed_origins: &[&str]) -> ClientToken {
+        ClientToken {
+            id: Uuid::nil(),
+            name: String::from("Test"),
+            token: Uuid::nil(),
+            store_id: Uuid::nil(),
+            created_at: Utc::now(),
+            typ,
+            allowed_origins: allowed_origins.iter().map(|o| o.to_string()).collect(),
+            allowed_cryptos: None,
+            allowed_fiats: None,
+            max_price: None,
+            max_price_fiat: None,
+        }
+    }
+
+    #[test]
+    fn wildcard_origins_match_subdomains_only() {
+        let client_token = client_token(Client::Web, &["*.example.com"]);
+
+        assert!(client_token.allows_origin(Some("shop.example.com")));
+        assert!(client_token.allows_origin(Some("a.b.example.com")));
+        assert!(!client_token.allows_origin(Some("example.com")));
+        assert!(!client_token.allows_origin(Some("evilexample.com")));
+        assert!(!client_token.allows_origin(Some("example.com.evil.io")));
+    }
+
+    #[test]
+    fn origins_match_the_apex_only_when_listed() {
+        let client_token = client_token(Client::Web, &["example.com"]);
+
+        assert!(client_token.allows_origin(Some("example.com")));
+        assert!(!client_token.allows_origin(Some("shop.example.com")));
+    }
+
+    #[test]
+    fn origins_match_ports_exactly() {
+        let client_token = client_token(Client::Web, &["example.com:8080", "*.example.org"]);
+
+        assert!(client_token.allows_origin(Some("example.com:8080")));
+        assert!(!client_token.allows_origin(Some("example.com")));
+        assert!(!client_token.allows_origin(Some("example.com:8081")));
+        assert!(!client_token.allows_origin(Some("shop.example.org:8080")));
+    }
+
+    #[test]
+    fn origins_match_regardless_of_case() {
+        let client_token = client_token(Client::Web, &["Shop.Example.com", "*.EXAMPLE.org"]);
+
+        assert!(client_token.allows_origin(Some("shop.example.COM")));
+        assert!(client_token.allows_origin(Some("Shop.Example.Org")));
+    }
+
+    #[test]
+    fn only_native_tokens_go_without_an_origin() {
+        assert!(!client_token(Client::Web, &["example.com"]).allows_origin(None));
+        assert!(client_token(Client::Native, &[]).allows_origin(None));
+    }
+
+    #[test]
+    fn max_price_only_allows_its_fiat() {
+        let mut client_token = client_token(Client::Web, &["example.com"]);
+        client_token.max_price = Some(BigDecimal::from(100));
+        client_token.max_price_fiat = Some(Fiat::Usd);
+
+        assert!(client_token.allows_price(&BigDecimal::from(100), &Fiat::Usd));
+        assert!(!client_token.allows_price(&BigDecimal::from(101), &Fiat::Usd));
+
+        // Left over from before maximum prices had a fiat currency.
+        client_token.max_price_fiat = None;
+
+        assert!(!client_token.allows_price(&BigDecimal::from(1), &Fiat::Usd));
+    }
+}
@@ -0,0 +1,110 @@
+use chrono::prelude::*;
+use futures::Future;
+use serde_json::Value;
+use uuid::Uuid;
+
+use db::{
+    email_templates::{Delete, FindByStore, FindByStoreAndKind, Upsert},
+    postgres::PgExecutorAddr,
+};
+use models::Error;
+use schema::email_templates;
+use types::NotificationKind;
+
+#[derive(Debug, Insertable)]
+#[table_name = "email_templates"]
+pub struct EmailTemplatePayload {
+    pub store_id: Uuid,
+    pub kind: NotificationKind,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl EmailTemplatePayload {
+    pub fn new(
+        store_id: Uuid,
+        kind: NotificationKind,
+        subject: String,
+        html: String,
+        text: String,
+    ) -> Self {
+        EmailTemplatePayload {
+            store_id,
+            kind,
+            subject,
+            html,
+            text,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// A store's own version of one of its emails, sent instead of the default.
+#[derive(Debug, Identifiable, Queryable, Serialize)]
+pub struct EmailTemplate {
+    #[serde(skip_serializing)]
+    pub id: Uuid,
+    #[serde(skip_serializing)]
+    pub store_id: Uuid,
+    pub kind: NotificationKind,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+    #[serde(skip_serializing)]
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing)]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl EmailTemplate {
+    /// Replaces the store's template of the same kind, if it has one.
+    pub fn upsert(
+        payload: EmailTemplatePayload,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = EmailTemplate, Error = Error> {
+        (*postgres)
+            .send(Upsert(payload))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_by_store(
+        store_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<EmailTemplate>, Error = Error> {
+        (*postgres)
+            .send(FindByStore(store_id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_by_store_and_kind(
+        store_id: Uuid,
+        kind: NotificationKind,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Option<EmailTemplate>, Error = Error> {
+        (*postgres)
+            .send(FindByStoreAndKind { store_id, kind })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn delete(
+        store_id: Uuid,
+        kind: NotificationKind,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = usize, Error = Error> {
+        (*postgres)
+            .send(Delete { store_id, kind })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn export(&self) -> Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
@@ -3,6 +3,7 @@ use futures::Future;
 use db::{
     ethereum::blockchain_statuses::{FindByNetwork, Insert, Update},
     postgres::PgExecutorAddr,
+    redis::{Publish, RedisExecutorAddr},
 };
 use models::Error;
 use schema::eth_blockchain_statuses;
@@ -22,6 +23,10 @@ pub struct BlockchainStatus {
 }
 
 impl BlockchainStatus {
+    pub fn channel(network: Network) -> String {
+        format!("eth_blocks:{}", network)
+    }
+
     pub fn insert(
         payload: BlockchainStatusPayload,
         postgres: &PgExecutorAddr,
@@ -52,4 +57,11 @@ impl BlockchainStatus {
             .from_err()
             .and_then(|res| res.map_err(|e| Error::from(e)))
     }
+
+    pub fn notify(&self, redis: &RedisExecutorAddr) {
+        (*redis).do_send(Publish {
+            key: BlockchainStatus::channel(self.network),
+            value: format!("{}", self.block_height),
+        });
+    }
 }
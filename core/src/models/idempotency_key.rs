@@ -0,0 +1,105 @@
+use chrono::prelude::*;
+use futures::Future;
+use serde_json::Value;
+use uuid::Uuid;
+
+use db::{
+    idempotency_keys::{Claim, Complete, Delete, DeleteExpired},
+    postgres::PgExecutorAddr,
+};
+use models::Error;
+use schema::idempotency_keys;
+
+/// How long a key is remembered for.
+pub const IDEMPOTENCY_KEY_HOURS: i64 = 24;
+
+/// How long a request holds a key before a retry can take it over, in case it never finishes.
+pub const IDEMPOTENCY_KEY_LEASE_MINUTES: i64 = 5;
+
+#[derive(Debug, Insertable)]
+#[table_name = "idempotency_keys"]
+pub struct IdempotencyKeyPayload {
+    pub scope: String,
+    pub key: String,
+    pub fingerprint: String,
+    pub created_at: DateTime<Utc>,
+    pub locked_at: DateTime<Utc>,
+}
+
+impl IdempotencyKeyPayload {
+    pub fn new(scope: String, key: String, fingerprint: String) -> Self {
+        let now = Utc::now();
+
+        IdempotencyKeyPayload {
+            scope,
+            key,
+            fingerprint,
+            created_at: now,
+            locked_at: now,
+        }
+    }
+}
+
+/// A request made with an `Idempotency-Key` header. The response is kept so a retry gets the
+/// same one, it's empty while the first request is still being handled. `locked_at` is when the
+/// request handling it took the key.
+#[derive(Debug, Identifiable, Queryable)]
+pub struct IdempotencyKey {
+    pub id: Uuid,
+    pub scope: String,
+    pub key: String,
+    pub fingerprint: String,
+    pub response: Option<Value>,
+    pub created_at: DateTime<Utc>,
+    pub locked_at: DateTime<Utc>,
+}
+
+impl IdempotencyKey {
+    /// Returns the key along with whether the request got to handle it, either because the key
+    /// was new or because the request holding it let its lease run out.
+    pub fn claim(
+        payload: IdempotencyKeyPayload,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = (IdempotencyKey, bool), Error = Error> {
+        (*postgres)
+            .send(Claim(payload))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Keeps the response, unless another request took the key over since `locked_at`.
+    pub fn complete(
+        id: Uuid,
+        locked_at: DateTime<Utc>,
+        response: Value,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = usize, Error = Error> {
+        (*postgres)
+            .send(Complete {
+                id,
+                locked_at,
+                response,
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Forgets the key, unless another request took it over since `locked_at`.
+    pub fn delete(
+        id: Uuid,
+        locked_at: DateTime<Utc>,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = usize, Error = Error> {
+        (*postgres)
+            .send(Delete { id, locked_at })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn delete_expired(postgres: &PgExecutorAddr) -> impl Future<Item = usize, Error = Error> {
+        (*postgres)
+            .send(DeleteExpired)
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+}
@@ -0,0 +1,128 @@
+use bigdecimal::BigDecimal;
+use chrono::{prelude::*, Duration};
+use futures::Future;
+use serde_json::Value;
+use uuid::Uuid;
+
+use db::{
+    invoices::{FindById, Insert},
+    postgres::PgExecutorAddr,
+};
+use models::{payment::Payment, store::Store, Error};
+use schema::invoices;
+use types::currency::Fiat;
+
+#[derive(Debug, Insertable, Clone)]
+#[table_name = "invoices"]
+pub struct InvoicePayload {
+    pub store_id: Option<Uuid>,
+    pub created_by: Option<Uuid>, // AuthClient id
+    pub created_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub fiat: Option<Fiat>,
+    pub price: Option<BigDecimal>,
+    pub identifier: Option<String>,
+    pub signed: Option<bool>,
+    pub metadata: Option<Value>,
+    pub customer_email: Option<String>,
+}
+
+impl InvoicePayload {
+    pub fn new() -> Self {
+        InvoicePayload {
+            store_id: None,
+            created_by: None,
+            created_at: None,
+            expires_at: None,
+            fiat: None,
+            price: None,
+            identifier: None,
+            signed: None,
+            metadata: None,
+            customer_email: None,
+        }
+    }
+
+    pub fn set_created_at(&mut self) {
+        self.created_at = Some(Utc::now());
+    }
+
+    pub fn set_expires_at(&mut self) {
+        self.expires_at = Some(Utc::now() + Duration::seconds(3600))
+    }
+}
+
+/// Holds the fiat price of a checkout. The crypto charge is only worked out once the customer
+/// picks a currency, at which point a payment pointing back to the invoice is created.
+#[derive(Debug, Identifiable, Queryable, Associations, Clone, Serialize)]
+#[belongs_to(Store, foreign_key = "store_id")]
+pub struct Invoice {
+    pub id: Uuid,
+    #[serde(skip_serializing)]
+    pub store_id: Uuid,
+    #[serde(skip_serializing)]
+    pub created_by: Uuid,
+    #[serde(skip_serializing)]
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing)]
+    pub expires_at: DateTime<Utc>,
+    pub fiat: Fiat,
+    pub price: BigDecimal,
+    pub identifier: Option<String>,
+    pub signed: bool,
+    pub metadata: Option<Value>,
+    #[serde(skip_serializing)]
+    pub customer_email: Option<String>,
+}
+
+impl Invoice {
+    pub fn insert(
+        mut payload: InvoicePayload,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Invoice, Error = Error> {
+        payload.set_created_at();
+        payload.set_expires_at();
+
+        (*postgres)
+            .send(Insert(payload))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_by_id(
+        id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Invoice, Error = Error> {
+        (*postgres)
+            .send(FindById(id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn store(&self, postgres: &PgExecutorAddr) -> impl Future<Item = Store, Error = Error> {
+        Store::find_by_id_with_deleted(self.store_id, postgres)
+    }
+
+    pub fn payments(
+        &self,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Payment>, Error = Error> {
+        Payment::find_all_by_invoice(self.id, postgres)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at < Utc::now()
+    }
+
+    pub fn export(&self) -> Value {
+        json!({
+            "id": self.id,
+            "fiat": self.fiat,
+            "price": self.price,
+            "identifier": self.identifier,
+            "signed": self.signed,
+            "metadata": self.metadata,
+            "expires_at": self.expires_at.timestamp(),
+        })
+    }
+}
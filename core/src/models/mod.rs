@@ -2,11 +2,21 @@ mod errors;
 
 pub use self::errors::Error;
 
+pub mod audit_log;
 pub mod bitcoin;
 pub mod client_token;
+pub mod email_template;
 pub mod ethereum;
+pub mod idempotency_key;
+pub mod invoice;
+pub mod notification;
 pub mod payment;
 pub mod payout;
+pub mod payout_address_change;
+pub mod payout_leg;
+pub mod retired_store_key;
+pub mod session;
 pub mod store;
+pub mod store_membership;
 pub mod user;
 pub mod voucher;
@@ -0,0 +1,98 @@
+use chrono::prelude::*;
+use futures::Future;
+use uuid::Uuid;
+
+use db::{
+    notifications::{Claim, Delete, DeleteExpired, FindNotified},
+    postgres::PgExecutorAddr,
+};
+use models::Error;
+use schema::notifications;
+use types::NotificationKind;
+
+/// How long sent notifications are remembered for. Only recent payments and payouts are looked
+/// at, so older ones can't come up again.
+pub const NOTIFICATION_DAYS: i64 = 7;
+
+#[derive(Debug, Insertable)]
+#[table_name = "notifications"]
+pub struct NotificationPayload {
+    pub store_id: Uuid,
+    pub kind: NotificationKind,
+    pub subject_id: Uuid,
+    pub period: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl NotificationPayload {
+    pub fn new(
+        store_id: Uuid,
+        kind: NotificationKind,
+        subject_id: Uuid,
+        period: Option<NaiveDate>,
+    ) -> Self {
+        NotificationPayload {
+            store_id,
+            kind,
+            subject_id,
+            period,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// An email that was sent, about the payment or payout `subject_id` or, for daily summaries,
+/// about the store's `period`.
+#[derive(Debug, Identifiable, Queryable)]
+pub struct Notification {
+    pub id: Uuid,
+    pub store_id: Uuid,
+    pub kind: NotificationKind,
+    pub subject_id: Uuid,
+    pub period: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Notification {
+    /// Returns `None` if the notification was sent already.
+    pub fn claim(
+        payload: NotificationPayload,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Option<Notification>, Error = Error> {
+        (*postgres)
+            .send(Claim(payload))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn delete(id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = usize, Error = Error> {
+        (*postgres)
+            .send(Delete(id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Which of `subject_ids` the notification was sent for already.
+    pub fn find_notified(
+        kind: NotificationKind,
+        subject_ids: Vec<Uuid>,
+        period: Option<NaiveDate>,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Uuid>, Error = Error> {
+        (*postgres)
+            .send(FindNotified {
+                kind,
+                subject_ids,
+                period,
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn delete_expired(postgres: &PgExecutorAddr) -> impl Future<Item = usize, Error = Error> {
+        (*postgres)
+            .send(DeleteExpired)
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+}
@@ -1,14 +1,21 @@
-use std::convert::From;
+use std::{convert::From, str::FromStr};
 
 use bigdecimal::BigDecimal;
 use chrono::{prelude::*, Duration};
+use diesel::sql_types::{
+    BigInt, Double, Nullable, Numeric, Text, Timestamp, Timestamptz, Uuid as SqlUuid,
+};
 use futures::Future;
 use serde_json::Value;
 use uuid::Uuid;
 
 use db::{
-    payments::{FindAllByAddress, FindById, Insert, Update},
+    payments::{
+        FindAllByAddress, FindAllByInvoice, FindAllByStore, FindById, FindByStore, FindForExport,
+        FindInsufficientAmountsDue, FindLargeDue, FindReceiptsDue, Insert, Stats, Update,
+    },
     postgres::PgExecutorAddr,
+    redis::{Publish, RedisExecutorAddr},
 };
 use models::{store::Store, Error};
 use schema::payments;
@@ -16,7 +23,7 @@ use types::{
     bitcoin::Network as BtcNetwork,
     currency::{Crypto, Fiat},
     ethereum::Network as EthNetwork,
-    PaymentStatus, H256, U128,
+    PaymentStatus, PayoutAction, PayoutStatus, H256, U128,
 };
 
 #[derive(Debug, Insertable, AsChangeset, Serialize, Clone)]
@@ -41,6 +48,11 @@ pub struct PaymentPayload {
     pub btc_network: Option<BtcNetwork>,
     pub eth_network: Option<EthNetwork>,
     pub identifier: Option<String>,
+    pub invoice_id: Option<Uuid>,
+    pub hd_path: Option<String>,
+    pub signed: Option<bool>,
+    pub metadata: Option<Value>,
+    pub customer_email: Option<String>,
 }
 
 impl PaymentPayload {
@@ -65,6 +77,11 @@ impl PaymentPayload {
             btc_network: None,
             eth_network: None,
             identifier: None,
+            invoice_id: None,
+            hd_path: None,
+            signed: None,
+            metadata: None,
+            customer_email: None,
         }
     }
 
@@ -103,11 +120,19 @@ impl From<Payment> for PaymentPayload {
             btc_network: payment.btc_network,
             eth_network: payment.eth_network,
             identifier: payment.identifier,
+            invoice_id: payment.invoice_id,
+            hd_path: Some(payment.hd_path),
+            signed: Some(payment.signed),
+            metadata: payment.metadata,
+            customer_email: payment.customer_email,
         }
     }
 }
 
-#[derive(Debug, Identifiable, Queryable, Associations, Clone, Serialize, Deserialize)]
+#[derive(
+    Debug, Identifiable, Queryable, QueryableByName, Associations, Clone, Serialize, Deserialize,
+)]
+#[table_name = "payments"]
 #[belongs_to(Store, foreign_key = "store_id")]
 pub struct Payment {
     pub id: Uuid,
@@ -141,9 +166,98 @@ pub struct Payment {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub eth_network: Option<EthNetwork>,
     pub identifier: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invoice_id: Option<Uuid>,
+    #[serde(skip_serializing)]
+    pub hd_path: String,
+    /// Whether the price and identifier came from a checkout signed by the merchant.
+    #[serde(default)]
+    pub signed: bool,
+    pub metadata: Option<Value>,
+    /// Where the receipt goes. Kept out of exports, payments are readable by anyone with the id.
+    #[serde(skip_serializing, default)]
+    pub customer_email: Option<String>,
+}
+
+/// Published on the payment's channel whenever its status changes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusEvent {
+    pub id: Uuid,
+    pub status: PaymentStatus,
+    pub block_height_required: Option<U128>,
+}
+
+/// A row of `Payment::stats`, the payments of one bucket with the same status and currencies.
+#[derive(Debug, QueryableByName)]
+pub struct PaymentStats {
+    /// Start of the bucket, in UTC.
+    #[sql_type = "Timestamp"]
+    pub bucket: NaiveDateTime,
+    #[sql_type = "Text"]
+    pub status: PaymentStatus,
+    #[sql_type = "Text"]
+    pub crypto: Crypto,
+    #[sql_type = "Text"]
+    pub fiat: Fiat,
+    #[sql_type = "BigInt"]
+    pub count: i64,
+    #[sql_type = "Numeric"]
+    pub amount_paid: BigDecimal,
+    #[sql_type = "Numeric"]
+    pub fiat_paid: BigDecimal,
+    /// How many of the payments were paid, `seconds_to_paid` adds up how long it took them.
+    #[sql_type = "BigInt"]
+    pub paid_count: i64,
+    #[sql_type = "Double"]
+    pub seconds_to_paid: f64,
+}
+
+/// A row of `Payment::find_for_export`, a payment along with its payout. `paid_out` and `fees`
+/// are in the payment's crypto and only known once the payout was sent.
+#[derive(Debug, QueryableByName, Serialize)]
+pub struct PaymentExport {
+    #[sql_type = "SqlUuid"]
+    pub id: Uuid,
+    #[sql_type = "Timestamptz"]
+    pub created_at: DateTime<Utc>,
+    #[sql_type = "Nullable<Timestamptz>"]
+    pub paid_at: Option<DateTime<Utc>>,
+    #[sql_type = "Text"]
+    pub status: PaymentStatus,
+    #[sql_type = "Nullable<Text>"]
+    pub identifier: Option<String>,
+    #[sql_type = "Text"]
+    pub fiat: Fiat,
+    #[sql_type = "Numeric"]
+    pub price: BigDecimal,
+    #[sql_type = "Text"]
+    pub crypto: Crypto,
+    #[sql_type = "Numeric"]
+    pub charge: BigDecimal,
+    #[sql_type = "Nullable<Numeric>"]
+    pub amount_paid: Option<BigDecimal>,
+    #[sql_type = "Nullable<Text>"]
+    pub transaction_hash: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    pub payout_status: Option<PayoutStatus>,
+    #[sql_type = "Nullable<Text>"]
+    pub payout_action: Option<PayoutAction>,
+    #[sql_type = "Nullable<Timestamptz>"]
+    pub payout_created_at: Option<DateTime<Utc>>,
+    #[sql_type = "Nullable<Numeric>"]
+    pub paid_out: Option<BigDecimal>,
+    #[sql_type = "Nullable<Numeric>"]
+    pub fees: Option<BigDecimal>,
+    /// Space separated, split payouts have a transaction per leg.
+    #[sql_type = "Nullable<Text>"]
+    pub payout_transaction_hashes: Option<String>,
 }
 
 impl Payment {
+    pub fn channel(id: Uuid) -> String {
+        format!("payments:{}", id)
+    }
+
     pub fn insert(
         mut payload: PaymentPayload,
         postgres: &PgExecutorAddr,
@@ -196,7 +310,292 @@ impl Payment {
             .and_then(|res| res.map_err(|e| Error::from(e)))
     }
 
+    pub fn find_all_by_invoice(
+        invoice_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Payment>, Error = Error> {
+        (*postgres)
+            .send(FindAllByInvoice(invoice_id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_all_by_store(
+        store_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Payment>, Error = Error> {
+        (*postgres)
+            .send(FindAllByStore(store_id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Newest first.
+    pub fn find_by_store(
+        store_id: Uuid,
+        limit: i64,
+        offset: i64,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Payment>, Error = Error> {
+        (*postgres)
+            .send(FindByStore {
+                store_id,
+                limit,
+                offset,
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_receipts_due(
+        since: DateTime<Utc>,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Payment>, Error = Error> {
+        (*postgres)
+            .send(FindReceiptsDue(since))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_large_due(
+        since: DateTime<Utc>,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Payment>, Error = Error> {
+        (*postgres)
+            .send(FindLargeDue(since))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_insufficient_amounts_due(
+        since: DateTime<Utc>,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Payment>, Error = Error> {
+        (*postgres)
+            .send(FindInsufficientAmountsDue(since))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn stats(
+        store_id: Uuid,
+        bucket: String,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<PaymentStats>, Error = Error> {
+        (*postgres)
+            .send(Stats {
+                store_id,
+                bucket,
+                from,
+                to,
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// A page of the store's payments with their payouts, starting after the payment created at
+    /// `after`. See `db::payments::find_for_export`.
+    pub fn find_for_export(
+        store_id: Uuid,
+        status: Option<PaymentStatus>,
+        to: DateTime<Utc>,
+        after: (DateTime<Utc>, Uuid),
+        limit: i64,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<PaymentExport>, Error = Error> {
+        (*postgres)
+            .send(FindForExport {
+                store_id,
+                status,
+                to,
+                after,
+                limit,
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn notify(&self, redis: &RedisExecutorAddr) {
+        let event = StatusEvent {
+            id: self.id,
+            status: self.status.clone(),
+            block_height_required: self.block_height_required,
+        };
+
+        (*redis).do_send(Publish {
+            key: Payment::channel(self.id),
+            value: serde_json::to_string(&event).unwrap(),
+        });
+    }
+
+    pub fn apply(&mut self, event: StatusEvent) {
+        self.status = event.status;
+        self.block_height_required = event.block_height_required;
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at < Utc::now()
+    }
+
+    /// Whether the payment has enough confirmations for a voucher to be issued.
+    pub fn is_settled(&self) -> bool {
+        match self.status {
+            PaymentStatus::Paid => self.confirmations_required == 0,
+            PaymentStatus::Confirmed | PaymentStatus::Completed => true,
+            _ => false,
+        }
+    }
+
+    pub fn remaining_confirmations(&self, block_height: U128) -> U128 {
+        let mut remaining_confirmations = U128::from(self.confirmations_required);
+
+        if self.status == PaymentStatus::Paid && self.confirmations_required == 0 {
+            remaining_confirmations = U128::from(0);
+        }
+
+        if let Some(block_height_required) = self.block_height_required {
+            if block_height_required < block_height {
+                remaining_confirmations = U128::from(0);
+            } else {
+                remaining_confirmations = block_height_required - block_height;
+            }
+        }
+
+        remaining_confirmations
+    }
+
+    /// Payment URI for wallets: BIP21 for BTC with the charge in BTC, EIP-681 for ETH with the
+    /// charge in wei and the chain id. The label is only part of BIP21.
+    pub fn uri(&self, label: Option<&str>) -> String {
+        match self.crypto {
+            Crypto::Btc => {
+                let mut uri = format!(
+                    "bitcoin:{}?amount={}",
+                    self.address,
+                    btc_amount(&self.charge)
+                );
+
+                if let Some(label) = label {
+                    uri.push_str("&label=");
+                    uri.push_str(&percent_encode(label));
+                }
+
+                uri
+            }
+            Crypto::Eth => {
+                let wei = eth_value(&self.charge);
+
+                match self.eth_network {
+                    Some(network) => format!(
+                        "ethereum:{}@{}?value={}",
+                        self.address,
+                        network.chain_id(),
+                        wei
+                    ),
+                    None => format!("ethereum:{}?value={}", self.address, wei),
+                }
+            }
+        }
+    }
+
     pub fn export(&self) -> Value {
         serde_json::to_value(self).unwrap()
     }
+
+    pub fn export_status(&self, block_height: U128) -> Value {
+        json!({
+            "status": self.status,
+            "confirmations_required": self.confirmations_required,
+            "remaining_confirmations": self.remaining_confirmations(block_height),
+            // Without the store's name, so it can be built without looking the store up.
+            "uri": self.uri(None),
+        })
+    }
+}
+
+/// The charge in BTC, rounded up to the satoshi. Wallets won't pay a fraction of one, and rounding
+/// down would leave the payment short.
+fn btc_amount(charge: &BigDecimal) -> String {
+    let satoshi = BigDecimal::from_str("100000000").unwrap();
+    let amount = (round_up(charge, &satoshi) / satoshi).with_scale(8);
+
+    trim_decimal(&amount.to_string()).to_owned()
+}
+
+/// The charge in wei, rounded up.
+fn eth_value(charge: &BigDecimal) -> BigDecimal {
+    round_up(
+        charge,
+        &BigDecimal::from_str("1000000000000000000").unwrap(),
+    )
+}
+
+/// `value` in the currency's smallest unit, `units` of which make one, rounded up to a whole one.
+fn round_up(value: &BigDecimal, units: &BigDecimal) -> BigDecimal {
+    let value = value.clone() * units.clone();
+    let whole = value.with_scale(0);
+
+    if whole < value {
+        whole + BigDecimal::from_str("1").unwrap()
+    } else {
+        whole
+    }
+}
+
+fn trim_decimal(value: &str) -> &str {
+    if value.contains('.') {
+        value.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        value
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn btc_amount_rounds_up_to_the_satoshi() {
+        // 25.50 USD at 0.00012345 BTC per USD.
+        let charge = decimal("25.50") * decimal("0.00012345");
+
+        assert_eq!(charge, decimal("0.003147975"));
+        assert_eq!(btc_amount(&charge), "0.00314798");
+    }
+
+    #[test]
+    fn btc_amount_keeps_whole_satoshi() {
+        assert_eq!(btc_amount(&decimal("0.00314700")), "0.003147");
+        assert_eq!(btc_amount(&decimal("1.00000000")), "1");
+    }
+
+    #[test]
+    fn eth_value_rounds_up_to_the_wei() {
+        assert_eq!(
+            eth_value(&decimal("0.0123456789012345678")),
+            decimal("12345678901234568")
+        );
+        assert_eq!(eth_value(&decimal("0.5")), decimal("500000000000000000"));
+    }
 }
@@ -2,11 +2,15 @@ use std::convert::From;
 
 use bigdecimal::BigDecimal;
 use chrono::prelude::*;
+use diesel::sql_types::{BigInt, Text};
 use futures::Future;
 use uuid::Uuid;
 
 use db::{
-    payouts::{FindAllConfirmed, InsertBtc, InsertEth, Update, UpdateWithPayment},
+    payouts::{
+        FindAllConfirmed, FindFailedDue, InsertBtc, InsertEth, RefundPending, Stats, Update,
+        UpdateWithPayment,
+    },
     postgres::PgExecutorAddr,
 };
 use models::{
@@ -63,7 +67,18 @@ impl From<Payout> for PayoutPayload {
     }
 }
 
-#[derive(Debug, Identifiable, Queryable, Associations, Clone, Copy, Serialize, Deserialize)]
+#[derive(
+    Debug,
+    Identifiable,
+    Queryable,
+    QueryableByName,
+    Associations,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+)]
+#[table_name = "payouts"]
 #[belongs_to(Store, foreign_key = "store_id")]
 #[belongs_to(Payment, foreign_key = "payment_id")]
 pub struct Payout {
@@ -78,6 +93,19 @@ pub struct Payout {
     pub created_at: DateTime<Utc>,
 }
 
+/// A row of `Payout::stats`.
+#[derive(Debug, QueryableByName)]
+pub struct PayoutStats {
+    #[sql_type = "Text"]
+    pub status: PayoutStatus,
+    #[sql_type = "Text"]
+    pub action: PayoutAction,
+    #[sql_type = "Text"]
+    pub typ: Crypto,
+    #[sql_type = "BigInt"]
+    pub count: i64,
+}
+
 impl Payout {
     pub fn store(&self, postgres: &PgExecutorAddr) -> impl Future<Item = Store, Error = Error> {
         Store::find_by_id_with_deleted(self.store_id, postgres)
@@ -127,14 +155,23 @@ impl Payout {
             _ => payout_payload.action = Some(PayoutAction::Refund),
         };
 
-        (*postgres)
-            .send(InsertBtc {
-                payout_payload,
-                payment_payload,
-                transaction_payload: transaction,
-            })
-            .from_err()
-            .and_then(|res| res.map_err(|e| Error::from(e)))
+        let postgres = postgres.clone();
+
+        Store::find_by_id_with_deleted(payment.store_id, &postgres).and_then(move |store| {
+            // Watch-only stores sweep their own funds, the payout is only kept for the record.
+            if store.is_watch_only() {
+                payout_payload.status = Some(PayoutStatus::Skipped);
+            }
+
+            postgres
+                .send(InsertBtc {
+                    payout_payload,
+                    payment_payload,
+                    transaction_payload: transaction,
+                })
+                .from_err()
+                .and_then(|res| res.map_err(|e| Error::from(e)))
+        })
     }
 
     pub fn insert_eth_payout(
@@ -177,14 +214,23 @@ impl Payout {
             _ => payout_payload.action = Some(PayoutAction::Refund),
         };
 
-        (*postgres)
-            .send(InsertEth {
-                payout_payload,
-                payment_payload,
-                transaction_payload: transaction,
-            })
-            .from_err()
-            .and_then(|res| res.map_err(|e| Error::from(e)))
+        let postgres = postgres.clone();
+
+        Store::find_by_id_with_deleted(payment.store_id, &postgres).and_then(move |store| {
+            // Watch-only stores sweep their own funds, the payout is only kept for the record.
+            if store.is_watch_only() {
+                payout_payload.status = Some(PayoutStatus::Skipped);
+            }
+
+            postgres
+                .send(InsertEth {
+                    payout_payload,
+                    payment_payload,
+                    transaction_payload: transaction,
+                })
+                .from_err()
+                .and_then(|res| res.map_err(|e| Error::from(e)))
+        })
     }
 
     pub fn find_all_confirmed(
@@ -214,7 +260,7 @@ impl Payout {
         payout_payload: PayoutPayload,
         payment_payload: PaymentPayload,
         postgres: &PgExecutorAddr,
-    ) -> impl Future<Item = Payout, Error = Error> {
+    ) -> impl Future<Item = (Payout, Payment), Error = Error> {
         (*postgres)
             .send(UpdateWithPayment {
                 id,
@@ -224,4 +270,37 @@ impl Payout {
             .from_err()
             .and_then(|res| res.map_err(|e| Error::from(e)))
     }
+    /// Refunds the payment instead of paying it out, which is only possible for ETH payouts that
+    /// are still pending. Returns `None` if there is no such payout.
+    pub fn refund_pending(
+        payment_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Option<Payout>, Error = Error> {
+        (*postgres)
+            .send(RefundPending(payment_id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_failed_due(
+        since: DateTime<Utc>,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Payout>, Error = Error> {
+        (*postgres)
+            .send(FindFailedDue(since))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn stats(
+        store_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<PayoutStats>, Error = Error> {
+        (*postgres)
+            .send(Stats { store_id, from, to })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
 }
@@ -0,0 +1,143 @@
+use chrono::{prelude::*, Duration};
+use futures::Future;
+use serde_json::Value;
+use uuid::Uuid;
+
+use db::{
+    payout_address_changes::{Apply, Cancel, Confirm, FindDue, FindPendingByStore, Insert},
+    postgres::PgExecutorAddr,
+};
+use models::{store::Store, Error};
+use schema::payout_address_changes;
+use types::{bitcoin::Address as BtcAddress, H160};
+
+/// How long the emailed confirmation link stays valid.
+const CONFIRMATION_HOURS: i64 = 24;
+
+#[derive(Debug, Insertable)]
+#[table_name = "payout_address_changes"]
+pub struct PayoutAddressChangePayload {
+    pub store_id: Uuid,
+    pub eth_payout_addresses: Option<Vec<H160>>,
+    pub btc_payout_addresses: Option<Vec<BtcAddress>>,
+    pub confirmation_token_expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub eth_payout_splits: Option<Vec<i32>>,
+    pub btc_payout_splits: Option<Vec<i32>>,
+}
+
+impl PayoutAddressChangePayload {
+    pub fn new(store_id: Uuid) -> Self {
+        let now = Utc::now();
+
+        PayoutAddressChangePayload {
+            store_id,
+            eth_payout_addresses: None,
+            btc_payout_addresses: None,
+            confirmation_token_expires_at: now + Duration::hours(CONFIRMATION_HOURS),
+            created_at: now,
+            eth_payout_splits: None,
+            btc_payout_splits: None,
+        }
+    }
+}
+
+/// New payout addresses for a store. `None` leaves the store's addresses for that currency as
+/// they are, new addresses replace the splits along with them.
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[belongs_to(Store, foreign_key = "store_id")]
+pub struct PayoutAddressChange {
+    pub id: Uuid,
+    pub store_id: Uuid,
+    pub eth_payout_addresses: Option<Vec<H160>>,
+    pub btc_payout_addresses: Option<Vec<BtcAddress>>,
+    pub confirmation_token: Uuid,
+    pub confirmation_token_expires_at: DateTime<Utc>,
+    pub cancellation_token: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub effective_at: Option<DateTime<Utc>>,
+    pub applied_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub eth_payout_splits: Option<Vec<i32>>,
+    pub btc_payout_splits: Option<Vec<i32>>,
+}
+
+impl PayoutAddressChange {
+    /// Records a pending change, cancelling any other change of the store still pending.
+    pub fn insert(
+        payload: PayoutAddressChangePayload,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = PayoutAddressChange, Error = Error> {
+        (*postgres)
+            .send(Insert(payload))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_pending_by_store(
+        store_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Option<PayoutAddressChange>, Error = Error> {
+        (*postgres)
+            .send(FindPendingByStore(store_id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn confirm(
+        token: Uuid,
+        delay: Duration,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = PayoutAddressChange, Error = Error> {
+        (*postgres)
+            .send(Confirm { token, delay })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn cancel(
+        token: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = PayoutAddressChange, Error = Error> {
+        (*postgres)
+            .send(Cancel(token))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_due(
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<PayoutAddressChange>, Error = Error> {
+        (*postgres)
+            .send(FindDue)
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn apply(
+        id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = (PayoutAddressChange, Store), Error = Error> {
+        (*postgres)
+            .send(Apply(id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Leaves out the tokens, those only ever go out by email.
+    pub fn export(&self) -> Value {
+        json!({
+            "id": self.id,
+            "store_id": self.store_id,
+            "eth_payout_addresses": self.eth_payout_addresses,
+            "btc_payout_addresses": self.btc_payout_addresses,
+            "eth_payout_splits": self.eth_payout_splits,
+            "btc_payout_splits": self.btc_payout_splits,
+            "confirmed": self.confirmed_at.is_some(),
+            "confirmation_expires_at": self.confirmation_token_expires_at.timestamp(),
+            "effective_at": self.effective_at.map(|effective_at| effective_at.timestamp()),
+            "created_at": self.created_at.timestamp(),
+        })
+    }
+}
@@ -0,0 +1,77 @@
+use chrono::prelude::*;
+use futures::Future;
+use uuid::Uuid;
+
+use db::{
+    payout_legs::{FindByPayout, Insert},
+    postgres::PgExecutorAddr,
+};
+use models::{payout::Payout, Error};
+use schema::payout_legs;
+use types::{H256, U128, U256};
+
+#[derive(Debug, Insertable)]
+#[table_name = "payout_legs"]
+pub struct PayoutLegPayload {
+    pub payout_id: Uuid,
+    pub leg: i32,
+    pub address: String,
+    pub value: U256,
+    pub transaction_hash: H256,
+    pub created_at: DateTime<Utc>,
+    pub nonce: Option<U128>,
+    pub raw_transaction: Option<String>,
+}
+
+impl PayoutLegPayload {
+    pub fn new(payout_id: Uuid, leg: i32, address: String, value: U256, hash: H256) -> Self {
+        PayoutLegPayload {
+            payout_id,
+            leg,
+            address,
+            value,
+            transaction_hash: hash,
+            created_at: Utc::now(),
+            nonce: None,
+            raw_transaction: None,
+        }
+    }
+}
+
+/// One transfer of a payout, in satoshi or wei. Split payouts have a leg per payout address. ETH
+/// legs keep their nonce and signed transaction, they are recorded before being broadcast.
+#[derive(Debug, Identifiable, Queryable, Associations, Serialize)]
+#[belongs_to(Payout, foreign_key = "payout_id")]
+pub struct PayoutLeg {
+    pub id: Uuid,
+    pub payout_id: Uuid,
+    pub leg: i32,
+    pub address: String,
+    pub value: U256,
+    pub transaction_hash: H256,
+    pub created_at: DateTime<Utc>,
+    pub nonce: Option<U128>,
+    pub raw_transaction: Option<String>,
+}
+
+impl PayoutLeg {
+    pub fn insert(
+        payload: PayoutLegPayload,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = PayoutLeg, Error = Error> {
+        (*postgres)
+            .send(Insert(payload))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_by_payout(
+        payout_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<PayoutLeg>, Error = Error> {
+        (*postgres)
+            .send(FindByPayout(payout_id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+}
@@ -0,0 +1,69 @@
+use chrono::{prelude::*, Duration};
+use futures::Future;
+use uuid::Uuid;
+
+use db::{
+    postgres::PgExecutorAddr,
+    retired_store_keys::{FindById, FindByStore},
+};
+use models::{store::Store, Error};
+use schema::retired_store_keys;
+use types::PublicKey;
+
+/// Retired keys are still published for this long after a rotation, so merchants caching the
+/// JWKS document can keep verifying vouchers signed just before it.
+const RETENTION_DAYS: i64 = 7;
+
+#[derive(Debug, Insertable)]
+#[table_name = "retired_store_keys"]
+pub struct RetiredStoreKeyPayload {
+    pub id: Uuid,
+    pub store_id: Uuid,
+    pub public_key: PublicKey,
+    pub retired_at: DateTime<Utc>,
+}
+
+impl RetiredStoreKeyPayload {
+    pub fn from_store(store: Store) -> Self {
+        RetiredStoreKeyPayload {
+            id: store.key_id,
+            store_id: store.id,
+            public_key: store.public_key,
+            retired_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[belongs_to(Store, foreign_key = "store_id")]
+pub struct RetiredStoreKey {
+    pub id: Uuid,
+    pub store_id: Uuid,
+    pub public_key: PublicKey,
+    pub retired_at: DateTime<Utc>,
+}
+
+impl RetiredStoreKey {
+    pub fn find_by_id(
+        id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = RetiredStoreKey, Error = Error> {
+        (*postgres)
+            .send(FindById(id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_published_by_store(
+        store_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<RetiredStoreKey>, Error = Error> {
+        (*postgres)
+            .send(FindByStore {
+                store_id,
+                retired_since: Utc::now() - Duration::days(RETENTION_DAYS),
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+}
@@ -0,0 +1,157 @@
+use chrono::{prelude::*, Duration};
+use futures::Future;
+use hex;
+use ring::digest;
+use serde_json::Value;
+use uuid::Uuid;
+
+use db::{
+    postgres::PgExecutorAddr,
+    sessions::{
+        DeleteUsedRefreshTokens, FindActive, FindByUser, Insert, Refresh, Revoke, RevokeByUser,
+    },
+};
+use models::Error;
+use schema::sessions;
+
+/// How long a session lasts without being refreshed.
+pub const REFRESH_TOKEN_DAYS: i64 = 30;
+
+/// Refresh tokens are only stored as their SHA-256, so reading the database doesn't give anyone
+/// a session. They're random UUIDs, a salt wouldn't add anything.
+pub fn hash_refresh_token(token: &Uuid) -> String {
+    hex::encode(digest::digest(&digest::SHA256, token.to_string().as_bytes()).as_ref())
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "sessions"]
+pub struct SessionPayload {
+    pub user_id: Uuid,
+    pub refresh_token_hash: String,
+    pub refresh_token_expires_at: DateTime<Utc>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+}
+
+impl SessionPayload {
+    pub fn new(user_id: Uuid, refresh_token: &Uuid) -> Self {
+        let now = Utc::now();
+
+        SessionPayload {
+            user_id,
+            refresh_token_hash: hash_refresh_token(refresh_token),
+            refresh_token_expires_at: now + Duration::days(REFRESH_TOKEN_DAYS),
+            ip: None,
+            user_agent: None,
+            created_at: now,
+            last_used_at: now,
+        }
+    }
+}
+
+/// A signed in device of a user. The refresh token is replaced every time it's used.
+#[derive(Debug, Identifiable, Queryable)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub refresh_token_expires_at: DateTime<Utc>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub refresh_token_hash: String,
+}
+
+impl Session {
+    pub fn insert(
+        payload: SessionPayload,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Session, Error = Error> {
+        (*postgres)
+            .send(Insert(payload))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_active(
+        id: Uuid,
+        user_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Option<Session>, Error = Error> {
+        (*postgres)
+            .send(FindActive { id, user_id })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_by_user(
+        user_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Session>, Error = Error> {
+        (*postgres)
+            .send(FindByUser(user_id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Swaps `token` for `next_token`. `None` if `token` isn't the session's current refresh
+    /// token; one that was swapped before revokes its session.
+    pub fn refresh(
+        token: &Uuid,
+        next_token: &Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Option<Session>, Error = Error> {
+        (*postgres)
+            .send(Refresh {
+                token_hash: hash_refresh_token(token),
+                next_token_hash: hash_refresh_token(next_token),
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn revoke(
+        id: Uuid,
+        user_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = usize, Error = Error> {
+        (*postgres)
+            .send(Revoke { id, user_id })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn revoke_by_user(
+        user_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = usize, Error = Error> {
+        (*postgres)
+            .send(RevokeByUser(user_id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn delete_used_refresh_tokens(
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = usize, Error = Error> {
+        (*postgres)
+            .send(DeleteUsedRefreshTokens)
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Leaves out the refresh token, it's only handed out when issued.
+    pub fn export(&self) -> Value {
+        json!({
+            "id": self.id,
+            "ip": self.ip,
+            "user_agent": self.user_agent,
+            "created_at": self.created_at.timestamp(),
+            "last_used_at": self.last_used_at.timestamp(),
+            "expires_at": self.refresh_token_expires_at.timestamp(),
+        })
+    }
+}
@@ -1,17 +1,27 @@
 use std::convert::From;
 
+use base64::{decode, encode};
+use bigdecimal::BigDecimal;
 use chrono::prelude::*;
 use futures::Future;
 use serde_json::Value;
 use uuid::Uuid;
 
+use crypto::{self, Error as CryptoError, Keychain};
 use db::{
     postgres::PgExecutorAddr,
-    stores::{FindById, FindByIdWithDeleted, FindByOwner, Insert, SoftDelete, Update},
+    stores::{
+        FindAllWithDailySummary, FindById, FindByIdWithDeleted, FindByKeyId, FindByMember, Insert,
+        NextAddressIndex, NextPayoutIndex, RotateKey, SoftDelete, Update,
+    },
 };
 use models::{user::User, Error};
 use schema::stores;
-use types::{bitcoin::Address as BtcAddress, currency::Crypto, PrivateKey, PublicKey, H160};
+use types::{
+    bitcoin::{Address as BtcAddress, AddressFormat},
+    currency::{Crypto, Fiat},
+    PrivateKey, PublicKey, H160,
+};
 
 #[derive(Debug, Insertable, AsChangeset, Deserialize)]
 #[table_name = "stores"]
@@ -31,6 +41,20 @@ pub struct StorePayload {
     pub mnemonic: Option<String>,
     pub hd_path: Option<String>,
     pub deleted_at: Option<Option<DateTime<Utc>>>,
+    pub success_url: Option<Option<String>>,
+    pub key_id: Option<Uuid>,
+    pub data_key: Option<Vec<u8>>,
+    pub key_version: Option<i32>,
+    pub btc_xpub: Option<String>,
+    pub eth_xpub: Option<String>,
+    pub passphrase: Option<String>,
+    pub eth_payout_splits: Option<Option<Vec<i32>>>,
+    pub btc_payout_splits: Option<Option<Vec<i32>>>,
+    pub checkout_secret: Option<Option<String>>,
+    pub large_payment_threshold: Option<Option<BigDecimal>>,
+    pub daily_summary: Option<bool>,
+    pub btc_address_format: Option<AddressFormat>,
+    pub large_payment_threshold_fiat: Option<Option<Fiat>>,
 }
 
 impl StorePayload {
@@ -51,6 +75,20 @@ impl StorePayload {
             mnemonic: None,
             hd_path: None,
             deleted_at: None,
+            success_url: None,
+            key_id: None,
+            data_key: None,
+            key_version: None,
+            btc_xpub: None,
+            eth_xpub: None,
+            passphrase: None,
+            eth_payout_splits: None,
+            btc_payout_splits: None,
+            checkout_secret: None,
+            large_payment_threshold: None,
+            daily_summary: None,
+            btc_address_format: None,
+            large_payment_threshold_fiat: None,
         }
     }
 
@@ -62,11 +100,57 @@ impl StorePayload {
         self.updated_at = Some(Utc::now());
     }
 
+    /// Encrypts the mnemonic, its BIP39 passphrase, the checkout secret and the voucher signing
+    /// key under a new data key. Watch-only stores have no mnemonic, and an empty passphrase
+    /// isn't stored.
+    pub fn set_secrets(
+        &mut self,
+        mnemonic: Option<&str>,
+        passphrase: Option<&str>,
+        checkout_secret: Option<&str>,
+        private_key: &[u8],
+        keychain: &Keychain,
+    ) -> Result<(), CryptoError> {
+        let (data_key, wrapped_data_key, key_version) = keychain.generate_data_key()?;
+
+        if let Some(mnemonic) = mnemonic {
+            self.mnemonic = Some(encode(&crypto::seal(
+                &data_key,
+                b"mnemonic",
+                mnemonic.as_bytes(),
+            )?));
+        }
+        if let Some(passphrase) = passphrase.filter(|passphrase| !passphrase.is_empty()) {
+            self.passphrase = Some(encode(&crypto::seal(
+                &data_key,
+                b"passphrase",
+                passphrase.as_bytes(),
+            )?));
+        }
+        if let Some(checkout_secret) = checkout_secret {
+            self.checkout_secret = Some(Some(encode(&crypto::seal(
+                &data_key,
+                b"checkout_secret",
+                checkout_secret.as_bytes(),
+            )?)));
+        }
+        self.private_key = Some(crypto::seal(&data_key, b"private_key", private_key)?);
+        self.data_key = Some(wrapped_data_key);
+        self.key_version = Some(key_version);
+
+        Ok(())
+    }
+
     pub fn set_deleted(&mut self) {
         self.name = Some(String::from(""));
         self.description = Some(String::from(""));
         self.eth_payout_addresses = Some(None);
         self.eth_confirmations_required = Some(None);
+        self.success_url = Some(None);
+        self.checkout_secret = Some(None);
+        self.large_payment_threshold = Some(None);
+        self.large_payment_threshold_fiat = Some(None);
+        self.daily_summary = Some(false);
         self.deleted_at = Some(Some(Utc::now()));
     }
 }
@@ -86,9 +170,23 @@ impl From<Store> for StorePayload {
             eth_confirmations_required: Some(store.eth_confirmations_required),
             btc_payout_addresses: Some(store.btc_payout_addresses),
             btc_confirmations_required: Some(store.btc_confirmations_required),
-            mnemonic: Some(store.mnemonic),
+            mnemonic: store.mnemonic,
             hd_path: Some(store.hd_path),
             deleted_at: Some(store.deleted_at),
+            success_url: Some(store.success_url),
+            key_id: Some(store.key_id),
+            data_key: store.data_key,
+            key_version: store.key_version,
+            btc_xpub: store.btc_xpub,
+            eth_xpub: store.eth_xpub,
+            passphrase: store.passphrase,
+            eth_payout_splits: Some(store.eth_payout_splits),
+            btc_payout_splits: Some(store.btc_payout_splits),
+            checkout_secret: Some(store.checkout_secret),
+            large_payment_threshold: Some(store.large_payment_threshold),
+            daily_summary: Some(store.daily_summary),
+            btc_address_format: Some(store.btc_address_format),
+            large_payment_threshold_fiat: Some(store.large_payment_threshold_fiat),
         }
     }
 }
@@ -108,13 +206,56 @@ pub struct Store {
     pub eth_confirmations_required: Option<i32>,
     pub btc_payout_addresses: Option<Vec<BtcAddress>>,
     pub btc_confirmations_required: Option<i32>,
-    pub mnemonic: String,
+    pub mnemonic: Option<String>,
     pub hd_path: String,
     pub deleted_at: Option<DateTime<Utc>>,
+    pub success_url: Option<String>,
+    pub key_id: Uuid,
+    pub data_key: Option<Vec<u8>>,
+    pub key_version: Option<i32>,
+    pub btc_xpub: Option<String>,
+    pub eth_xpub: Option<String>,
+    pub btc_address_index: i32,
+    pub eth_address_index: i32,
+    pub passphrase: Option<String>,
+    pub eth_payout_splits: Option<Vec<i32>>,
+    pub btc_payout_splits: Option<Vec<i32>>,
+    pub eth_payout_index: i32,
+    pub btc_payout_index: i32,
+    pub checkout_secret: Option<String>,
+    /// Payments of at least this much are reported to the owner, if they're in
+    /// `large_payment_threshold_fiat`.
+    pub large_payment_threshold: Option<BigDecimal>,
+    /// Whether the owner gets a summary of each day's payments.
+    pub daily_summary: bool,
+    /// Whether Bitcoin deposit addresses are P2PKH ones at BIP44 paths or native segwit ones at
+    /// BIP84 paths. Fixed when the store is created.
+    pub btc_address_format: AddressFormat,
+    pub large_payment_threshold_fiat: Option<Fiat>,
 }
 
 impl Store {
+    /// Watch-only stores derive their addresses from the merchant's xpubs. Finch holds no keys
+    /// for them, so there's nothing to pay out and payments end at `Confirmed`.
+    pub fn is_watch_only(&self) -> bool {
+        self.mnemonic.is_none()
+    }
+
+    pub fn xpub(&self, crypto: &Crypto) -> Option<&String> {
+        match crypto {
+            Crypto::Btc => self.btc_xpub.as_ref(),
+            Crypto::Eth => self.eth_xpub.as_ref(),
+        }
+    }
+
     pub fn can_accept(&self, crypto: &Crypto) -> bool {
+        if self.is_watch_only() {
+            return match crypto {
+                Crypto::Btc => self.btc_xpub.is_some() && self.btc_confirmations_required.is_some(),
+                Crypto::Eth => self.eth_xpub.is_some() && self.eth_confirmations_required.is_some(),
+            };
+        }
+
         match crypto {
             Crypto::Btc => {
                 self.btc_payout_addresses.is_some() && self.btc_confirmations_required.is_some()
@@ -125,6 +266,81 @@ impl Store {
         }
     }
 
+    // Stores without a key version are left over from before secrets were encrypted and still
+    // hold them in plaintext until the startup migration gets to them.
+    fn unwrap_data_key(&self, keychain: &Keychain) -> Result<Option<Vec<u8>>, CryptoError> {
+        match (self.key_version, self.data_key.as_ref()) {
+            (Some(key_version), Some(data_key)) => {
+                Ok(Some(keychain.unwrap(key_version, data_key)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns `None` for watch-only stores.
+    pub fn decrypt_mnemonic(&self, keychain: &Keychain) -> Result<Option<String>, CryptoError> {
+        let mnemonic = match self.mnemonic {
+            Some(ref mnemonic) => mnemonic,
+            None => return Ok(None),
+        };
+
+        match self.unwrap_data_key(keychain)? {
+            Some(data_key) => {
+                let sealed = decode(mnemonic).map_err(|_| CryptoError::DecryptionFailed)?;
+                let mnemonic = crypto::open(&data_key, b"mnemonic", &sealed)?;
+
+                String::from_utf8(mnemonic)
+                    .map(Some)
+                    .map_err(|_| CryptoError::DecryptionFailed)
+            }
+            None => Ok(Some(mnemonic.clone())),
+        }
+    }
+
+    /// The BIP39 passphrase, empty if the store has none.
+    pub fn decrypt_passphrase(&self, keychain: &Keychain) -> Result<String, CryptoError> {
+        let passphrase = match self.passphrase {
+            Some(ref passphrase) => passphrase,
+            None => return Ok(String::new()),
+        };
+
+        let data_key = self
+            .unwrap_data_key(keychain)?
+            .ok_or(CryptoError::DecryptionFailed)?;
+        let sealed = decode(passphrase).map_err(|_| CryptoError::DecryptionFailed)?;
+        let passphrase = crypto::open(&data_key, b"passphrase", &sealed)?;
+
+        String::from_utf8(passphrase).map_err(|_| CryptoError::DecryptionFailed)
+    }
+
+    /// The secret checkouts are signed with, `None` unless the store requires signed checkouts.
+    pub fn decrypt_checkout_secret(
+        &self,
+        keychain: &Keychain,
+    ) -> Result<Option<String>, CryptoError> {
+        let checkout_secret = match self.checkout_secret {
+            Some(ref checkout_secret) => checkout_secret,
+            None => return Ok(None),
+        };
+
+        let data_key = self
+            .unwrap_data_key(keychain)?
+            .ok_or(CryptoError::DecryptionFailed)?;
+        let sealed = decode(checkout_secret).map_err(|_| CryptoError::DecryptionFailed)?;
+        let checkout_secret = crypto::open(&data_key, b"checkout_secret", &sealed)?;
+
+        String::from_utf8(checkout_secret)
+            .map(Some)
+            .map_err(|_| CryptoError::DecryptionFailed)
+    }
+
+    pub fn decrypt_private_key(&self, keychain: &Keychain) -> Result<PrivateKey, CryptoError> {
+        match self.unwrap_data_key(keychain)? {
+            Some(data_key) => crypto::open(&data_key, b"private_key", &self.private_key),
+            None => Ok(self.private_key.clone()),
+        }
+    }
+
     pub fn insert(
         mut payload: StorePayload,
         postgres: &PgExecutorAddr,
@@ -151,15 +367,63 @@ impl Store {
             .and_then(|res| res.map_err(|e| Error::from(e)))
     }
 
-    pub fn find_by_owner(
-        owner_id: Uuid,
+    pub fn rotate_key(
+        id: Uuid,
+        mut payload: StorePayload,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Store, Error = Error> {
+        payload.key_id = Some(Uuid::new_v4());
+        payload.set_updated_at();
+
+        (*postgres)
+            .send(RotateKey { id, payload })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Reserves the next address index of a watch-only store's xpub for `crypto`.
+    pub fn next_address_index(
+        id: Uuid,
+        crypto: Crypto,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = i32, Error = Error> {
+        (*postgres)
+            .send(NextAddressIndex { id, crypto })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Advances the rotation through the store's payout addresses for `crypto`.
+    pub fn next_payout_index(
+        id: Uuid,
+        crypto: Crypto,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = i32, Error = Error> {
+        (*postgres)
+            .send(NextPayoutIndex { id, crypto })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_by_key_id(
+        key_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Store, Error = Error> {
+        (*postgres)
+            .send(FindByKeyId(key_id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_by_member(
+        user_id: Uuid,
         limit: i64,
         offset: i64,
         postgres: &PgExecutorAddr,
     ) -> impl Future<Item = Vec<Store>, Error = Error> {
         (*postgres)
-            .send(FindByOwner {
-                owner_id,
+            .send(FindByMember {
+                user_id,
                 limit,
                 offset,
             })
@@ -167,6 +431,15 @@ impl Store {
             .and_then(|res| res.map_err(|e| Error::from(e)))
     }
 
+    pub fn find_all_with_daily_summary(
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<Store>, Error = Error> {
+        (*postgres)
+            .send(FindAllWithDailySummary)
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
     pub fn find_by_id(
         id: Uuid,
         postgres: &PgExecutorAddr,
@@ -208,7 +481,19 @@ impl Store {
             "eth_confirmations_required": self.eth_confirmations_required,
             "btc_payout_addresses": self.btc_payout_addresses,
             "btc_confirmations_required": self.btc_confirmations_required,
+            "eth_payout_splits": self.eth_payout_splits,
+            "btc_payout_splits": self.btc_payout_splits,
+            "success_url": self.success_url,
+            "signed_checkout": self.checkout_secret.is_some(),
+            "large_payment_threshold": self.large_payment_threshold,
+            "large_payment_threshold_fiat": self.large_payment_threshold_fiat,
+            "daily_summary": self.daily_summary,
             "public_key": String::from_utf8_lossy(&self.public_key),
+            "key_id": self.key_id,
+            "watch_only": self.is_watch_only(),
+            "btc_xpub": self.btc_xpub,
+            "btc_address_format": self.btc_address_format,
+            "eth_xpub": self.eth_xpub,
             "can_accept_eth": self.can_accept(&Crypto::Eth),
             "can_accept_btc": self.can_accept(&Crypto::Btc),
             "created_at": self.created_at.timestamp(),
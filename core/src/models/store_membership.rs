@@ -0,0 +1,153 @@
+use chrono::{prelude::*, Duration};
+use futures::Future;
+use serde_json::Value;
+use uuid::Uuid;
+
+use db::{
+    postgres::PgExecutorAddr,
+    store_memberships::{
+        Accept, Delete, FindById, FindByStore, FindByStoreAndUser, Insert, UpdateRole,
+    },
+};
+use models::{store::Store, Error};
+use schema::store_memberships;
+use types::Role;
+
+/// How long the emailed invitation stays valid.
+const INVITATION_DAYS: i64 = 7;
+
+#[derive(Debug, Insertable)]
+#[table_name = "store_memberships"]
+pub struct StoreMembershipPayload {
+    pub store_id: Uuid,
+    pub email: String,
+    pub role: Role,
+    pub invitation_expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl StoreMembershipPayload {
+    pub fn new(store_id: Uuid, email: String, role: Role) -> Self {
+        let now = Utc::now();
+
+        StoreMembershipPayload {
+            store_id,
+            email,
+            role,
+            invitation_expires_at: now + Duration::days(INVITATION_DAYS),
+            created_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Identifiable, Queryable, Associations)]
+#[belongs_to(Store, foreign_key = "store_id")]
+pub struct StoreMembership {
+    pub id: Uuid,
+    pub store_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub email: String,
+    pub role: Role,
+    pub invitation_token: Uuid,
+    pub invitation_expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl StoreMembership {
+    /// Records an invitation, replacing one to the same email that is still pending.
+    pub fn insert(
+        payload: StoreMembershipPayload,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = StoreMembership, Error = Error> {
+        (*postgres)
+            .send(Insert(payload))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_by_id(
+        id: Uuid,
+        store_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = StoreMembership, Error = Error> {
+        (*postgres)
+            .send(FindById { id, store_id })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_by_store(
+        store_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Vec<StoreMembership>, Error = Error> {
+        (*postgres)
+            .send(FindByStore(store_id))
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn find_by_store_and_user(
+        store_id: Uuid,
+        user_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Option<StoreMembership>, Error = Error> {
+        (*postgres)
+            .send(FindByStoreAndUser { store_id, user_id })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn accept(
+        token: Uuid,
+        user_id: Uuid,
+        email: String,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = StoreMembership, Error = Error> {
+        (*postgres)
+            .send(Accept {
+                token,
+                user_id,
+                email,
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn update_role(
+        id: Uuid,
+        store_id: Uuid,
+        role: Role,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = StoreMembership, Error = Error> {
+        (*postgres)
+            .send(UpdateRole { id, store_id, role })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    pub fn delete(
+        id: Uuid,
+        store_id: Uuid,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = usize, Error = Error> {
+        (*postgres)
+            .send(Delete { id, store_id })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Leaves out the invitation token, that only ever goes out by email.
+    pub fn export(&self) -> Value {
+        json!({
+            "id": self.id,
+            "store_id": self.store_id,
+            "user_id": self.user_id,
+            "email": self.email,
+            "role": self.role,
+            "accepted": self.accepted_at.is_some(),
+            "invitation_expires_at": self.invitation_expires_at.timestamp(),
+            "created_at": self.created_at.timestamp(),
+        })
+    }
+}
@@ -5,10 +5,12 @@ use futures::Future;
 use serde_json::Value;
 use uuid::Uuid;
 
+use crypto::{self, Error as CryptoError, Keychain};
 use db::{
     postgres::PgExecutorAddr,
     users::{
         Activate, Delete, DeleteExpired, FindByEmail, FindById, FindByResetToken, Insert, Update,
+        UseRecoveryCode, UseTotpStep,
     },
 };
 use models::Error;
@@ -27,6 +29,13 @@ pub struct UserPayload {
     pub verification_token_expires_at: Option<DateTime<Utc>>,
     pub reset_token: Option<Option<Uuid>>,
     pub reset_token_expires_at: Option<Option<DateTime<Utc>>>,
+    pub totp_secret: Option<Option<Vec<u8>>>,
+    pub data_key: Option<Option<Vec<u8>>>,
+    pub key_version: Option<Option<i32>>,
+    pub totp_enabled_at: Option<Option<DateTime<Utc>>>,
+    pub totp_last_step: Option<Option<i64>>,
+    pub recovery_codes: Option<Option<Vec<String>>>,
+    pub password_changed_at: Option<Option<DateTime<Utc>>>,
 }
 
 impl UserPayload {
@@ -42,6 +51,13 @@ impl UserPayload {
             verification_token_expires_at: None,
             reset_token: None,
             reset_token_expires_at: None,
+            totp_secret: None,
+            data_key: None,
+            key_version: None,
+            totp_enabled_at: None,
+            totp_last_step: None,
+            recovery_codes: None,
+            password_changed_at: None,
         }
     }
 
@@ -63,6 +79,34 @@ impl UserPayload {
         self.reset_token = Some(Some(Uuid::new_v4()));
         self.reset_token_expires_at = Some(Some(Utc::now() + Duration::days(1)));
     }
+
+    /// Encrypts a new TOTP secret under a new data key. Two-factor authentication stays disabled
+    /// until the user confirms a code generated from it.
+    pub fn set_totp_secret(
+        &mut self,
+        secret: &[u8],
+        keychain: &Keychain,
+    ) -> Result<(), CryptoError> {
+        let (data_key, wrapped_data_key, key_version) = keychain.generate_data_key()?;
+
+        self.totp_secret = Some(Some(crypto::seal(&data_key, b"totp_secret", secret)?));
+        self.data_key = Some(Some(wrapped_data_key));
+        self.key_version = Some(Some(key_version));
+        self.totp_enabled_at = Some(None);
+        self.totp_last_step = Some(None);
+        self.recovery_codes = Some(None);
+
+        Ok(())
+    }
+
+    pub fn clear_two_factor(&mut self) {
+        self.totp_secret = Some(None);
+        self.data_key = Some(None);
+        self.key_version = Some(None);
+        self.totp_enabled_at = Some(None);
+        self.totp_last_step = Some(None);
+        self.recovery_codes = Some(None);
+    }
 }
 
 impl From<User> for UserPayload {
@@ -78,6 +122,13 @@ impl From<User> for UserPayload {
             verification_token_expires_at: Some(user.verification_token_expires_at),
             reset_token: Some(user.reset_token),
             reset_token_expires_at: Some(user.reset_token_expires_at),
+            totp_secret: Some(user.totp_secret),
+            data_key: Some(user.data_key),
+            key_version: Some(user.key_version),
+            totp_enabled_at: Some(user.totp_enabled_at),
+            totp_last_step: Some(user.totp_last_step),
+            recovery_codes: Some(user.recovery_codes),
+            password_changed_at: Some(user.password_changed_at),
         }
     }
 }
@@ -95,6 +146,13 @@ pub struct User {
     pub verification_token_expires_at: DateTime<Utc>,
     pub reset_token: Option<Uuid>,
     pub reset_token_expires_at: Option<DateTime<Utc>>,
+    pub totp_secret: Option<Vec<u8>>,
+    pub data_key: Option<Vec<u8>>,
+    pub key_version: Option<i32>,
+    pub totp_enabled_at: Option<DateTime<Utc>>,
+    pub totp_last_step: Option<i64>,
+    pub recovery_codes: Option<Vec<String>>,
+    pub password_changed_at: Option<DateTime<Utc>>,
 }
 
 impl User {
@@ -125,6 +183,45 @@ impl User {
             .and_then(|res| res.map_err(|e| Error::from(e)))
     }
 
+    /// Records `step` as the last accepted TOTP step. `None` if the same or a later step was
+    /// accepted in the meantime, i.e. the code was used already.
+    pub fn use_totp_step(
+        id: Uuid,
+        step: i64,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Option<User>, Error = Error> {
+        let mut payload = UserPayload::new();
+        payload.totp_last_step = Some(Some(step));
+        payload.set_updated_at();
+
+        (*postgres)
+            .send(UseTotpStep { id, step, payload })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
+    /// Replaces the user's `recovery_codes` with `remaining`. `None` if they changed in the
+    /// meantime, i.e. the code was used already.
+    pub fn use_recovery_code(
+        id: Uuid,
+        recovery_codes: Vec<String>,
+        remaining: Vec<String>,
+        postgres: &PgExecutorAddr,
+    ) -> impl Future<Item = Option<User>, Error = Error> {
+        let mut payload = UserPayload::new();
+        payload.recovery_codes = Some(Some(remaining));
+        payload.set_updated_at();
+
+        (*postgres)
+            .send(UseRecoveryCode {
+                id,
+                recovery_codes,
+                payload,
+            })
+            .from_err()
+            .and_then(|res| res.map_err(|e| Error::from(e)))
+    }
+
     pub fn find_by_reset_token(
         token: Uuid,
         postgres: &PgExecutorAddr,
@@ -182,10 +279,27 @@ impl User {
             .and_then(|res| res.map_err(|e| Error::from(e)))
     }
 
+    pub fn two_factor_enabled(&self) -> bool {
+        self.totp_enabled_at.is_some()
+    }
+
+    /// Returns `None` if the user never started enrolling.
+    pub fn decrypt_totp_secret(&self, keychain: &Keychain) -> Result<Option<Vec<u8>>, CryptoError> {
+        match (&self.totp_secret, &self.data_key, self.key_version) {
+            (Some(totp_secret), Some(data_key), Some(key_version)) => {
+                let data_key = keychain.unwrap(key_version, data_key)?;
+
+                Ok(Some(crypto::open(&data_key, b"totp_secret", totp_secret)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
     pub fn export(&self) -> Value {
         json!({
             "id": self.id,
             "email": self.email,
+            "two_factor_enabled": self.two_factor_enabled(),
             "created_at": self.created_at.timestamp(),
             "updated_at": self.updated_at.timestamp(),
         })
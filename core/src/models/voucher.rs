@@ -1,6 +1,6 @@
 use chrono::prelude::*;
 use jwt;
-use serde_json;
+use serde_json::{self, Value};
 use uuid::Uuid;
 
 use bigdecimal::BigDecimal;
@@ -27,6 +27,14 @@ pub struct Voucher {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub eth_network: Option<EthNetwork>,
     pub identifier: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invoice_id: Option<Uuid>,
+    /// Set when the price and identifier were signed by the merchant's backend, so they can be
+    /// trusted as is.
+    #[serde(default)]
+    pub signed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
     pub exp: u64,
 }
 
@@ -44,13 +52,17 @@ impl Voucher {
             btc_network: payment.btc_network,
             eth_network: payment.eth_network,
             identifier: payment.identifier,
+            invoice_id: payment.invoice_id,
+            signed: payment.signed,
+            metadata: payment.metadata,
             exp: exp.timestamp() as u64,
         }
     }
 
-    pub fn encode(&self, private_key: &[u8]) -> Result<String, Error> {
+    pub fn encode(&self, private_key: &[u8], key_id: Uuid) -> Result<String, Error> {
         let mut header = jwt::Header::default();
         header.alg = jwt::Algorithm::RS256;
+        header.kid = Some(key_id.to_string());
 
         jwt::encode(&header, &self, private_key).map_err(|e| Error::from(e))
     }
@@ -1,3 +1,16 @@
+table! {
+    audit_logs (id) {
+        id -> Uuid,
+        action -> Varchar,
+        store_id -> Nullable<Uuid>,
+        user_id -> Nullable<Uuid>,
+        subject_id -> Nullable<Uuid>,
+        ip -> Nullable<Varchar>,
+        changes -> Nullable<Json>,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     btc_blockchain_statuses (network) {
         network -> Varchar,
@@ -18,9 +31,26 @@ table! {
         name -> Varchar,
         token -> Uuid,
         store_id -> Uuid,
-        domain -> Varchar,
         created_at -> Timestamptz,
         typ -> Varchar,
+        allowed_origins -> Array<Varchar>,
+        allowed_cryptos -> Nullable<Array<Varchar>>,
+        allowed_fiats -> Nullable<Array<Varchar>>,
+        max_price -> Nullable<Numeric>,
+        max_price_fiat -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    email_templates (id) {
+        id -> Uuid,
+        store_id -> Uuid,
+        kind -> Varchar,
+        subject -> Varchar,
+        html -> Text,
+        text -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -47,6 +77,45 @@ table! {
     }
 }
 
+table! {
+    idempotency_keys (id) {
+        id -> Uuid,
+        scope -> Varchar,
+        key -> Varchar,
+        fingerprint -> Varchar,
+        response -> Nullable<Jsonb>,
+        created_at -> Timestamptz,
+        locked_at -> Timestamptz,
+    }
+}
+
+table! {
+    invoices (id) {
+        id -> Uuid,
+        store_id -> Uuid,
+        created_by -> Uuid,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        fiat -> Varchar,
+        price -> Numeric,
+        identifier -> Nullable<Varchar>,
+        signed -> Bool,
+        metadata -> Nullable<Jsonb>,
+        customer_email -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    notifications (id) {
+        id -> Uuid,
+        store_id -> Uuid,
+        kind -> Varchar,
+        subject_id -> Uuid,
+        period -> Nullable<Date>,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     payments (id) {
         id -> Uuid,
@@ -69,6 +138,44 @@ table! {
         btc_network -> Nullable<Varchar>,
         eth_network -> Nullable<Varchar>,
         identifier -> Nullable<Varchar>,
+        invoice_id -> Nullable<Uuid>,
+        hd_path -> Varchar,
+        signed -> Bool,
+        metadata -> Nullable<Jsonb>,
+        customer_email -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    payout_address_changes (id) {
+        id -> Uuid,
+        store_id -> Uuid,
+        eth_payout_addresses -> Nullable<Array<Text>>,
+        btc_payout_addresses -> Nullable<Array<Text>>,
+        confirmation_token -> Uuid,
+        confirmation_token_expires_at -> Timestamptz,
+        cancellation_token -> Uuid,
+        created_at -> Timestamptz,
+        confirmed_at -> Nullable<Timestamptz>,
+        effective_at -> Nullable<Timestamptz>,
+        applied_at -> Nullable<Timestamptz>,
+        cancelled_at -> Nullable<Timestamptz>,
+        eth_payout_splits -> Nullable<Array<Int4>>,
+        btc_payout_splits -> Nullable<Array<Int4>>,
+    }
+}
+
+table! {
+    payout_legs (id) {
+        id -> Uuid,
+        payout_id -> Uuid,
+        leg -> Int4,
+        address -> Varchar,
+        value -> Numeric,
+        transaction_hash -> Varchar,
+        created_at -> Timestamptz,
+        nonce -> Nullable<Numeric>,
+        raw_transaction -> Nullable<Varchar>,
     }
 }
 
@@ -86,6 +193,43 @@ table! {
     }
 }
 
+table! {
+    retired_store_keys (id) {
+        id -> Uuid,
+        store_id -> Uuid,
+        public_key -> Bytea,
+        retired_at -> Timestamptz,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        refresh_token_expires_at -> Timestamptz,
+        ip -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+        last_used_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        refresh_token_hash -> Varchar,
+    }
+}
+
+table! {
+    store_memberships (id) {
+        id -> Uuid,
+        store_id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        email -> Varchar,
+        role -> Varchar,
+        invitation_token -> Uuid,
+        invitation_expires_at -> Timestamptz,
+        accepted_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     stores (id) {
         id -> Uuid,
@@ -100,9 +244,35 @@ table! {
         eth_confirmations_required -> Nullable<Int4>,
         btc_payout_addresses -> Nullable<Array<Text>>,
         btc_confirmations_required -> Nullable<Int4>,
-        mnemonic -> Varchar,
+        mnemonic -> Nullable<Varchar>,
         hd_path -> Varchar,
         deleted_at -> Nullable<Timestamptz>,
+        success_url -> Nullable<Varchar>,
+        key_id -> Uuid,
+        data_key -> Nullable<Bytea>,
+        key_version -> Nullable<Int4>,
+        btc_xpub -> Nullable<Varchar>,
+        eth_xpub -> Nullable<Varchar>,
+        btc_address_index -> Int4,
+        eth_address_index -> Int4,
+        passphrase -> Nullable<Varchar>,
+        eth_payout_splits -> Nullable<Array<Int4>>,
+        btc_payout_splits -> Nullable<Array<Int4>>,
+        eth_payout_index -> Int4,
+        btc_payout_index -> Int4,
+        checkout_secret -> Nullable<Varchar>,
+        large_payment_threshold -> Nullable<Numeric>,
+        daily_summary -> Bool,
+        btc_address_format -> Varchar,
+        large_payment_threshold_fiat -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    used_refresh_tokens (token_hash) {
+        token_hash -> Varchar,
+        session_id -> Uuid,
+        used_at -> Timestamptz,
     }
 }
 
@@ -119,17 +289,35 @@ table! {
         verification_token_expires_at -> Timestamptz,
         reset_token -> Nullable<Uuid>,
         reset_token_expires_at -> Nullable<Timestamptz>,
+        totp_secret -> Nullable<Bytea>,
+        data_key -> Nullable<Bytea>,
+        key_version -> Nullable<Int4>,
+        totp_enabled_at -> Nullable<Timestamptz>,
+        totp_last_step -> Nullable<Int8>,
+        recovery_codes -> Nullable<Array<Text>>,
+        password_changed_at -> Nullable<Timestamptz>,
     }
 }
 
 allow_tables_to_appear_in_same_query!(
+    audit_logs,
     btc_blockchain_statuses,
     btc_transactions,
     client_tokens,
+    email_templates,
     eth_blockchain_statuses,
     eth_transactions,
+    idempotency_keys,
+    invoices,
+    notifications,
     payments,
+    payout_address_changes,
+    payout_legs,
     payouts,
+    retired_store_keys,
+    sessions,
+    store_memberships,
     stores,
+    used_refresh_tokens,
     users,
 );
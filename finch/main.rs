@@ -2,7 +2,9 @@ extern crate actix;
 #[macro_use]
 extern crate clap;
 extern crate env_logger;
+extern crate futures;
 extern crate openssl;
+extern crate uuid;
 
 extern crate blockchain_api_client;
 extern crate toml;
@@ -10,6 +12,7 @@ extern crate toml;
 extern crate block_processor;
 extern crate config;
 extern crate core;
+extern crate hd_keyring;
 extern crate payouter;
 extern crate server;
 extern crate types;
@@ -24,9 +27,14 @@ use blockchain_api_client::{
     ethereum::BlockchainApiClient as EthBlockchainApiClient,
 };
 use config::Config;
-use core::db::postgres;
+use core::{
+    crypto::Keychain,
+    db::{postgres, redis, stores},
+};
 use types::currency::Crypto;
 
+mod sweep;
+
 fn main() {
     env::set_var(
         "RUST_LOG",
@@ -49,6 +57,11 @@ fn main() {
 
     let config: Config = toml::from_str(&settings).unwrap();
 
+    // Sweeping may happen with nothing but the mnemonic, the server's keys aren't needed for it.
+    if let Some(matches) = matches.subcommand_matches("sweep") {
+        return sweep::run(matches, &config);
+    }
+
     if !Path::new(&config.server.private_key_path).exists()
         || !Path::new(&config.server.private_key_path).exists()
     {
@@ -73,6 +86,8 @@ fn main() {
             .expect("failed to write to public key file");
     }
 
+    let keychain = keychain(&config);
+
     let currencies = {
         if matches.is_present("currencies") {
             values_t!(matches, "currencies", Crypto).unwrap()
@@ -85,8 +100,18 @@ fn main() {
 
     let postgres_url = config.postgres.clone();
     let pg_pool = postgres::init_pool(&postgres_url);
+
+    stores::encrypt_secrets(
+        &keychain,
+        &pg_pool.get().expect("failed to get a database connection"),
+    )
+    .expect("failed to encrypt store secrets");
+
     let postgres = SyncArbiter::start(4, move || postgres::PgExecutor(pg_pool.clone()));
 
+    let redis_pool = redis::init_pool(&config.redis);
+    let redis = SyncArbiter::start(2, move || redis::RedisExecutor(redis_pool.clone()));
+
     let skip_missed_blocks = matches.is_present("skip_missed_blocks");
 
     let mut _btc_block_processor;
@@ -112,11 +137,19 @@ fn main() {
 
                 _btc_block_processor = block_processor::run(
                     postgres.clone(),
+                    redis.clone(),
                     blockchain_api_client.clone(),
                     network,
                     skip_missed_blocks,
                 );
-                payouter::run(postgres.clone(), blockchain_api_client.clone(), network);
+                payouter::run(
+                    postgres.clone(),
+                    redis.clone(),
+                    blockchain_api_client.clone(),
+                    network,
+                    keychain.clone(),
+                    config.signer.clone(),
+                );
             }
             Crypto::Eth => {
                 use block_processor::ethereum::service as block_processor;
@@ -130,16 +163,39 @@ fn main() {
 
                 _eth_block_processor = block_processor::run(
                     postgres.clone(),
+                    redis.clone(),
                     blockchain_api_client.clone(),
                     network,
                     skip_missed_blocks,
                 );
-                payouter::run(postgres.clone(), blockchain_api_client.clone(), network);
+                payouter::run(
+                    postgres.clone(),
+                    redis.clone(),
+                    blockchain_api_client.clone(),
+                    network,
+                    keychain.clone(),
+                    config.signer.clone(),
+                );
             }
         }
     }
 
-    server::run(postgres, config);
+    server::run(postgres, config, keychain);
 
     system.run();
 }
+
+fn keychain(config: &Config) -> Keychain {
+    Keychain::new(
+        config
+            .server
+            .master_keys
+            .iter()
+            .map(|key| {
+                let value = key.read().expect("failed to read master key");
+                (key.version, value)
+            })
+            .collect(),
+    )
+    .expect("invalid master key configuration")
+}
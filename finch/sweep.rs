@@ -0,0 +1,244 @@
+//! `finch sweep` moves everything left on a store's deposit addresses to a single address. It
+//! talks to the node directly and never touches payouts, so given the mnemonic it also works
+//! when the database is gone.
+
+use std::{fs::File, io::prelude::*, str::FromStr};
+
+use actix::prelude::*;
+use clap::ArgMatches;
+use futures::Future;
+use uuid::Uuid;
+
+use blockchain_api_client::{
+    bitcoin::BlockchainApiClient as BtcBlockchainApiClient,
+    ethereum::BlockchainApiClient as EthBlockchainApiClient,
+};
+use config::Config;
+use core::db::{payments, postgres, stores};
+use hd_keyring::{bip44_path, bip84_path, HdKeyring};
+use payouter::{bitcoin::sweeper as btc_sweeper, ethereum::sweeper as eth_sweeper};
+use types::{
+    bitcoin::{Address as BtcAddress, Network as BtcNetwork},
+    currency::Crypto,
+    H160,
+};
+
+pub fn run(matches: &ArgMatches, config: &Config) {
+    let currency = value_t!(matches, "currency", Crypto).unwrap_or_else(|e| e.exit());
+    let gap_limit = value_t!(matches, "gap_limit", u32).unwrap_or_else(|e| e.exit());
+    let destination = matches.value_of("destination").unwrap().to_owned();
+    let dry_run = matches.is_present("dry_run");
+
+    let btc_network = config
+        .bitcoin
+        .as_ref()
+        .map_or(BtcNetwork::Test, |config| config.network);
+
+    let (mnemonic, passphrase, address_index, mut paths) =
+        recovery_material(matches, config, currency);
+
+    // Addresses handed out so far, plus a gap in case the counter is behind or unknown. Bitcoin
+    // deposits may be on either legacy or segwit addresses depending on the store.
+    for index in 0..(address_index + gap_limit) {
+        let mut gap_paths = vec![bip44_path(&currency, btc_network, index)];
+        if currency == Crypto::Btc {
+            gap_paths.push(bip84_path(btc_network, index));
+        }
+
+        for path in gap_paths {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    let keyring = HdKeyring::from_mnemonic("m", &mnemonic, &passphrase, 0, btc_network)
+        .expect("invalid mnemonic");
+    let wallets = paths
+        .into_iter()
+        .map(|path| {
+            let wallet = keyring
+                .get_wallet_by_path(&path)
+                .expect("invalid derivation path");
+
+            (path, wallet)
+        })
+        .collect::<Vec<_>>();
+
+    println!("Scanning {} {} addresses", wallets.len(), currency);
+
+    let system = System::new("finch-sweep");
+
+    match currency {
+        Crypto::Btc => {
+            let btc_config = config.bitcoin.clone().expect("no bitcoin configuration");
+
+            let destination = BtcAddress::from_str(&destination)
+                .expect("invalid destination address")
+                .to_string();
+
+            let blockchain_api_client = Arbiter::start(move |_| {
+                BtcBlockchainApiClient::new(
+                    &btc_config.rpc_url,
+                    &btc_config.rpc_user,
+                    &btc_config.rpc_pass,
+                )
+            });
+
+            Arbiter::spawn(
+                btc_sweeper::sweep(wallets, destination, dry_run, blockchain_api_client).then(
+                    |res| -> Result<(), ()> {
+                        match res {
+                            Ok(sweeps) => {
+                                for sweep in sweeps {
+                                    report(
+                                        &sweep.hd_path,
+                                        &sweep.address,
+                                        &format!("{} satoshi", sweep.value),
+                                        sweep.transaction_hash.map(|hash| hash.to_string()),
+                                        sweep.error,
+                                    );
+                                }
+                            }
+                            Err(e) => println!("Sweep failed: {}", e),
+                        }
+
+                        System::current().stop();
+                        Ok(())
+                    },
+                ),
+            );
+        }
+        Crypto::Eth => {
+            let eth_config = config.ethereum.clone().expect("no ethereum configuration");
+            let network = eth_config.network;
+
+            let destination = H160::from_str(destination.trim_left_matches("0x"))
+                .expect("invalid destination address");
+
+            let blockchain_api_client =
+                Arbiter::start(move |_| EthBlockchainApiClient::new(eth_config.rpc_url));
+
+            Arbiter::spawn(
+                eth_sweeper::sweep(
+                    wallets,
+                    destination,
+                    dry_run,
+                    network,
+                    blockchain_api_client,
+                )
+                .then(|res| -> Result<(), ()> {
+                    match res {
+                        Ok(sweeps) => {
+                            for sweep in sweeps {
+                                report(
+                                    &sweep.hd_path,
+                                    &sweep.address.hex(),
+                                    &format!("{} wei", sweep.value),
+                                    sweep.transaction_hash.map(|hash| hash.to_string()),
+                                    sweep.error,
+                                );
+                            }
+                        }
+                        Err(e) => println!("Sweep failed: {}", e),
+                    }
+
+                    System::current().stop();
+                    Ok(())
+                }),
+            );
+        }
+    }
+
+    system.run();
+}
+
+/// Returns the mnemonic and BIP39 passphrase, the store's address counter for `currency` and the
+/// paths of its recorded payments. Without `--store` there are only the given files to go on. The
+/// master keys are only read when the store's secrets have to be decrypted.
+fn recovery_material(
+    matches: &ArgMatches,
+    config: &Config,
+    currency: Crypto,
+) -> (String, String, u32, Vec<String>) {
+    let mut mnemonic = matches.value_of("mnemonic_file").map(read_secret);
+    let mut passphrase = matches.value_of("passphrase_file").map(read_secret);
+    let mut address_index = 0;
+    let mut paths = Vec::new();
+
+    if let Some(id) = matches.value_of("store") {
+        let id = Uuid::from_str(id).expect("invalid store id");
+
+        let pg_pool = postgres::init_pool(&config.postgres);
+        let conn = pg_pool.get().expect("failed to get a database connection");
+
+        let store = stores::find_by_id_with_deleted(id, &conn).expect("failed to find the store");
+
+        assert!(
+            !store.is_watch_only(),
+            "watch-only stores hold no keys to sweep"
+        );
+
+        if mnemonic.is_none() || passphrase.is_none() {
+            let keychain = super::keychain(config);
+
+            if mnemonic.is_none() {
+                mnemonic = store
+                    .decrypt_mnemonic(&keychain)
+                    .expect("failed to decrypt the mnemonic");
+            }
+
+            if passphrase.is_none() {
+                passphrase = Some(
+                    store
+                        .decrypt_passphrase(&keychain)
+                        .expect("failed to decrypt the passphrase"),
+                );
+            }
+        }
+
+        address_index = match currency {
+            Crypto::Btc => store.btc_address_index,
+            Crypto::Eth => store.eth_address_index,
+        } as u32;
+
+        paths = payments::find_all_by_store(id, &conn)
+            .expect("failed to load payments")
+            .into_iter()
+            .filter(|payment| payment.crypto == currency)
+            .map(|payment| payment.hd_path)
+            .collect();
+    }
+
+    let mnemonic = mnemonic.expect("no mnemonic given");
+    let passphrase = passphrase.unwrap_or_default();
+
+    (mnemonic, passphrase, address_index, paths)
+}
+
+/// Only strips the trailing newline, spaces may well be part of a passphrase.
+fn read_secret(path: &str) -> String {
+    let mut secret = String::new();
+
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut secret))
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", path, e));
+
+    secret
+        .trim_right_matches(|c| c == '\n' || c == '\r')
+        .to_owned()
+}
+
+fn report(
+    hd_path: &str,
+    address: &str,
+    value: &str,
+    transaction_hash: Option<String>,
+    error: Option<String>,
+) {
+    match (transaction_hash, error) {
+        (Some(hash), _) => println!("{} {} {} swept in {}", hd_path, address, value, hash),
+        (None, Some(error)) => println!("{} {} {} failed: {}", hd_path, address, value, error),
+        (None, None) => println!("{} {} {} not swept", hd_path, address, value),
+    }
+}
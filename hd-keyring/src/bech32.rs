@@ -0,0 +1,112 @@
+//! Segwit address encoding, see BIP173.
+
+const CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [
+    0x3b6a_57b2,
+    0x2650_8e6d,
+    0x1ea1_19fa,
+    0x3d42_33dd,
+    0x2a14_62b3,
+];
+
+/// Encodes a witness program, e.g. the 20 byte key hash of a P2WPKH output with version 0.
+pub fn encode_segwit(hrp: &str, version: u8, program: &[u8]) -> String {
+    let mut data = vec![version];
+    data.extend(convert_bits(program, 8, 5));
+
+    let checksum = checksum(hrp, &data);
+
+    let mut address = String::with_capacity(hrp.len() + 1 + data.len() + checksum.len());
+    address.push_str(hrp);
+    address.push('1');
+
+    for value in data.iter().chain(checksum.iter()) {
+        address.push(CHARSET[*value as usize] as char);
+    }
+
+    address
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    let mut chk: u32 = 1;
+
+    for value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ff_ffff) << 5) ^ u32::from(*value);
+
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+
+    chk
+}
+
+fn expand_hrp(hrp: &str) -> Vec<u8> {
+    let mut expanded = Vec::with_capacity(hrp.len() * 2 + 1);
+    expanded.extend(hrp.bytes().map(|b| b >> 5));
+    expanded.push(0);
+    expanded.extend(hrp.bytes().map(|b| b & 0x1f));
+    expanded
+}
+
+fn checksum(hrp: &str, data: &[u8]) -> Vec<u8> {
+    let mut values = expand_hrp(hrp);
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0; 6]);
+
+    let polymod = polymod(&values) ^ 1;
+
+    (0..6)
+        .map(|i| ((polymod >> (5 * (5 - i))) & 0x1f) as u8)
+        .collect()
+}
+
+/// Regroups `from` bit values into `to` bit ones, padding the last with zeros.
+fn convert_bits(data: &[u8], from: u32, to: u32) -> Vec<u8> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let max = (1 << to) - 1;
+    let mut converted = Vec::new();
+
+    for value in data {
+        acc = (acc << from) | u32::from(*value);
+        bits += from;
+
+        while bits >= to {
+            bits -= to;
+            converted.push(((acc >> bits) & max) as u8);
+        }
+    }
+
+    if bits > 0 {
+        converted.push(((acc << (to - bits)) & max) as u8);
+    }
+
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_hex::FromHex;
+
+    #[test]
+    fn p2wpkh_address() {
+        // From the BIP173 examples.
+        let program: Vec<u8> = "751e76e8199196d454941c45d1b3a323f1433bd6"
+            .from_hex()
+            .unwrap();
+
+        assert_eq!(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            encode_segwit("bc", 0, &program)
+        );
+        assert_eq!(
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+            encode_segwit("tb", 0, &program)
+        );
+    }
+}
@@ -12,7 +12,10 @@ use secp256k1::{
 use sha2::Sha512;
 
 use errors::Error;
-use types::{bitcoin::Network as BtcNetwork, H160, H256};
+use types::{
+    bitcoin::{AddressFormat, Network as BtcNetwork},
+    H160, H256,
+};
 
 const MASTER_SECRET: &'static [u8] = b"Bitcoin seed";
 const HARDENED_OFFSET: u32 = 0x80000000;
@@ -157,18 +160,19 @@ impl XKeyPair {
         Ok(XKeyPair { xprv, xpub })
     }
 
+    /// Keys under a BIP84 path export their public key as a zpub (vpub on testnet).
     pub fn from_path(&self, path: &DerivationPath) -> Result<Self, Error> {
         let xprv = self.xprv.derive(path)?;
+        let mut xpub = Xpub::from_private(&xprv)?;
+        xpub.format = AddressFormat::from_path(&path.to_string());
 
-        Ok(XKeyPair {
-            xprv,
-            xpub: Xpub::from_private(&xprv)?,
-        })
+        Ok(XKeyPair { xprv, xpub })
     }
 
     pub fn derive(&self, index: &Index) -> Result<XKeyPair, Error> {
         let xprv = self.xprv.ckd_priv(index)?;
-        let xpub = Xpub::from_private(&xprv)?;
+        let mut xpub = Xpub::from_private(&xprv)?;
+        xpub.format = self.xpub.format;
 
         Ok(XKeyPair { xprv, xpub })
     }
@@ -347,6 +351,7 @@ pub struct Xpub {
     public_key: PublicKey,
     chain_code: ChainCode,
     network: BtcNetwork,
+    format: AddressFormat,
     depth: u32,
     index: Index,
     parent_fingerprint: Fingerprint,
@@ -358,6 +363,7 @@ impl Xpub {
 
         Ok(Xpub {
             network: xprv.network,
+            format: AddressFormat::Legacy,
             depth: xprv.depth,
             index: xprv.index,
             parent_fingerprint: xprv.parent_fingerprint,
@@ -366,6 +372,16 @@ impl Xpub {
         })
     }
 
+    pub fn derive(&self, path: &DerivationPath) -> Result<Self, Error> {
+        let mut xpub = *self;
+
+        for index in path.iter() {
+            xpub = xpub.ckd_pub(index)?;
+        }
+
+        Ok(xpub)
+    }
+
     pub fn ckd_pub(&self, index: &Index) -> Result<Self, Error> {
         let mut mac = Hmac::<Sha512>::new_varkey(&self.chain_code).unwrap();
         let secp = Secp256k1::new();
@@ -391,6 +407,7 @@ impl Xpub {
             depth: self.depth + 1,
             index: *index,
             network: self.network,
+            format: self.format,
             parent_fingerprint: self.fingerprint(),
             public_key,
             chain_code: ChainCode::from(&result[32..]),
@@ -409,15 +426,26 @@ impl Xpub {
     pub fn as_raw(&self) -> &PublicKey {
         &self.public_key
     }
+
+    pub fn network(&self) -> BtcNetwork {
+        self.network
+    }
+
+    /// Segwit for zpubs and vpubs, legacy for xpubs and tpubs.
+    pub fn address_format(&self) -> AddressFormat {
+        self.format
+    }
 }
 
 impl ToString for Xpub {
     fn to_string(&self) -> String {
         let mut data = [0; 78];
         data[0..4].copy_from_slice(
-            &match self.network {
-                BtcNetwork::Mainnet => [0x04u8, 0x88, 0xB2, 0x1E],
-                BtcNetwork::Test => [0x04u8, 0x35, 0x87, 0xCF],
+            &match (self.network, self.format) {
+                (BtcNetwork::Mainnet, AddressFormat::Legacy) => [0x04u8, 0x88, 0xB2, 0x1E],
+                (BtcNetwork::Test, AddressFormat::Legacy) => [0x04u8, 0x35, 0x87, 0xCF],
+                (BtcNetwork::Mainnet, AddressFormat::Segwit) => [0x04u8, 0xB2, 0x47, 0x46],
+                (BtcNetwork::Test, AddressFormat::Segwit) => [0x04u8, 0x5F, 0x1C, 0xF6],
             }[..],
         );
         data[4] = self.depth as u8;
@@ -444,6 +472,10 @@ impl FromStr for Xpub {
 
         let bytes = input.from_base58().map_err(|_| Error::InvalidBase58Byte)?;
 
+        if bytes.len() < 4 {
+            return Err(Error::InvalidKeyLength);
+        }
+
         let expected = LittleEndian::read_u32(&H256::from_data(&bytes[..bytes.len() - 4])[0..4]);
         let actual = LittleEndian::read_u32(&bytes[bytes.len() - 4..]);
 
@@ -464,14 +496,17 @@ impl FromStr for Xpub {
             Index::Hard(n)
         };
 
+        let (network, format) = match &data[0..4] {
+            [0x04u8, 0x88, 0xB2, 0x1E] => (BtcNetwork::Mainnet, AddressFormat::Legacy),
+            [0x04u8, 0x35, 0x87, 0xCF] => (BtcNetwork::Test, AddressFormat::Legacy),
+            [0x04u8, 0xB2, 0x47, 0x46] => (BtcNetwork::Mainnet, AddressFormat::Segwit),
+            [0x04u8, 0x5F, 0x1C, 0xF6] => (BtcNetwork::Test, AddressFormat::Segwit),
+            _ => return Err(Error::InvalidNetwork),
+        };
+
         Ok(Xpub {
-            network: if &data[0..4] == [0x04u8, 0x88, 0xB2, 0x1E] {
-                BtcNetwork::Mainnet
-            } else if &data[0..4] == [0x04u8, 0x35, 0x87, 0xCF] {
-                BtcNetwork::Test
-            } else {
-                return Err(Error::InvalidNetwork);
-            },
+            network,
+            format,
             depth: data[4] as u32,
             parent_fingerprint: Fingerprint::from(&data[5..9]),
             index,
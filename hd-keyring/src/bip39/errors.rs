@@ -12,6 +12,8 @@ pub enum Error {
     CustomError(String),
     #[fail(display = "invalid checksum")]
     InvalidChecksum,
+    #[fail(display = "unknown language {}", _0)]
+    UnknownLanguage(String),
 }
 
 impl From<ParseIntError> for Error {
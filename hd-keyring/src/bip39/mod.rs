@@ -1,42 +1,50 @@
 mod errors;
 
-use std::{collections::HashMap, iter::Iterator};
+use std::{collections::HashMap, fmt, iter::Iterator, str::FromStr};
 
 use bit_vec::BitVec;
 use rand::{thread_rng, Rng};
 use ring::{digest, pbkdf2};
 use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
 
 pub use self::errors::Error;
 
 static PBKDF2_ROUNDS: u32 = 2048;
 static PBKDF2_BYTES: usize = 64;
 
-lazy_static! {
-    static ref ENGLISH_WORDLIST: Vec<&'static str> = {
-        include_str!("wordlist/english.txt")
-            .split_whitespace()
-            .collect()
-    };
-    static ref FRENCH_WORDLIST: Vec<&'static str> = {
-        include_str!("wordlist/french.txt")
-            .split_whitespace()
-            .collect()
-    };
-    static ref ENGLISH_WORDMAP: HashMap<&'static str, u16> = {
-        ENGLISH_WORDLIST
-            .iter()
-            .enumerate()
-            .map(|(i, word)| (*word, i as u16))
-            .collect()
-    };
-    static ref FRENCH_WORDMAP: HashMap<&'static str, u16> = {
-        FRENCH_WORDLIST
+struct Wordlist {
+    words: Vec<&'static str>,
+    // Keyed by the NFKD form, which is what phrases are compared and hashed in.
+    map: HashMap<String, u16>,
+}
+
+impl Wordlist {
+    fn load(raw: &'static str) -> Self {
+        let words: Vec<&'static str> = raw.split_whitespace().collect();
+        let map = words
             .iter()
             .enumerate()
-            .map(|(i, word)| (*word, i as u16))
-            .collect()
-    };
+            .map(|(i, word)| (word.nfkd().collect(), i as u16))
+            .collect();
+
+        Wordlist { words, map }
+    }
+}
+
+lazy_static! {
+    static ref ENGLISH: Wordlist = Wordlist::load(include_str!("wordlist/english.txt"));
+    static ref CHINESE_SIMPLIFIED: Wordlist =
+        Wordlist::load(include_str!("wordlist/chinese_simplified.txt"));
+    static ref CHINESE_TRADITIONAL: Wordlist =
+        Wordlist::load(include_str!("wordlist/chinese_traditional.txt"));
+    static ref CZECH: Wordlist = Wordlist::load(include_str!("wordlist/czech.txt"));
+    static ref FRENCH: Wordlist = Wordlist::load(include_str!("wordlist/french.txt"));
+    static ref ITALIAN: Wordlist = Wordlist::load(include_str!("wordlist/italian.txt"));
+    static ref JAPANESE: Wordlist = Wordlist::load(include_str!("wordlist/japanese.txt"));
+    static ref KOREAN: Wordlist = Wordlist::load(include_str!("wordlist/korean.txt"));
+    static ref PORTUGUESE: Wordlist = Wordlist::load(include_str!("wordlist/portuguese.txt"));
+    static ref SPANISH: Wordlist = Wordlist::load(include_str!("wordlist/spanish.txt"));
 }
 
 pub enum MnemonicType {
@@ -77,36 +85,102 @@ impl MnemonicType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Language {
     English,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
     French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
 }
 
+// Detection order. English comes first since it's what every existing phrase is in.
+const LANGUAGES: [Language; 10] = [
+    Language::English,
+    Language::ChineseSimplified,
+    Language::ChineseTraditional,
+    Language::Czech,
+    Language::French,
+    Language::Italian,
+    Language::Japanese,
+    Language::Korean,
+    Language::Portuguese,
+    Language::Spanish,
+];
+
 impl Language {
-    fn get_wordlist(&self) -> &'static Vec<&'static str> {
+    fn get_wordlist(&self) -> &'static Wordlist {
         match *self {
-            Language::English => &*ENGLISH_WORDLIST,
-            Language::French => &*FRENCH_WORDLIST,
+            Language::English => &*ENGLISH,
+            Language::ChineseSimplified => &*CHINESE_SIMPLIFIED,
+            Language::ChineseTraditional => &*CHINESE_TRADITIONAL,
+            Language::Czech => &*CZECH,
+            Language::French => &*FRENCH,
+            Language::Italian => &*ITALIAN,
+            Language::Japanese => &*JAPANESE,
+            Language::Korean => &*KOREAN,
+            Language::Portuguese => &*PORTUGUESE,
+            Language::Spanish => &*SPANISH,
         }
     }
 
-    fn get_wordmap(&self) -> &'static HashMap<&'static str, u16> {
+    /// Japanese phrases are written with ideographic spaces.
+    pub fn separator(&self) -> &'static str {
         match *self {
-            Language::English => &*ENGLISH_WORDMAP,
-            Language::French => &*FRENCH_WORDMAP,
+            Language::Japanese => "\u{3000}",
+            _ => " ",
         }
     }
 }
 
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Language::English => "english",
+                Language::ChineseSimplified => "chinese_simplified",
+                Language::ChineseTraditional => "chinese_traditional",
+                Language::Czech => "czech",
+                Language::French => "french",
+                Language::Italian => "italian",
+                Language::Japanese => "japanese",
+                Language::Korean => "korean",
+                Language::Portuguese => "portuguese",
+                Language::Spanish => "spanish",
+            }
+        )
+    }
+}
+
+impl FromStr for Language {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LANGUAGES
+            .iter()
+            .find(|lang| lang.to_string() == s)
+            .cloned()
+            .ok_or(Error::UnknownLanguage(s.to_owned()))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Seed {
     bytes: Vec<u8>,
 }
 
 impl Seed {
-    pub fn new(entropy: &[u8], pass: String) -> Self {
-        let salt = format!("mnemonic{}", pass);
+    /// Both the phrase and the passphrase are NFKD normalized first, as BIP39 requires.
+    pub fn new(phrase: &str, pass: &str) -> Self {
+        let phrase: String = phrase.nfkd().collect();
+        let salt = format!("mnemonic{}", pass.nfkd().collect::<String>());
 
         let mut bytes = vec![0u8; PBKDF2_BYTES];
 
@@ -116,7 +190,7 @@ impl Seed {
             DIGEST_ALG,
             PBKDF2_ROUNDS,
             salt.as_bytes(),
-            entropy,
+            phrase.as_bytes(),
             &mut bytes,
         );
 
@@ -149,7 +223,7 @@ impl Mnemonic {
         let mut entropy = vec![0u8; entropy_length / 8];
         rng.fill_bytes(&mut entropy);
 
-        let wordlist = lang.get_wordlist();
+        let wordlist = &lang.get_wordlist().words;
 
         let mut with_checksum = BitVec::from_bytes(
             &entropy
@@ -177,46 +251,82 @@ impl Mnemonic {
             }
         }
 
-        let seed = Seed::new(&phrase.join(" ").as_bytes(), pass);
+        let phrase = phrase.join(lang.separator());
+        let seed = Seed::new(&phrase, &pass);
 
         Ok(Mnemonic {
-            phrase: phrase.join(" "),
+            phrase,
             lang,
             entropy,
             seed,
         })
     }
 
-    pub fn from_string<T>(phrase: T, lang: Language, pass: T) -> Result<Self, Error>
+    /// Parses a phrase in any of the supported languages. Words may be separated by any
+    /// whitespace, the returned phrase uses the language's own separator.
+    pub fn from_string<T>(phrase: T, pass: T) -> Result<Self, Error>
     where
         T: Into<String>,
     {
         let pass = pass.into();
-        let phrase = phrase.into();
+        let phrase: String = phrase.into().nfkd().collect();
+        let words: Vec<&str> = phrase.split_whitespace().collect();
+
+        let typ = MnemonicType::from_word_count(words.len())?;
+
+        let candidates: Vec<Language> = LANGUAGES
+            .iter()
+            .filter(|lang| {
+                let wordmap = &lang.get_wordlist().map;
+                words.iter().all(|word| wordmap.contains_key(*word))
+            })
+            .cloned()
+            .collect();
+
+        if candidates.is_empty() {
+            let word = words
+                .iter()
+                .find(|word| !ENGLISH.map.contains_key(**word))
+                .unwrap_or(&"");
+
+            return Err(Error::InvalidWord(word.to_string()));
+        }
 
-        let typ = MnemonicType::from_word_count(
-            phrase
-                .split(" ")
-                .map(|s| s.to_owned())
-                .collect::<Vec<String>>()
-                .len(),
-        )?;
+        // A phrase made of words shared between lists only decodes under one of them, unless the
+        // checksum happens to match by chance as well.
+        let mut result = Err(Error::InvalidChecksum);
+        for lang in candidates {
+            result = Mnemonic::from_words(&words, &typ, lang, &pass);
 
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        result
+    }
+
+    fn from_words(
+        words: &[&str],
+        typ: &MnemonicType,
+        lang: Language,
+        pass: &str,
+    ) -> Result<Self, Error> {
         let checksum_length = typ.checksum_length();
 
-        let wordmap = lang.get_wordmap();
+        let wordlist = lang.get_wordlist();
 
         let mut indexes = Vec::new();
 
-        for word in phrase.split(" ") {
-            match wordmap.get(&word) {
-                Some(index) => indexes.push(index),
-                None => return Err(Error::InvalidWord(word.to_owned())),
+        for word in words {
+            match wordlist.map.get(*word) {
+                Some(index) => indexes.push(*index),
+                None => return Err(Error::InvalidWord(word.to_string())),
             }
         }
 
         let mut with_checksum = BitVec::new();
-        for index in indexes {
+        for index in indexes.iter() {
             for x in (0..11).rev() {
                 let bit = ((index >> x as u32) & 1) == 1;
                 with_checksum.push(bit);
@@ -240,10 +350,15 @@ impl Mnemonic {
             return Err(Error::InvalidChecksum);
         }
 
-        let seed = Seed::new(&phrase.as_bytes(), pass);
+        let phrase = indexes
+            .iter()
+            .map(|index| wordlist.words[*index as usize])
+            .collect::<Vec<&str>>()
+            .join(lang.separator());
+        let seed = Seed::new(&phrase, pass);
 
         Ok(Mnemonic {
-            phrase: phrase.to_owned(),
+            phrase,
             lang,
             entropy,
             seed,
@@ -254,6 +369,10 @@ impl Mnemonic {
         self.phrase.to_owned()
     }
 
+    pub fn language(&self) -> Language {
+        self.lang
+    }
+
     pub fn seed(&self) -> Seed {
         self.seed.to_owned()
     }
@@ -1,4 +1,5 @@
 use bip39::Error as Bip39Error;
+use openssl::error::ErrorStack;
 use secp256k1::Error as Secp256k1Error;
 use std::io::Error as IoError;
 
@@ -22,6 +23,12 @@ pub enum Error {
     IoError(#[cause] IoError),
     #[fail(display = "invalid network")]
     InvalidNetwork,
+    #[fail(display = "{}", _0)]
+    OpenSslError(#[cause] ErrorStack),
+    #[fail(display = "failed to generate random bytes")]
+    RandomFailed,
+    #[fail(display = "signer error: {}", _0)]
+    SignerError(String),
 }
 
 impl From<Bip39Error> for Error {
@@ -41,3 +48,9 @@ impl From<IoError> for Error {
         Error::IoError(e)
     }
 }
+
+impl From<ErrorStack> for Error {
+    fn from(e: ErrorStack) -> Error {
+        Error::OpenSslError(e)
+    }
+}
@@ -0,0 +1,156 @@
+//! Password protected exports of single keys, for importing into third party wallets: BIP38 for
+//! Bitcoin and the version 3 keystore format for Ethereum.
+
+use openssl::{
+    pkcs5::scrypt,
+    symm::{encrypt, Cipher, Crypter, Mode},
+};
+use ring::rand::{SecureRandom, SystemRandom};
+use rust_base58::ToBase58;
+use tiny_keccak::keccak256;
+
+use errors::Error;
+use types::{H160, H256};
+use wallet::{btc_address, Wallet};
+
+// BIP38 fixes its scrypt parameters.
+const BIP38_N: u64 = 16384;
+const BIP38_R: u64 = 8;
+const BIP38_P: u64 = 8;
+
+// The "light" parameters geth uses, so exporting a few hundred keys stays reasonably fast.
+const KEYSTORE_N: u64 = 4096;
+const KEYSTORE_R: u64 = 8;
+const KEYSTORE_P: u64 = 6;
+const KEYSTORE_DKLEN: usize = 32;
+
+/// The encrypted parts of a version 3 keystore file. The caller picks the `id` and renders it as
+/// JSON.
+#[derive(Debug)]
+pub struct Keystore {
+    pub address: H160,
+    pub iv: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub salt: Vec<u8>,
+    pub mac: Vec<u8>,
+    pub n: u64,
+    pub r: u64,
+    pub p: u64,
+    pub dklen: usize,
+}
+
+impl Wallet {
+    /// Encrypts the secret key as a non-EC-multiplied BIP38 key for the compressed public key.
+    /// BIP38 salts with the P2PKH address, whatever the wallet's address format.
+    pub fn to_bip38(&self, passphrase: &str) -> Result<String, Error> {
+        let address = btc_address(&self.public_key, self.btc_network);
+        let address_hash = H256::from_data(address.as_bytes());
+        let salt = &address_hash[0..4];
+
+        let mut derived = [0u8; 64];
+        scrypt(
+            passphrase.as_bytes(),
+            salt,
+            BIP38_N,
+            BIP38_R,
+            BIP38_P,
+            0,
+            &mut derived,
+        )?;
+
+        let (half1, half2) = derived.split_at(32);
+
+        let mut block = [0u8; 32];
+        for (i, b) in self.secret_key[..].iter().enumerate() {
+            block[i] = b ^ half1[i];
+        }
+
+        // ECB over both 16 byte blocks is exactly encryptedhalf1 || encryptedhalf2.
+        let mut crypter = Crypter::new(Cipher::aes_256_ecb(), Mode::Encrypt, half2, None)?;
+        crypter.pad(false);
+        let mut encrypted = vec![0u8; block.len() + Cipher::aes_256_ecb().block_size()];
+        let count = crypter.update(&block, &mut encrypted)?;
+        let rest = crypter.finalize(&mut encrypted[count..])?;
+        encrypted.truncate(count + rest);
+
+        // 0x0142 prefix, then the flag byte for "no EC multiply, compressed".
+        let mut payload = vec![0x01, 0x42, 0xe0];
+        payload.extend_from_slice(salt);
+        payload.extend_from_slice(&encrypted);
+
+        let checksum = H256::from_data(&payload);
+        payload.extend_from_slice(&checksum[0..4]);
+
+        Ok(payload.to_base58())
+    }
+
+    /// Encrypts the secret key with scrypt and AES-128-CTR as a version 3 keystore.
+    pub fn to_keystore(&self, passphrase: &str) -> Result<Keystore, Error> {
+        let rng = SystemRandom::new();
+
+        let mut salt = vec![0u8; 32];
+        let mut iv = vec![0u8; 16];
+        rng.fill(&mut salt).map_err(|_| Error::RandomFailed)?;
+        rng.fill(&mut iv).map_err(|_| Error::RandomFailed)?;
+
+        let mut derived = [0u8; KEYSTORE_DKLEN];
+        scrypt(
+            passphrase.as_bytes(),
+            &salt,
+            KEYSTORE_N,
+            KEYSTORE_R,
+            KEYSTORE_P,
+            0,
+            &mut derived,
+        )?;
+
+        let ciphertext = encrypt(
+            Cipher::aes_128_ctr(),
+            &derived[0..16],
+            Some(&iv),
+            &self.secret_key[..],
+        )?;
+
+        let mut mac_input = derived[16..32].to_vec();
+        mac_input.extend_from_slice(&ciphertext);
+        let mac = keccak256(&mac_input).to_vec();
+
+        Ok(Keystore {
+            address: self.get_eth_address(),
+            iv,
+            ciphertext,
+            salt,
+            mac,
+            n: KEYSTORE_N,
+            r: KEYSTORE_R,
+            p: KEYSTORE_P,
+            dklen: KEYSTORE_DKLEN,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::{key::SecretKey, Secp256k1};
+    use types::bitcoin::Network as BtcNetwork;
+
+    fn from_hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn bip38_compressed_test_vector() {
+        let secret = from_hex("CBF4B9F70470856BB4F40F80B87EDB90865997FFEE6DF315AB166D713AF433A5");
+        let secret_key = SecretKey::from_slice(&Secp256k1::new(), &secret).unwrap();
+        let wallet = Wallet::from_secret_key(secret_key, BtcNetwork::Mainnet).unwrap();
+
+        assert_eq!(
+            "6PYNKZ1EAgYgmQfmNVamxyXVWHzK5s6DGhwP4J5o44cvXdoY7sRzhtpUeo",
+            wallet.to_bip38("TestingOneTwoThree").unwrap()
+        );
+    }
+}
@@ -2,11 +2,14 @@ use std::str::FromStr;
 
 use bip39::{Language, Mnemonic, MnemonicType};
 
-use bip32::{DerivationPath, Index, XKeyPair};
+use bip32::{DerivationPath, Index, XKeyPair, Xpub};
 use errors::Error;
-use wallet::Wallet;
+use wallet::{self, Wallet};
 
-use types::bitcoin::Network as BtcNetwork;
+use types::{
+    bitcoin::{AddressFormat, Network as BtcNetwork},
+    currency::Crypto,
+};
 
 #[derive(Debug)]
 pub struct HdKeyring {
@@ -22,26 +25,31 @@ impl HdKeyring {
     pub fn new(
         path: &str,
         number_of_accounts: u32,
+        language: Language,
+        passphrase: &str,
         btc_network: BtcNetwork,
     ) -> Result<Self, Error> {
         let path = DerivationPath::from_str(path)?;
 
-        let mnemonic = Mnemonic::new(MnemonicType::Words12, Language::English, String::from(""))?;
+        let mnemonic = Mnemonic::new(MnemonicType::Words12, language, passphrase)?;
 
         let mut keyring = HdKeyring::init_from_mnemonic(mnemonic, &path, btc_network)?;
         keyring.load_wallets(number_of_accounts)?;
         Ok(keyring)
     }
 
+    /// The phrase may be in any supported language. `passphrase` is the optional BIP39 one,
+    /// empty if there is none.
     pub fn from_mnemonic(
         path: &str,
         mnemonic: &str,
+        passphrase: &str,
         number_of_accounts: u32,
         btc_network: BtcNetwork,
     ) -> Result<Self, Error> {
         let path = DerivationPath::from_str(path)?;
 
-        let mnemonic = Mnemonic::from_string(mnemonic, Language::English, "")?;
+        let mnemonic = Mnemonic::from_string(mnemonic, passphrase)?;
         let mut keyring = HdKeyring::init_from_mnemonic(mnemonic, &path, btc_network)?;
         keyring.load_wallets(number_of_accounts)?;
         Ok(keyring)
@@ -79,6 +87,81 @@ impl HdKeyring {
         let key_pair = self.root.derive(&Index::Soft(index - 1))?;
         Wallet::from_secret_key(*key_pair.xprv().as_raw(), self.btc_network)
     }
+
+    /// Derives the wallet at `path` from the master key, regardless of the keyring's own path.
+    /// Wallets at BIP84 paths have segwit Bitcoin addresses.
+    pub fn get_wallet_by_path(&self, path: &str) -> Result<Wallet, Error> {
+        let key_pair = self.hd_wallet.from_path(&DerivationPath::from_str(path)?)?;
+        let mut wallet = Wallet::from_secret_key(*key_pair.xprv().as_raw(), self.btc_network)?;
+        wallet.btc_address_format = AddressFormat::from_path(path);
+
+        Ok(wallet)
+    }
+}
+
+/// Path of the `index`th receive address of the first account, as standard wallets derive it.
+/// Bitcoin testnet uses coin type 1 per SLIP-44.
+pub fn bip44_path(currency: &Crypto, btc_network: BtcNetwork, index: u32) -> String {
+    let coin_type = match (currency, btc_network) {
+        (Crypto::Btc, BtcNetwork::Mainnet) => 0,
+        (Crypto::Btc, BtcNetwork::Test) => 1,
+        (Crypto::Eth, _) => 60,
+    };
+
+    format!("m/44'/{}'/0'/0/{}", coin_type, index)
+}
+
+/// Path of the `index`th native segwit receive address of the first account, see BIP84.
+pub fn bip84_path(btc_network: BtcNetwork, index: u32) -> String {
+    let coin_type = match btc_network {
+        BtcNetwork::Mainnet => 0,
+        BtcNetwork::Test => 1,
+    };
+
+    format!("m/84'/{}'/0'/0/{}", coin_type, index)
+}
+
+/// Derives addresses from an extended public key alone. Used for stores where the merchant keeps
+/// the private keys, so there's nothing to sign with.
+#[derive(Debug)]
+pub struct WatchOnlyKeyring {
+    pub xpub: Xpub,
+    pub hd_path: DerivationPath,
+    root: Xpub,
+}
+
+impl WatchOnlyKeyring {
+    /// `path` is relative to the extended public key and can only contain soft indexes.
+    pub fn from_xpub(xpub: &str, path: &str) -> Result<Self, Error> {
+        let xpub = Xpub::from_str(xpub)?;
+        let hd_path = DerivationPath::from_str(path)?;
+        let root = xpub.derive(&hd_path)?;
+
+        Ok(WatchOnlyKeyring {
+            xpub,
+            hd_path,
+            root,
+        })
+    }
+
+    pub fn btc_network(&self) -> BtcNetwork {
+        self.xpub.network()
+    }
+
+    /// Indexes start at 1 like `HdKeyring::get_wallet_by_index`.
+    pub fn get_address_by_index(&self, index: u32, currency: &Crypto) -> Result<String, Error> {
+        let xpub = self.root.ckd_pub(&Index::Soft(index - 1))?;
+
+        Ok(match currency {
+            Crypto::Btc => match self.xpub.address_format() {
+                AddressFormat::Legacy => wallet::btc_address(xpub.as_raw(), self.btc_network()),
+                AddressFormat::Segwit => {
+                    wallet::btc_segwit_address(xpub.as_raw(), self.btc_network())
+                }
+            },
+            Crypto::Eth => format!("0x{}", wallet::eth_address(xpub.as_raw())),
+        })
+    }
 }
 
 #[cfg(test)]
@@ -88,7 +171,14 @@ mod tests {
 
     #[test]
     fn create_new_keyring() {
-        HdKeyring::new("m/44'/60'/0'/0", 1, BtcNetwork::Mainnet).unwrap();
+        HdKeyring::new(
+            "m/44'/60'/0'/0",
+            1,
+            Language::English,
+            "",
+            BtcNetwork::Mainnet,
+        )
+        .unwrap();
     }
 
     #[test]
@@ -98,6 +188,7 @@ mod tests {
         let keyring = HdKeyring::from_mnemonic(
             "m/44'/60'/0'/0",
             "addict else general weird gospel excite void debate north include exercise liberty",
+            "",
             1,
             BtcNetwork::Mainnet,
         )
@@ -115,6 +206,7 @@ mod tests {
         let keyring = HdKeyring::from_mnemonic(
             "m/44'/0'/0'/0",
             "addict else general weird gospel excite void debate north include exercise liberty",
+            "",
             1,
             BtcNetwork::Mainnet,
         )
@@ -133,6 +225,7 @@ mod tests {
         let keyring = HdKeyring::from_mnemonic(
             "m/44'/60'/0'/0",
             "addict else general weird gospel excite void debate north include exercise liberty",
+            "",
             0,
             BtcNetwork::Mainnet,
         )
@@ -142,4 +235,107 @@ mod tests {
 
         assert_eq!(address, wallet.get_eth_address());
     }
+
+    #[test]
+    fn watch_only_addresses_match_keyring() {
+        let mnemonic =
+            "addict else general weird gospel excite void debate north include exercise liberty";
+
+        for (path, currency) in vec![("m/44'/0'/0'", Crypto::Btc), ("m/44'/60'/0'", Crypto::Eth)] {
+            let keyring =
+                HdKeyring::from_mnemonic(path, mnemonic, "", 0, BtcNetwork::Mainnet).unwrap();
+            let watch_only =
+                WatchOnlyKeyring::from_xpub(&keyring.root.xpub().to_string(), "m/0").unwrap();
+
+            let wallet = HdKeyring::from_mnemonic(
+                &format!("{}/0", path),
+                mnemonic,
+                "",
+                0,
+                BtcNetwork::Mainnet,
+            )
+            .unwrap()
+            .get_wallet_by_index(5)
+            .unwrap();
+
+            assert_eq!(
+                wallet.get_address(&currency),
+                watch_only.get_address_by_index(5, &currency).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn wallet_at_bip44_path() {
+        let keyring = HdKeyring::from_mnemonic(
+            "m",
+            "addict else general weird gospel excite void debate north include exercise liberty",
+            "",
+            0,
+            BtcNetwork::Mainnet,
+        )
+        .unwrap();
+
+        let path = bip44_path(&Crypto::Btc, BtcNetwork::Mainnet, 0);
+        let wallet = keyring.get_wallet_by_path(&path).unwrap();
+
+        assert_eq!("m/44'/0'/0'/0/0", path);
+        assert_eq!(
+            "195BqgTp3yH1ZWmt1L9LmMkGbTMAc1vGPN",
+            wallet.get_btc_address()
+        );
+    }
+
+    #[test]
+    fn segwit_wallet_at_bip84_path() {
+        // From the BIP84 test vectors.
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                        abandon abandon about";
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCTo\
+                    UG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let address = "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu";
+
+        let keyring = HdKeyring::from_mnemonic("m", mnemonic, "", 0, BtcNetwork::Mainnet).unwrap();
+        let path = bip84_path(BtcNetwork::Mainnet, 0);
+        let wallet = keyring.get_wallet_by_path(&path).unwrap();
+
+        assert_eq!("m/84'/0'/0'/0/0", path);
+        assert_eq!(AddressFormat::Segwit, wallet.btc_address_format);
+        assert_eq!(address, wallet.get_btc_address());
+
+        let account =
+            HdKeyring::from_mnemonic("m/84'/0'/0'", mnemonic, "", 0, BtcNetwork::Mainnet).unwrap();
+        assert_eq!(zpub, account.root.xpub().to_string());
+
+        let watch_only = WatchOnlyKeyring::from_xpub(zpub, "m/0").unwrap();
+        assert_eq!(AddressFormat::Segwit, watch_only.xpub.address_format());
+        assert_eq!(
+            address,
+            watch_only.get_address_by_index(1, &Crypto::Btc).unwrap()
+        );
+    }
+
+    #[test]
+    fn japanese_mnemonic_with_passphrase() {
+        // From the BIP39 Japanese test vectors, typed with plain spaces.
+        let phrase =
+            "あいこくしん あいこくしん あいこくしん あいこくしん あいこくしん あいこくしん \
+                      あいこくしん あいこくしん あいこくしん あいこくしん あいこくしん あおぞら";
+        let seed = "a262d6fb6122ecf45be09c50492b31f92e9beb7d9a845987a02cefda57a15f9c\
+                    467a17872029a9e92299b5cbdf306e3a0ee620245cbd508959b6cb7ca637bd55";
+
+        let mnemonic = Mnemonic::from_string(phrase, "㍍ガバヴァぱばぐゞちぢ十人十色").unwrap();
+
+        assert_eq!(Language::Japanese, mnemonic.language());
+        assert_eq!(12, mnemonic.phrase().split('\u{3000}').count());
+        assert_eq!(
+            seed,
+            mnemonic
+                .seed()
+                .as_bytes()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        );
+    }
 }
@@ -5,13 +5,16 @@ extern crate bit_vec;
 extern crate byteorder;
 extern crate digest;
 extern crate hmac;
+extern crate openssl;
 extern crate rand;
 extern crate regex;
 extern crate ring;
 extern crate ripemd160;
+extern crate rustc_hex;
 extern crate secp256k1;
 extern crate sha2;
 extern crate tiny_keccak;
+extern crate unicode_normalization;
 #[macro_use]
 extern crate failure;
 #[macro_use]
@@ -20,13 +23,19 @@ extern crate rust_base58;
 
 extern crate types;
 
+mod bech32;
 mod bip32;
 mod bip39;
 mod errors;
+mod export;
 mod keyring;
+mod signer;
 mod wallet;
 
 pub use bip32::{DerivationPath, Index, XKeyPair, Xprv, Xpub};
+pub use bip39::Language;
 pub use errors::Error;
-pub use keyring::HdKeyring;
-pub use wallet::Wallet;
+pub use export::Keystore;
+pub use keyring::{bip44_path, bip84_path, HdKeyring, WatchOnlyKeyring};
+pub use signer::{Signer, SocketSigner};
+pub use wallet::{btc_address, btc_segwit_address, Wallet};
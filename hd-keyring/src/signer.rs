@@ -0,0 +1,201 @@
+//! Signing of payouts without handing out secret keys. `HdKeyring` signs in-process with keys
+//! derived from the mnemonic, `SocketSigner` asks a separate signing daemon over a Unix socket so
+//! the key material never has to live in the server process.
+//!
+//! The daemon protocol is one line based request per connection, hex encoding all binary data:
+//!
+//! ```text
+//! > public_key <key id> <derivation path>
+//! < ok <33 byte compressed public key>
+//! > sign <key id> <derivation path> <32 byte hash>
+//! < ok <64 byte compact signature followed by the recovery id byte>
+//! < error <message>
+//! ```
+//!
+//! The key id tells the daemon which root key to derive from, e.g. a store's id.
+
+use std::{
+    io::{prelude::*, BufReader},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use rustc_hex::{FromHex, ToHex};
+use secp256k1::{key::PublicKey, Message, RecoverableSignature, RecoveryId, Secp256k1};
+
+use errors::Error;
+use keyring::HdKeyring;
+use types::H256;
+
+/// Signers are shared with the threads payouts are signed on.
+pub trait Signer: Send + Sync {
+    fn public_key(&self, path: &str) -> Result<PublicKey, Error>;
+
+    /// Signs the 32 byte `hash` with the key at `path`. The signature is recoverable so it works
+    /// for both Bitcoin and Ethereum transactions.
+    fn sign(&self, path: &str, hash: &H256) -> Result<RecoverableSignature, Error>;
+}
+
+/// The default signer, deriving keys from the keyring's mnemonic.
+impl Signer for HdKeyring {
+    fn public_key(&self, path: &str) -> Result<PublicKey, Error> {
+        Ok(self.get_wallet_by_path(path)?.public_key)
+    }
+
+    fn sign(&self, path: &str, hash: &H256) -> Result<RecoverableSignature, Error> {
+        let wallet = self.get_wallet_by_path(path)?;
+
+        let secp = Secp256k1::new();
+        Ok(secp.sign_recoverable(&Message::from_slice(&hash[..])?, &wallet.secret_key))
+    }
+}
+
+/// Talks to a local signing daemon listening on a Unix socket.
+#[derive(Debug, Clone)]
+pub struct SocketSigner {
+    socket: PathBuf,
+    key_id: String,
+    timeout: Duration,
+}
+
+impl SocketSigner {
+    pub fn new<P: AsRef<Path>>(socket: P, key_id: &str) -> Self {
+        SocketSigner {
+            socket: socket.as_ref().to_path_buf(),
+            key_id: key_id.to_owned(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn request(&self, request: &str) -> Result<Vec<u8>, Error> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(b"\n")?;
+
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response)?;
+
+        let mut parts = response.trim_right().splitn(2, ' ');
+        match (parts.next(), parts.next()) {
+            (Some("ok"), Some(data)) => data
+                .from_hex()
+                .map_err(|_| Error::SignerError("malformed response".to_owned())),
+            (Some("error"), Some(message)) => Err(Error::SignerError(message.to_owned())),
+            _ => Err(Error::SignerError("malformed response".to_owned())),
+        }
+    }
+}
+
+impl Signer for SocketSigner {
+    fn public_key(&self, path: &str) -> Result<PublicKey, Error> {
+        let data = self.request(&format!("public_key {} {}", self.key_id, path))?;
+
+        Ok(PublicKey::from_slice(&Secp256k1::new(), &data)?)
+    }
+
+    fn sign(&self, path: &str, hash: &H256) -> Result<RecoverableSignature, Error> {
+        let data = self.request(&format!(
+            "sign {} {} {}",
+            self.key_id,
+            path,
+            hash[..].to_hex()
+        ))?;
+
+        if data.len() != 65 {
+            return Err(Error::SignerError("malformed signature".to_owned()));
+        }
+
+        let recovery_id = RecoveryId::from_i32(data[64] as i32)?;
+
+        Ok(RecoverableSignature::from_compact(
+            &Secp256k1::new(),
+            &data[0..64],
+            recovery_id,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, os::unix::net::UnixListener, process, thread};
+    use types::bitcoin::Network as BtcNetwork;
+
+    const MNEMONIC: &str =
+        "addict else general weird gospel excite void debate north include exercise liberty";
+    const PATH: &str = "m/44'/60'/0'/0/0";
+
+    fn keyring() -> HdKeyring {
+        HdKeyring::from_mnemonic("m", MNEMONIC, "", 0, BtcNetwork::Mainnet).unwrap()
+    }
+
+    fn to_bytes(signature: &RecoverableSignature) -> Vec<u8> {
+        let (recovery_id, data) = signature.serialize_compact(&Secp256k1::new());
+
+        let mut bytes = data.to_vec();
+        bytes.push(recovery_id.to_i32() as u8);
+        bytes
+    }
+
+    /// Answers `requests` requests the way a signing daemon holding `MNEMONIC` would.
+    fn daemon(socket: &Path, requests: usize) -> thread::JoinHandle<()> {
+        let _ = fs::remove_file(socket);
+        let listener = UnixListener::bind(socket).unwrap();
+
+        thread::spawn(move || {
+            let keyring = keyring();
+
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+                BufReader::new(&stream).read_line(&mut request).unwrap();
+
+                let parts = request.trim_right().split(' ').collect::<Vec<_>>();
+                let response = match parts[..] {
+                    ["public_key", _, path] => {
+                        let public_key = keyring.public_key(path).unwrap();
+                        format!("ok {}\n", public_key.serialize()[..].to_hex())
+                    }
+                    ["sign", _, path, hash] => {
+                        let hash = H256::from_slice(&hash.from_hex().unwrap());
+                        let signature = keyring.sign(path, &hash).unwrap();
+                        format!("ok {}\n", to_bytes(&signature).to_hex())
+                    }
+                    _ => "error unknown request\n".to_owned(),
+                };
+
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        })
+    }
+
+    #[test]
+    fn socket_signer_matches_keyring() {
+        let socket = env::temp_dir().join(format!("hd-keyring-signer-{}.sock", process::id()));
+        let handle = daemon(&socket, 2);
+
+        let hash = H256::from_data(b"payout");
+        let signer = SocketSigner::new(&socket, "store");
+
+        assert_eq!(
+            keyring().public_key(PATH).unwrap(),
+            signer.public_key(PATH).unwrap()
+        );
+        assert_eq!(
+            to_bytes(&keyring().sign(PATH, &hash).unwrap()),
+            to_bytes(&signer.sign(PATH, &hash).unwrap())
+        );
+
+        handle.join().unwrap();
+        fs::remove_file(&socket).unwrap();
+    }
+}
@@ -7,14 +7,21 @@ use secp256k1::{
 };
 use tiny_keccak::keccak256;
 
+use bech32;
 use errors::Error;
-use types::{bitcoin::Network as BtcNetwork, currency::Crypto, H160, H256};
+use types::{
+    bitcoin::{AddressFormat, Network as BtcNetwork},
+    currency::Crypto,
+    H160, H256,
+};
 
 #[derive(Debug)]
 pub struct Wallet {
     pub secret_key: SecretKey,
     pub public_key: PublicKey,
     pub btc_network: BtcNetwork,
+    /// Legacy unless the wallet was derived at a BIP84 path.
+    pub btc_address_format: AddressFormat,
 }
 
 impl Wallet {
@@ -26,6 +33,7 @@ impl Wallet {
             secret_key,
             public_key,
             btc_network: btc_network,
+            btc_address_format: AddressFormat::Legacy,
         })
     }
 
@@ -37,42 +45,63 @@ impl Wallet {
     }
 
     pub fn get_eth_address(&self) -> H160 {
-        let key_hash = keccak256(&self.public_key.serialize_uncompressed()[1..]); // Ignoring prefix 0x04.
-
-        let mut address = String::new();
-        address.push_str(
-            &key_hash[key_hash.len() - 20..]
-                .iter()
-                .map(|b| format!("{:02X}", b))
-                .collect::<String>(),
-        );
-
-        H160::from_str(&address).unwrap()
+        eth_address(&self.public_key)
     }
 
     pub fn get_btc_address(&self) -> String {
-        // h160 on public key.
-        let h160 = H160::from_data(&self.public_key.serialize()[..]);
+        match self.btc_address_format {
+            AddressFormat::Legacy => btc_address(&self.public_key, self.btc_network),
+            AddressFormat::Segwit => btc_segwit_address(&self.public_key, self.btc_network),
+        }
+    }
+}
 
-        // Add version prefix.
-        let mut prefixed = [0; 21];
+pub fn eth_address(public_key: &PublicKey) -> H160 {
+    let key_hash = keccak256(&public_key.serialize_uncompressed()[1..]); // Ignoring prefix 0x04.
 
-        prefixed[0] = match self.btc_network {
-            BtcNetwork::Mainnet => 0,
-            BtcNetwork::Test => 111,
-        };
+    let mut address = String::new();
+    address.push_str(
+        &key_hash[key_hash.len() - 20..]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<String>(),
+    );
 
-        prefixed[1..].copy_from_slice(&h160[..]);
+    H160::from_str(&address).unwrap()
+}
 
-        // h256 on prefixed h160.
-        let h256 = H256::from_data(&prefixed);
+pub fn btc_address(public_key: &PublicKey, btc_network: BtcNetwork) -> String {
+    // h160 on public key.
+    let h160 = H160::from_data(&public_key.serialize()[..]);
 
-        // 25 byte binary Bitcoin Address.
-        let mut address = [0; 25];
-        address[0..21].copy_from_slice(&prefixed);
-        address[21..].copy_from_slice(&h256[0..4]);
+    // Add version prefix.
+    let mut prefixed = [0; 21];
 
-        // Base58 string of the address.
-        address.to_base58()
-    }
+    prefixed[0] = match btc_network {
+        BtcNetwork::Mainnet => 0,
+        BtcNetwork::Test => 111,
+    };
+
+    prefixed[1..].copy_from_slice(&h160[..]);
+
+    // h256 on prefixed h160.
+    let h256 = H256::from_data(&prefixed);
+
+    // 25 byte binary Bitcoin Address.
+    let mut address = [0; 25];
+    address[0..21].copy_from_slice(&prefixed);
+    address[21..].copy_from_slice(&h256[0..4]);
+
+    // Base58 string of the address.
+    address.to_base58()
+}
+
+/// Native segwit (P2WPKH) address of the key, as BIP84 wallets derive them.
+pub fn btc_segwit_address(public_key: &PublicKey, btc_network: BtcNetwork) -> String {
+    let hrp = match btc_network {
+        BtcNetwork::Mainnet => "bc",
+        BtcNetwork::Test => "tb",
+    };
+
+    bech32::encode_segwit(hrp, 0, &H160::from_data(&public_key.serialize()[..])[..])
 }
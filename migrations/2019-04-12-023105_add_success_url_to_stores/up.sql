@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE stores ADD COLUMN success_url VARCHAR;
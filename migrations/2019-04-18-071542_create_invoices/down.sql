@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX payments_invoice_id_idx;
+ALTER TABLE payments DROP COLUMN invoice_id;
+DROP TABLE invoices;
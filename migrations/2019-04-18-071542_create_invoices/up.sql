@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE invoices
+(
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    store_id uuid NOT NULL,
+    created_by uuid NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    fiat VARCHAR NOT NULL,
+    price DECIMAL NOT NULL,
+    identifier VARCHAR(100)
+);
+
+ALTER TABLE payments ADD COLUMN invoice_id uuid;
+CREATE INDEX payments_invoice_id_idx ON payments (invoice_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE retired_store_keys;
+ALTER TABLE stores DROP COLUMN key_id;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+ALTER TABLE stores ADD COLUMN key_id uuid NOT NULL DEFAULT uuid_generate_v4();
+
+CREATE TABLE retired_store_keys
+(
+    id uuid PRIMARY KEY NOT NULL,
+    store_id uuid NOT NULL,
+    public_key BYTEA NOT NULL,
+    retired_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX retired_store_keys_store_id_idx ON retired_store_keys (store_id);
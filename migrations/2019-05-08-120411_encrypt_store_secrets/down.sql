@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE stores DROP COLUMN key_version;
+ALTER TABLE stores DROP COLUMN data_key;
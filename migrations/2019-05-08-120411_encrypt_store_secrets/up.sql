@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Rows with a NULL key_version still hold plaintext secrets. Finch encrypts them in place with
+-- the configured master key when it starts.
+ALTER TABLE stores ADD COLUMN data_key BYTEA;
+ALTER TABLE stores ADD COLUMN key_version INTEGER;
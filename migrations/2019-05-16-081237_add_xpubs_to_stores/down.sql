@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE stores DROP COLUMN eth_address_index;
+ALTER TABLE stores DROP COLUMN btc_address_index;
+ALTER TABLE stores DROP COLUMN eth_xpub;
+ALTER TABLE stores DROP COLUMN btc_xpub;
+ALTER TABLE stores ALTER COLUMN mnemonic SET NOT NULL;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- Watch-only stores have no mnemonic, their addresses are derived from the merchant's xpubs.
+ALTER TABLE stores ALTER COLUMN mnemonic DROP NOT NULL;
+ALTER TABLE stores ADD COLUMN btc_xpub VARCHAR;
+ALTER TABLE stores ADD COLUMN eth_xpub VARCHAR;
+ALTER TABLE stores ADD COLUMN btc_address_index INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE stores ADD COLUMN eth_address_index INTEGER NOT NULL DEFAULT 0;
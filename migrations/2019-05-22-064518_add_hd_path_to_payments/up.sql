@@ -0,0 +1,20 @@
+-- Your SQL goes here
+ALTER TABLE payments ADD COLUMN hd_path VARCHAR;
+
+-- Payments made before per-coin paths were derived under the store's path followed by the
+-- payment's creation time in seconds and microseconds.
+UPDATE payments
+SET hd_path = stores.hd_path
+    || '/' || FLOOR(EXTRACT(EPOCH FROM payments.created_at))::BIGINT
+    || '/' || (EXTRACT(MICROSECONDS FROM payments.created_at)::BIGINT % 1000000)
+    || '/' || (payments.index - 1)
+FROM stores
+WHERE payments.store_id = stores.id AND stores.mnemonic IS NOT NULL;
+
+-- Watch-only payments are relative to the store's xpub.
+UPDATE payments
+SET hd_path = stores.hd_path || '/' || (payments.index - 1)
+FROM stores
+WHERE payments.store_id = stores.id AND stores.mnemonic IS NULL;
+
+ALTER TABLE payments ALTER COLUMN hd_path SET NOT NULL;
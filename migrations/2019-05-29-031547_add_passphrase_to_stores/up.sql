@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Optional BIP39 passphrase, encrypted with the store's data key like the mnemonic.
+ALTER TABLE stores ADD COLUMN passphrase VARCHAR;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- The TOTP secret is encrypted like store secrets, under its own data key wrapped by the master key.
+ALTER TABLE users ADD COLUMN totp_secret BYTEA;
+ALTER TABLE users ADD COLUMN data_key BYTEA;
+ALTER TABLE users ADD COLUMN key_version INTEGER;
+-- Set once the user has confirmed a code, until then the secret is only pending.
+ALTER TABLE users ADD COLUMN totp_enabled_at TIMESTAMPTZ;
+-- Time step of the last accepted code, so codes can't be replayed.
+ALTER TABLE users ADD COLUMN totp_last_step BIGINT;
+-- SHA-256 hashes of the unused recovery codes.
+ALTER TABLE users ADD COLUMN recovery_codes VARCHAR[];
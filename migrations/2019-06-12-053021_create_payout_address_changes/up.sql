@@ -0,0 +1,20 @@
+-- Your SQL goes here
+-- Payout address changes wait here until they are confirmed by email and their cool-down has
+-- passed, so a stolen session can't redirect payouts right away.
+CREATE TABLE payout_address_changes
+(
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    store_id uuid NOT NULL,
+    eth_payout_addresses VARCHAR[],
+    btc_payout_addresses VARCHAR[],
+    confirmation_token uuid NOT NULL DEFAULT uuid_generate_v4(),
+    confirmation_token_expires_at TIMESTAMPTZ NOT NULL,
+    cancellation_token uuid NOT NULL DEFAULT uuid_generate_v4(),
+    created_at TIMESTAMPTZ NOT NULL,
+    confirmed_at TIMESTAMPTZ,
+    effective_at TIMESTAMPTZ,
+    applied_at TIMESTAMPTZ,
+    cancelled_at TIMESTAMPTZ
+);
+
+CREATE INDEX payout_address_changes_store_id_idx ON payout_address_changes (store_id);
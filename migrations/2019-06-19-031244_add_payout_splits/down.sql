@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE payout_legs;
+
+ALTER TABLE payout_address_changes DROP COLUMN btc_payout_splits;
+ALTER TABLE payout_address_changes DROP COLUMN eth_payout_splits;
+
+ALTER TABLE stores DROP COLUMN btc_payout_index;
+ALTER TABLE stores DROP COLUMN eth_payout_index;
+ALTER TABLE stores DROP COLUMN btc_payout_splits;
+ALTER TABLE stores DROP COLUMN eth_payout_splits;
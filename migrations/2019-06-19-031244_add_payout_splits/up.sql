@@ -0,0 +1,25 @@
+-- Your SQL goes here
+-- Percentage of each payout going to the payout address at the same position, summing to 100.
+-- Without splits payouts rotate through the payout addresses.
+ALTER TABLE stores ADD COLUMN eth_payout_splits INTEGER[];
+ALTER TABLE stores ADD COLUMN btc_payout_splits INTEGER[];
+-- Number of payouts rotated through the payout addresses so far.
+ALTER TABLE stores ADD COLUMN eth_payout_index INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE stores ADD COLUMN btc_payout_index INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE payout_address_changes ADD COLUMN eth_payout_splits INTEGER[];
+ALTER TABLE payout_address_changes ADD COLUMN btc_payout_splits INTEGER[];
+
+-- One row per transfer of a payout. A split BTC payout is a single transaction with an output
+-- per leg, a split ETH payout is a transfer per leg.
+CREATE TABLE payout_legs
+(
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    payout_id uuid NOT NULL,
+    leg INTEGER NOT NULL,
+    address VARCHAR NOT NULL,
+    value NUMERIC NOT NULL,
+    transaction_hash VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    UNIQUE (payout_id, leg)
+);
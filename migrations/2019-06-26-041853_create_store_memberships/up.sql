@@ -0,0 +1,18 @@
+-- Your SQL goes here
+-- Users a store is shared with. The owner of a store isn't listed, `stores.owner_id` already
+-- makes them one. `user_id` is filled in once the emailed invitation is accepted.
+CREATE TABLE store_memberships
+(
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    store_id uuid NOT NULL,
+    user_id uuid,
+    email VARCHAR NOT NULL,
+    role VARCHAR NOT NULL,
+    invitation_token uuid NOT NULL DEFAULT uuid_generate_v4(),
+    invitation_expires_at TIMESTAMPTZ NOT NULL,
+    accepted_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL,
+    UNIQUE (store_id, email)
+);
+
+CREATE INDEX store_memberships_user_id_idx ON store_memberships (user_id);
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- Who did what to an account or a store. Entries are only ever added, the rules below turn
+-- updates and deletes into no-ops.
+CREATE TABLE audit_logs
+(
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    action VARCHAR NOT NULL,
+    store_id uuid,
+    user_id uuid,
+    subject_id uuid,
+    ip VARCHAR,
+    changes JSON,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX audit_logs_store_id_created_at_idx ON audit_logs (store_id, created_at);
+
+CREATE RULE audit_logs_no_update AS ON UPDATE TO audit_logs DO INSTEAD NOTHING;
+CREATE RULE audit_logs_no_delete AS ON DELETE TO audit_logs DO INSTEAD NOTHING;
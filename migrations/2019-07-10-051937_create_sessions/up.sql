@@ -0,0 +1,21 @@
+-- Your SQL goes here
+-- A signed in device. Access tokens name the session they were issued for and stop working once
+-- it's revoked; the refresh token is replaced every time it's used.
+CREATE TABLE sessions
+(
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL,
+    refresh_token uuid NOT NULL,
+    refresh_token_expires_at TIMESTAMPTZ NOT NULL,
+    ip VARCHAR,
+    user_agent VARCHAR,
+    created_at TIMESTAMPTZ NOT NULL,
+    last_used_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX sessions_refresh_token_idx ON sessions (refresh_token);
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
+
+-- Access tokens issued before this are rejected.
+ALTER TABLE users ADD COLUMN password_changed_at TIMESTAMPTZ;
@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE client_tokens DROP COLUMN max_price;
+ALTER TABLE client_tokens DROP COLUMN allowed_fiats;
+ALTER TABLE client_tokens DROP COLUMN allowed_cryptos;
+
+DELETE FROM client_tokens WHERE typ = 'native';
+ALTER TABLE client_tokens ADD COLUMN domain VARCHAR NOT NULL DEFAULT '';
+UPDATE client_tokens SET domain = COALESCE(allowed_origins[1], '');
+ALTER TABLE client_tokens ALTER COLUMN domain DROP DEFAULT;
+ALTER TABLE client_tokens DROP COLUMN allowed_origins;
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- Origins a web token may be used from. A pattern starting with `*.` matches any subdomain.
+ALTER TABLE client_tokens ADD COLUMN allowed_origins VARCHAR[] NOT NULL DEFAULT '{}';
+UPDATE client_tokens SET allowed_origins = ARRAY[domain];
+ALTER TABLE client_tokens DROP COLUMN domain;
+
+-- Without restrictions a token can request any currency the store accepts, at any price.
+ALTER TABLE client_tokens ADD COLUMN allowed_cryptos VARCHAR[];
+ALTER TABLE client_tokens ADD COLUMN allowed_fiats VARCHAR[];
+ALTER TABLE client_tokens ADD COLUMN max_price NUMERIC;
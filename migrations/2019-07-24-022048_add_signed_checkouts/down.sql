@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE payments DROP COLUMN signed;
+ALTER TABLE invoices DROP COLUMN signed;
+
+ALTER TABLE stores DROP COLUMN checkout_secret;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- Secret the merchant's backend signs checkouts with, sealed like the store's other secrets.
+-- Once a store has one, payments and invoices can only be created from a signed checkout.
+ALTER TABLE stores ADD COLUMN checkout_secret VARCHAR;
+
+ALTER TABLE invoices ADD COLUMN signed BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE payments ADD COLUMN signed BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE payments DROP COLUMN metadata;
+ALTER TABLE invoices DROP COLUMN metadata;
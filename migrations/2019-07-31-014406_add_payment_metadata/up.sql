@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Whatever the merchant wants to find the payment by later, like order and customer ids.
+-- Payments created for an invoice get the invoice's metadata.
+ALTER TABLE invoices ADD COLUMN metadata JSONB;
+ALTER TABLE payments ADD COLUMN metadata JSONB;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- Requests made with an Idempotency-Key header. `scope` keeps keys of different clients and
+-- endpoints apart, `fingerprint` is a hash of the request's parameters and `response` is empty
+-- while the first request is still being handled.
+CREATE TABLE idempotency_keys
+(
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    scope VARCHAR NOT NULL,
+    key VARCHAR NOT NULL,
+    fingerprint VARCHAR NOT NULL,
+    response JSONB,
+    created_at TIMESTAMPTZ NOT NULL,
+    UNIQUE (scope, key)
+);
+
+CREATE INDEX idempotency_keys_created_at_idx ON idempotency_keys (created_at);
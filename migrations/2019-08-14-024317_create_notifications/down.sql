@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE notifications;
+DROP TABLE email_templates;
+
+ALTER TABLE stores DROP COLUMN daily_summary;
+ALTER TABLE stores DROP COLUMN large_payment_threshold;
+
+ALTER TABLE invoices DROP COLUMN customer_email;
+ALTER TABLE payments DROP COLUMN customer_email;
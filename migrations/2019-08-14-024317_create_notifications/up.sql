@@ -0,0 +1,41 @@
+-- Your SQL goes here
+-- Where the customer's receipt goes, if the merchant passes it along.
+ALTER TABLE payments ADD COLUMN customer_email VARCHAR;
+ALTER TABLE invoices ADD COLUMN customer_email VARCHAR;
+
+-- Payments of at least `large_payment_threshold`, in the payment's fiat, are reported to the
+-- owner. `daily_summary` opts the owner in to a summary of the previous day.
+ALTER TABLE stores ADD COLUMN large_payment_threshold NUMERIC;
+ALTER TABLE stores ADD COLUMN daily_summary BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- A store's own version of one of the emails it sends, used instead of the default.
+CREATE TABLE email_templates
+(
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    store_id uuid NOT NULL,
+    kind VARCHAR NOT NULL,
+    subject VARCHAR NOT NULL,
+    html TEXT NOT NULL,
+    text TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    UNIQUE (store_id, kind)
+);
+
+-- Emails that went out, so each one is only sent once. `subject_id` is the payment or payout the
+-- email is about, or the store for daily summaries, which also have the day they cover.
+CREATE TABLE notifications
+(
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    store_id uuid NOT NULL,
+    kind VARCHAR NOT NULL,
+    subject_id uuid NOT NULL,
+    period DATE,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE UNIQUE INDEX notifications_kind_subject_id_idx ON notifications (kind, subject_id)
+    WHERE period IS NULL;
+CREATE UNIQUE INDEX notifications_kind_subject_id_period_idx
+    ON notifications (kind, subject_id, period) WHERE period IS NOT NULL;
+CREATE INDEX notifications_created_at_idx ON notifications (created_at);
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- 'segwit' stores take Bitcoin deposits on native segwit addresses at BIP84 paths, 'legacy'
+-- ones on P2PKH addresses at BIP44 paths.
+ALTER TABLE stores ADD COLUMN btc_address_format VARCHAR NOT NULL DEFAULT 'legacy';
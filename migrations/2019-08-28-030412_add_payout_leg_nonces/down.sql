@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE payout_legs DROP COLUMN raw_transaction;
+ALTER TABLE payout_legs DROP COLUMN nonce;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- ETH legs are recorded before they are broadcast. A retried payout resends the recorded
+-- transaction unless the payout address already used its nonce.
+ALTER TABLE payout_legs ADD COLUMN nonce NUMERIC;
+ALTER TABLE payout_legs ADD COLUMN raw_transaction VARCHAR;
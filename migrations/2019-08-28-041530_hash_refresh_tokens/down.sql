@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE used_refresh_tokens;
+
+-- The tokens can't be recovered from their hashes, so the sessions end.
+DELETE FROM sessions;
+
+DROP INDEX sessions_refresh_token_hash_idx;
+ALTER TABLE sessions DROP COLUMN refresh_token_hash;
+ALTER TABLE sessions ADD COLUMN refresh_token uuid NOT NULL;
+CREATE UNIQUE INDEX sessions_refresh_token_idx ON sessions (refresh_token);
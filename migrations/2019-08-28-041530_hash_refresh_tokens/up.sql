@@ -0,0 +1,20 @@
+-- Your SQL goes here
+-- Refresh tokens are only stored as their hex encoded SHA-256.
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+
+ALTER TABLE sessions ADD COLUMN refresh_token_hash VARCHAR;
+UPDATE sessions SET refresh_token_hash = encode(digest(refresh_token::text, 'sha256'), 'hex');
+ALTER TABLE sessions ALTER COLUMN refresh_token_hash SET NOT NULL;
+
+DROP INDEX sessions_refresh_token_idx;
+ALTER TABLE sessions DROP COLUMN refresh_token;
+CREATE UNIQUE INDEX sessions_refresh_token_hash_idx ON sessions (refresh_token_hash);
+
+-- Refresh tokens that were swapped for new ones. One that comes back has been copied, and the
+-- session it belongs to is revoked.
+CREATE TABLE used_refresh_tokens
+(
+    token_hash VARCHAR PRIMARY KEY NOT NULL,
+    session_id uuid NOT NULL,
+    used_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE client_tokens DROP COLUMN max_price_fiat;
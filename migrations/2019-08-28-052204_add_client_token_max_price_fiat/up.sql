@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- The fiat currency max_price is in. Payments in any other currency are rejected while a token
+-- has a maximum price. Tokens limited to a single fiat keep their maximum in it; others have to
+-- have their maximum set again before they take payments.
+ALTER TABLE client_tokens ADD COLUMN max_price_fiat VARCHAR;
+UPDATE client_tokens SET max_price_fiat = allowed_fiats[1]
+    WHERE max_price IS NOT NULL AND array_length(allowed_fiats, 1) = 1;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- When the request holding a key took it. A key without a response whose request took it more
+-- than a few minutes ago was left behind by a request that crashed or timed out, and the next
+-- retry takes it over.
+ALTER TABLE idempotency_keys ADD COLUMN locked_at TIMESTAMPTZ;
+UPDATE idempotency_keys SET locked_at = created_at;
+ALTER TABLE idempotency_keys ALTER COLUMN locked_at SET NOT NULL;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- The fiat currency large_payment_threshold is in. Payments in other currencies aren't compared
+-- with it. USD is the only fiat payments are taken in so far, so existing thresholds are in it.
+ALTER TABLE stores ADD COLUMN large_payment_threshold_fiat VARCHAR;
+UPDATE stores SET large_payment_threshold_fiat = 'usd' WHERE large_payment_threshold IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Notifications look for payments paid in the last day every minute.
+CREATE INDEX payments_paid_at_idx ON payments (paid_at);
@@ -1,49 +1,76 @@
+use std::sync::Arc;
+
 use actix::prelude::*;
-use futures::future::{self, Future, IntoFuture};
+use futures::{
+    future::{self, Future},
+    stream, Stream,
+};
 
 use blockchain_api_client::bitcoin::{
     BlockchainApiClientAddr, EstimateSmartFee, SendRawTransaction, UnsignedTransaction,
 };
 use errors::Error;
 
+use config::SignerConfig;
 use core::{
-    bitcoin::{ScriptType, Transaction},
-    db::postgres::PgExecutorAddr,
+    bitcoin::Transaction,
+    crypto::Keychain,
+    db::{postgres::PgExecutorAddr, redis::RedisExecutorAddr},
     payment::PaymentPayload,
     payout::{Payout, PayoutPayload},
+    payout_leg::{PayoutLeg, PayoutLegPayload},
     store::Store,
 };
-use hd_keyring::{HdKeyring, Wallet};
-use types::{bitcoin::Network as BtcNetwork, PaymentStatus, PayoutStatus, H256};
+use hd_keyring::Signer;
+use signer::{self, GetBtcAddress, SignBtcTransaction, SignerExecutorAddr};
+use split;
+use types::{bitcoin::Network as BtcNetwork, currency::Crypto, PaymentStatus, PayoutStatus, U256};
+
+// Outputs below this many satoshi are rejected as dust by nodes.
+const DUST_LIMIT: u64 = 546;
 
 pub type PayouterAddr = Addr<Payouter>;
 
 pub struct Payouter {
     pub postgres: PgExecutorAddr,
+    pub redis: RedisExecutorAddr,
     pub blockchain_api_client: BlockchainApiClientAddr,
     pub network: BtcNetwork,
+    pub keychain: Keychain,
+    pub signer: Option<SignerConfig>,
+    pub signer_executor: SignerExecutorAddr,
 }
 
 impl Payouter {
     pub fn new(
         pg_addr: PgExecutorAddr,
+        redis: RedisExecutorAddr,
         blockchain_api_client: BlockchainApiClientAddr,
         network: BtcNetwork,
+        keychain: Keychain,
+        signer: Option<SignerConfig>,
+        signer_executor: SignerExecutorAddr,
     ) -> Self {
         Payouter {
             postgres: pg_addr,
+            redis,
             blockchain_api_client,
             network,
+            keychain,
+            signer,
+            signer_executor,
         }
     }
 
     pub fn prepare_payout(
         &self,
         payout: Payout,
-    ) -> impl Future<Item = (Wallet, Transaction, Store, f64), Error = Error> {
+    ) -> impl Future<Item = (Arc<Signer>, String, Transaction, Store, f64), Error = Error> {
         let postgres = self.postgres.clone();
         let blockchain_api_client = self.blockchain_api_client.clone();
+        let keychain = self.keychain.clone();
         let network = self.network.clone();
+        let signer_config = self.signer.clone();
 
         let store = payout.store(&postgres).from_err();
         let payment = payout.payment(&postgres).from_err();
@@ -66,81 +93,145 @@ impl Payouter {
                 Transaction::find_by_hash(payment.clone().transaction_hash.unwrap(), &postgres)
                     .from_err()
                     .and_then(move |transaction| {
-                        let mut path = store.hd_path.clone();
-
-                        path.push_str("/");
-                        path.push_str(&payment.created_at.timestamp().to_string());
-                        path.push_str("/");
-                        path.push_str(&payment.created_at.timestamp_subsec_micros().to_string());
-
-                        HdKeyring::from_mnemonic(&path, &store.mnemonic.clone(), 0, network)
-                            .into_future()
-                            .from_err()
-                            .and_then(move |keyring| {
-                                keyring
-                                    .get_wallet_by_index(payment.index as u32)
-                                    .into_future()
-                                    .from_err()
-                                    .and_then(move |wallet| {
-                                        future::ok((wallet, transaction, store, transaction_fee))
-                                    })
-                            })
+                        signer::for_store(&store, signer_config.as_ref(), &keychain, network).map(
+                            move |signer| {
+                                (signer, payment.hd_path, transaction, store, transaction_fee)
+                            },
+                        )
                     })
             })
     }
 
-    pub fn payout(&self, payout: Payout) -> impl Future<Item = H256, Error = Error> {
+    pub fn payout(&self, payout: Payout) -> impl Future<Item = Vec<PayoutLeg>, Error = Error> {
         let blockchain_api_client = self.blockchain_api_client.clone();
+        let signer_executor = self.signer_executor.clone();
+        let _signer_executor = self.signer_executor.clone();
+        let network = self.network;
+        let postgres = self.postgres.clone();
 
         self.prepare_payout(payout)
             .and_then(
-                move |(wallet, transaction, store, transaction_fee)| -> Box<Future<Item=H256, Error = Error>> {
-                    let payout_address = if let Some(payout_addresses) = store.btc_payout_addresses {
-                        payout_addresses[0].to_owned()
-                    } else {
-                        return Box::new(future::err(Error::NoPayoutAddress));
-                    };
-
-                    let recepient = wallet.get_btc_address();
-
+                move |(signer, hd_path, transaction, store, transaction_fee)| {
+                    split::recipients(
+                        &store,
+                        Crypto::Btc,
+                        store.btc_payout_addresses.clone(),
+                        store.btc_payout_splits.clone(),
+                    )
+                    .map(move |recipients| {
+                        (signer, hd_path, transaction, recipients, transaction_fee)
+                    })
+                },
+            )
+            .and_then(
+                move |(signer, hd_path, transaction, recipients, transaction_fee)| {
+                    signer_executor
+                        .send(GetBtcAddress {
+                            signer: signer.clone(),
+                            hd_path: hd_path.clone(),
+                            network,
+                        })
+                        .from_err()
+                        .and_then(move |res| res)
+                        .map(move |recepient| {
+                            (
+                                signer,
+                                hd_path,
+                                recepient,
+                                transaction,
+                                recipients,
+                                transaction_fee,
+                            )
+                        })
+                },
+            )
+            .and_then(
+                move |(signer, hd_path, recepient, transaction, recipients, transaction_fee)| {
                     let mut utxo_n = 0;
                     for output in transaction.vout.iter() {
-                        match output.script.script_type {
-                            ScriptType::PubKeyHash => {
-                                if let Some(ref addresses) = output.script.addresses {
-                                    if addresses[0] == recepient {
-                                        utxo_n = output.n;
-                                    }
-                                }
+                        if let Some(ref addresses) = output.script.addresses {
+                            if addresses[0] == recepient {
+                                utxo_n = output.n;
                             }
-                            _ => (),
-                        };
+                        }
                     }
                     let utxo = transaction.vout[utxo_n as usize].clone();
                     let value = (utxo.value * (100_000_000 as f64)) as u64;
 
                     // In satoshi
                     let tx_fee_per_byte = (transaction_fee * (100_000_000 as f64)) / 1000 as f64;
+                    // Every output past the first adds about 34 bytes.
+                    let fee = tx_fee_per_byte as u64 * (192 + 34 * (recipients.len() as u64 - 1));
 
-                    if value <= tx_fee_per_byte as u64 * 192 {
+                    if value <= fee {
                         info!("Insufficient funds to pay out.");
-                        return Box::new(future::err(Error::InsufficientFunds));
+                        return Err(Error::InsufficientFunds);
                     }
 
-                    let mut tx = UnsignedTransaction::new(
+                    let shares = recipients
+                        .iter()
+                        .map(|&(_, share)| share)
+                        .collect::<Vec<_>>();
+                    let values = split::split(U256::from(value - fee), &shares);
+
+                    let outputs = recipients
+                        .into_iter()
+                        .zip(values)
+                        .map(|((address, _), value)| (address.to_string(), value.low_u64()))
+                        .collect::<Vec<_>>();
+
+                    // Outputs too small to be relayed go to the largest one instead.
+                    let outputs = match split::merge_dust(outputs, DUST_LIMIT) {
+                        Some(outputs) => outputs,
+                        None => {
+                            info!("Insufficient funds to pay out.");
+                            return Err(Error::InsufficientFunds);
+                        }
+                    };
+
+                    let tx = UnsignedTransaction::new(
                         vec![(transaction.clone(), utxo.n)],
-                        vec![(payout_address.to_string().clone(), value - tx_fee_per_byte as u64 * 192)],
+                        outputs.clone(),
                     );
 
-                    tx.sign(wallet.secret_key, wallet.public_key);
-                    let raw_transaction = tx.into_raw_transaction();
-
-                    Box::new(blockchain_api_client
-                        .send(SendRawTransaction(raw_transaction))
-                        .from_err()
-                        .and_then(move |res| res.map_err(|e| Error::from(e))))
+                    Ok((signer, hd_path, tx, outputs))
                 },
             )
+            .and_then(move |(signer, hd_path, tx, outputs)| {
+                _signer_executor
+                    .send(SignBtcTransaction {
+                        signer,
+                        hd_path,
+                        transaction: tx,
+                    })
+                    .from_err()
+                    .and_then(move |res| res)
+                    .and_then(move |tx| {
+                        blockchain_api_client
+                            .send(SendRawTransaction(tx.into_raw_transaction()))
+                            .from_err()
+                            .and_then(move |res| res.map_err(|e| Error::from(e)))
+                    })
+                    .map(move |hash| (hash, outputs))
+            })
+            .and_then(move |(hash, outputs)| {
+                // A split payout is a single transaction with an output per leg.
+                stream::iter_ok(outputs.into_iter().enumerate())
+                    .and_then(move |(leg, (address, value))| {
+                        PayoutLeg::insert(
+                            PayoutLegPayload::new(
+                                payout.id,
+                                leg as i32,
+                                address,
+                                U256::from(value),
+                                hash,
+                            ),
+                            &postgres,
+                        )
+                        .from_err()
+                    })
+                    .collect()
+            })
     }
 }
 
@@ -179,11 +270,13 @@ impl Handler<PayOut> for Payouter {
     fn handle(&mut self, PayOut(payout): PayOut, _: &mut Self::Context) -> Self::Result {
         let postgres = self.postgres.clone();
         let _postgres = self.postgres.clone();
+        let redis = self.redis.clone();
 
         Box::new(
             self.payout(payout)
                 .from_err()
-                .and_then(move |hash| {
+                .and_then(move |legs| {
+                    let hash = legs[0].transaction_hash;
                     info!("Paid out {}", hash);
 
                     let mut payout_payload = PayoutPayload::from(payout);
@@ -200,8 +293,11 @@ impl Handler<PayOut> for Payouter {
                         &postgres,
                     )
                     .from_err()
+                    .and_then(move |(_, payment)| {
+                        payment.notify(&redis);
+                        split::rotate(&payout, &postgres)
+                    })
                 })
-                .map(move |_| ())
                 .or_else(move |e| -> Self::Result {
                     match e {
                         Error::InsufficientFunds => {
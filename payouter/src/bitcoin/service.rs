@@ -1,13 +1,36 @@
 use actix::prelude::*;
 
 use super::{monitor::Monitor, payouter::Payouter};
-use core::db::postgres;
+use config::SignerConfig;
+use core::{
+    crypto::Keychain,
+    db::{postgres, redis::RedisExecutorAddr},
+};
 use blockchain_api_client::bitcoin::BlockchainApiClientAddr;
+use signer::SignerExecutor;
 use types::bitcoin::Network as BtcNetwork;
 
-pub fn run(postgres: postgres::PgExecutorAddr, blockchain_api_client: BlockchainApiClientAddr, network: BtcNetwork) {
+pub fn run(
+    postgres: postgres::PgExecutorAddr,
+    redis: RedisExecutorAddr,
+    blockchain_api_client: BlockchainApiClientAddr,
+    network: BtcNetwork,
+    keychain: Keychain,
+    signer: Option<SignerConfig>,
+) {
     let pg = postgres.clone();
-    let payouter = Arbiter::start(move |_| Payouter::new(pg, blockchain_api_client, network));
+    let signer_executor = SyncArbiter::start(2, || SignerExecutor);
+    let payouter = Arbiter::start(move |_| {
+        Payouter::new(
+            pg,
+            redis,
+            blockchain_api_client,
+            network,
+            keychain,
+            signer,
+            signer_executor,
+        )
+    });
 
     Arbiter::start(move |_| Monitor::new(payouter, network, postgres));
 }
@@ -0,0 +1,147 @@
+use futures::{future, stream, Future, Stream};
+
+use blockchain_api_client::bitcoin::{
+    BlockchainApiClientAddr, EstimateSmartFee, GetRawTransaction, ScanTxOutSet, SendRawTransaction,
+    UnsignedTransaction,
+};
+use errors::Error;
+use hd_keyring::Wallet;
+use types::{bitcoin::AddressFormat, H160, H256};
+
+/// One unspent output found on a deposit address. `transaction_hash` stays `None` on a dry run,
+/// when the output can't cover the fee and when sweeping it failed, see `error`.
+#[derive(Debug)]
+pub struct Sweep {
+    pub address: String,
+    pub hd_path: String,
+    pub value: u64,
+    pub transaction_hash: Option<H256>,
+    pub error: Option<String>,
+}
+
+/// Sends every unspent output on the given `(hd_path, wallet)` addresses to `destination`. This
+/// bypasses payouts entirely and is meant for recovering funds, e.g. from `finch sweep`. An output
+/// that fails to sweep doesn't stop the others.
+pub fn sweep(
+    wallets: Vec<(String, Wallet)>,
+    destination: String,
+    dry_run: bool,
+    blockchain_api_client: BlockchainApiClientAddr,
+) -> impl Future<Item = Vec<Sweep>, Error = Error> {
+    let client = blockchain_api_client.clone();
+
+    let addresses = wallets
+        .iter()
+        .map(|&(_, ref wallet)| wallet.get_btc_address())
+        .collect();
+
+    let transaction_fee = blockchain_api_client
+        .send(EstimateSmartFee(10))
+        .from_err()
+        .and_then(move |res| res.map_err(|e| Error::from(e)));
+    let outpoints = blockchain_api_client
+        .send(ScanTxOutSet(addresses))
+        .from_err()
+        .and_then(move |res| res.map_err(|e| Error::from(e)));
+
+    transaction_fee
+        .join(outpoints)
+        .and_then(|(transaction_fee, outpoints)| {
+            if transaction_fee == 0 as f64 {
+                return Err(Error::InvalidGasPrice);
+            }
+
+            Ok((transaction_fee, outpoints))
+        })
+        .and_then(move |(transaction_fee, outpoints)| {
+            // In satoshi, sized for one input and one output like regular payouts.
+            let fee = ((transaction_fee * (100_000_000 as f64)) / 1000 as f64) as u64 * 192;
+
+            let outputs = outpoints
+                .into_iter()
+                .filter_map(|(txid, vout, script_pubkey)| {
+                    wallets
+                        .iter()
+                        .find(|&&(_, ref wallet)| btc_script_pubkey(wallet) == script_pubkey)
+                        .map(|&(ref hd_path, ref wallet)| {
+                            let sweep = Sweep {
+                                address: wallet.get_btc_address(),
+                                hd_path: hd_path.clone(),
+                                value: 0,
+                                transaction_hash: None,
+                                error: None,
+                            };
+
+                            (sweep, txid, vout, wallet.secret_key, wallet.public_key)
+                        })
+                })
+                .collect::<Vec<_>>();
+
+            stream::iter_ok(outputs)
+                .and_then(move |(mut sweep, txid, vout, secret_key, public_key)| {
+                    let _client = client.clone();
+                    let destination = destination.clone();
+
+                    client
+                        .send(GetRawTransaction(txid))
+                        .from_err()
+                        .and_then(move |res| res.map_err(|e| Error::from(e)))
+                        .then(move |res| -> Box<Future<Item = Sweep, Error = Error>> {
+                            let transaction = match res {
+                                Ok(transaction) => transaction,
+                                Err(e) => {
+                                    sweep.error = Some(e.to_string());
+                                    return Box::new(future::ok(sweep));
+                                }
+                            };
+
+                            let value = transaction.vout[vout as usize].value;
+                            sweep.value = (value * (100_000_000 as f64)) as u64;
+
+                            if sweep.value <= fee || dry_run {
+                                return Box::new(future::ok(sweep));
+                            }
+
+                            let mut tx = UnsignedTransaction::new(
+                                vec![(transaction, vout)],
+                                vec![(destination, sweep.value - fee)],
+                            );
+                            tx.sign(secret_key, public_key);
+
+                            broadcast(sweep, tx.into_raw_transaction(), &_client)
+                        })
+                })
+                .collect()
+        })
+}
+
+/// Hex of the script pubkey of the wallet's Bitcoin address, as `scantxoutset` reports it.
+fn btc_script_pubkey(wallet: &Wallet) -> String {
+    let hash = H160::from_data(&wallet.public_key.serialize()[..]);
+
+    match wallet.btc_address_format {
+        AddressFormat::Legacy => format!("76a914{}88ac", hash),
+        AddressFormat::Segwit => format!("0014{}", hash),
+    }
+}
+
+fn broadcast(
+    mut sweep: Sweep,
+    raw_transaction: Vec<u8>,
+    blockchain_api_client: &BlockchainApiClientAddr,
+) -> Box<Future<Item = Sweep, Error = Error>> {
+    Box::new(
+        blockchain_api_client
+            .send(SendRawTransaction(raw_transaction))
+            .from_err()
+            .and_then(move |res| res.map_err(|e| Error::from(e)))
+            .then(move |res| -> Result<Sweep, Error> {
+                match res {
+                    Ok(hash) => sweep.transaction_hash = Some(hash),
+                    Err(e) => sweep.error = Some(e.to_string()),
+                }
+
+                Ok(sweep)
+            }),
+    )
+}
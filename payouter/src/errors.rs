@@ -1,7 +1,7 @@
 use blockchain_api_client::errors::Error as BlockchainApiClientError;
 
 use actix::MailboxError;
-use core::ModelError;
+use core::{crypto::Error as CryptoError, ModelError};
 use hd_keyring::Error as KeyringError;
 
 #[derive(Debug, Fail)]
@@ -15,6 +15,8 @@ pub enum Error {
     #[fail(display = "{}", _0)]
     ModelError(#[cause] ModelError),
     #[fail(display = "{}", _0)]
+    CryptoError(#[cause] CryptoError),
+    #[fail(display = "{}", _0)]
     BlockchainApiClientError(#[cause] BlockchainApiClientError),
     #[fail(display = "{}", _0)]
     MailboxError(#[cause] MailboxError),
@@ -24,6 +26,8 @@ pub enum Error {
     InvalidGasPrice,
     #[fail(display = "insufficient funds")]
     InsufficientFunds,
+    #[fail(display = "watch-only stores can't be paid out")]
+    WatchOnlyStore,
 }
 
 impl From<KeyringError> for Error {
@@ -38,6 +42,12 @@ impl From<ModelError> for Error {
     }
 }
 
+impl From<CryptoError> for Error {
+    fn from(e: CryptoError) -> Error {
+        Error::CryptoError(e)
+    }
+}
+
 impl From<BlockchainApiClientError> for Error {
     fn from(e: BlockchainApiClientError) -> Error {
         Error::BlockchainApiClientError(e)
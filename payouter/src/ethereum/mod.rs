@@ -1,3 +1,4 @@
 pub mod monitor;
 pub mod payouter;
 pub mod service;
+pub mod sweeper;
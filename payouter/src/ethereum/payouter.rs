@@ -1,53 +1,76 @@
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc};
 
 use actix::prelude::*;
-use futures::future::{self, Future, IntoFuture};
+use futures::{
+    future::{self, Future, IntoFuture},
+    stream, Stream,
+};
 
 use blockchain_api_client::ethereum::{
-    BlockchainApiClientAddr, GetGasPrice, GetTransactionCount, SendRawTransaction,
-    UnsignedTransaction,
+    BlockchainApiClientAddr, GetGasPrice, GetTransactionCount, ResendRawTransaction,
+    SendRawTransaction, UnsignedTransaction,
 };
+use config::SignerConfig;
 use core::{
-    db::postgres::PgExecutorAddr,
+    crypto::Keychain,
+    db::{postgres::PgExecutorAddr, redis::RedisExecutorAddr},
     ethereum::Transaction,
     payment::PaymentPayload,
     payout::{Payout, PayoutPayload},
+    payout_leg::{PayoutLeg, PayoutLegPayload},
     store::Store,
 };
 use errors::Error;
-use hd_keyring::{HdKeyring, Wallet};
+use hd_keyring::Signer;
+use signer::{self, SignEthTransaction, SignerExecutorAddr};
+use split;
 use types::{
-    bitcoin::Network as BtcNetwork, ethereum::Network as EthNetwork, PaymentStatus, PayoutAction,
-    PayoutStatus, H160, H256, U128, U256,
+    bitcoin::Network as BtcNetwork, currency::Crypto, ethereum::Network as EthNetwork,
+    PaymentStatus, PayoutAction, PayoutStatus, H160, H256, U128, U256,
 };
 
 pub type PayouterAddr = Addr<Payouter>;
 
 pub struct Payouter {
     pub postgres: PgExecutorAddr,
+    pub redis: RedisExecutorAddr,
     pub blockchain_api_client: BlockchainApiClientAddr,
     pub network: EthNetwork,
+    pub keychain: Keychain,
+    pub signer: Option<SignerConfig>,
+    pub signer_executor: SignerExecutorAddr,
 }
 
 impl Payouter {
     pub fn new(
         pg_addr: PgExecutorAddr,
+        redis: RedisExecutorAddr,
         blockchain_api_client: BlockchainApiClientAddr,
         network: EthNetwork,
+        keychain: Keychain,
+        signer: Option<SignerConfig>,
+        signer_executor: SignerExecutorAddr,
     ) -> Self {
         Payouter {
             postgres: pg_addr,
+            redis,
             blockchain_api_client,
             network,
+            keychain,
+            signer,
+            signer_executor,
         }
     }
 
     pub fn prepare_payout(
         &self,
         payout: Payout,
-    ) -> impl Future<Item = (Wallet, Transaction, Store, U256, U128), Error = Error> {
+    ) -> impl Future<Item = (Arc<Signer>, String, Transaction, Store, U256, U128), Error = Error>
+    {
         let postgres = self.postgres.clone();
         let blockchain_api_client = self.blockchain_api_client.clone();
+        let keychain = self.keychain.clone();
+        let signer_config = self.signer.clone();
 
         let store = payout.store(&postgres).from_err();
         let payment = payout.payment(&postgres).from_err();
@@ -58,7 +81,7 @@ impl Payouter {
 
         store.join3(payment, gas_price).and_then(
             move |(store, payment, gas_price)| -> Box<
-                Future<Item = (Wallet, Transaction, Store, U256, U128), Error = Error>,
+                Future<Item = (Arc<Signer>, String, Transaction, Store, U256, U128), Error = Error>,
             > {
                 if gas_price == U256::from(0) {
                     return Box::new(future::err(Error::InvalidGasPrice));
@@ -77,39 +100,35 @@ impl Payouter {
 
                 Box::new(transaction.join(nonce).and_then(
                     move |(transaction, nonce)| -> Box<
-                        Future<Item = (Wallet, Transaction, Store, U256, U128), Error = Error>,
+                        Future<
+                            Item = (Arc<Signer>, String, Transaction, Store, U256, U128),
+                            Error = Error,
+                        >,
                     > {
                         if transaction.value <= (gas_price * U256::from(21_000)) {
                             info!("Insufficient funds to pay out");
                             return Box::new(future::err(Error::InsufficientFunds));
                         }
 
-                        let mut path = store.hd_path.clone();
-
-                        path.push_str("/");
-                        path.push_str(&payment.created_at.timestamp().to_string());
-                        path.push_str("/");
-                        path.push_str(&payment.created_at.timestamp_subsec_micros().to_string());
-
                         Box::new(
-                            HdKeyring::from_mnemonic(
-                                &path,
-                                &store.mnemonic.clone(),
-                                0,
+                            signer::for_store(
+                                &store,
+                                signer_config.as_ref(),
+                                &keychain,
                                 // Dummy
                                 BtcNetwork::Test,
                             )
-                            .into_future()
-                            .from_err()
-                            .and_then(move |keyring| {
-                                keyring
-                                    .get_wallet_by_index(payment.index as u32)
-                                    .into_future()
-                                    .from_err()
-                                    .and_then(move |wallet| {
-                                        future::ok((wallet, transaction, store, gas_price, nonce))
-                                    })
-                            }),
+                            .map(move |signer| {
+                                (
+                                    signer,
+                                    payment.hd_path,
+                                    transaction,
+                                    store,
+                                    gas_price,
+                                    nonce,
+                                )
+                            })
+                            .into_future(),
                         )
                     },
                 ))
@@ -117,52 +136,160 @@ impl Payouter {
         )
     }
 
-    pub fn payout(&self, payout: Payout) -> impl Future<Item = H256, Error = Error> {
+    /// Pays out to the store's payout addresses, a transfer per leg. Every leg is signed and
+    /// recorded before anything is broadcast. A retry resends the recorded transactions instead
+    /// of signing new ones, unless the payment address already used their nonces.
+    pub fn payout(&self, payout: Payout) -> impl Future<Item = Vec<PayoutLeg>, Error = Error> {
         let chain_id = self.network.chain_id();
         let blockchain_api_client = self.blockchain_api_client.clone();
+        let signer_executor = self.signer_executor.clone();
+        let postgres = self.postgres.clone();
+        let _postgres = self.postgres.clone();
 
         self.prepare_payout(payout)
-            .and_then(move |(wallet, transaction, store, gas_price, nonce)| {
-                match store.eth_payout_addresses {
-                    Some(payout_addresses) => {
-                        future::ok((wallet, transaction, gas_price, nonce, payout_addresses))
-                    }
-                    None => future::err(Error::NoPayoutAddress),
-                }
-            })
             .and_then(
-                move |(wallet, transaction, gas_price, nonce, payout_addresses)| {
-                    let value = transaction.value - gas_price * U256::from(21_000);
-
-                    let raw_transaction = UnsignedTransaction {
-                        nonce,
-                        gas_price,
-                        gas: U256::from(21_000),
-                        to: payout_addresses[0],
-                        value,
-                        data: b"".to_vec(),
-                    };
-
-                    raw_transaction
-                        .sign(wallet.secret_key, chain_id)
+                move |(signer, hd_path, transaction, store, gas_price, nonce)| {
+                    let recipients = split::recipients(
+                        &store,
+                        Crypto::Eth,
+                        store.eth_payout_addresses.clone(),
+                        store.eth_payout_splits.clone(),
+                    );
+                    let recorded = PayoutLeg::find_by_payout(payout.id, &postgres).from_err();
+
+                    recipients
                         .into_future()
-                        .from_err()
-                        .and_then(move |signed_transaction| {
-                            blockchain_api_client
-                                .send(SendRawTransaction(signed_transaction))
+                        .join(recorded)
+                        .map(move |(recipients, recorded)| {
+                            (
+                                signer,
+                                hd_path,
+                                transaction,
+                                gas_price,
+                                nonce,
+                                recipients,
+                                recorded,
+                            )
+                        })
+                },
+            )
+            .and_then(
+                move |(signer, hd_path, transaction, gas_price, nonce, recipients, recorded)| {
+                    // Every leg is a transfer of its own and pays its own gas.
+                    let fees = gas_price * U256::from(21_000) * U256::from(recipients.len() as u64);
+
+                    if transaction.value <= fees {
+                        info!("Insufficient funds to pay out");
+                        return Err(Error::InsufficientFunds);
+                    }
+
+                    let shares = recipients
+                        .iter()
+                        .map(|&(_, share)| share)
+                        .collect::<Vec<_>>();
+                    let values = split::split(transaction.value - fees, &shares);
+
+                    // Legs recorded by an earlier attempt keep their transaction.
+                    let unrecorded = recipients
+                        .into_iter()
+                        .zip(values)
+                        .enumerate()
+                        .map(|(leg, ((to, _), value))| (leg as i32, to, value))
+                        .filter(|&(leg, _, _)| !recorded.iter().any(|recorded| recorded.leg == leg))
+                        .collect::<Vec<_>>();
+
+                    // New legs come after the nonces of recorded ones, sent or not.
+                    let next_nonce = recorded
+                        .iter()
+                        .filter_map(|leg| leg.nonce)
+                        .map(|nonce| nonce + U128::from(1))
+                        .fold(nonce, |next, after| if after > next { after } else { next });
+
+                    Ok((
+                        signer, hd_path, gas_price, nonce, next_nonce, unrecorded, recorded,
+                    ))
+                },
+            )
+            .and_then(
+                move |(signer, hd_path, gas_price, nonce, next_nonce, unrecorded, recorded)| {
+                    stream::iter_ok(unrecorded.into_iter().enumerate())
+                        .and_then(move |(i, (leg, to, value))| {
+                            let postgres = _postgres.clone();
+                            let leg_nonce = next_nonce + U128::from(i as u64);
+
+                            let raw_transaction = UnsignedTransaction {
+                                nonce: leg_nonce,
+                                gas_price,
+                                gas: U256::from(21_000),
+                                to,
+                                value,
+                                data: b"".to_vec(),
+                            };
+
+                            signer_executor
+                                .send(SignEthTransaction {
+                                    signer: signer.clone(),
+                                    hd_path: hd_path.clone(),
+                                    transaction: raw_transaction,
+                                    chain_id,
+                                })
                                 .from_err()
-                                .and_then(move |res| res.map_err(|e| Error::from(e)))
+                                .and_then(move |res| res)
+                                .and_then(move |signed_transaction| {
+                                    let mut payload = PayoutLegPayload::new(
+                                        payout.id,
+                                        leg,
+                                        to.hex(),
+                                        value,
+                                        signed_transaction.hash(),
+                                    );
+                                    payload.nonce = Some(leg_nonce);
+                                    payload.raw_transaction = Some(signed_transaction.rlp_encode());
+
+                                    PayoutLeg::insert(payload, &postgres).from_err()
+                                })
+                        })
+                        .collect()
+                        .map(move |legs| {
+                            let mut legs = recorded.into_iter().chain(legs).collect::<Vec<_>>();
+                            legs.sort_by_key(|leg| leg.leg);
+
+                            (nonce, legs)
                         })
                 },
             )
+            .and_then(move |(nonce, legs)| {
+                stream::iter_ok(legs)
+                    .and_then(move |leg| -> Box<Future<Item = PayoutLeg, Error = Error>> {
+                        // Below the payment address's transaction count the nonce is used and
+                        // the leg went out. Resending the same transaction can't pay it twice.
+                        let resend = match leg.nonce {
+                            Some(leg_nonce) if leg_nonce >= nonce => leg.raw_transaction.clone(),
+                            _ => None,
+                        };
+
+                        match resend {
+                            Some(raw_transaction) => Box::new(
+                                blockchain_api_client
+                                    .send(ResendRawTransaction(raw_transaction))
+                                    .from_err()
+                                    .and_then(move |res| res.map_err(|e| Error::from(e)))
+                                    .map(move |_| leg),
+                            ),
+                            None => Box::new(future::ok(leg)),
+                        }
+                    })
+                    .collect()
+            })
     }
 
     pub fn refund(&self, payout: Payout) -> impl Future<Item = H256, Error = Error> {
         let chain_id = self.network.chain_id();
         let blockchain_api_client = self.blockchain_api_client.clone();
+        let signer_executor = self.signer_executor.clone();
 
-        self.prepare_payout(payout)
-            .and_then(move |(wallet, transaction, _, gas_price, nonce)| {
+        self.prepare_payout(payout).and_then(
+            move |(signer, hd_path, transaction, _, gas_price, nonce)| {
                 let value = transaction.value - gas_price * U256::from(21_000);
 
                 let raw_transaction = UnsignedTransaction {
@@ -174,19 +301,23 @@ impl Payouter {
                     data: b"".to_vec(),
                 };
 
-                Box::new(
-                    raw_transaction
-                        .sign(wallet.secret_key, chain_id)
-                        .into_future()
-                        .from_err()
-                        .and_then(move |signed_transaction| {
-                            blockchain_api_client
-                                .send(SendRawTransaction(signed_transaction))
-                                .from_err()
-                                .and_then(move |res| res.map_err(|e| Error::from(e)))
-                        }),
-                )
-            })
+                signer_executor
+                    .send(SignEthTransaction {
+                        signer,
+                        hd_path,
+                        transaction: raw_transaction,
+                        chain_id,
+                    })
+                    .from_err()
+                    .and_then(move |res| res)
+                    .and_then(move |signed_transaction| {
+                        blockchain_api_client
+                            .send(SendRawTransaction(signed_transaction))
+                            .from_err()
+                            .and_then(move |res| res.map_err(|e| Error::from(e)))
+                    })
+            },
+        )
     }
 }
 
@@ -251,8 +382,12 @@ impl Handler<PayOut> for Payouter {
 
     fn handle(&mut self, PayOut(payout): PayOut, _: &mut Self::Context) -> Self::Result {
         let postgres = self.postgres.clone();
+        let _postgres = self.postgres.clone();
+        let redis = self.redis.clone();
 
-        Box::new(self.payout(payout).from_err().and_then(move |hash| {
+        Box::new(self.payout(payout).from_err().and_then(move |legs| {
+            // The first leg stands for the payout, the others are on its legs.
+            let hash = legs[0].transaction_hash;
             info!("Paid out {}", hash.hex());
 
             let mut payout_payload = PayoutPayload::from(payout);
@@ -264,7 +399,10 @@ impl Handler<PayOut> for Payouter {
 
             Payout::update_with_payment(payout.id, payout_payload, payment_payload, &postgres)
                 .from_err()
-                .map(move |_| ())
+                .and_then(move |(_, payment)| {
+                    payment.notify(&redis);
+                    split::rotate(&payout, &postgres)
+                })
                 .or_else(move |e| -> Box<Future<Item = (), Error = Error>> {
                     match e {
                         // If payout address doesn't exist for the store, change payout object's action to Refund.
@@ -273,7 +411,7 @@ impl Handler<PayOut> for Payouter {
                             payload.action = Some(PayoutAction::Refund);
 
                             Box::new(
-                                Payout::update(payout.id, payload, &postgres)
+                                Payout::update(payout.id, payload, &_postgres)
                                     .from_err()
                                     .map(move |_| ()),
                             )
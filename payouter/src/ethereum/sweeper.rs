@@ -0,0 +1,136 @@
+use futures::{
+    future::{self, IntoFuture},
+    stream, Future, Stream,
+};
+
+use blockchain_api_client::ethereum::{
+    BlockchainApiClientAddr, GetBalance, GetGasPrice, GetTransactionCount, SendRawTransaction,
+    UnsignedTransaction,
+};
+use errors::Error;
+use hd_keyring::Wallet;
+use types::{ethereum::Network as EthNetwork, H160, H256, U256};
+
+/// A deposit address with a non-zero balance, or one that couldn't be swept. `transaction_hash`
+/// stays `None` on a dry run, when the balance can't cover the gas and when sweeping failed, see
+/// `error`.
+#[derive(Debug)]
+pub struct Sweep {
+    pub address: H160,
+    pub hd_path: String,
+    pub value: U256,
+    pub transaction_hash: Option<H256>,
+    pub error: Option<String>,
+}
+
+/// Sends the whole balance of the given `(hd_path, wallet)` addresses to `destination`. This
+/// bypasses payouts entirely and is meant for recovering funds, e.g. from `finch sweep`. An address
+/// that fails to sweep doesn't stop the others.
+pub fn sweep(
+    wallets: Vec<(String, Wallet)>,
+    destination: H160,
+    dry_run: bool,
+    network: EthNetwork,
+    blockchain_api_client: BlockchainApiClientAddr,
+) -> impl Future<Item = Vec<Sweep>, Error = Error> {
+    let chain_id = network.chain_id();
+    let client = blockchain_api_client.clone();
+
+    blockchain_api_client
+        .send(GetGasPrice)
+        .from_err()
+        .and_then(move |res| res.map_err(|e| Error::from(e)))
+        .and_then(|gas_price| {
+            if gas_price == U256::from(0) {
+                return Err(Error::InvalidGasPrice);
+            }
+
+            Ok(gas_price)
+        })
+        .and_then(move |gas_price| {
+            stream::iter_ok(wallets)
+                .and_then(move |(hd_path, wallet)| {
+                    let client = client.clone();
+                    let address = wallet.get_eth_address();
+                    let mut sweep = Sweep {
+                        address,
+                        hd_path,
+                        value: U256::from(0),
+                        transaction_hash: None,
+                        error: None,
+                    };
+
+                    let balance = client
+                        .send(GetBalance(address))
+                        .from_err()
+                        .and_then(move |res| res.map_err(|e| Error::from(e)));
+                    let nonce = client
+                        .send(GetTransactionCount(address))
+                        .from_err()
+                        .and_then(move |res| res.map_err(|e| Error::from(e)));
+
+                    balance.join(nonce).then(
+                        move |res| -> Box<Future<Item = Sweep, Error = Error>> {
+                            let (balance, nonce) = match res {
+                                Ok(res) => res,
+                                Err(e) => {
+                                    sweep.error = Some(e.to_string());
+                                    return Box::new(future::ok(sweep));
+                                }
+                            };
+                            let fee = gas_price * U256::from(21_000);
+
+                            sweep.value = balance;
+
+                            if balance <= fee || dry_run {
+                                return Box::new(future::ok(sweep));
+                            }
+
+                            let raw_transaction = UnsignedTransaction {
+                                nonce,
+                                gas_price,
+                                gas: U256::from(21_000),
+                                to: destination,
+                                value: balance - fee,
+                                data: b"".to_vec(),
+                            };
+
+                            broadcast(sweep, raw_transaction, &wallet, chain_id, &client)
+                        },
+                    )
+                })
+                .filter(|sweep| sweep.value > U256::from(0) || sweep.error.is_some())
+                .collect()
+        })
+}
+
+fn broadcast(
+    mut sweep: Sweep,
+    raw_transaction: UnsignedTransaction,
+    wallet: &Wallet,
+    chain_id: u64,
+    blockchain_api_client: &BlockchainApiClientAddr,
+) -> Box<Future<Item = Sweep, Error = Error>> {
+    let client = blockchain_api_client.clone();
+
+    Box::new(
+        raw_transaction
+            .sign(wallet.secret_key, chain_id)
+            .into_future()
+            .from_err()
+            .and_then(move |signed_transaction| {
+                client
+                    .send(SendRawTransaction(signed_transaction))
+                    .from_err()
+                    .and_then(move |res| res.map_err(|e| Error::from(e)))
+            })
+            .then(move |res| -> Result<Sweep, Error> {
+                match res {
+                    Ok(hash) => sweep.transaction_hash = Some(hash),
+                    Err(e) => sweep.error = Some(e.to_string()),
+                }
+
+                Ok(sweep)
+            }),
+    )
+}
@@ -22,3 +22,5 @@ extern crate types;
 pub mod bitcoin;
 pub mod errors;
 pub mod ethereum;
+mod signer;
+mod split;
@@ -0,0 +1,124 @@
+use std::{sync::Arc, time::Duration};
+
+use actix::prelude::*;
+
+use blockchain_api_client::{
+    bitcoin::UnsignedTransaction as BtcUnsignedTransaction,
+    ethereum::{SignedTransaction, UnsignedTransaction as EthUnsignedTransaction},
+};
+use config::SignerConfig;
+use core::{crypto::Keychain, store::Store};
+use errors::Error;
+use hd_keyring::{btc_address, btc_segwit_address, HdKeyring, Signer, SocketSigner};
+use types::bitcoin::{AddressFormat, Network as BtcNetwork};
+
+/// Returns what signs the payouts of `store`: the signing daemon if one is configured, otherwise
+/// keys derived in-process from the store's mnemonic.
+pub fn for_store(
+    store: &Store,
+    config: Option<&SignerConfig>,
+    keychain: &Keychain,
+    btc_network: BtcNetwork,
+) -> Result<Arc<Signer>, Error> {
+    if let Some(config) = config {
+        let mut signer = SocketSigner::new(&config.socket, &store.id.to_string());
+
+        if let Some(timeout) = config.timeout {
+            signer = signer.timeout(Duration::from_secs(timeout));
+        }
+
+        return Ok(Arc::new(signer));
+    }
+
+    let mnemonic = store
+        .decrypt_mnemonic(keychain)?
+        .ok_or(Error::WatchOnlyStore)?;
+    let passphrase = store.decrypt_passphrase(keychain)?;
+
+    Ok(Arc::new(HdKeyring::from_mnemonic(
+        "m",
+        &mnemonic,
+        &passphrase,
+        0,
+        btc_network,
+    )?))
+}
+
+/// Runs the signer calls of the payouters on threads of their own. The socket signer blocks on
+/// the daemon for up to its timeout, which would otherwise stall the payouter's event loop.
+pub struct SignerExecutor;
+
+pub type SignerExecutorAddr = Addr<SignerExecutor>;
+
+impl Actor for SignerExecutor {
+    type Context = SyncContext<Self>;
+}
+
+/// The Bitcoin address of the key at `hd_path`, segwit if it's a BIP84 path.
+#[derive(Message)]
+#[rtype(result = "Result<String, Error>")]
+pub struct GetBtcAddress {
+    pub signer: Arc<Signer>,
+    pub hd_path: String,
+    pub network: BtcNetwork,
+}
+
+impl Handler<GetBtcAddress> for SignerExecutor {
+    type Result = Result<String, Error>;
+
+    fn handle(&mut self, msg: GetBtcAddress, _: &mut Self::Context) -> Self::Result {
+        let public_key = msg.signer.public_key(&msg.hd_path)?;
+
+        Ok(match AddressFormat::from_path(&msg.hd_path) {
+            AddressFormat::Legacy => btc_address(&public_key, msg.network),
+            AddressFormat::Segwit => btc_segwit_address(&public_key, msg.network),
+        })
+    }
+}
+
+/// Signs every input of `transaction` with the key at `hd_path`.
+#[derive(Message)]
+#[rtype(result = "Result<BtcUnsignedTransaction, Error>")]
+pub struct SignBtcTransaction {
+    pub signer: Arc<Signer>,
+    pub hd_path: String,
+    pub transaction: BtcUnsignedTransaction,
+}
+
+impl Handler<SignBtcTransaction> for SignerExecutor {
+    type Result = Result<BtcUnsignedTransaction, Error>;
+
+    fn handle(&mut self, msg: SignBtcTransaction, _: &mut Self::Context) -> Self::Result {
+        let SignBtcTransaction {
+            signer,
+            hd_path,
+            mut transaction,
+        } = msg;
+
+        let public_key = signer.public_key(&hd_path)?;
+        transaction.sign_with(public_key, |hash| signer.sign(&hd_path, &hash))?;
+
+        Ok(transaction)
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<SignedTransaction, Error>")]
+pub struct SignEthTransaction {
+    pub signer: Arc<Signer>,
+    pub hd_path: String,
+    pub transaction: EthUnsignedTransaction,
+    pub chain_id: u64,
+}
+
+impl Handler<SignEthTransaction> for SignerExecutor {
+    type Result = Result<SignedTransaction, Error>;
+
+    fn handle(&mut self, msg: SignEthTransaction, _: &mut Self::Context) -> Self::Result {
+        let signature = msg
+            .signer
+            .sign(&msg.hd_path, &msg.transaction.hash(msg.chain_id))?;
+
+        Ok(msg.transaction.with_signature(signature, msg.chain_id))
+    }
+}
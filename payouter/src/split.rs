@@ -0,0 +1,147 @@
+use futures::Future;
+
+use core::{db::postgres::PgExecutorAddr, payout::Payout, store::Store};
+use errors::Error;
+use types::{currency::Crypto, U256};
+
+/// Returns who gets a payout of `store` and what percentage of it: every payout address with its
+/// split, or the current address in rotation when the store has no splits.
+pub fn recipients<T: Clone>(
+    store: &Store,
+    crypto: Crypto,
+    addresses: Option<Vec<T>>,
+    splits: Option<Vec<i32>>,
+) -> Result<Vec<(T, i32)>, Error> {
+    let addresses = match addresses.filter(|addresses| !addresses.is_empty()) {
+        Some(addresses) => addresses,
+        None => return Err(Error::NoPayoutAddress),
+    };
+
+    if let Some(splits) = splits {
+        return Ok(addresses.into_iter().zip(splits).collect());
+    }
+
+    let index = match crypto {
+        Crypto::Btc => store.btc_payout_index,
+        Crypto::Eth => store.eth_payout_index,
+    };
+    let address = addresses[index as usize % addresses.len()].clone();
+
+    Ok(vec![(address, 100)])
+}
+
+/// Moves the rotation of the payout's store on to its next payout address. Only called once the
+/// payout went out, so a failed attempt is retried with the same address.
+pub fn rotate(payout: &Payout, postgres: &PgExecutorAddr) -> impl Future<Item = (), Error = Error> {
+    Store::next_payout_index(payout.store_id, payout.typ, postgres)
+        .from_err()
+        .map(|_| ())
+}
+
+/// Splits `value` by the percentages in `shares`, rounding down. What's left over from rounding
+/// goes to the first share.
+pub fn split(value: U256, shares: &[i32]) -> Vec<U256> {
+    let mut values = shares
+        .iter()
+        .map(|share| value * U256::from(*share) / U256::from(100))
+        .collect::<Vec<_>>();
+
+    let total = values
+        .iter()
+        .fold(U256::from(0), |total, value| total + *value);
+    values[0] = values[0] + (value - total);
+
+    values
+}
+
+/// Folds every output below `dust_limit`, empty ones included, into the largest output. Nodes
+/// reject a transaction with any output that small. Returns `None` when all of them are.
+pub fn merge_dust<T>(outputs: Vec<(T, u64)>, dust_limit: u64) -> Option<Vec<(T, u64)>> {
+    let dust = outputs
+        .iter()
+        .filter(|&&(_, value)| value < dust_limit)
+        .map(|&(_, value)| value)
+        .sum::<u64>();
+    let mut outputs = outputs
+        .into_iter()
+        .filter(|&(_, value)| value >= dust_limit)
+        .collect::<Vec<_>>();
+
+    let largest = outputs
+        .iter()
+        .enumerate()
+        .max_by_key(|&(_, &(_, value))| value)
+        .map(|(index, _)| index)?;
+    outputs[largest].1 += dust;
+
+    Some(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_rounds_down_and_gives_the_rest_to_the_first_share() {
+        let values = split(U256::from(1_000), &[33, 33, 34]);
+
+        assert_eq!(
+            values,
+            vec![U256::from(332), U256::from(330), U256::from(340)]
+        );
+    }
+
+    #[test]
+    fn split_keeps_the_total() {
+        let value = U256::from(123_456_789);
+        let values = split(value, &[10, 15, 25, 50]);
+
+        let total = values
+            .iter()
+            .fold(U256::from(0), |total, value| total + *value);
+        assert_eq!(total, value);
+    }
+
+    #[test]
+    fn split_leaves_dust_to_the_first_share() {
+        // 1% of 99 rounds down to nothing, the 1 it loses goes to the first share.
+        let values = split(U256::from(99), &[99, 1]);
+
+        assert_eq!(values, vec![U256::from(99), U256::from(0)]);
+
+        let values = split(U256::from(1), &[50, 50]);
+
+        assert_eq!(values, vec![U256::from(1), U256::from(0)]);
+    }
+
+    #[test]
+    fn merge_dust_moves_a_dust_first_share_to_the_largest_output() {
+        // 1% of 30,000 satoshi is below the 546 satoshi dust limit.
+        let values = split(U256::from(30_000), &[1, 99]);
+        let outputs = vec![("a", values[0].low_u64()), ("b", values[1].low_u64())];
+
+        assert_eq!(merge_dust(outputs, 546), Some(vec![("b", 30_000)]));
+    }
+
+    #[test]
+    fn merge_dust_drops_empty_outputs() {
+        let outputs = vec![("a", 0), ("b", 1_000), ("c", 600), ("d", 500)];
+
+        assert_eq!(
+            merge_dust(outputs, 546),
+            Some(vec![("b", 1_500), ("c", 600)])
+        );
+    }
+
+    #[test]
+    fn merge_dust_gives_up_when_everything_is_dust() {
+        assert_eq!(merge_dust(vec![("a", 300), ("b", 200)], 546), None);
+    }
+
+    #[test]
+    fn split_gives_a_single_share_everything() {
+        let value = U256::from(21_000);
+
+        assert_eq!(split(value, &[100]), vec![value]);
+    }
+}
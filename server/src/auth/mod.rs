@@ -1,19 +1,29 @@
+use std::net::IpAddr;
+
 use actix_web::{error, Error as ActixError, FromRequest, HttpMessage, HttpRequest};
 use base64::decode;
 use chrono::prelude::*;
-use futures::future::{err, Future};
+use futures::future::{err, ok, Future};
 use jwt;
 use uuid::Uuid;
 
 use core::client_token::ClientToken;
 use services;
 use state::AppState;
-use types::PrivateKey;
+use types::{currency::Crypto, PrivateKey, PublicKey};
+
+pub mod permissions;
+pub mod signed_checkout;
+pub mod two_factor;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JWTPayload {
     pub client: Option<AuthClient>,
     pub user: Option<AuthUser>,
+    /// The session a user's token was issued for, see `services::sessions`.
+    pub sid: Option<Uuid>,
+    #[serde(default)]
+    pub iat: u64,
     pub exp: u64,
 }
 
@@ -22,6 +32,8 @@ impl JWTPayload {
         JWTPayload {
             client,
             user,
+            sid: None,
+            iat: Utc::now().timestamp() as u64,
             exp: exp.timestamp() as u64,
         }
     }
@@ -31,13 +43,18 @@ impl JWTPayload {
 
         jwt::encode(&header, &self, jwt_private)
     }
-}
 
-impl FromRequest<AppState> for JWTPayload {
-    type Config = ();
-    type Result = Result<JWTPayload, ActixError>;
+    pub fn decode(token: &str, jwt_public: &PublicKey) -> Result<JWTPayload, ActixError> {
+        let validation = jwt::Validation::new(jwt::Algorithm::RS256);
 
-    fn from_request(req: &HttpRequest<AppState>, _cfg: &Self::Config) -> Self::Result {
+        match jwt::decode::<JWTPayload>(token, jwt_public, &validation) {
+            Ok(token) => Ok(token.claims),
+            Err(_) => Err(error::ErrorUnauthorized("invalid authorization token")),
+        }
+    }
+
+    /// Decodes the bearer token of the request without looking up its session.
+    fn from_header(req: &HttpRequest<AppState>) -> Result<JWTPayload, ActixError> {
         let state = req.state();
 
         let auth_header = match req.headers().get("authorization") {
@@ -54,12 +71,39 @@ impl FromRequest<AppState> for JWTPayload {
             return Err(error::ErrorUnauthorized("invalid authorization token"));
         }
 
-        let validation = jwt::Validation::new(jwt::Algorithm::RS256);
+        JWTPayload::decode(&auth_header_parts[1], &state.jwt_public)
+    }
+}
 
-        match jwt::decode::<JWTPayload>(&auth_header_parts[1], &state.jwt_public, &validation) {
-            Ok(token) => Ok(token.claims),
-            Err(_) => Err(error::ErrorUnauthorized("invalid authorization token")),
-        }
+impl FromRequest<AppState> for JWTPayload {
+    type Config = ();
+    type Result = Box<Future<Item = JWTPayload, Error = ActixError>>;
+
+    fn from_request(req: &HttpRequest<AppState>, _cfg: &Self::Config) -> Self::Result {
+        let token = match JWTPayload::from_header(req) {
+            Ok(token) => token,
+            Err(e) => return Box::new(err(e)),
+        };
+
+        // A user's token stops working with its session, or once the password changes.
+        let (session_id, user_id) = match (token.sid, token.user.as_ref().map(|user| user.id)) {
+            (_, None) => return Box::new(ok(token)),
+            (Some(session_id), Some(user_id)) => (session_id, user_id),
+            (None, Some(_)) => {
+                return Box::new(err(error::ErrorUnauthorized("invalid authorization token")));
+            }
+        };
+
+        Box::new(
+            services::sessions::validate(
+                session_id,
+                user_id,
+                token.iat as i64,
+                &req.state().postgres,
+            )
+            .map(move |_| token)
+            .from_err(),
+        )
     }
 }
 
@@ -68,6 +112,9 @@ pub struct AuthClient {
     pub id: Uuid,
     pub store_id: Uuid,
     pub created_at: i64,
+    /// Carried over from the client token, for the currency the customer picks on an invoice.
+    #[serde(default)]
+    pub allowed_cryptos: Option<Vec<Crypto>>,
 }
 
 impl AuthClient {
@@ -76,6 +123,14 @@ impl AuthClient {
             id: Uuid::new_v4(),
             store_id: client_token.store_id,
             created_at: Utc::now().timestamp(),
+            allowed_cryptos: client_token.allowed_cryptos,
+        }
+    }
+
+    pub fn allows_crypto(&self, crypto: &Crypto) -> bool {
+        match self.allowed_cryptos {
+            Some(ref cryptos) => cryptos.contains(crypto),
+            None => true,
         }
     }
 }
@@ -85,7 +140,7 @@ impl FromRequest<AppState> for AuthClient {
     type Result = Result<AuthClient, ActixError>;
 
     fn from_request(req: &HttpRequest<AppState>, _cfg: &Self::Config) -> Self::Result {
-        let token = JWTPayload::extract(&req)?;
+        let token = JWTPayload::from_header(&req)?;
 
         match token.client {
             Some(client) => Ok(client),
@@ -101,14 +156,139 @@ pub struct AuthUser {
 
 impl FromRequest<AppState> for AuthUser {
     type Config = ();
-    type Result = Result<AuthUser, ActixError>;
+    type Result = Box<Future<Item = AuthUser, Error = ActixError>>;
 
     fn from_request(req: &HttpRequest<AppState>, _cfg: &Self::Config) -> Self::Result {
-        let token = JWTPayload::extract(&req)?;
+        Box::new(
+            JWTPayload::extract(&req).and_then(|token| match token.user {
+                Some(user) => Ok(user),
+                None => Err(error::ErrorUnauthorized("invalid authorization token")),
+            }),
+        )
+    }
+}
 
-        match token.user {
-            Some(user) => Ok(user),
-            None => Err(error::ErrorUnauthorized("invalid authorization token")),
+/// The second factor for sensitive requests, sent in the `X-OTP` header. Either a code from the
+/// authenticator app or a recovery code.
+#[derive(Debug, Clone)]
+pub struct TwoFactorCode(pub Option<String>);
+
+impl FromRequest<AppState> for TwoFactorCode {
+    type Config = ();
+    type Result = TwoFactorCode;
+
+    fn from_request(req: &HttpRequest<AppState>, _cfg: &Self::Config) -> Self::Result {
+        TwoFactorCode(
+            req.headers()
+                .get("x-otp")
+                .and_then(|code| code.to_str().ok())
+                .map(|code| code.to_owned()),
+        )
+    }
+}
+
+/// The `Idempotency-Key` header, see `services::idempotency`.
+pub struct IdempotencyKey(pub Option<String>);
+
+impl FromRequest<AppState> for IdempotencyKey {
+    type Config = ();
+    type Result = IdempotencyKey;
+
+    fn from_request(req: &HttpRequest<AppState>, _cfg: &Self::Config) -> Self::Result {
+        IdempotencyKey(
+            req.headers()
+                .get("idempotency-key")
+                .and_then(|key| key.to_str().ok())
+                .map(|key| key.to_owned()),
+        )
+    }
+}
+
+/// The address a request came from: the socket's peer, or when that is one of the configured
+/// `trusted_proxies`, the address they forwarded it for. `X-Forwarded-For` from anyone else is
+/// ignored, clients can't pick their own address.
+pub fn remote_ip(req: &HttpRequest<AppState>) -> Option<String> {
+    let peer = req.peer_addr()?.ip();
+    let trusted_proxies = match req.state().config.trusted_proxies {
+        Some(ref trusted_proxies) => trusted_proxies,
+        None => return Some(peer.to_string()),
+    };
+
+    let forwarded_for = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|address| address.trim())
+        .collect::<Vec<_>>();
+
+    Some(client_ip(peer, &forwarded_for, trusted_proxies).to_string())
+}
+
+/// Walks `X-Forwarded-For` back from `peer` for as long as the hops are trusted proxies. The
+/// first one that isn't is the client, anything before it could be made up.
+fn client_ip(peer: IpAddr, forwarded_for: &[&str], trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut ip = peer;
+
+    for address in forwarded_for.iter().rev() {
+        if !trusted_proxies.contains(&ip) {
+            break;
+        }
+
+        match address.parse() {
+            Ok(address) => ip = address,
+            Err(_) => break,
+        }
+    }
+
+    ip
+}
+
+/// Who is making a request, as recorded in the audit log and on new sessions: the signed in
+/// user, if any, and the address and user agent the request came from.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub user_id: Option<Uuid>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl Actor {
+    /// Changes the server makes on its own, like applying confirmed payout address changes.
+    pub fn system() -> Self {
+        Actor {
+            user_id: None,
+            ip: None,
+            user_agent: None,
+        }
+    }
+}
+
+impl FromRequest<AppState> for Actor {
+    type Config = ();
+    type Result = Actor;
+
+    fn from_request(req: &HttpRequest<AppState>, _cfg: &Self::Config) -> Self::Result {
+        // Handlers that act on the user's behalf authenticate them separately, this only names
+        // them.
+        let user_id = JWTPayload::from_header(&req)
+            .ok()
+            .and_then(|token| token.user)
+            .map(|user| user.id);
+
+        let ip = remote_ip(req);
+
+        let user_agent = req
+            .headers()
+            .get("user-agent")
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .map(|user_agent| user_agent.to_owned());
+
+        Actor {
+            user_id,
+            ip,
+            user_agent,
         }
     }
 }
@@ -150,24 +330,73 @@ impl FromRequest<AppState> for ClientToken {
             }
         };
 
-        let origin_header = match headers.get("origin") {
-            Some(origin_header) => origin_header,
-            None => return Box::new(err(error::ErrorUnauthorized("invalid origin header"))),
-        };
-
-        let origin_header_parts: Vec<_> = origin_header.to_str().unwrap().split("://").collect();
+        // Native apps send no Origin header, whether that's allowed is up to the token.
+        let origin = match headers.get("origin") {
+            Some(origin_header) => {
+                let origin_header_parts: Vec<_> = match origin_header.to_str() {
+                    Ok(origin_header) => origin_header.split("://").collect(),
+                    Err(_) => {
+                        return Box::new(err(error::ErrorUnauthorized("invalid origin header")));
+                    }
+                };
+
+                if origin_header_parts.len() != 2 {
+                    return Box::new(err(error::ErrorUnauthorized("invalid origin header")));
+                }
 
-        if origin_header_parts.len() != 2 {
-            return Box::new(err(error::ErrorUnauthorized("invalid origin header")));
-        }
+                Some(origin_header_parts[1].trim_matches('/').to_string())
+            }
+            None => None,
+        };
 
         Box::new(
-            services::client_tokens::get_by_token_and_domain(
-                token,
-                origin_header_parts[1].trim_matches('/').to_string(),
-                &state.postgres,
-            )
-            .from_err(),
+            services::client_tokens::get_by_token_and_origin(token, origin, &state.postgres)
+                .from_err(),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn client_ip_is_the_peer_unless_it_is_a_trusted_proxy() {
+        let trusted_proxies = [ip("10.0.0.1")];
+
+        assert_eq!(
+            client_ip(ip("203.0.113.7"), &["198.51.100.1"], &trusted_proxies),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &["198.51.100.1"], &trusted_proxies),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn client_ip_ignores_what_the_client_forwarded() {
+        let trusted_proxies = [ip("10.0.0.1"), ip("10.0.0.2")];
+
+        assert_eq!(
+            client_ip(
+                ip("10.0.0.1"),
+                &["1.2.3.4", "198.51.100.1", "10.0.0.2"],
+                &trusted_proxies
+            ),
+            ip("198.51.100.1")
+        );
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &["198.51.100.1", "junk"], &trusted_proxies),
+            ip("10.0.0.1")
+        );
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &[], &trusted_proxies),
+            ip("10.0.0.1")
+        );
+    }
+}
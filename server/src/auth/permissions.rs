@@ -0,0 +1,42 @@
+//! What each store role may do. Owners may do everything, admins everything short of deleting
+//! the store, exporting its keys or reading its audit log.
+
+use types::Role;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ReadStore,
+    ManageStore,
+    DeleteStore,
+    ExportRecovery,
+    ManagePayoutAddresses,
+    ManageMembers,
+    ManageClientTokens,
+    ReadPayments,
+    IssueRefunds,
+    ReadAuditLog,
+}
+
+pub fn allows(role: Role, permission: Permission) -> bool {
+    match role {
+        Role::Owner => true,
+        Role::Admin => match permission {
+            Permission::DeleteStore | Permission::ExportRecovery | Permission::ReadAuditLog => {
+                false
+            }
+            _ => true,
+        },
+        Role::Developer => match permission {
+            Permission::ReadStore | Permission::ManageClientTokens => true,
+            _ => false,
+        },
+        Role::Finance => match permission {
+            Permission::ReadStore | Permission::ReadPayments | Permission::IssueRefunds => true,
+            _ => false,
+        },
+        Role::ReadOnly => match permission {
+            Permission::ReadStore | Permission::ReadPayments => true,
+            _ => false,
+        },
+    }
+}
@@ -0,0 +1,236 @@
+//! Checkouts signed by the merchant's backend. Once a store has a checkout secret, the price,
+//! fiat currency, identifier and metadata of its payments and invoices are only taken from a JWT
+//! signed with it (HS256), so customers can't change what they're charged by editing the
+//! request.
+
+use bigdecimal::BigDecimal;
+use jwt;
+use ring::rand::{SecureRandom, SystemRandom};
+use rustc_hex::ToHex;
+use serde_json::Value;
+
+use core::{crypto::Keychain, store::Store};
+use services::Error;
+use types::currency::Fiat;
+
+const SECRET_LEN: usize = 32;
+
+/// The claims the merchant signs. `exp` is required, a signed checkout shouldn't be good forever.
+#[derive(Debug, Deserialize)]
+pub struct SignedCheckout {
+    pub fiat: Fiat,
+    pub price: BigDecimal,
+    pub identifier: Option<String>,
+    pub metadata: Option<Value>,
+    pub exp: u64,
+}
+
+/// What a payment or invoice gets created with.
+#[derive(Debug)]
+pub struct Checkout {
+    pub fiat: Fiat,
+    pub price: BigDecimal,
+    pub identifier: Option<String>,
+    pub metadata: Option<Value>,
+    pub signed: bool,
+}
+
+/// A new checkout secret, hex encoded. The merchant signs with the string as is.
+pub fn generate_secret() -> Result<String, Error> {
+    let mut secret = vec![0u8; SECRET_LEN];
+    SystemRandom::new()
+        .fill(&mut secret)
+        .map_err(|_| Error::InternalServerError)?;
+
+    Ok(secret.to_hex())
+}
+
+pub fn verify(checkout: &str, secret: &str) -> Result<SignedCheckout, Error> {
+    let validation = jwt::Validation::new(jwt::Algorithm::HS256);
+
+    jwt::decode::<SignedCheckout>(checkout, secret.as_bytes(), &validation)
+        .map(|data| data.claims)
+        .map_err(|_| Error::InvalidCheckoutSignature)
+}
+
+/// Works out the checkout for a request to the store. Stores with a checkout secret need a
+/// signed checkout, and any price, fiat, identifier or metadata sent along with it has to match
+/// it.
+pub fn resolve(
+    store: &Store,
+    keychain: &Keychain,
+    fiat: Option<Fiat>,
+    price: Option<BigDecimal>,
+    identifier: Option<String>,
+    metadata: Option<Value>,
+    checkout: Option<String>,
+) -> Result<Checkout, Error> {
+    let secret = store.decrypt_checkout_secret(keychain)?;
+
+    resolve_with_secret(secret, fiat, price, identifier, metadata, checkout)
+}
+
+fn resolve_with_secret(
+    secret: Option<String>,
+    fiat: Option<Fiat>,
+    price: Option<BigDecimal>,
+    identifier: Option<String>,
+    metadata: Option<Value>,
+    checkout: Option<String>,
+) -> Result<Checkout, Error> {
+    let secret = match secret {
+        Some(secret) => secret,
+        None => {
+            if checkout.is_some() {
+                return Err(Error::BadRequest("store doesn't take signed checkouts"));
+            }
+
+            return match (fiat, price) {
+                (Some(fiat), Some(price)) => Ok(Checkout {
+                    fiat,
+                    price,
+                    identifier,
+                    metadata,
+                    signed: false,
+                }),
+                _ => Err(Error::BadRequest("fiat and price are required")),
+            };
+        }
+    };
+
+    let signed = match checkout {
+        Some(checkout) => verify(&checkout, &secret)?,
+        None => return Err(Error::InvalidCheckoutSignature),
+    };
+
+    if fiat.map_or(false, |fiat| fiat != signed.fiat)
+        || price.map_or(false, |price| price != signed.price)
+        || identifier.map_or(false, |identifier| Some(identifier) != signed.identifier)
+        || metadata.map_or(false, |metadata| Some(metadata) != signed.metadata)
+    {
+        return Err(Error::InvalidCheckoutSignature);
+    }
+
+    Ok(Checkout {
+        fiat: signed.fiat,
+        price: signed.price,
+        identifier: signed.identifier,
+        metadata: signed.metadata,
+        signed: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::{prelude::*, Duration};
+
+    use super::*;
+
+    const SECRET: &str = "5e3f7c1b9a2d4e6f8a0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f";
+
+    fn sign(claims: Value) -> Option<String> {
+        Some(jwt::encode(&jwt::Header::default(), &claims, SECRET.as_bytes()).unwrap())
+    }
+
+    fn expires_in(minutes: i64) -> i64 {
+        (Utc::now() + Duration::minutes(minutes)).timestamp()
+    }
+
+    fn checkout() -> Option<String> {
+        sign(json!({
+            "fiat": "usd",
+            "price": "25.50",
+            "identifier": "order-1",
+            "exp": expires_in(10),
+        }))
+    }
+
+    fn price(price: &str) -> Option<BigDecimal> {
+        Some(BigDecimal::from_str(price).unwrap())
+    }
+
+    fn resolve(
+        fiat: Option<Fiat>,
+        price: Option<BigDecimal>,
+        identifier: Option<&str>,
+        checkout: Option<String>,
+    ) -> Result<Checkout, Error> {
+        resolve_with_secret(
+            Some(String::from(SECRET)),
+            fiat,
+            price,
+            identifier.map(String::from),
+            None,
+            checkout,
+        )
+    }
+
+    fn rejected(res: Result<Checkout, Error>) -> bool {
+        match res {
+            Err(Error::InvalidCheckoutSignature) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn signed_checkout_is_taken_as_is() {
+        let checkout = resolve(None, None, None, checkout()).unwrap();
+
+        assert_eq!(checkout.fiat, Fiat::Usd);
+        assert_eq!(checkout.price, price("25.50").unwrap());
+        assert_eq!(checkout.identifier, Some(String::from("order-1")));
+        assert!(checkout.signed);
+
+        let matching = resolve(Some(Fiat::Usd), price("25.50"), Some("order-1"), checkout());
+        assert!(matching.is_ok());
+    }
+
+    #[test]
+    fn mismatched_price_is_rejected() {
+        assert!(rejected(resolve(None, price("0.01"), None, checkout())));
+    }
+
+    #[test]
+    fn mismatched_fiat_is_rejected() {
+        // USD is the only fiat there is, so a claim in another one stands in for the mismatch.
+        let checkout = sign(json!({ "fiat": "eur", "price": "25.50", "exp": expires_in(10) }));
+
+        assert!(rejected(resolve(Some(Fiat::Usd), None, None, checkout)));
+    }
+
+    #[test]
+    fn mismatched_identifier_is_rejected() {
+        assert!(rejected(resolve(None, None, Some("order-2"), checkout())));
+    }
+
+    #[test]
+    fn expired_checkout_is_rejected() {
+        let checkout = sign(json!({ "fiat": "usd", "price": "25.50", "exp": expires_in(-10) }));
+
+        assert!(rejected(resolve(None, None, None, checkout)));
+    }
+
+    #[test]
+    fn checkout_without_exp_is_rejected() {
+        let checkout = sign(json!({ "fiat": "usd", "price": "25.50" }));
+
+        assert!(rejected(resolve(None, None, None, checkout)));
+    }
+
+    #[test]
+    fn checkout_signed_with_another_secret_is_rejected() {
+        let claims = json!({ "fiat": "usd", "price": "25.50", "exp": expires_in(10) });
+        let checkout = jwt::encode(&jwt::Header::default(), &claims, b"another secret").unwrap();
+
+        assert!(rejected(resolve(None, None, None, Some(checkout))));
+    }
+
+    #[test]
+    fn missing_checkout_is_rejected() {
+        let res = resolve(Some(Fiat::Usd), price("25.50"), None, None);
+
+        assert!(rejected(res));
+    }
+}
@@ -0,0 +1,189 @@
+//! Time-based one-time passwords (RFC 6238) the way authenticator apps generate them, i.e.
+//! HMAC-SHA1 over 30 second steps truncated to 6 digits, and single use recovery codes for when
+//! the app is lost.
+
+use chrono::prelude::*;
+use data_encoding::{BASE32_NOPAD, BASE64};
+use ring::{constant_time, digest, hmac, pbkdf2, rand::SecureRandom, rand::SystemRandom};
+
+use services::Error;
+
+const STEP: i64 = 30;
+const DIGITS: usize = 6;
+const SECRET_LEN: usize = 20;
+const RECOVERY_CODES: usize = 10;
+// Recovery codes are hashed like passwords, they only have 40 bits to them.
+const RECOVERY_CODE_HASH_LEN: usize = digest::SHA512_OUTPUT_LEN;
+const RECOVERY_CODE_ITER: u32 = 100_000;
+
+pub fn generate_secret() -> Result<Vec<u8>, Error> {
+    let mut secret = vec![0u8; SECRET_LEN];
+    SystemRandom::new()
+        .fill(&mut secret)
+        .map_err(|_| Error::InternalServerError)?;
+
+    Ok(secret)
+}
+
+/// The secret as the base32 string authenticator apps expect when it's typed in.
+pub fn encode_secret(secret: &[u8]) -> String {
+    BASE32_NOPAD.encode(secret)
+}
+
+/// The `otpauth://` URL to render as a QR code for authenticator apps.
+pub fn provisioning_url(secret: &[u8], email: &str) -> String {
+    format!(
+        "otpauth://totp/Finch:{}?secret={}&issuer=Finch",
+        email,
+        encode_secret(secret)
+    )
+}
+
+fn code_at(secret: &[u8], step: i64) -> String {
+    let mut counter = [0u8; 8];
+    for (i, byte) in counter.iter_mut().enumerate() {
+        *byte = (step >> (56 - i * 8)) as u8;
+    }
+
+    let key = hmac::SigningKey::new(&digest::SHA1, secret);
+    let hash = hmac::sign(&key, &counter);
+    let hash = hash.as_ref();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = (u32::from(hash[offset]) & 0x7f) << 24
+        | u32::from(hash[offset + 1]) << 16
+        | u32::from(hash[offset + 2]) << 8
+        | u32::from(hash[offset + 3]);
+
+    format!("{:06}", binary % 1_000_000)
+}
+
+/// Returns the time step `code` belongs to if it's valid now, allowing a step of clock drift
+/// either way. Steps up to `last_step` are rejected so an accepted code can't be used again.
+pub fn verify_code(secret: &[u8], code: &str, last_step: Option<i64>) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS {
+        return None;
+    }
+
+    let now = Utc::now().timestamp() / STEP;
+
+    (now - 1..=now + 1)
+        .filter(|step| last_step.map_or(true, |last_step| *step > last_step))
+        .find(|step| {
+            constant_time::verify_slices_are_equal(
+                code_at(secret, *step).as_bytes(),
+                code.as_bytes(),
+            )
+            .is_ok()
+        })
+}
+
+/// Returns the recovery codes to show to the user once, along with the hashes to store. The codes
+/// share a random salt, which is stored with each hash as `<salt>$<hash>`.
+pub fn generate_recovery_codes() -> Result<(Vec<String>, Vec<String>), Error> {
+    let rng = SystemRandom::new();
+
+    let mut salt = [0u8; RECOVERY_CODE_HASH_LEN];
+    rng.fill(&mut salt)
+        .map_err(|_| Error::InternalServerError)?;
+
+    let mut codes = Vec::new();
+    let mut hashes = Vec::new();
+
+    for _ in 0..RECOVERY_CODES {
+        let mut bytes = [0u8; 5];
+        rng.fill(&mut bytes)
+            .map_err(|_| Error::InternalServerError)?;
+
+        let code = BASE32_NOPAD.encode(&bytes).to_lowercase();
+
+        hashes.push(format!(
+            "{}${}",
+            BASE64.encode(&salt),
+            BASE64.encode(&hash_recovery_code(&code, &salt))
+        ));
+        codes.push(format!("{}-{}", &code[0..4], &code[4..8]));
+    }
+
+    Ok((codes, hashes))
+}
+
+fn hash_recovery_code(code: &str, salt: &[u8]) -> [u8; RECOVERY_CODE_HASH_LEN] {
+    let mut hash = [0u8; RECOVERY_CODE_HASH_LEN];
+    pbkdf2::derive(
+        &digest::SHA512,
+        RECOVERY_CODE_ITER,
+        salt,
+        code.as_bytes(),
+        &mut hash,
+    );
+
+    hash
+}
+
+/// Returns the index of the stored hash matching `code` the way it was typed, ignoring case,
+/// spaces and dashes.
+pub fn find_recovery_code(code: &str, hashes: &[String]) -> Option<usize> {
+    let code = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_lowercase();
+
+    // The codes share their salt, so the code only needs hashing once.
+    let mut derived: Option<(Vec<u8>, [u8; RECOVERY_CODE_HASH_LEN])> = None;
+
+    hashes.iter().position(|stored| {
+        let mut parts = stored
+            .splitn(2, '$')
+            .map(|part| BASE64.decode(part.as_bytes()));
+
+        let (salt, hash) = match (parts.next(), parts.next()) {
+            (Some(Ok(salt)), Some(Ok(hash))) => (salt, hash),
+            _ => return false,
+        };
+
+        if derived
+            .as_ref()
+            .map_or(true, |&(ref derived_salt, _)| *derived_salt != salt)
+        {
+            derived = Some((salt.clone(), hash_recovery_code(&code, &salt)));
+        }
+
+        derived.as_ref().map_or(false, |&(_, ref derived_hash)| {
+            constant_time::verify_slices_are_equal(&derived_hash[..], &hash).is_ok()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc6238_sha1_vectors() {
+        // From RFC 6238 appendix B, keeping the last 6 of the 8 digits.
+        let secret = b"12345678901234567890";
+
+        for &(time, code) in &[
+            (59, "287082"),
+            (1_111_111_109, "081804"),
+            (1_234_567_890, "005924"),
+            (2_000_000_000, "279037"),
+        ] {
+            assert_eq!(code, code_at(secret, time / STEP));
+        }
+    }
+
+    #[test]
+    fn recovery_codes_are_salted_and_found_as_typed() {
+        let (codes, hashes) = generate_recovery_codes().unwrap();
+
+        assert_eq!(RECOVERY_CODES, codes.len());
+
+        let typed = format!(" {} ", codes[3].to_uppercase());
+        assert_eq!(Some(3), find_recovery_code(&typed, &hashes));
+        assert_eq!(None, find_recovery_code("aaaa-aaaa", &hashes));
+    }
+}
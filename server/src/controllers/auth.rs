@@ -1,13 +1,20 @@
-use actix_web::{Json, Path, State};
+use actix_web::{Json, Path, Query, State};
 use futures::future::{err, Future};
 use serde_json::Value;
 use uuid::Uuid;
 
-use auth::AuthUser;
-use core::user::UserPayload;
-use services::{self, Error};
+use auth::{Actor, AuthUser, TwoFactorCode};
+use controllers::stores::ListParams;
+use core::{
+    session::Session,
+    user::{User, UserPayload},
+};
+use services::{self, sessions::Tokens, Error};
 use state::AppState;
 
+const LIMIT: i64 = 15;
+const OFFSET: i64 = 0;
+
 #[derive(Debug, Deserialize)]
 pub struct RegistrationParams {
     pub email: String,
@@ -43,26 +50,46 @@ pub fn registration(
     )
 }
 
+/// What signing in returns: an access token, the refresh token of the new session and the user.
+pub fn signed_in(tokens: Tokens, session: Session, user: User) -> Json<Value> {
+    Json(json!({
+        "token": tokens.access_token,
+        "refresh_token": tokens.refresh_token,
+        "session": session.export(),
+        "user": user.export(),
+    }))
+}
+
 #[derive(Deserialize)]
 pub struct LoginParams {
     pub email: String,
     pub password: String,
+    /// Required once two-factor authentication is enabled.
+    pub code: Option<String>,
 }
 
 pub fn authentication(
-    (state, params): (State<AppState>, Json<LoginParams>),
+    (state, params, actor): (State<AppState>, Json<LoginParams>, Actor),
 ) -> impl Future<Item = Json<Value>, Error = Error> {
     let params = params.into_inner();
+    let key = format!("login:account:{}", params.email.to_lowercase());
 
-    services::users::authenticate(
-        params.email,
-        params.password,
-        &state.postgres,
-        state.jwt_private.clone(),
-    )
-    .then(|res| {
-        res.and_then(|(token, user)| Ok(Json(json!({ "token": token, "user": user.export() }))))
-    })
+    state
+        .rate_limiter
+        .take(key, &state.rate_limiter.limits.login)
+        .and_then(move |_| {
+            services::users::authenticate(
+                params.email,
+                params.password,
+                params.code,
+                actor,
+                &state.rate_limiter,
+                &state.keychain,
+                &state.postgres,
+                state.jwt_private.clone(),
+            )
+        })
+        .then(|res| res.and_then(|(token, session, user)| Ok(signed_in(token, session, user))))
 }
 
 #[derive(Deserialize)]
@@ -71,15 +98,17 @@ pub struct ActivationParams {
 }
 
 pub fn activation(
-    (state, params): (State<AppState>, Json<ActivationParams>),
+    (state, params, actor): (State<AppState>, Json<ActivationParams>, Actor),
 ) -> impl Future<Item = Json<Value>, Error = Error> {
     let params = params.into_inner();
 
-    services::users::activate(params.token, &state.postgres, state.jwt_private.clone()).then(
-        |res| {
-            res.and_then(|(token, user)| Ok(Json(json!({ "token": token, "user": user.export() }))))
-        },
+    services::users::activate(
+        params.token,
+        actor,
+        &state.postgres,
+        state.jwt_private.clone(),
     )
+    .then(|res| res.and_then(|(token, session, user)| Ok(signed_in(token, session, user))))
 }
 
 #[derive(Deserialize)]
@@ -91,37 +120,47 @@ pub fn reset_password(
     (state, params): (State<AppState>, Json<ResetPasswordParams>),
 ) -> impl Future<Item = Json<Value>, Error = Error> {
     let params = params.into_inner();
+    let key = format!("reset_password:account:{}", params.email.to_lowercase());
 
-    services::users::reset_password(
-        params.email,
-        state.mailer.clone(),
-        &state.postgres,
-        state.config.web_client_url.clone(),
-        state.config.mail_sender.clone(),
-    )
-    .then(|res| res.and_then(|_| Ok(Json(json!({})))))
+    state
+        .rate_limiter
+        .take(key, &state.rate_limiter.limits.reset_password)
+        .and_then(move |_| {
+            services::users::reset_password(
+                params.email,
+                state.mailer.clone(),
+                &state.postgres,
+                state.config.web_client_url.clone(),
+                state.config.mail_sender.clone(),
+            )
+        })
+        .then(|res| res.and_then(|_| Ok(Json(json!({})))))
 }
 
 #[derive(Deserialize)]
 pub struct ChangePasswordParams {
     pub token: Uuid,
     pub password: String,
+    /// Required once two-factor authentication is enabled.
+    pub code: Option<String>,
 }
 
 pub fn change_password(
-    (state, params): (State<AppState>, Json<ChangePasswordParams>),
+    (state, params, actor): (State<AppState>, Json<ChangePasswordParams>, Actor),
 ) -> impl Future<Item = Json<Value>, Error = Error> {
     let params = params.into_inner();
 
     services::users::change_password(
         params.token,
         params.password,
+        params.code,
+        actor,
+        &state.rate_limiter,
+        &state.keychain,
         &state.postgres,
         state.jwt_private.clone(),
     )
-    .then(|res| {
-        res.and_then(|(token, user)| Ok(Json(json!({ "token": token, "user": user.export() }))))
-    })
+    .then(|res| res.and_then(|(token, session, user)| Ok(signed_in(token, session, user))))
 }
 
 pub fn profile(
@@ -131,8 +170,37 @@ pub fn profile(
         .then(|res| res.and_then(|user| Ok(Json(user.export()))))
 }
 
+/// Lists the audit log entries about the user's own account, like password resets and
+/// two-factor changes, newest first.
+pub fn audit(
+    (state, params, user): (State<AppState>, Query<ListParams>, AuthUser),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let mut limit = LIMIT;
+    let mut offset = OFFSET;
+
+    if let Some(_limit) = params.limit {
+        if _limit < LIMIT {
+            limit = _limit;
+        }
+    };
+
+    if let Some(_offset) = params.offset {
+        offset = _offset;
+    };
+
+    services::audit::find_by_user(user.id, limit, offset, &state.postgres).map(move |entries| {
+        let exported: Vec<Value> = entries.iter().map(|entry| entry.export()).collect();
+
+        Json(json!({
+            "entries": exported,
+            "limit": limit,
+            "offset": offset,
+        }))
+    })
+}
+
 pub fn delete(
-    (state, path, user): (State<AppState>, Path<Uuid>, AuthUser),
+    (state, path, user, code, actor): (State<AppState>, Path<Uuid>, AuthUser, TwoFactorCode, Actor),
 ) -> Box<Future<Item = Json<Value>, Error = Error>> {
     let id = path.into_inner();
 
@@ -141,7 +209,95 @@ pub fn delete(
     }
 
     Box::new(
-        services::users::delete(user.id, &state.postgres)
-            .then(|res| res.and_then(|deleted| Ok(Json(json!({ "deleted": deleted }))))),
+        services::users::verify_two_factor(
+            user.id,
+            code.0,
+            &state.rate_limiter,
+            &state.keychain,
+            &state.postgres,
+        )
+        .and_then(move |_| services::users::delete(user.id, actor, &state.postgres))
+        .then(|res| res.and_then(|deleted| Ok(Json(json!({ "deleted": deleted }))))),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct EnrollTwoFactorParams {
+    pub password: String,
+}
+
+pub fn enroll_two_factor(
+    (state, params, user, actor): (
+        State<AppState>,
+        Json<EnrollTwoFactorParams>,
+        AuthUser,
+        Actor,
+    ),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let params = params.into_inner();
+
+    services::users::enroll_two_factor(
+        user.id,
+        params.password,
+        actor,
+        &state.keychain,
+        &state.postgres,
+    )
+    .then(|res| {
+        res.and_then(|(secret, url)| Ok(Json(json!({ "secret": secret, "provisioning_url": url }))))
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTwoFactorParams {
+    pub code: String,
+}
+
+pub fn confirm_two_factor(
+    (state, params, user, actor): (
+        State<AppState>,
+        Json<ConfirmTwoFactorParams>,
+        AuthUser,
+        Actor,
+    ),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let params = params.into_inner();
+
+    services::users::confirm_two_factor(
+        user.id,
+        params.code,
+        actor,
+        &state.rate_limiter,
+        &state.keychain,
+        &state.postgres,
+    )
+    .then(|res| res.and_then(|codes| Ok(Json(json!({ "recovery_codes": codes })))))
+}
+
+#[derive(Deserialize)]
+pub struct DisableTwoFactorParams {
+    pub password: String,
+}
+
+pub fn disable_two_factor(
+    (state, params, user, code, actor): (
+        State<AppState>,
+        Json<DisableTwoFactorParams>,
+        AuthUser,
+        TwoFactorCode,
+        Actor,
+    ),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let params = params.into_inner();
+
+    services::users::disable_two_factor(
+        user.id,
+        params.password,
+        code.0,
+        actor,
+        &state.rate_limiter,
+        &state.keychain,
+        &state.postgres,
     )
+    .then(|res| res.and_then(|user| Ok(Json(user.export()))))
 }
@@ -0,0 +1,133 @@
+use actix_web::{http, HttpResponse, Path, State};
+use chrono::prelude::*;
+use futures::future::{ok, Future};
+use qrcode::{render::svg, QrCode};
+use uuid::Uuid;
+
+use core::{payment::Payment, store::Store};
+use services::{self, Error};
+use state::AppState;
+use templates::{self, escape};
+use types::{PaymentStatus, U128};
+
+// Reload the page this often while there's something to wait for, so the page keeps working
+// for customers with JavaScript disabled.
+const REFRESH_INTERVAL: u64 = 15;
+
+fn qr_code(data: &str) -> String {
+    match QrCode::new(data.as_bytes()) {
+        Ok(code) => code.render::<svg::Color>().min_dimensions(220, 220).build(),
+        Err(_) => String::new(),
+    }
+}
+
+fn render_invoice(payment: &Payment, store: &Store, block_height: U128) -> String {
+    let expires_in = (payment.expires_at - Utc::now()).num_seconds();
+    let is_expired = payment.status == PaymentStatus::Expired
+        || (payment.status == PaymentStatus::Pending && expires_in <= 0);
+
+    let message = if is_expired {
+        String::from("This invoice has expired.")
+    } else {
+        match payment.status {
+            PaymentStatus::Pending => format!(
+                "Send exactly {} {} to the address below.",
+                payment.charge,
+                payment.crypto.to_str().to_uppercase()
+            ),
+            PaymentStatus::Paid => format!(
+                "Payment received. Waiting for {} more confirmation(s).",
+                payment.remaining_confirmations(block_height)
+            ),
+            PaymentStatus::InsufficientAmount => String::from(
+                "The amount received is lower than requested. Please contact the merchant.",
+            ),
+            _ => String::from("Payment complete. Thank you!"),
+        }
+    };
+
+    let details = if payment.status == PaymentStatus::Pending && !is_expired {
+        let uri = payment.uri(Some(&store.name));
+
+        templates::render(
+            templates::INVOICE_DETAILS,
+            &[
+                ("qr_code", &qr_code(&uri)),
+                ("address", &escape(&payment.address)),
+                ("uri", &escape(&uri)),
+                ("expires_in", &expires_in.to_string()),
+                (
+                    "expires_in_text",
+                    &format!("{}:{:02}", expires_in / 60, expires_in % 60),
+                ),
+            ],
+        )
+    } else {
+        String::new()
+    };
+
+    let refresh = match payment.status {
+        PaymentStatus::Pending | PaymentStatus::Paid if !is_expired => format!(
+            "<meta http-equiv=\"refresh\" content=\"{}\">",
+            REFRESH_INTERVAL
+        ),
+        _ => String::new(),
+    };
+
+    templates::render(
+        templates::INVOICE,
+        &[
+            ("refresh", &refresh),
+            ("store_name", &escape(&store.name)),
+            ("store_description", &escape(&store.description)),
+            ("charge", &payment.charge.to_string()),
+            ("crypto", &payment.crypto.to_str().to_uppercase()),
+            ("price", &payment.price.to_string()),
+            ("fiat", &payment.fiat.to_str().to_uppercase()),
+            ("message", &escape(&message)),
+            ("details", &details),
+        ],
+    )
+}
+
+/// Hosted invoice page. Payment ids are random, so like the payment JWT handed out on creation
+/// the id itself is what grants access to the page.
+pub fn invoice(
+    (state, path): (State<AppState>, Path<Uuid>),
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    let id = path.into_inner();
+    let postgres = state.postgres.clone();
+
+    services::payments::get(id, &state.postgres)
+        .and_then(move |payment| {
+            let block_height = services::payments::get_block_height(&payment, &postgres);
+
+            payment
+                .store(&postgres)
+                .from_err()
+                .join(block_height)
+                .map(move |(store, block_height)| (payment, store, block_height))
+        })
+        .and_then(
+            move |(payment, store, block_height)| -> Box<Future<Item = HttpResponse, Error = Error>> {
+                match store.success_url.clone() {
+                    Some(success_url) if payment.is_settled() => Box::new(
+                        services::vouchers::create(payment, &state.keychain, &state.postgres)
+                            .map(move |voucher| {
+                                let separator = if success_url.contains('?') { "&" } else { "?" };
+
+                                HttpResponse::Found()
+                                    .header(
+                                        http::header::LOCATION,
+                                        format!("{}{}voucher={}", success_url, separator, voucher),
+                                    )
+                                    .finish()
+                            }),
+                    ),
+                    _ => Box::new(ok(HttpResponse::Ok()
+                        .content_type("text/html; charset=utf-8")
+                        .body(render_invoice(&payment, &store, block_height)))),
+                }
+            },
+        )
+}
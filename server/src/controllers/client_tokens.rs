@@ -1,13 +1,19 @@
+use std::str::FromStr;
+
 use actix_web::{Json, Path, Query, State};
+use bigdecimal::BigDecimal;
 use futures::future::{Future, IntoFuture};
 use serde_json::Value;
 use uuid::Uuid;
 
-use auth::AuthUser;
-use core::{client_token::ClientTokenPayload, store::Store};
+use auth::{permissions::Permission, Actor, AuthUser};
+use core::client_token::ClientTokenPayload;
+use services::{self, store_memberships::authorize, Error};
 use state::AppState;
-use services::{self, Error};
-use types::Client;
+use types::{
+    currency::{Crypto, Fiat},
+    Client,
+};
 
 const LIMIT: i64 = 15;
 const OFFSET: i64 = 0;
@@ -15,21 +21,78 @@ const OFFSET: i64 = 0;
 #[derive(Debug, Deserialize)]
 pub struct CreateParams {
     pub name: String,
-    pub domain: String,
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
     pub typ: Client,
     pub store_id: Uuid,
+    pub allowed_cryptos: Option<Vec<Crypto>>,
+    pub allowed_fiats: Option<Vec<Fiat>>,
+    pub max_price: Option<BigDecimal>,
+    /// Required with `max_price`, payments in other fiat currencies are rejected.
+    pub max_price_fiat: Option<Fiat>,
+}
+
+/// Origins are a host with an optional port, like `shop.example.com:8080`. A leading `*.`
+/// matches any subdomain.
+fn valid_origin(origin: &str) -> bool {
+    let host = if origin.starts_with("*.") {
+        &origin[2..]
+    } else {
+        origin
+    };
+
+    !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == ':')
 }
 
-fn validate_store_owner(store: &Store, user: &AuthUser) -> Result<bool, Error> {
-    if store.owner_id != user.id {
-        return Err(Error::InvalidRequestAccount);
+fn validate(params: &CreateParams) -> Result<(), Error> {
+    if params.typ == Client::Web && params.allowed_origins.is_empty() {
+        return Err(Error::BadRequest("web tokens need an allowed origin"));
+    }
+
+    if !params.allowed_origins.iter().all(|o| valid_origin(o)) {
+        return Err(Error::BadRequest("invalid allowed origin"));
+    }
+
+    if let Some(ref cryptos) = params.allowed_cryptos {
+        if cryptos.is_empty() {
+            return Err(Error::BadRequest("allowed_cryptos can't be empty"));
+        }
+    }
+
+    if let Some(ref fiats) = params.allowed_fiats {
+        if fiats.is_empty() {
+            return Err(Error::BadRequest("allowed_fiats can't be empty"));
+        }
+    }
+
+    if let Some(ref max_price) = params.max_price {
+        if *max_price <= BigDecimal::from_str("0").unwrap() {
+            return Err(Error::BadRequest("max_price has to be above 0"));
+        }
     }
 
-    Ok(true)
+    if params.max_price.is_some() != params.max_price_fiat.is_some() {
+        return Err(Error::BadRequest(
+            "max_price and max_price_fiat have to be set together",
+        ));
+    }
+
+    if let (Some(fiat), Some(fiats)) = (&params.max_price_fiat, &params.allowed_fiats) {
+        if !fiats.contains(fiat) {
+            return Err(Error::BadRequest(
+                "max_price_fiat has to be one of allowed_fiats",
+            ));
+        }
+    }
+
+    Ok(())
 }
 
 pub fn create(
-    (state, user, params): (State<AppState>, AuthUser, Json<CreateParams>),
+    (state, user, params, actor): (State<AppState>, AuthUser, Json<CreateParams>, Actor),
 ) -> impl Future<Item = Json<Value>, Error = Error> {
     let mut params = params.into_inner();
 
@@ -37,24 +100,44 @@ pub fn create(
         params.name = String::from("My API Key");
     }
 
-    services::stores::get(params.store_id, &state.postgres).and_then(move |store| {
-        validate_store_owner(&store, &user)
-            .into_future()
+    params.allowed_origins = params
+        .allowed_origins
+        .iter()
+        .map(|origin| origin.to_lowercase())
+        .collect();
+
+    validate(&params)
+        .into_future()
+        .and_then(move |_| {
+            services::stores::get(params.store_id, &state.postgres)
+                .map(|store| (state, params, store))
+        })
+        .and_then(move |(state, params, store)| {
+            authorize(
+                &store,
+                user.id,
+                Permission::ManageClientTokens,
+                &state.postgres,
+            )
             .and_then(move |_| {
                 let payload = ClientTokenPayload {
                     id: None,
                     name: params.name,
                     token: None,
                     store_id: store.id,
-                    domain: params.domain,
                     created_at: None,
                     typ: params.typ,
+                    allowed_origins: params.allowed_origins,
+                    allowed_cryptos: params.allowed_cryptos,
+                    allowed_fiats: params.allowed_fiats,
+                    max_price: params.max_price,
+                    max_price_fiat: params.max_price_fiat,
                 };
 
-                services::client_tokens::create(payload, &state.postgres)
+                services::client_tokens::create(payload, actor, &state.postgres)
                     .then(|res| res.and_then(|client_token| Ok(Json(client_token.export()))))
             })
-    })
+        })
 }
 
 #[derive(Debug, Deserialize)]
@@ -81,24 +164,29 @@ pub fn list(
     };
 
     services::stores::get(params.store_id, &state.postgres).and_then(move |store| {
-        validate_store_owner(&store, &user)
-            .into_future()
-            .and_then(move |_| {
-                services::client_tokens::find_by_store(store.id, limit, offset, &state.postgres)
-                    .then(move |res| {
-                        res.and_then(|client_tokens| {
-                            let mut exported = Vec::new();
-                            client_tokens
-                                .into_iter()
-                                .for_each(|client_token| exported.push(client_token.export()));
-                            Ok(Json(json!({
-                                "client_tokens": exported,
-                                "limit": limit,
-                                "offset": offset,
-                            })))
-                        })
+        authorize(
+            &store,
+            user.id,
+            Permission::ManageClientTokens,
+            &state.postgres,
+        )
+        .and_then(move |_| {
+            services::client_tokens::find_by_store(store.id, limit, offset, &state.postgres).then(
+                move |res| {
+                    res.and_then(|client_tokens| {
+                        let mut exported = Vec::new();
+                        client_tokens
+                            .into_iter()
+                            .for_each(|client_token| exported.push(client_token.export()));
+                        Ok(Json(json!({
+                            "client_tokens": exported,
+                            "limit": limit,
+                            "offset": offset,
+                        })))
                     })
-            })
+                },
+            )
+        })
     })
 }
 
@@ -109,26 +197,34 @@ pub fn get(
 
     services::client_tokens::get(id, &state.postgres).and_then(move |client_token| {
         services::stores::get(client_token.store_id, &state.postgres).and_then(move |store| {
-            validate_store_owner(&store, &user)
-                .into_future()
-                .then(move |_| Ok(Json(client_token.export())))
+            authorize(
+                &store,
+                user.id,
+                Permission::ManageClientTokens,
+                &state.postgres,
+            )
+            .map(move |_| Json(client_token.export()))
         })
     })
 }
 
 pub fn delete(
-    (state, path, user): (State<AppState>, Path<Uuid>, AuthUser),
+    (state, path, user, actor): (State<AppState>, Path<Uuid>, AuthUser, Actor),
 ) -> impl Future<Item = Json<Value>, Error = Error> {
     let id = path.into_inner();
 
     services::client_tokens::get(id, &state.postgres).and_then(move |client_token| {
         services::stores::get(client_token.store_id, &state.postgres).and_then(move |store| {
-            validate_store_owner(&store, &user)
-                .into_future()
-                .and_then(move |_| {
-                    services::client_tokens::delete(id, &state.postgres)
-                        .then(|res| res.and_then(|res| Ok(Json(json!({ "deleted": res })))))
-                })
+            authorize(
+                &store,
+                user.id,
+                Permission::ManageClientTokens,
+                &state.postgres,
+            )
+            .and_then(move |_| {
+                services::client_tokens::delete(client_token, actor, &state.postgres)
+                    .then(|res| res.and_then(|res| Ok(Json(json!({ "deleted": res })))))
+            })
         })
     })
 }
@@ -0,0 +1,73 @@
+use actix_web::{Json, Path, State};
+use futures::future::Future;
+use serde_json::Value;
+use uuid::Uuid;
+
+use auth::{permissions::Permission, Actor, AuthUser};
+use mailer::templates::Template;
+use services::{self, store_memberships::authorize, Error};
+use state::AppState;
+use types::NotificationKind;
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateParams {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+pub fn list(
+    (state, path, user): (State<AppState>, Path<Uuid>, AuthUser),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let id = path.into_inner();
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ManageStore, &state.postgres).and_then(move |_| {
+            services::email_templates::list(id, &state.postgres).map(|templates| Json(templates))
+        })
+    })
+}
+
+pub fn update(
+    (state, path, params, user, actor): (
+        State<AppState>,
+        Path<(Uuid, NotificationKind)>,
+        Json<UpdateParams>,
+        AuthUser,
+        Actor,
+    ),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let (id, kind) = path.into_inner();
+    let params = params.into_inner();
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ManageStore, &state.postgres).and_then(move |_| {
+            let template = Template {
+                subject: params.subject,
+                html: params.html,
+                text: params.text,
+            };
+
+            services::email_templates::update(id, kind, template, actor, &state.postgres)
+                .map(|template| Json(template))
+        })
+    })
+}
+
+pub fn delete(
+    (state, path, user, actor): (
+        State<AppState>,
+        Path<(Uuid, NotificationKind)>,
+        AuthUser,
+        Actor,
+    ),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let (id, kind) = path.into_inner();
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ManageStore, &state.postgres).and_then(move |_| {
+            services::email_templates::remove(id, kind, actor, &state.postgres)
+                .map(|res| Json(json!({ "deleted": res })))
+        })
+    })
+}
@@ -0,0 +1,225 @@
+use actix_web::{Json, Path, State};
+use bigdecimal::BigDecimal;
+use futures::future::{err, ok, Future, IntoFuture};
+use serde_json::Value;
+use uuid::Uuid;
+
+use auth::{signed_checkout, AuthClient, JWTPayload};
+use controllers::payments::{create_payment, validate_customer_email, validate_metadata};
+use core::{
+    client_token::ClientToken,
+    invoice::{Invoice, InvoicePayload},
+    payment::PaymentPayload,
+};
+use services::{self, Error};
+use state::AppState;
+use types::{
+    currency::{Crypto, Fiat},
+    PaymentStatus,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateParams {
+    pub fiat: Option<Fiat>,
+    pub price: Option<BigDecimal>,
+    pub identifier: Option<String>,
+    pub metadata: Option<Value>,
+    /// Where the receipt goes, the payment made for the invoice inherits it.
+    pub customer_email: Option<String>,
+    /// Required by stores with a checkout secret, see `auth::signed_checkout`.
+    pub checkout: Option<String>,
+}
+
+pub fn create(
+    (state, client_token, params): (State<AppState>, ClientToken, Json<CreateParams>),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let params = params.into_inner();
+
+    services::stores::get(client_token.store_id, &state.postgres).and_then(
+        move |store| -> Box<Future<Item = Json<Value>, Error = Error>> {
+            let checkout = match signed_checkout::resolve(
+                &store,
+                &state.keychain,
+                params.fiat,
+                params.price,
+                params.identifier,
+                params.metadata,
+                params.checkout,
+            ) {
+                Ok(checkout) => checkout,
+                Err(e) => return Box::new(err(e)),
+            };
+
+            if let Some(ref identifier) = checkout.identifier {
+                if identifier.len() > 100 {
+                    return Box::new(err(Error::BadRequest("identifier is too long. Max: 100")));
+                }
+            }
+
+            if let Err(e) = validate_metadata(checkout.metadata.as_ref()) {
+                return Box::new(err(e));
+            }
+
+            if let Err(e) = validate_customer_email(params.customer_email.as_ref()) {
+                return Box::new(err(e));
+            }
+
+            if let Err(e) = services::client_tokens::check_restrictions(
+                &client_token,
+                None,
+                &checkout.fiat,
+                &checkout.price,
+            ) {
+                return Box::new(err(e));
+            }
+
+            let cryptos: Vec<Crypto> = [Crypto::Btc, Crypto::Eth]
+                .iter()
+                .filter(|crypto| {
+                    store.can_accept(crypto)
+                        && state.supports(crypto)
+                        && client_token.allows_crypto(crypto)
+                })
+                .cloned()
+                .collect();
+
+            if cryptos.is_empty() {
+                return Box::new(err(Error::CurrencyNotSupported));
+            }
+
+            let auth_client = AuthClient::new(client_token);
+
+            let mut payload = InvoicePayload::new();
+            payload.store_id = Some(auth_client.store_id);
+            payload.created_by = Some(auth_client.id);
+            payload.fiat = Some(checkout.fiat);
+            payload.price = Some(checkout.price);
+            payload.identifier = checkout.identifier;
+            payload.signed = Some(checkout.signed);
+            payload.metadata = checkout.metadata;
+            payload.customer_email = params.customer_email;
+
+            Box::new(
+                services::invoices::create(payload, &state.postgres).and_then(move |invoice| {
+                    JWTPayload::new(None, Some(auth_client), invoice.expires_at)
+                        .encode(&state.jwt_private)
+                        .map_err(|e| Error::from(e))
+                        .into_future()
+                        .map(move |auth_token| {
+                            Json(json!({
+                                "invoice": invoice.export(),
+                                "cryptos": cryptos,
+                                "store": {
+                                    "name": store.name,
+                                    "description": store.description
+                                },
+                                "token": auth_token,
+                            }))
+                        })
+                }),
+            )
+        },
+    )
+}
+
+fn validate_client(invoice: &Invoice, client: &AuthClient) -> Result<bool, Error> {
+    if invoice.created_by != client.id {
+        return Err(Error::InvalidRequestAccount);
+    }
+
+    Ok(true)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePaymentParams {
+    pub crypto: Crypto,
+}
+
+/// Called when the customer picks a currency. Picking the same currency again returns the
+/// payment created the first time for as long as it can still be paid.
+pub fn create_payment_option(
+    (state, client, path, params): (
+        State<AppState>,
+        AuthClient,
+        Path<Uuid>,
+        Json<CreatePaymentParams>,
+    ),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let id = path.into_inner();
+    let crypto = params.into_inner().crypto;
+    let postgres = state.postgres.clone();
+
+    services::invoices::get(id, &state.postgres)
+        .and_then(move |invoice| {
+            validate_client(&invoice, &client)
+                .and_then(|_| {
+                    if client.allows_crypto(&crypto) {
+                        Ok(true)
+                    } else {
+                        Err(Error::BadRequest("crypto is not allowed for this client token"))
+                    }
+                })
+                .into_future()
+                .and_then(move |_| ok((invoice, client)))
+        })
+        .and_then(move |(invoice, client)| {
+            let store = invoice.store(&postgres).from_err();
+
+            invoice
+                .payments(&postgres)
+                .from_err()
+                .join(store)
+                .map(move |(payments, store)| (invoice, client, payments, store))
+        })
+        .and_then(
+            move |(invoice, client, payments, store)| -> Box<Future<Item = Json<Value>, Error = Error>> {
+                if payments
+                    .iter()
+                    .any(|payment| payment.status != PaymentStatus::Pending)
+                {
+                    return Box::new(err(Error::BadRequest("invoice has already been paid")));
+                }
+
+                let existing = payments.into_iter().find(|payment| {
+                    payment.crypto == crypto && !payment.is_expired()
+                });
+
+                let payment_future: Box<Future<Item = _, Error = Error>> = match existing {
+                    Some(payment) => Box::new(ok(payment)),
+                    None => {
+                        if invoice.is_expired() {
+                            return Box::new(err(Error::BadRequest("invoice has expired")));
+                        }
+
+                        let mut payload = PaymentPayload::new();
+                        payload.store_id = Some(invoice.store_id);
+                        payload.created_by = Some(client.id);
+                        payload.fiat = Some(invoice.fiat);
+                        payload.price = Some(invoice.price.clone());
+                        payload.crypto = Some(crypto);
+                        payload.identifier = invoice.identifier.clone();
+                        payload.invoice_id = Some(invoice.id);
+                        payload.signed = Some(invoice.signed);
+                        payload.metadata = invoice.metadata.clone();
+                        payload.customer_email = invoice.customer_email.clone();
+
+                        create_payment(&state, &store, payload)
+                    }
+                };
+
+                Box::new(payment_future.and_then(move |payment| {
+                    JWTPayload::new(None, Some(client), payment.expires_at)
+                        .encode(&state.jwt_private)
+                        .map_err(|e| Error::from(e))
+                        .into_future()
+                        .map(move |auth_token| {
+                            Json(json!({
+                                "payment": payment.export(),
+                                "uri": payment.uri(Some(&store.name)),
+                                "token": auth_token,
+                            }))
+                        })
+                }))
+            },
+        )
+}
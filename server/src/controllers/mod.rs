@@ -1,6 +1,12 @@
 pub mod auth;
+pub mod checkout;
 pub mod client_tokens;
+pub mod email_templates;
+pub mod invoices;
 pub mod payments;
+pub mod payout_address_changes;
 pub mod root;
+pub mod sessions;
+pub mod store_memberships;
 pub mod stores;
 pub mod vouchers;
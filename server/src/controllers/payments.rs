@@ -1,117 +1,241 @@
-use actix_web::{Json, Path, State};
+use actix_web::{
+    error, ws, Error as ActixError, FromRequest, HttpRequest, HttpResponse, Json, Path, Query,
+    State,
+};
 use bigdecimal::BigDecimal;
-use futures::future::{self, err, ok, Future, IntoFuture};
+use futures::future::{self, err, Future, IntoFuture};
 use serde_json::Value;
 use uuid::Uuid;
 
-use auth::{AuthClient, JWTPayload};
+use auth::{signed_checkout, AuthClient, IdempotencyKey, JWTPayload};
 use core::{
-    bitcoin::BlockchainStatus as BtcBlockchainStatus,
     client_token::ClientToken,
-    ethereum::BlockchainStatus as EthBlockchainStatus,
     payment::{Payment, PaymentPayload},
+    store::Store,
 };
+use hub::StatusSocket;
 use services::{self, Error};
 use state::AppState;
-use types::{
-    currency::{Crypto, Fiat},
-    PaymentStatus, U128,
-};
+use types::currency::{Crypto, Fiat};
 
-#[derive(Debug, Deserialize)]
+const MAX_METADATA_KEYS: usize = 50;
+const MAX_METADATA_BYTES: usize = 4096;
+const MAX_EMAIL_LEN: usize = 254;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateParams {
     pub crypto: Crypto,
-    pub fiat: Fiat,
-    pub price: BigDecimal,
+    pub fiat: Option<Fiat>,
+    pub price: Option<BigDecimal>,
     pub identifier: Option<String>,
+    pub metadata: Option<Value>,
+    /// Where the receipt goes once the payment is confirmed.
+    pub customer_email: Option<String>,
+    /// Required by stores with a checkout secret, see `auth::signed_checkout`.
+    pub checkout: Option<String>,
+}
+
+/// Metadata is a JSON object of at most `MAX_METADATA_KEYS` keys, taking up at most
+/// `MAX_METADATA_BYTES` once serialized.
+pub fn validate_metadata(metadata: Option<&Value>) -> Result<(), Error> {
+    let metadata = match metadata {
+        Some(metadata) => metadata,
+        None => return Ok(()),
+    };
+
+    match metadata.as_object() {
+        Some(object) if object.len() > MAX_METADATA_KEYS => {
+            Err(Error::BadRequest("metadata has too many keys. Max: 50"))
+        }
+        Some(_) if metadata.to_string().len() > MAX_METADATA_BYTES => {
+            Err(Error::BadRequest("metadata is too large. Max: 4096 bytes"))
+        }
+        Some(_) => Ok(()),
+        None => Err(Error::BadRequest("metadata has to be an object")),
+    }
+}
+
+/// A single address, as it ends up in the `To` header of the receipt. Anything that could add
+/// recipients or headers is refused.
+pub fn validate_customer_email(customer_email: Option<&String>) -> Result<(), Error> {
+    let customer_email = match customer_email {
+        Some(customer_email) => customer_email,
+        None => return Ok(()),
+    };
+
+    let valid = customer_email.len() <= MAX_EMAIL_LEN
+        && !customer_email
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || ",;<>\"\\()[]".contains(c))
+        && match customer_email.find('@') {
+            Some(at) => {
+                let (local, domain) = (&customer_email[..at], &customer_email[at + 1..]);
+
+                !local.is_empty()
+                    && !domain.contains('@')
+                    && domain.contains('.')
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.')
+            }
+            None => false,
+        };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::BadRequest("invalid customer_email"))
+    }
 }
 
+/// Fills in the network and confirmation settings for the payload's crypto and creates the
+/// payment. Used both for direct payments and for currencies picked on an invoice.
+pub fn create_payment(
+    state: &AppState,
+    store: &Store,
+    mut payload: PaymentPayload,
+) -> Box<Future<Item = Payment, Error = Error>> {
+    let crypto = payload.crypto.unwrap();
+
+    if !store.can_accept(&crypto) || !state.supports(&crypto) {
+        return Box::new(err(Error::CurrencyNotSupported));
+    }
+
+    let min_charge;
+
+    match crypto {
+        Crypto::Btc => {
+            let config = state.btc_config.clone().unwrap();
+            payload.confirmations_required = store.btc_confirmations_required;
+            payload.btc_network = Some(config.network);
+            min_charge = config.min_charge;
+        }
+        Crypto::Eth => {
+            let config = state.eth_config.clone().unwrap();
+            payload.confirmations_required = store.eth_confirmations_required;
+            payload.eth_network = Some(config.network);
+            min_charge = config.min_charge;
+        }
+    }
+
+    Box::new(services::payments::create(
+        payload,
+        store,
+        &state.postgres,
+        min_charge,
+        state.currency_api_client.clone(),
+        &state.keychain,
+    ))
+}
+
+/// Retries sent with the same `Idempotency-Key` get the payment created the first time, rather
+/// than a new one with its own address.
 pub fn create(
-    (state, client_token, params): (State<AppState>, ClientToken, Json<CreateParams>),
-) -> impl Future<Item = Json<Value>, Error = Error> {
+    (state, client_token, params, idempotency_key): (
+        State<AppState>,
+        ClientToken,
+        Json<CreateParams>,
+        IdempotencyKey,
+    ),
+) -> Box<Future<Item = Json<Value>, Error = Error>> {
     let params = params.into_inner();
 
-    services::stores::get(client_token.store_id, &state.postgres)
-        .and_then(move |store| {
-            if !store.can_accept(&params.crypto) {
-                return err(Error::CurrencyNotSupported);
-            }
+    let fingerprint = match services::idempotency::fingerprint(&params) {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => return Box::new(err(e)),
+    };
+    let postgres = state.postgres.clone();
 
-            ok((store, params))
-        })
-        .and_then(
-            move |(store, params)| -> Box<Future<Item = Json<Value>, Error = Error>> {
-                let auth_client = AuthClient::new(client_token);
-
-                let mut payload = PaymentPayload::new();
-                payload.store_id = Some(auth_client.store_id);
-                payload.created_by = Some(auth_client.id);
-                payload.fiat = Some(params.fiat);
-                payload.price = Some(params.price);
-                payload.crypto = Some(params.crypto);
-
-                if let Some(ref identifier) = params.identifier {
-                    if identifier.len() > 100 {
-                        return Box::new(err(Error::BadRequest("identifier is too long. Max: 100")));
-                    }
-
-                    payload.identifier = params.identifier.to_owned();
-                }
+    Box::new(
+        services::idempotency::run(
+            format!("payments:{}", client_token.id),
+            idempotency_key.0,
+            fingerprint,
+            &postgres,
+            move || create_for_client(state, client_token, params).map(|res| res.into_inner()),
+        )
+        .map(|res| Json(res)),
+    )
+}
 
-                if !state.supports(&params.crypto) {
-                    return Box::new(err(Error::CurrencyNotSupported));
-                }
+fn create_for_client(
+    state: State<AppState>,
+    client_token: ClientToken,
+    params: CreateParams,
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    services::stores::get(client_token.store_id, &state.postgres).and_then(
+        move |store| -> Box<Future<Item = Json<Value>, Error = Error>> {
+            let checkout = match signed_checkout::resolve(
+                &store,
+                &state.keychain,
+                params.fiat,
+                params.price,
+                params.identifier,
+                params.metadata,
+                params.checkout,
+            ) {
+                Ok(checkout) => checkout,
+                Err(e) => return Box::new(err(e)),
+            };
 
-                let min_charge;
-
-                match params.crypto {
-                    Crypto::Btc => {
-                        payload.confirmations_required = store.btc_confirmations_required;
-                        payload.btc_network = state
-                            .clone()
-                            .btc_config
-                            .map_or(None, |config| Some(config.network));
-                        min_charge = state.clone().btc_config.unwrap().min_charge;
-                    }
-                    Crypto::Eth => {
-                        payload.confirmations_required = store.eth_confirmations_required;
-                        payload.eth_network = state
-                            .clone()
-                            .eth_config
-                            .map_or(None, |config| Some(config.network));
-                        min_charge = state.clone().eth_config.unwrap().min_charge;
-                    }
+            if let Some(ref identifier) = checkout.identifier {
+                if identifier.len() > 100 {
+                    return Box::new(err(Error::BadRequest("identifier is too long. Max: 100")));
                 }
+            }
+
+            if let Err(e) = validate_metadata(checkout.metadata.as_ref()) {
+                return Box::new(err(e));
+            }
+
+            if let Err(e) = validate_customer_email(params.customer_email.as_ref()) {
+                return Box::new(err(e));
+            }
+
+            if let Err(e) = services::client_tokens::check_restrictions(
+                &client_token,
+                Some(&params.crypto),
+                &checkout.fiat,
+                &checkout.price,
+            ) {
+                return Box::new(err(e));
+            }
 
-                Box::new(
-                    services::payments::create(
-                        payload,
-                        &store,
-                        &state.postgres,
-                        min_charge,
-                        state.currency_api_client.clone(),
-                    )
-                    .and_then(move |payment| {
-                        JWTPayload::new(None, Some(auth_client), payment.expires_at)
-                            .encode(&state.jwt_private)
-                            .map_err(|e| Error::from(e))
-                            .into_future()
-                            .then(move |res| {
-                                res.and_then(|auth_token| {
-                                    Ok(Json(json!({
-                                        "payment": payment.export(),
-                                        "store": {
-                                            "name": store.name,
-                                            "description": store.description
-                                        },
-                                        "token": auth_token,
-                                    })))
-                                })
+            let auth_client = AuthClient::new(client_token);
+
+            let mut payload = PaymentPayload::new();
+            payload.store_id = Some(auth_client.store_id);
+            payload.created_by = Some(auth_client.id);
+            payload.fiat = Some(checkout.fiat);
+            payload.price = Some(checkout.price);
+            payload.crypto = Some(params.crypto);
+            payload.identifier = checkout.identifier;
+            payload.signed = Some(checkout.signed);
+            payload.metadata = checkout.metadata;
+            payload.customer_email = params.customer_email;
+
+            Box::new(
+                create_payment(&state, &store, payload).and_then(move |payment| {
+                    JWTPayload::new(None, Some(auth_client), payment.expires_at)
+                        .encode(&state.jwt_private)
+                        .map_err(|e| Error::from(e))
+                        .into_future()
+                        .then(move |res| {
+                            res.and_then(|auth_token| {
+                                Ok(Json(json!({
+                                    "payment": payment.export(),
+                                    "uri": payment.uri(Some(&store.name)),
+                                    "store": {
+                                        "name": store.name,
+                                        "description": store.description
+                                    },
+                                    "token": auth_token,
+                                })))
                             })
-                    }),
-                )
-            },
-        )
+                        })
+                }),
+            )
+        },
+    )
 }
 
 fn validate_client(payment: &Payment, client: &AuthClient) -> Result<bool, Error> {
@@ -134,40 +258,24 @@ pub fn get_status(
                 .and_then(move |_| future::ok(payment))
         })
         .and_then(move |payment| {
-            let block_height_future: Box<Future<Item = U128, Error = Error>> = match payment.crypto
-            {
-                Crypto::Btc => Box::new(
-                    BtcBlockchainStatus::find(payment.btc_network.unwrap(), &state.postgres)
-                        .from_err()
-                        .map(move |status| status.block_height),
-                ),
-                Crypto::Eth => Box::new(
-                    EthBlockchainStatus::find(payment.eth_network.unwrap(), &state.postgres)
-                        .from_err()
-                        .map(move |status| status.block_height),
-                ),
-            };
-
-            block_height_future.and_then(move |block_height| {
-                let mut remaining_confirmations = U128::from(payment.confirmations_required);
+            services::payments::get_block_height(&payment, &state.postgres)
+                .map(move |block_height| Json(payment.export_status(block_height)))
+        })
+}
 
-                if payment.status == PaymentStatus::Paid && payment.confirmations_required == 0 {
-                    remaining_confirmations = U128::from(0);
-                }
+#[derive(Debug, Deserialize)]
+pub struct StatusSocketParams {
+    pub token: String,
+}
 
-                if let Some(block_height_required) = payment.block_height_required {
-                    if block_height_required < block_height {
-                        remaining_confirmations = U128::from(0);
-                    } else {
-                        remaining_confirmations = block_height_required - block_height;
-                    }
-                }
+/// Upgrades to a websocket which pushes the same body as `get_status` every time it changes.
+/// Browsers can't set headers on websocket requests, so the payment JWT comes in the query.
+pub fn status_socket(req: &HttpRequest<AppState>) -> Result<HttpResponse, ActixError> {
+    let id = Path::<Uuid>::extract(req)?.into_inner();
+    let params = Query::<StatusSocketParams>::extract(req)?;
 
-                future::ok(Json(json!({
-                    "status": payment.status,
-                    "confirmations_required": payment.confirmations_required,
-                    "remaining_confirmations": remaining_confirmations,
-                })))
-            })
-        })
+    match JWTPayload::decode(&params.token, &req.state().jwt_public)?.client {
+        Some(client) => ws::start(req, StatusSocket::new(id, client)),
+        None => Err(error::ErrorUnauthorized("invalid authorization token")),
+    }
 }
@@ -0,0 +1,37 @@
+use actix_web::{Json, State};
+use futures::future::Future;
+use serde_json::Value;
+use uuid::Uuid;
+
+use auth::Actor;
+use services::{self, Error};
+use state::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct TokenParams {
+    pub token: Uuid,
+}
+
+pub fn confirm(
+    (state, params, actor): (State<AppState>, Json<TokenParams>, Actor),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    services::payout_address_changes::confirm(
+        params.into_inner().token,
+        services::payout_address_changes::delay(&state.config),
+        actor,
+        state.mailer.clone(),
+        &state.postgres,
+        state.config.web_client_url.clone(),
+        state.config.mail_sender.clone(),
+    )
+    .then(|res| res.and_then(|change| Ok(Json(change.export()))))
+}
+
+/// Needs nothing but the token from the email, so the owner can stop a change they didn't ask
+/// for even without access to their account.
+pub fn cancel(
+    (state, params, actor): (State<AppState>, Json<TokenParams>, Actor),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    services::payout_address_changes::cancel(params.into_inner().token, actor, &state.postgres)
+        .then(|res| res.and_then(|change| Ok(Json(change.export()))))
+}
@@ -0,0 +1,78 @@
+use actix_web::{Json, Path, State};
+use futures::future::{err, Future};
+use serde_json::Value;
+use uuid::Uuid;
+
+use auth::{AuthUser, JWTPayload};
+use controllers::auth::signed_in;
+use services::{self, Error};
+use state::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshParams {
+    pub refresh_token: Uuid,
+}
+
+/// Hands out a new access token for the session. The response holds the session's next refresh
+/// token, the one sent can't be used again.
+pub fn refresh(
+    (state, params): (State<AppState>, Json<RefreshParams>),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let postgres = state.postgres.clone();
+
+    services::sessions::refresh(
+        params.into_inner().refresh_token,
+        &state.postgres,
+        state.jwt_private.clone(),
+    )
+    .and_then(move |(token, session)| {
+        services::users::get(session.user_id, &postgres)
+            .map(move |user| signed_in(token, session, user))
+    })
+}
+
+pub fn list(
+    (state, token): (State<AppState>, JWTPayload),
+) -> Box<Future<Item = Json<Value>, Error = Error>> {
+    let user = match token.user {
+        Some(user) => user,
+        None => return Box::new(err(Error::InvalidSession)),
+    };
+    let current = token.sid;
+
+    Box::new(
+        services::sessions::list(user.id, &state.postgres).map(move |sessions| {
+            let exported: Vec<Value> = sessions
+                .iter()
+                .map(|session| {
+                    let mut exported = session.export();
+                    exported["current"] = json!(Some(session.id) == current);
+                    exported
+                })
+                .collect();
+
+            Json(json!({ "sessions": exported }))
+        }),
+    )
+}
+
+/// Signs a device out. Access tokens already issued for it stop working right away.
+pub fn delete(
+    (state, path, user): (State<AppState>, Path<Uuid>, AuthUser),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    services::sessions::revoke(path.into_inner(), user.id, &state.postgres)
+        .map(|res| Json(json!({ "revoked": res })))
+}
+
+/// Signs out of the session the request's token was issued for.
+pub fn logout(
+    (state, token): (State<AppState>, JWTPayload),
+) -> Box<Future<Item = Json<Value>, Error = Error>> {
+    match (token.user, token.sid) {
+        (Some(user), Some(id)) => Box::new(
+            services::sessions::revoke(id, user.id, &state.postgres)
+                .map(|res| Json(json!({ "revoked": res }))),
+        ),
+        _ => Box::new(err(Error::InvalidSession)),
+    }
+}
@@ -0,0 +1,113 @@
+use actix_web::{Json, Path, State};
+use futures::future::Future;
+use serde_json::Value;
+use uuid::Uuid;
+
+use auth::{permissions::Permission, Actor, AuthUser};
+use services::{self, store_memberships::authorize, Error};
+use state::AppState;
+use types::Role;
+
+#[derive(Debug, Deserialize)]
+pub struct InviteParams {
+    pub email: String,
+    pub role: Role,
+}
+
+pub fn invite(
+    (state, path, params, user, actor): (
+        State<AppState>,
+        Path<Uuid>,
+        Json<InviteParams>,
+        AuthUser,
+        Actor,
+    ),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let id = path.into_inner();
+    let InviteParams { email, role } = params.into_inner();
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ManageMembers, &state.postgres).and_then(move |_| {
+            services::store_memberships::invite(
+                store,
+                email,
+                role,
+                actor,
+                state.mailer.clone(),
+                &state.postgres,
+                state.config.web_client_url.clone(),
+                state.config.mail_sender.clone(),
+            )
+            .map(|membership| Json(membership.export()))
+        })
+    })
+}
+
+pub fn list(
+    (state, path, user): (State<AppState>, Path<Uuid>, AuthUser),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let id = path.into_inner();
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ManageMembers, &state.postgres).and_then(move |_| {
+            services::store_memberships::list(id, &state.postgres).map(|memberships| {
+                let exported: Vec<Value> = memberships
+                    .iter()
+                    .map(|membership| membership.export())
+                    .collect();
+
+                Json(json!({ "members": exported }))
+            })
+        })
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PatchParams {
+    pub role: Role,
+}
+
+pub fn patch(
+    (state, path, params, user, actor): (
+        State<AppState>,
+        Path<(Uuid, Uuid)>,
+        Json<PatchParams>,
+        AuthUser,
+        Actor,
+    ),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let (id, member_id) = path.into_inner();
+    let role = params.into_inner().role;
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ManageMembers, &state.postgres).and_then(move |_| {
+            services::store_memberships::update_role(member_id, id, role, actor, &state.postgres)
+                .map(|membership| Json(membership.export()))
+        })
+    })
+}
+
+pub fn delete(
+    (state, path, user, actor): (State<AppState>, Path<(Uuid, Uuid)>, AuthUser, Actor),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let (id, member_id) = path.into_inner();
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ManageMembers, &state.postgres).and_then(move |_| {
+            services::store_memberships::remove(member_id, id, actor, &state.postgres)
+                .map(|res| Json(json!({ "deleted": res })))
+        })
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptParams {
+    pub token: Uuid,
+}
+
+pub fn accept(
+    (state, params, user, actor): (State<AppState>, Json<AcceptParams>, AuthUser, Actor),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    services::store_memberships::accept(params.into_inner().token, user.id, actor, &state.postgres)
+        .map(|membership| Json(membership.export()))
+}
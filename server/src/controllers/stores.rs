@@ -1,28 +1,44 @@
-use actix_web::{Json, Path, Query, State};
-use futures::future::{Future, IntoFuture};
+use std::str::FromStr;
+
+use actix_web::{http, HttpResponse, Json, Path, Query, State};
+use bigdecimal::BigDecimal;
+use chrono::{prelude::*, Duration};
+use futures::future::{err, ok, Future, IntoFuture};
 use serde_json::Value;
 use uuid::Uuid;
 
-use auth::AuthUser;
-use core::store::{Store, StorePayload};
-use services::{self, Error};
+use auth::{permissions::Permission, Actor, AuthUser, IdempotencyKey, TwoFactorCode};
+use core::{
+    payout_address_change::PayoutAddressChangePayload,
+    store::{Store, StorePayload},
+};
+use hd_keyring::Language;
+use services::{self, exports::Format, stats::Bucket, store_memberships::authorize, Error};
 use state::AppState;
 use types::{
-    bitcoin::{Address as BtcAddress, Network as BtcNetwork},
-    H160,
+    bitcoin::{Address as BtcAddress, AddressFormat, Network as BtcNetwork},
+    currency::Fiat,
+    PaymentStatus, H160,
 };
 
 const LIMIT: i64 = 15;
 const OFFSET: i64 = 0;
+const STATS_DAYS: i64 = 30;
+const MAX_STATS_DAYS: i64 = 731;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateParams {
     pub name: String,
     pub description: String,
+    pub btc_xpub: Option<String>,
+    pub eth_xpub: Option<String>,
+    pub mnemonic_language: Option<String>,
+    pub passphrase: Option<String>,
+    pub btc_address_format: Option<AddressFormat>,
 }
 
 pub fn create(
-    (state, params, user): (State<AppState>, Json<CreateParams>, AuthUser),
+    (state, params, user, actor): (State<AppState>, Json<CreateParams>, AuthUser, Actor),
 ) -> impl Future<Item = Json<Value>, Error = Error> {
     let mut params = params.into_inner();
 
@@ -30,10 +46,20 @@ pub fn create(
         params.name = String::from("My Store");
     }
 
+    let passphrase = params.passphrase.take();
+    let language = params
+        .mnemonic_language
+        .as_ref()
+        .map_or(Ok(Language::English), |language| language.parse())
+        .map_err(|_| Error::BadRequest("unknown mnemonic_language"));
+
     let mut payload = StorePayload::new();
     payload.name = Some(params.name);
     payload.description = Some(params.description);
     payload.owner_id = Some(user.id);
+    payload.btc_xpub = params.btc_xpub;
+    payload.eth_xpub = params.eth_xpub;
+    payload.btc_address_format = params.btc_address_format;
     payload.eth_confirmations_required = Some(Some(1));
     payload.btc_confirmations_required = Some(Some(1));
 
@@ -42,7 +68,19 @@ pub fn create(
         .btc_config
         .map_or(BtcNetwork::Test, |config| config.network);
 
-    services::stores::create(payload, btc_network, &state.postgres)
+    language
+        .into_future()
+        .and_then(move |language| {
+            services::stores::create(
+                payload,
+                language,
+                passphrase,
+                btc_network,
+                actor,
+                &state.keychain,
+                &state.postgres,
+            )
+        })
         .then(|res| res.and_then(|store| Ok(Json(store.export()))))
 }
 
@@ -54,31 +92,137 @@ pub struct PatchParams {
     pub eth_confirmations_required: Option<i32>,
     pub btc_payout_addresses: Option<Vec<BtcAddress>>,
     pub btc_confirmations_required: Option<i32>,
+    pub success_url: Option<String>,
+    /// Percentages of each payout going to the payout address at the same position. Without
+    /// splits payouts rotate through the payout addresses.
+    pub eth_payout_splits: Option<Vec<i32>>,
+    pub btc_payout_splits: Option<Vec<i32>>,
+    /// Payments of at least this much get the owner an email. Zero turns the emails off.
+    pub large_payment_threshold: Option<BigDecimal>,
+    /// Required with a threshold, payments in other fiat currencies aren't compared with it.
+    pub large_payment_threshold_fiat: Option<Fiat>,
+    pub daily_summary: Option<bool>,
 }
 
-fn validate_store_owner(store: &Store, user: &AuthUser) -> Result<bool, Error> {
-    if store.owner_id != user.id {
-        return Err(Error::InvalidRequestAccount);
+fn validate_splits<T>(addresses: &Option<Vec<T>>, splits: &Option<Vec<i32>>) -> Result<(), Error> {
+    let splits = match splits {
+        Some(splits) => splits,
+        None => return Ok(()),
+    };
+
+    match addresses {
+        Some(addresses) if addresses.len() == splits.len() => (),
+        Some(_) => {
+            return Err(Error::BadRequest(
+                "payout splits must match the payout addresses",
+            ))
+        }
+        None => {
+            return Err(Error::BadRequest(
+                "payout splits must be given along with the payout addresses",
+            ))
+        }
+    }
+
+    if splits.iter().any(|split| *split <= 0) || splits.iter().sum::<i32>() != 100 {
+        return Err(Error::BadRequest(
+            "payout splits must be positive and add up to 100",
+        ));
     }
 
-    Ok(true)
+    Ok(())
 }
 
 pub fn patch(
-    (state, path, params, user): (State<AppState>, Path<Uuid>, Json<PatchParams>, AuthUser),
+    (state, path, params, user, code, actor): (
+        State<AppState>,
+        Path<Uuid>,
+        Json<PatchParams>,
+        AuthUser,
+        TwoFactorCode,
+        Actor,
+    ),
 ) -> Box<Future<Item = Json<Value>, Error = Error>> {
     let id = path.into_inner();
     let mut params = params.into_inner();
+    let rate_limiter = state.rate_limiter.clone();
+    let keychain = state.keychain.clone();
+    let postgres = state.postgres.clone();
+
+    if let Err(e) = validate_splits(&params.eth_payout_addresses, &params.eth_payout_splits)
+        .and_then(|_| validate_splits(&params.btc_payout_addresses, &params.btc_payout_splits))
+    {
+        return Box::new(err(e));
+    }
+
+    // Redirecting payouts is what a stolen session is after, so it takes the second factor. New
+    // payout addresses also only take effect once confirmed by email and after a cool-down, see
+    // `services::payout_address_changes`.
+    let mut change = PayoutAddressChangePayload::new(id);
+    change.eth_payout_addresses = params.eth_payout_addresses.take();
+    change.eth_payout_splits = params.eth_payout_splits.take();
+    change.btc_payout_addresses = params.btc_payout_addresses.take();
+    change.btc_payout_splits = params.btc_payout_splits.take();
+
+    let changes_payout_addresses =
+        change.eth_payout_addresses.is_some() || change.btc_payout_addresses.is_some();
+
+    let clears_threshold = params
+        .large_payment_threshold
+        .as_ref()
+        .map_or(false, |threshold| {
+            *threshold <= BigDecimal::from_str("0").unwrap()
+        });
+
+    if params.large_payment_threshold.is_some() != params.large_payment_threshold_fiat.is_some()
+        && !clears_threshold
+    {
+        return Box::new(err(Error::BadRequest(
+            "large_payment_threshold and large_payment_threshold_fiat have to be set together",
+        )));
+    }
 
     if params.name.is_some() && params.name.clone().unwrap().len() == 0 {
         params.name = Some(String::from("My Store"));
     }
 
+    if let Some(ref success_url) = params.success_url {
+        if success_url.len() > 0
+            && !success_url.starts_with("https://")
+            && !success_url.starts_with("http://")
+        {
+            return Box::new(err(Error::BadRequest("success_url must be an http(s) URL")));
+        }
+    }
+
     Box::new(
         services::stores::get(id, &state.postgres).and_then(move |store| {
-            validate_store_owner(&store, &user)
-                .into_future()
-                .and_then(move |_| {
+            authorize(&store, user.id, Permission::ManageStore, &state.postgres)
+                .and_then(move |_| -> Box<Future<Item = Store, Error = Error>> {
+                    if !changes_payout_addresses {
+                        return Box::new(ok(store));
+                    }
+
+                    Box::new(
+                        authorize(
+                            &store,
+                            user.id,
+                            Permission::ManagePayoutAddresses,
+                            &postgres,
+                        )
+                        .and_then(move |_| {
+                            services::users::verify_two_factor(
+                                user.id,
+                                code.0,
+                                &rate_limiter,
+                                &keychain,
+                                &postgres,
+                            )
+                        })
+                        .map(move |_| store),
+                    )
+                })
+                .and_then(move |store| {
                     let mut payload = StorePayload::new();
 
                     if let Some(name) = params.name {
@@ -89,29 +233,91 @@ pub fn patch(
                         payload.description = Some(description);
                     }
 
-                    if let Some(eth_payout_addresses) = params.eth_payout_addresses {
-                        payload.eth_payout_addresses = Some(Some(eth_payout_addresses));
-                    }
-
                     if let Some(eth_confirmations_required) = params.eth_confirmations_required {
                         payload.eth_confirmations_required = Some(Some(eth_confirmations_required));
                     }
 
-                    if let Some(btc_payout_address) = params.btc_payout_addresses {
-                        payload.btc_payout_addresses = Some(Some(btc_payout_address));
-                    }
-
                     if let Some(btc_confirmations_required) = params.btc_confirmations_required {
                         payload.btc_confirmations_required = Some(Some(btc_confirmations_required));
                     }
 
-                    services::stores::patch(id, payload, &state.postgres)
-                        .then(|res| res.and_then(|store| Ok(Json(store.export()))))
+                    if let Some(success_url) = params.success_url {
+                        if success_url.len() == 0 {
+                            payload.success_url = Some(None);
+                        } else {
+                            payload.success_url = Some(Some(success_url));
+                        }
+                    }
+
+                    if let Some(threshold) = params.large_payment_threshold {
+                        if clears_threshold {
+                            payload.large_payment_threshold = Some(None);
+                            payload.large_payment_threshold_fiat = Some(None);
+                        } else {
+                            payload.large_payment_threshold = Some(Some(threshold));
+                            payload.large_payment_threshold_fiat =
+                                Some(params.large_payment_threshold_fiat);
+                        }
+                    }
+
+                    if let Some(daily_summary) = params.daily_summary {
+                        payload.daily_summary = Some(daily_summary);
+                    }
+
+                    services::stores::patch(store, payload, actor.clone(), &state.postgres)
+                        .map(move |store| (state, store, actor))
                 })
+                .and_then(
+                    move |(state, store, actor)| -> Box<Future<Item = Json<Value>, Error = Error>> {
+                        let mut exported = store.export();
+
+                        if !changes_payout_addresses {
+                            return Box::new(ok(Json(exported)));
+                        }
+
+                        Box::new(
+                            services::payout_address_changes::request(
+                                store,
+                                change,
+                                actor,
+                                state.mailer.clone(),
+                                &state.postgres,
+                                state.config.web_client_url.clone(),
+                                state.config.mail_sender.clone(),
+                            )
+                            .map(move |change| {
+                                exported["pending_payout_address_change"] = change.export();
+                                Json(exported)
+                            }),
+                        )
+                    },
+                )
         }),
     )
 }
 
+pub fn payout_address_change(
+    (state, path, user): (State<AppState>, Path<Uuid>, AuthUser),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let id = path.into_inner();
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(
+            &store,
+            user.id,
+            Permission::ManagePayoutAddresses,
+            &state.postgres,
+        )
+        .and_then(move |_| {
+            services::payout_address_changes::pending(id, &state.postgres).map(|change| {
+                Json(json!({
+                    "pending_payout_address_change": change.map(|change| change.export()),
+                }))
+            })
+        })
+    })
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ListParams {
     pub limit: Option<i64>,
@@ -134,7 +340,7 @@ pub fn list(
         offset = _offset;
     };
 
-    services::stores::find_by_owner(user.id, limit, offset, &state.postgres).then(move |res| {
+    services::stores::find_by_member(user.id, limit, offset, &state.postgres).then(move |res| {
         res.and_then(|stores| {
             let exported: Vec<Value> = stores.into_iter().map(|store| store.export()).collect();
 
@@ -153,26 +359,408 @@ pub fn get(
     let id = path.into_inner();
 
     services::stores::get(id, &state.postgres).and_then(move |store| {
-        validate_store_owner(&store, &user)
-            .into_future()
-            .and_then(move |_| {
-                services::stores::get(id, &state.postgres)
-                    .then(|res| res.and_then(|store| Ok(Json(store.export()))))
-            })
+        authorize(&store, user.id, Permission::ReadStore, &state.postgres).map(move |role| {
+            let mut exported = store.export();
+            exported["role"] = json!(role);
+
+            Json(exported)
+        })
     })
 }
 
 pub fn delete(
-    (state, path, user): (State<AppState>, Path<Uuid>, AuthUser),
+    (state, path, user, code, actor): (State<AppState>, Path<Uuid>, AuthUser, TwoFactorCode, Actor),
 ) -> impl Future<Item = Json<Value>, Error = Error> {
     let id = path.into_inner();
 
     services::stores::get(id, &state.postgres).and_then(move |store| {
-        validate_store_owner(&store, &user)
-            .into_future()
+        authorize(&store, user.id, Permission::DeleteStore, &state.postgres)
             .and_then(move |_| {
-                services::stores::delete(id, &state.postgres)
+                services::users::verify_two_factor(
+                    user.id,
+                    code.0,
+                    &state.rate_limiter,
+                    &state.keychain,
+                    &state.postgres,
+                )
+                .map(move |_| (state, store))
+            })
+            .and_then(move |(state, store)| {
+                services::stores::delete(store, actor, &state.postgres)
                     .then(|res| res.and_then(|res| Ok(Json(json!({ "deleted": res })))))
             })
     })
 }
+
+pub fn rotate_key(
+    (state, path, user, actor): (State<AppState>, Path<Uuid>, AuthUser, Actor),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let id = path.into_inner();
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ManageStore, &state.postgres).and_then(move |_| {
+            services::stores::rotate_key(store, actor, &state.keychain, &state.postgres)
+                .then(|res| res.and_then(|store| Ok(Json(store.export()))))
+        })
+    })
+}
+
+/// Turns on signed checkouts, or replaces the secret they're signed with. The secret is only
+/// shown in this response.
+pub fn rotate_checkout_secret(
+    (state, path, user, actor): (State<AppState>, Path<Uuid>, AuthUser, Actor),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let id = path.into_inner();
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ManageStore, &state.postgres).and_then(move |_| {
+            services::stores::rotate_checkout_secret(store, actor, &state.keychain, &state.postgres)
+                .map(|(store, checkout_secret)| {
+                    Json(json!({
+                        "store": store.export(),
+                        "checkout_secret": checkout_secret,
+                    }))
+                })
+        })
+    })
+}
+
+pub fn remove_checkout_secret(
+    (state, path, user, code, actor): (State<AppState>, Path<Uuid>, AuthUser, TwoFactorCode, Actor),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let id = path.into_inner();
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ManageStore, &state.postgres)
+            .and_then(move |_| {
+                services::users::verify_two_factor(
+                    user.id,
+                    code.0,
+                    &state.rate_limiter,
+                    &state.keychain,
+                    &state.postgres,
+                )
+                .map(move |_| (state, store))
+            })
+            .and_then(move |(state, store)| {
+                services::stores::remove_checkout_secret(store, actor, &state.postgres)
+                    .map(|store| Json(store.export()))
+            })
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecoveryParams {
+    pub password: String,
+    pub passphrase: Option<String>,
+}
+
+pub fn recovery(
+    (state, path, params, user, code, actor): (
+        State<AppState>,
+        Path<Uuid>,
+        Json<RecoveryParams>,
+        AuthUser,
+        TwoFactorCode,
+        Actor,
+    ),
+) -> Box<Future<Item = Json<Value>, Error = Error>> {
+    let id = path.into_inner();
+    let RecoveryParams {
+        password,
+        passphrase,
+    } = params.into_inner();
+
+    if let Some(ref passphrase) = passphrase {
+        if passphrase.len() < 8 {
+            return Box::new(err(Error::BadRequest(
+                "passphrase must be at least 8 characters",
+            )));
+        }
+    }
+
+    let btc_network = state
+        .clone()
+        .btc_config
+        .map_or(BtcNetwork::Test, |config| config.network);
+    let user_id = user.id;
+
+    Box::new(
+        services::users::reauthenticate(user_id, password, &state.postgres)
+            .and_then(move |_| {
+                services::users::verify_two_factor(
+                    user_id,
+                    code.0,
+                    &state.rate_limiter,
+                    &state.keychain,
+                    &state.postgres,
+                )
+                .map(move |_| state)
+            })
+            .and_then(move |state| {
+                services::stores::get(id, &state.postgres).map(move |store| (state, store))
+            })
+            .and_then(move |(state, store)| {
+                authorize(&store, user.id, Permission::ExportRecovery, &state.postgres).and_then(
+                    move |_| {
+                        services::stores::export_recovery(
+                            store,
+                            passphrase,
+                            btc_network,
+                            actor,
+                            &state.keychain,
+                            &state.postgres,
+                        )
+                        .map(|export| Json(export))
+                    },
+                )
+            }),
+    )
+}
+
+/// Lists the store's payments, newest first.
+pub fn payments(
+    (state, path, params, user): (State<AppState>, Path<Uuid>, Query<ListParams>, AuthUser),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let id = path.into_inner();
+    let mut limit = LIMIT;
+    let mut offset = OFFSET;
+
+    if let Some(_limit) = params.limit {
+        if _limit < LIMIT {
+            limit = _limit;
+        }
+    };
+
+    if let Some(_offset) = params.offset {
+        offset = _offset;
+    };
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ReadPayments, &state.postgres).and_then(move |_| {
+            services::payments::find_by_store(id, limit, offset, &state.postgres).map(
+                move |payments| {
+                    let exported: Vec<Value> =
+                        payments.iter().map(|payment| payment.export()).collect();
+
+                    Json(json!({
+                        "payments": exported,
+                        "limit": limit,
+                        "offset": offset,
+                    }))
+                },
+            )
+        })
+    })
+}
+
+/// `from` and `to` are unix timestamps, by default the last 30 days are reported in daily buckets.
+#[derive(Debug, Deserialize)]
+pub struct StatsParams {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub bucket: Option<Bucket>,
+}
+
+/// Payment and payout figures of the store for its dashboard.
+pub fn stats(
+    (state, path, params, user): (State<AppState>, Path<Uuid>, Query<StatsParams>, AuthUser),
+) -> Box<Future<Item = Json<Value>, Error = Error>> {
+    let id = path.into_inner();
+    let bucket = params.bucket.unwrap_or(Bucket::Day);
+    let (from, to) = match stats_period(&params) {
+        Ok(period) => period,
+        Err(e) => return Box::new(err(e)),
+    };
+
+    Box::new(
+        services::stores::get(id, &state.postgres).and_then(move |store| {
+            authorize(&store, user.id, Permission::ReadPayments, &state.postgres).and_then(
+                move |_| {
+                    services::stats::store(id, from, to, bucket, &state.postgres)
+                        .map(|stats| Json(stats))
+                },
+            )
+        }),
+    )
+}
+
+fn parse_timestamp(secs: i64) -> Result<DateTime<Utc>, Error> {
+    NaiveDateTime::from_timestamp_opt(secs, 0)
+        .map(|time| DateTime::<Utc>::from_utc(time, Utc))
+        .ok_or(Error::BadRequest("invalid timestamp"))
+}
+
+fn stats_period(params: &StatsParams) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    let to = match params.to {
+        Some(to) => parse_timestamp(to)?,
+        None => Utc::now(),
+    };
+    let from = match params.from {
+        Some(from) => parse_timestamp(from)?,
+        None => to - Duration::days(STATS_DAYS),
+    };
+
+    if from >= to {
+        return Err(Error::BadRequest("from must be before to"));
+    }
+
+    if to - from > Duration::days(MAX_STATS_DAYS) {
+        return Err(Error::BadRequest("stats can cover at most two years"));
+    }
+
+    Ok((from, to))
+}
+
+/// `from` and `to` are unix timestamps, by default every payment is exported as CSV.
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    pub format: Option<Format>,
+    pub status: Option<PaymentStatus>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+/// Streams the store's payments along with their payouts, oldest first, for accounting.
+pub fn export_payments(
+    (state, path, params, user): (State<AppState>, Path<Uuid>, Query<ExportParams>, AuthUser),
+) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let id = path.into_inner();
+    let params = params.into_inner();
+    let format = params.format.unwrap_or(Format::Csv);
+    let status = params.status.clone();
+    let (from, to) = match export_period(&params) {
+        Ok(period) => period,
+        Err(e) => return Box::new(err(e)),
+    };
+
+    Box::new(
+        services::stores::get(id, &state.postgres).and_then(move |store| {
+            authorize(&store, user.id, Permission::ReadPayments, &state.postgres).map(move |_| {
+                let payments =
+                    services::exports::payments(id, status, from, to, format, &state.postgres);
+
+                HttpResponse::Ok()
+                    .content_type(format.content_type())
+                    .header(
+                        http::header::CONTENT_DISPOSITION,
+                        format!(
+                            "attachment; filename=\"payments-{}.{}\"",
+                            id,
+                            format.extension()
+                        ),
+                    )
+                    .streaming(payments)
+            })
+        }),
+    )
+}
+
+fn export_period(params: &ExportParams) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    let to = match params.to {
+        Some(to) => parse_timestamp(to)?,
+        None => Utc::now(),
+    };
+    let from = match params.from {
+        Some(from) => parse_timestamp(from)?,
+        None => Utc.timestamp(0, 0),
+    };
+
+    if from >= to {
+        return Err(Error::BadRequest("from must be before to"));
+    }
+
+    Ok((from, to))
+}
+
+/// Refunds a payment. Retries with the same `Idempotency-Key` get the first response back without
+/// another second factor.
+pub fn refund(
+    (state, path, user, code, actor, idempotency_key): (
+        State<AppState>,
+        Path<(Uuid, Uuid)>,
+        AuthUser,
+        TwoFactorCode,
+        Actor,
+        IdempotencyKey,
+    ),
+) -> Box<Future<Item = Json<Value>, Error = Error>> {
+    let (id, payment_id) = path.into_inner();
+
+    let fingerprint = match services::idempotency::fingerprint(&payment_id) {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => return Box::new(err(e)),
+    };
+    let postgres = state.postgres.clone();
+    let user_id = user.id;
+
+    Box::new(
+        services::stores::get(id, &state.postgres)
+            .and_then(move |store| {
+                authorize(&store, user_id, Permission::IssueRefunds, &state.postgres)
+                    .map(move |_| state)
+            })
+            .and_then(move |state| {
+                services::idempotency::run(
+                    format!("refunds:{}", id),
+                    idempotency_key.0,
+                    fingerprint,
+                    &postgres,
+                    move || {
+                        services::users::verify_two_factor(
+                            user_id,
+                            code.0,
+                            &state.rate_limiter,
+                            &state.keychain,
+                            &state.postgres,
+                        )
+                        .map(move |_| state)
+                        .and_then(move |state| {
+                            services::payments::refund(id, payment_id, actor, &state.postgres)
+                        })
+                        .map(|payout| json!(payout))
+                    },
+                )
+            })
+            .map(|res| Json(res)),
+    )
+}
+
+/// Lists the store's audit log, newest first. Only the owner may read it.
+pub fn audit(
+    (state, path, params, user): (State<AppState>, Path<Uuid>, Query<ListParams>, AuthUser),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let id = path.into_inner();
+    let mut limit = LIMIT;
+    let mut offset = OFFSET;
+
+    if let Some(_limit) = params.limit {
+        if _limit < LIMIT {
+            limit = _limit;
+        }
+    };
+
+    if let Some(_offset) = params.offset {
+        offset = _offset;
+    };
+
+    services::stores::get(id, &state.postgres).and_then(move |store| {
+        authorize(&store, user.id, Permission::ReadAuditLog, &state.postgres).and_then(move |_| {
+            services::audit::find_by_store(id, limit, offset, &state.postgres).map(move |entries| {
+                let exported: Vec<Value> = entries.iter().map(|entry| entry.export()).collect();
+
+                Json(json!({
+                    "entries": exported,
+                    "limit": limit,
+                    "offset": offset,
+                }))
+            })
+        })
+    })
+}
+
+pub fn jwks(
+    (state, path): (State<AppState>, Path<Uuid>),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    services::stores::jwks(path.into_inner(), &state.postgres).map(|jwks| Json(jwks))
+}
@@ -1,16 +1,17 @@
 use actix_web::{Json, State};
-use futures::future::{Future, IntoFuture};
+use futures::future::{err, Future, IntoFuture};
 use serde_json::Value;
 use uuid::Uuid;
 
 use auth::AuthClient;
-use core::payment::Payment;
+use core::{invoice::Invoice, payment::Payment};
 use services::{self, Error};
 use state::AppState;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateParams {
-    pub payment_id: Uuid,
+    pub payment_id: Option<Uuid>,
+    pub invoice_id: Option<Uuid>,
 }
 
 fn validate_client(payment: &Payment, client: &AuthClient) -> Result<bool, Error> {
@@ -21,24 +22,98 @@ fn validate_client(payment: &Payment, client: &AuthClient) -> Result<bool, Error
     Ok(true)
 }
 
-pub fn create(
-    (state, client, params): (State<AppState>, AuthClient, Json<CreateParams>),
-) -> impl Future<Item = Json<Value>, Error = Error> {
-    let params = params.into_inner();
+fn validate_invoice_client(invoice: &Invoice, client: &AuthClient) -> Result<bool, Error> {
+    if invoice.created_by != client.id {
+        return Err(Error::InvalidRequestAccount);
+    }
+
+    Ok(true)
+}
 
-    services::payments::get(params.payment_id, &state.postgres).and_then(move |payment| {
+fn create_for_payment(
+    state: State<AppState>,
+    client: AuthClient,
+    payment_id: Uuid,
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    services::payments::get(payment_id, &state.postgres).and_then(move |payment| {
         validate_client(&payment, &client)
             .into_future()
             .and_then(move |_| {
-                services::vouchers::create(payment.clone(), &state.postgres).then(move |res| {
-                    match res {
+                services::vouchers::create(payment.clone(), &state.keychain, &state.postgres).then(
+                    move |res| match res {
                         Ok(voucher) => Ok(Json(json!({
                             "status": payment.status,
                             "voucher": voucher,
                         }))),
                         Err(e) => Err(e),
-                    }
-                })
+                    },
+                )
             })
     })
 }
+
+/// Issues the voucher for whichever of the invoice's payments got settled.
+fn create_for_invoice(
+    state: State<AppState>,
+    client: AuthClient,
+    invoice_id: Uuid,
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    let postgres = state.postgres.clone();
+
+    services::invoices::get(invoice_id, &state.postgres)
+        .and_then(move |invoice| {
+            validate_invoice_client(&invoice, &client)
+                .into_future()
+                .and_then(move |_| invoice.payments(&postgres).from_err())
+        })
+        .and_then(
+            move |payments| match payments.into_iter().find(|payment| payment.is_settled()) {
+                Some(payment) => Ok(payment),
+                None => Err(Error::PaymentNotConfirmed),
+            },
+        )
+        .and_then(move |payment| {
+            services::vouchers::create(payment.clone(), &state.keychain, &state.postgres).map(
+                move |voucher| {
+                    Json(json!({
+                        "status": payment.status,
+                        "crypto": payment.crypto,
+                        "voucher": voucher,
+                    }))
+                },
+            )
+        })
+}
+
+pub fn create(
+    (state, client, params): (State<AppState>, AuthClient, Json<CreateParams>),
+) -> Box<Future<Item = Json<Value>, Error = Error>> {
+    let params = params.into_inner();
+
+    match (params.payment_id, params.invoice_id) {
+        (Some(payment_id), None) => Box::new(create_for_payment(state, client, payment_id)),
+        (None, Some(invoice_id)) => Box::new(create_for_invoice(state, client, invoice_id)),
+        _ => Box::new(err(Error::BadRequest(
+            "either payment_id or invoice_id is required",
+        ))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyParams {
+    pub voucher: String,
+}
+
+pub fn verify(
+    (state, params): (State<AppState>, Json<VerifyParams>),
+) -> impl Future<Item = Json<Value>, Error = Error> {
+    services::vouchers::verify(params.into_inner().voucher, &state.postgres).map(|voucher| {
+        match voucher {
+            Some(voucher) => Json(json!({
+                "valid": true,
+                "voucher": voucher,
+            })),
+            None => Json(json!({ "valid": false })),
+        }
+    })
+}
@@ -0,0 +1,98 @@
+mod session;
+
+pub use self::session::StatusSocket;
+
+use std::collections::HashMap;
+
+use actix::prelude::*;
+use uuid::Uuid;
+
+use core::db::redis::Event;
+
+pub type HubAddr = Addr<Hub>;
+
+/// Fans events received from Redis out to the sessions listening on each channel, so that a
+/// single subscription serves every open checkout.
+pub struct Hub {
+    channels: HashMap<String, HashMap<Uuid, Recipient<Event>>>,
+}
+
+impl Hub {
+    pub fn new() -> Self {
+        Hub {
+            channels: HashMap::new(),
+        }
+    }
+}
+
+impl Actor for Hub {
+    type Context = Context<Self>;
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Join {
+    pub id: Uuid,
+    pub channels: Vec<String>,
+    pub recipient: Recipient<Event>,
+}
+
+impl Handler<Join> for Hub {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        Join {
+            id,
+            channels,
+            recipient,
+        }: Join,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        for channel in channels {
+            self.channels
+                .entry(channel)
+                .or_insert_with(HashMap::new)
+                .insert(id, recipient.clone());
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Leave {
+    pub id: Uuid,
+    pub channels: Vec<String>,
+}
+
+impl Handler<Leave> for Hub {
+    type Result = ();
+
+    fn handle(&mut self, Leave { id, channels }: Leave, _: &mut Self::Context) -> Self::Result {
+        for channel in channels {
+            let is_empty = match self.channels.get_mut(&channel) {
+                Some(sessions) => {
+                    sessions.remove(&id);
+                    sessions.is_empty()
+                }
+                None => false,
+            };
+
+            if is_empty {
+                self.channels.remove(&channel);
+            }
+        }
+    }
+}
+
+impl Handler<Event> for Hub {
+    type Result = ();
+
+    fn handle(&mut self, event: Event, _: &mut Self::Context) -> Self::Result {
+        if let Some(sessions) = self.channels.get(&event.channel) {
+            for recipient in sessions.values() {
+                let _ = recipient.do_send(event.clone());
+            }
+        }
+    }
+}
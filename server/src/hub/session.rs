@@ -0,0 +1,150 @@
+use actix::{
+    fut::{self, wrap_future, ActorFuture},
+    prelude::*,
+};
+use actix_web::ws;
+use futures::Future;
+use serde_json;
+use uuid::Uuid;
+
+use auth::AuthClient;
+use core::{
+    bitcoin::BlockchainStatus as BtcBlockchainStatus,
+    db::redis::Event,
+    ethereum::BlockchainStatus as EthBlockchainStatus,
+    payment::{Payment, StatusEvent},
+};
+use hub::{Join, Leave};
+use services;
+use state::AppState;
+use types::{currency::Crypto, U128};
+
+/// Websocket session watching the status of a single payment. The payment and the block height
+/// are read from Postgres once when the socket opens and then kept up to date from the hub.
+pub struct StatusSocket {
+    id: Uuid,
+    payment_id: Uuid,
+    client: AuthClient,
+    payment: Option<Payment>,
+    block_height: U128,
+    last_sent: Option<String>,
+}
+
+impl StatusSocket {
+    pub fn new(payment_id: Uuid, client: AuthClient) -> Self {
+        StatusSocket {
+            id: Uuid::new_v4(),
+            payment_id,
+            client,
+            payment: None,
+            block_height: U128::from(0),
+            last_sent: None,
+        }
+    }
+
+    fn channels(&self) -> Vec<String> {
+        match self.payment {
+            Some(ref payment) => {
+                let blocks_channel = match payment.crypto {
+                    Crypto::Btc => BtcBlockchainStatus::channel(payment.btc_network.unwrap()),
+                    Crypto::Eth => EthBlockchainStatus::channel(payment.eth_network.unwrap()),
+                };
+
+                vec![Payment::channel(payment.id), blocks_channel]
+            }
+            None => vec![],
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ws::WebsocketContext<Self, AppState>) {
+        let status = match self.payment {
+            Some(ref payment) => format!("{}", payment.export_status(self.block_height)),
+            None => return,
+        };
+
+        if self.last_sent.as_ref() != Some(&status) {
+            ctx.text(status.clone());
+            self.last_sent = Some(status);
+        }
+    }
+}
+
+impl Actor for StatusSocket {
+    type Context = ws::WebsocketContext<Self, AppState>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let postgres = ctx.state().postgres.clone();
+
+        let load = services::payments::get(self.payment_id, &postgres).and_then(move |payment| {
+            services::payments::get_block_height(&payment, &postgres)
+                .map(move |block_height| (payment, block_height))
+        });
+
+        ctx.spawn(wrap_future::<_, Self>(load).then(|res, socket, ctx| {
+            match res {
+                Ok((ref payment, _)) if payment.created_by != socket.client.id => {
+                    ctx.close(Some(ws::CloseCode::Policy.into()));
+                    ctx.stop();
+                }
+                Ok((payment, block_height)) => {
+                    socket.payment = Some(payment);
+                    socket.block_height = block_height;
+
+                    ctx.state().hub.do_send(Join {
+                        id: socket.id,
+                        channels: socket.channels(),
+                        recipient: ctx.address().recipient(),
+                    });
+
+                    socket.push(ctx);
+                }
+                Err(_) => {
+                    ctx.close(Some(ws::CloseCode::Error.into()));
+                    ctx.stop();
+                }
+            }
+
+            fut::ok(())
+        }));
+    }
+
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        ctx.state().hub.do_send(Leave {
+            id: self.id,
+            channels: self.channels(),
+        });
+    }
+}
+
+impl Handler<Event> for StatusSocket {
+    type Result = ();
+
+    fn handle(&mut self, event: Event, ctx: &mut Self::Context) -> Self::Result {
+        if event.channel == Payment::channel(self.payment_id) {
+            match (
+                serde_json::from_str::<StatusEvent>(&event.value),
+                self.payment.as_mut(),
+            ) {
+                (Ok(status_event), Some(payment)) => payment.apply(status_event),
+                _ => return,
+            }
+        } else {
+            match U128::from_dec_str(&event.value) {
+                Ok(block_height) => self.block_height = block_height,
+                Err(_) => return,
+            }
+        }
+
+        self.push(ctx);
+    }
+}
+
+impl StreamHandler<ws::Message, ws::ProtocolError> for StatusSocket {
+    fn handle(&mut self, msg: ws::Message, ctx: &mut Self::Context) {
+        match msg {
+            ws::Message::Ping(msg) => ctx.pong(&msg),
+            ws::Message::Close(_) => ctx.stop(),
+            _ => (),
+        }
+    }
+}
@@ -4,6 +4,7 @@ extern crate actix;
 extern crate actix_web;
 extern crate base64;
 extern crate bigdecimal;
+extern crate bytes;
 extern crate chrono;
 extern crate data_encoding;
 extern crate diesel;
@@ -14,10 +15,12 @@ extern crate futures;
 extern crate jsonwebtoken as jwt;
 extern crate lettre;
 extern crate lettre_email;
+#[macro_use]
 extern crate log;
 extern crate native_tls;
 extern crate num_cpus;
 extern crate openssl;
+extern crate qrcode;
 extern crate ring;
 extern crate rustc_hex;
 extern crate serde;
@@ -36,9 +39,13 @@ extern crate types;
 
 mod auth;
 mod controllers;
+mod hub;
 mod mailer;
+mod rate_limit;
+mod scheduler;
 mod services;
 mod state;
+mod templates;
 
 use std::fs;
 
@@ -46,11 +53,20 @@ use actix::prelude::*;
 use actix_web::{http, middleware, server, App};
 
 use config::Config;
-use core::db::postgres;
+use core::{
+    crypto::Keychain,
+    db::{
+        postgres,
+        redis::{self, RedisExecutor, RedisSubscriber, Subscribe},
+    },
+};
 use currency_api_client::Client as CurrencyApiClient;
+use hub::Hub;
 use mailer::Mailer;
+use rate_limit::{RateLimit, RateLimiter};
+use scheduler::Scheduler;
 
-pub fn run(postgres: postgres::PgExecutorAddr, config: Config) {
+pub fn run(postgres: postgres::PgExecutorAddr, config: Config, keychain: Keychain) {
     let smtp_config = config.smtp.clone();
     let mailer = SyncArbiter::start(num_cpus::get() * 1, move || {
         Mailer(mailer::init_mailer(
@@ -66,6 +82,28 @@ pub fn run(postgres: postgres::PgExecutorAddr, config: Config) {
     let currency_api_client =
         Arbiter::start(move |_| CurrencyApiClient::new(&currency_api, &currency_api_key));
 
+    let hub = Arbiter::start(|_| Hub::new());
+
+    let scheduler_postgres = postgres.clone();
+    let scheduler_mailer = mailer.clone();
+    let mail_sender = config.server.mail_sender.clone();
+    Arbiter::start(move |_| Scheduler::new(scheduler_postgres, scheduler_mailer, mail_sender));
+
+    let redis_url = config.redis.clone();
+    let redis_subscriber = SyncArbiter::start(1, move || RedisSubscriber::new(&redis_url));
+    redis_subscriber.do_send(Subscribe {
+        patterns: vec![
+            "payments:*".to_owned(),
+            "btc_blocks:*".to_owned(),
+            "eth_blocks:*".to_owned(),
+        ],
+        recipient: hub.clone().recipient(),
+    });
+
+    let redis_pool = redis::init_pool(&config.redis);
+    let redis = SyncArbiter::start(2, move || RedisExecutor(redis_pool.clone()));
+    let rate_limiter = RateLimiter::new(redis, config.server.rate_limits.clone());
+
     let host = config.server.host.clone();
     let port = config.server.port.clone();
 
@@ -81,8 +119,12 @@ pub fn run(postgres: postgres::PgExecutorAddr, config: Config) {
             btc_config: config.bitcoin.clone(),
             eth_config: config.ethereum.clone(),
             currency_api_client: currency_api_client.clone(),
+            hub: hub.clone(),
+            keychain: keychain.clone(),
+            rate_limiter: rate_limiter.clone(),
         })
         .middleware(middleware::Logger::default())
+        .middleware(RateLimit)
         .configure(|app| {
             middleware::cors::Cors::for_app(app)
                 .max_age(3600)
@@ -109,10 +151,42 @@ pub fn run(postgres: postgres::PgExecutorAddr, config: Config) {
                     r.method(http::Method::POST)
                         .with_async(controllers::auth::change_password);
                 })
+                .resource("/refresh", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::sessions::refresh);
+                })
+                .resource("/logout", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::sessions::logout);
+                })
+                .resource("/sessions", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::sessions::list);
+                })
+                .resource("/sessions/{id}", |r| {
+                    r.method(http::Method::DELETE)
+                        .with_async(controllers::sessions::delete);
+                })
                 .resource("/profile", |r| {
                     r.method(http::Method::GET)
                         .with_async(controllers::auth::profile);
                 })
+                .resource("/audit", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::auth::audit);
+                })
+                .resource("/two_factor", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::auth::enroll_two_factor);
+                })
+                .resource("/two_factor/confirm", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::auth::confirm_two_factor);
+                })
+                .resource("/two_factor/disable", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::auth::disable_two_factor);
+                })
                 .resource("/users/{id}", |r| {
                     r.method(http::Method::DELETE)
                         .with_async(controllers::auth::delete);
@@ -143,6 +217,82 @@ pub fn run(postgres: postgres::PgExecutorAddr, config: Config) {
                     r.method(http::Method::DELETE)
                         .with_async(controllers::stores::delete);
                 })
+                .resource("/stores/{id}/rotate_key", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::stores::rotate_key);
+                })
+                .resource("/stores/{id}/checkout_secret", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::stores::rotate_checkout_secret);
+                    r.method(http::Method::DELETE)
+                        .with_async(controllers::stores::remove_checkout_secret);
+                })
+                .resource("/stores/{id}/recovery", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::stores::recovery);
+                })
+                .resource("/stores/{id}/payout_address_change", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::stores::payout_address_change);
+                })
+                .resource("/stores/{id}/payments", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::stores::payments);
+                })
+                .resource("/stores/{id}/payments/export", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::stores::export_payments);
+                })
+                .resource("/stores/{id}/stats", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::stores::stats);
+                })
+                .resource("/stores/{id}/payments/{payment_id}/refund", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::stores::refund);
+                })
+                .resource("/stores/{id}/audit", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::stores::audit);
+                })
+                .resource("/stores/{id}/email_templates", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::email_templates::list);
+                })
+                .resource("/stores/{id}/email_templates/{kind}", |r| {
+                    r.method(http::Method::PUT)
+                        .with_async(controllers::email_templates::update);
+                    r.method(http::Method::DELETE)
+                        .with_async(controllers::email_templates::delete);
+                })
+                .resource("/stores/{id}/members", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::store_memberships::list);
+                    r.method(http::Method::POST)
+                        .with_async(controllers::store_memberships::invite);
+                })
+                .resource("/stores/{id}/members/{member_id}", |r| {
+                    r.method(http::Method::PATCH)
+                        .with_async(controllers::store_memberships::patch);
+                    r.method(http::Method::DELETE)
+                        .with_async(controllers::store_memberships::delete);
+                })
+                .resource("/stores/{id}/jwks.json", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::stores::jwks);
+                })
+                .resource("/store_memberships/accept", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::store_memberships::accept);
+                })
+                .resource("/payout_address_changes/confirm", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::payout_address_changes::confirm);
+                })
+                .resource("/payout_address_changes/cancel", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::payout_address_changes::cancel);
+                })
                 .resource("/payments", |r| {
                     r.method(http::Method::POST)
                         .with_async(controllers::payments::create);
@@ -151,10 +301,30 @@ pub fn run(postgres: postgres::PgExecutorAddr, config: Config) {
                     r.method(http::Method::GET)
                         .with_async(controllers::payments::get_status)
                 })
+                .resource("/payments/{id}/status/ws", |r| {
+                    r.method(http::Method::GET)
+                        .f(controllers::payments::status_socket)
+                })
+                .resource("/invoices", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::invoices::create);
+                })
+                .resource("/invoices/{id}/payments", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::invoices::create_payment_option);
+                })
+                .resource("/invoice/{id}", |r| {
+                    r.method(http::Method::GET)
+                        .with_async(controllers::checkout::invoice);
+                })
                 .resource("/vouchers", |r| {
                     r.method(http::Method::POST)
                         .with_async(controllers::vouchers::create);
                 })
+                .resource("/vouchers/verify", |r| {
+                    r.method(http::Method::POST)
+                        .with_async(controllers::vouchers::verify);
+                })
                 .register()
         })
     })
@@ -1,5 +1,6 @@
 mod errors;
 pub use self::errors::Error;
+pub mod templates;
 
 use std::time::Duration;
 
@@ -0,0 +1,307 @@
+//! The emails stores send about payments, as templates with `{{placeholder}}`s. Stores can
+//! replace the defaults with their own, see `core::email_template`. Values are HTML escaped in
+//! the HTML part, and placeholders without a value are left empty.
+
+use core::email_template::EmailTemplate;
+use types::NotificationKind;
+
+const MAX_SUBJECT_LEN: usize = 255;
+const MAX_BODY_LEN: usize = 65536;
+
+pub struct Template {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+impl From<EmailTemplate> for Template {
+    fn from(email_template: EmailTemplate) -> Self {
+        Template {
+            subject: email_template.subject,
+            html: email_template.html,
+            text: email_template.text,
+        }
+    }
+}
+
+/// A template filled in, ready for `SendMail`.
+pub struct Email {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// What a template of the kind may refer to.
+pub fn placeholders(kind: NotificationKind) -> &'static [&'static str] {
+    match kind {
+        NotificationKind::Receipt | NotificationKind::LargePayment => &[
+            "store_name",
+            "payment_id",
+            "identifier",
+            "price",
+            "fiat",
+            "amount_paid",
+            "crypto",
+            "transaction_hash",
+            "paid_at",
+        ],
+        NotificationKind::InsufficientAmount => &[
+            "store_name",
+            "payment_id",
+            "identifier",
+            "price",
+            "fiat",
+            "charge",
+            "amount_paid",
+            "crypto",
+            "transaction_hash",
+            "paid_at",
+        ],
+        NotificationKind::PayoutFailed => &[
+            "store_name",
+            "payment_id",
+            "payout_id",
+            "action",
+            "crypto",
+            "status",
+        ],
+        NotificationKind::DailySummary => &[
+            "store_name",
+            "date",
+            "payments",
+            "settled",
+            "revenue",
+            "received",
+        ],
+    }
+}
+
+pub fn default(kind: NotificationKind) -> Template {
+    let (subject, text) = match kind {
+        NotificationKind::Receipt => (
+            "Your payment to {{store_name}}",
+            "Thank you for your payment to {{store_name}}.\n\
+             \n\
+             Amount: {{price}} {{fiat}}\n\
+             Paid: {{amount_paid}} {{crypto}}\n\
+             Reference: {{identifier}}\n\
+             Payment: {{payment_id}}\n\
+             Transaction: {{transaction_hash}}\n\
+             Paid at: {{paid_at}}\n",
+        ),
+        NotificationKind::LargePayment => (
+            "{{store_name}} received {{price}} {{fiat}}",
+            "{{store_name}} received a payment of {{price}} {{fiat}}.\n\
+             \n\
+             Paid: {{amount_paid}} {{crypto}}\n\
+             Reference: {{identifier}}\n\
+             Payment: {{payment_id}}\n\
+             Transaction: {{transaction_hash}}\n\
+             Paid at: {{paid_at}}\n",
+        ),
+        NotificationKind::InsufficientAmount => (
+            "A payment to {{store_name}} fell short",
+            "A customer of {{store_name}} paid {{amount_paid}} {{crypto}} of the \
+             {{charge}} {{crypto}} charged for {{price}} {{fiat}}. The payment will be refunded.\n\
+             \n\
+             Reference: {{identifier}}\n\
+             Payment: {{payment_id}}\n\
+             Transaction: {{transaction_hash}}\n\
+             Paid at: {{paid_at}}\n",
+        ),
+        NotificationKind::PayoutFailed => (
+            "A payout of {{store_name}} failed",
+            "The {{crypto}} {{action}} of payment {{payment_id}} to {{store_name}} failed \
+             ({{status}}). The payment didn't cover the network fees.\n\
+             \n\
+             Payout: {{payout_id}}\n",
+        ),
+        NotificationKind::DailySummary => (
+            "{{store_name}} on {{date}}",
+            "{{store_name}} on {{date}}:\n\
+             \n\
+             Payments: {{payments}}\n\
+             Confirmed: {{settled}}\n\
+             Revenue: {{revenue}}\n\
+             Received: {{received}}\n",
+        ),
+    };
+
+    Template {
+        subject: subject.to_owned(),
+        html: text
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| format!("<p>{}</p>", line))
+            .collect::<String>(),
+        text: text.to_owned(),
+    }
+}
+
+/// Checks a store's own template refers to nothing but the kind's placeholders.
+pub fn validate(kind: NotificationKind, template: &Template) -> Result<(), &'static str> {
+    if template.subject.is_empty() || template.subject.len() > MAX_SUBJECT_LEN {
+        return Err("subject must be 1 to 255 characters");
+    }
+
+    // Line breaks would end the header, and anything after them would be taken as more headers.
+    if template.subject.contains(&['\r', '\n'][..]) {
+        return Err("subject can't contain line breaks");
+    }
+
+    if template.html.len() > MAX_BODY_LEN || template.text.len() > MAX_BODY_LEN {
+        return Err("templates can be at most 64 KiB");
+    }
+
+    let allowed = placeholders(kind);
+    let known = [&template.subject, &template.html, &template.text]
+        .iter()
+        .all(|part| {
+            names(part)
+                .iter()
+                .all(|name| allowed.iter().any(|allowed| allowed == name))
+        });
+
+    if !known {
+        return Err("template refers to an unknown placeholder");
+    }
+
+    Ok(())
+}
+
+pub fn render(template: &Template, values: &[(&str, String)]) -> Email {
+    let value = |name: &str| {
+        values
+            .iter()
+            .find(|&&(key, _)| key == name)
+            .map_or("", |&(_, ref value)| value.as_str())
+    };
+
+    Email {
+        // Line breaks would end the header.
+        subject: fill(&template.subject, |name| {
+            value(name).replace(&['\r', '\n'][..], " ")
+        }),
+        html: fill(&template.html, |name| escape(value(name))),
+        text: fill(&template.text, |name| value(name).to_owned()),
+    }
+}
+
+fn names(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        match rest[start + 2..].find("}}") {
+            Some(end) => {
+                names.push(rest[start + 2..start + 2 + end].trim());
+                rest = &rest[start + 2 + end + 2..];
+            }
+            None => break,
+        }
+    }
+
+    names
+}
+
+fn fill<F: Fn(&str) -> String>(template: &str, value: F) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        match rest[start + 2..].find("}}") {
+            Some(end) => {
+                filled.push_str(&rest[..start]);
+                filled.push_str(&value(rest[start + 2..start + 2 + end].trim()));
+                rest = &rest[start + 2 + end + 2..];
+            }
+            None => break,
+        }
+    }
+    filled.push_str(rest);
+
+    filled
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(subject: &str, html: &str, text: &str) -> Template {
+        Template {
+            subject: subject.to_owned(),
+            html: html.to_owned(),
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        for kind in NotificationKind::all().iter() {
+            assert_eq!(validate(*kind, &default(*kind)), Ok(()));
+        }
+    }
+
+    #[test]
+    fn subject_with_line_breaks_is_rejected() {
+        let template = template("Receipt\r\nBcc: everyone@example.com", "", "");
+
+        assert!(validate(NotificationKind::Receipt, &template).is_err());
+    }
+
+    #[test]
+    fn unknown_placeholder_is_rejected() {
+        let template = template("Receipt", "{{ customer_password }}", "");
+
+        assert!(validate(NotificationKind::Receipt, &template).is_err());
+    }
+
+    #[test]
+    fn line_breaks_in_values_become_spaces_in_the_subject() {
+        let template = template("Paid to {{store_name}}", "", "{{store_name}}");
+        let values = vec![("store_name", String::from("Shop\r\nBcc: x@example.com"))];
+
+        let email = render(&template, &values);
+
+        assert_eq!(email.subject, "Paid to Shop  Bcc: x@example.com");
+        assert_eq!(email.text, "Shop\r\nBcc: x@example.com");
+    }
+
+    #[test]
+    fn values_are_escaped_in_html_only() {
+        let template = template("Receipt", "<p>{{identifier}}</p>", "{{identifier}}");
+        let values = vec![("identifier", String::from("<script>alert('x')</script>"))];
+
+        let email = render(&template, &values);
+
+        assert_eq!(
+            email.html,
+            "<p>&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;</p>"
+        );
+        assert_eq!(email.text, "<script>alert('x')</script>");
+    }
+
+    #[test]
+    fn missing_values_and_unclosed_placeholders() {
+        let template = template("{{ paid_at }} {{store_name", "", "");
+
+        assert_eq!(render(&template, &[]).subject, " {{store_name");
+    }
+}
@@ -0,0 +1,234 @@
+//! Token buckets for the endpoints anyone can call, and for second factor checks, see
+//! `services::users`. The buckets live in Redis so that every server shares them; while Redis
+//! can't be reached each server falls back to its own buckets in memory.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    error::ResponseError,
+    http::Method,
+    middleware::{Middleware, Started},
+    Error as ActixError, HttpRequest, HttpResponse, Result as ActixResult,
+};
+use chrono::prelude::*;
+use futures::future::{self, Future};
+
+use auth;
+use config::{RateLimitConfig, RateLimitsConfig};
+use core::db::redis::{RedisExecutorAddr, TakeToken};
+use services::Error;
+use state::AppState;
+
+/// How long to wait for Redis before using the in-memory buckets instead.
+const REDIS_TIMEOUT_MS: u64 = 250;
+/// Once there are this many in-memory buckets, the ones that have refilled are dropped.
+const MAX_MEMORY_BUCKETS: usize = 10_000;
+
+fn limit(configured: Option<RateLimitConfig>, burst: u32, per_minute: u32) -> RateLimitConfig {
+    configured.unwrap_or(RateLimitConfig { burst, per_minute })
+}
+
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub login: RateLimitConfig,
+    pub registration: RateLimitConfig,
+    pub reset_password: RateLimitConfig,
+    pub payments: RateLimitConfig,
+    pub invoices: RateLimitConfig,
+    pub refresh: RateLimitConfig,
+    pub two_factor: RateLimitConfig,
+}
+
+impl Limits {
+    pub fn new(config: Option<RateLimitsConfig>) -> Self {
+        let config = config.unwrap_or(RateLimitsConfig {
+            login: None,
+            registration: None,
+            reset_password: None,
+            payments: None,
+            invoices: None,
+            refresh: None,
+            two_factor: None,
+        });
+
+        Limits {
+            login: limit(config.login, 10, 5),
+            registration: limit(config.registration, 5, 2),
+            reset_password: limit(config.reset_password, 5, 2),
+            payments: limit(config.payments, 60, 30),
+            invoices: limit(config.invoices, 60, 30),
+            refresh: limit(config.refresh, 10, 5),
+            two_factor: limit(config.two_factor, 5, 1),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    redis: RedisExecutorAddr,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+    pub limits: Limits,
+}
+
+impl RateLimiter {
+    pub fn new(redis: RedisExecutorAddr, config: Option<RateLimitsConfig>) -> Self {
+        RateLimiter {
+            redis,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+            limits: Limits::new(config),
+        }
+    }
+
+    /// Takes a token from the bucket at `key`, failing with `Error::TooManyRequests` when it's
+    /// empty.
+    pub fn take(
+        &self,
+        key: String,
+        limit: &RateLimitConfig,
+    ) -> Box<Future<Item = (), Error = Error>> {
+        let capacity = limit.burst.max(1);
+        let refill_ms = 60_000 / u64::from(limit.per_minute.max(1));
+        let buckets = self.buckets.clone();
+
+        Box::new(
+            self.redis
+                .send(TakeToken {
+                    key: format!("rate_limit:{}", key),
+                    capacity,
+                    refill_ms,
+                    now: Utc::now().timestamp_millis(),
+                })
+                .timeout(Duration::from_millis(REDIS_TIMEOUT_MS))
+                .then(move |res| {
+                    let wait = match res {
+                        Ok(Ok(wait)) => wait,
+                        res => {
+                            warn!("Rate limiting in memory, Redis failed: {:?}", res);
+                            take_from_memory(&buckets, key, capacity, refill_ms)
+                        }
+                    };
+
+                    if wait == 0 {
+                        Ok(())
+                    } else {
+                        Err(Error::TooManyRequests {
+                            retry_after: (wait + 999) / 1000,
+                        })
+                    }
+                }),
+        )
+    }
+}
+
+/// The same bucket as the Redis script, for when Redis is down.
+fn take_from_memory(
+    buckets: &Mutex<HashMap<String, Bucket>>,
+    key: String,
+    capacity: u32,
+    refill_ms: u64,
+) -> u64 {
+    let mut buckets = buckets.lock().unwrap();
+    let now = Instant::now();
+
+    if buckets.len() >= MAX_MEMORY_BUCKETS {
+        let full_after = Duration::from_millis(refill_ms * u64::from(capacity));
+        buckets.retain(|_, bucket| now.duration_since(bucket.updated_at) < full_after);
+    }
+
+    let bucket = buckets.entry(key).or_insert(Bucket {
+        tokens: f64::from(capacity),
+        updated_at: now,
+    });
+
+    let elapsed = now.duration_since(bucket.updated_at);
+    let elapsed_ms = elapsed.as_secs() as f64 * 1000.0 + f64::from(elapsed.subsec_millis());
+
+    bucket.tokens = (bucket.tokens + elapsed_ms / refill_ms as f64).min(f64::from(capacity));
+    bucket.updated_at = now;
+
+    if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        0
+    } else {
+        ((1.0 - bucket.tokens) * refill_ms as f64).ceil() as u64
+    }
+}
+
+/// `/invoices/{id}/payments`, which derives a payment address just like `/payments`.
+fn is_invoice_payment(path: &str) -> bool {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+
+    segments.len() == 3 && segments[0] == "invoices" && segments[2] == "payments"
+}
+
+/// Limits the public endpoints per IP, and the ones client tokens call per client token as well.
+/// Logins and password resets are also limited per account, see `controllers::auth`. The IP is
+/// the one `auth::remote_ip` trusts, a client can't get a fresh bucket by making one up.
+pub struct RateLimit;
+
+impl Middleware<AppState> for RateLimit {
+    fn start(&self, req: &HttpRequest<AppState>) -> ActixResult<Started> {
+        if req.method() != Method::POST {
+            return Ok(Started::Done);
+        }
+
+        let limiter = &req.state().rate_limiter;
+
+        let (name, limit) = match req.path() {
+            "/login" => ("login", &limiter.limits.login),
+            "/registration" => ("registration", &limiter.limits.registration),
+            "/reset_password" => ("reset_password", &limiter.limits.reset_password),
+            "/refresh" => ("refresh", &limiter.limits.refresh),
+            "/payments" => ("payments", &limiter.limits.payments),
+            "/invoices" => ("invoices", &limiter.limits.invoices),
+            path if is_invoice_payment(path) => ("payments", &limiter.limits.payments),
+            _ => return Ok(Started::Done),
+        };
+
+        let ip = auth::remote_ip(req).unwrap_or_else(|| String::from("unknown"));
+        let mut checks = vec![limiter.take(format!("{}:ip:{}", name, ip), limit)];
+
+        if name == "payments" || name == "invoices" {
+            let client_token = req
+                .headers()
+                .get("authorization")
+                .and_then(|auth_header| auth_header.to_str().ok())
+                .and_then(|auth_header| auth_header.split_whitespace().last());
+
+            if let Some(client_token) = client_token {
+                checks.push(limiter.take(format!("{}:client_token:{}", name, client_token), limit));
+            }
+        }
+
+        Ok(Started::Future(Box::new(future::join_all(checks).then(
+            |res| -> Result<Option<HttpResponse>, ActixError> {
+                match res {
+                    Ok(_) => Ok(None),
+                    Err(e) => Ok(Some(e.error_response())),
+                }
+            },
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invoice_payments_are_told_apart() {
+        assert!(is_invoice_payment("/invoices/6bd1a0d6/payments"));
+        assert!(!is_invoice_payment("/invoices"));
+        assert!(!is_invoice_payment("/invoices/6bd1a0d6"));
+        assert!(!is_invoice_payment("/stores/6bd1a0d6/payments"));
+    }
+}
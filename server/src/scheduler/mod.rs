@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use actix::{fut::wrap_future, prelude::*};
+use futures::Future;
+
+use core::db::postgres::PgExecutorAddr;
+use mailer::MailerAddr;
+use services;
+
+/// Runs the server's periodic jobs: applying payout address changes once their cool-down is over,
+/// sending the emails about payments and payouts, and forgetting expired idempotency keys,
+/// notifications and used refresh tokens.
+pub struct Scheduler {
+    pub postgres: PgExecutorAddr,
+    pub mailer: MailerAddr,
+    pub mail_sender: String,
+}
+
+impl Scheduler {
+    pub fn new(postgres: PgExecutorAddr, mailer: MailerAddr, mail_sender: String) -> Self {
+        Scheduler {
+            postgres,
+            mailer,
+            mail_sender,
+        }
+    }
+}
+
+impl Actor for Scheduler {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(Duration::new(60, 0), |scheduler, ctx| {
+            let apply = services::payout_address_changes::apply_due(
+                scheduler.mailer.clone(),
+                &scheduler.postgres,
+                scheduler.mail_sender.clone(),
+            )
+            .map(|applied| {
+                if applied > 0 {
+                    info!("Applied {} payout address changes", applied);
+                }
+            })
+            .map_err(|e| error!("{:?}", e));
+
+            ctx.spawn(wrap_future(apply));
+
+            let notify = services::notifications::send_due(
+                scheduler.mailer.clone(),
+                &scheduler.postgres,
+                scheduler.mail_sender.clone(),
+            )
+            .map(|sent| {
+                if sent > 0 {
+                    info!("Sent {} notifications", sent);
+                }
+            })
+            .map_err(|e| error!("{:?}", e));
+
+            ctx.spawn(wrap_future(notify));
+
+            let purge = services::idempotency::delete_expired(&scheduler.postgres)
+                .map(|_| ())
+                .map_err(|e| error!("{:?}", e));
+
+            ctx.spawn(wrap_future(purge));
+
+            let forget = services::notifications::delete_expired(&scheduler.postgres)
+                .map(|_| ())
+                .map_err(|e| error!("{:?}", e));
+
+            ctx.spawn(wrap_future(forget));
+
+            let expire = services::sessions::delete_used_refresh_tokens(&scheduler.postgres)
+                .map(|_| ())
+                .map_err(|e| error!("{:?}", e));
+
+            ctx.spawn(wrap_future(expire));
+        });
+    }
+}
@@ -0,0 +1,81 @@
+use futures::future::Future;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use auth::Actor;
+use core::{
+    audit_log::{AuditLog, AuditLogPayload},
+    db::postgres::PgExecutorAddr,
+};
+use services::Error;
+use types::AuditAction;
+
+/// The fields that differ between two exports of a record, each with its value before and after.
+/// Pass `Value::Null` as `before` for a record that was just created, or as `after` for one that
+/// was just deleted.
+pub fn diff(before: &Value, after: &Value) -> Value {
+    let empty = Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+
+    let mut changes = Map::new();
+
+    for key in before.keys().chain(after.keys()) {
+        if key == "updated_at" || changes.contains_key(key) {
+            continue;
+        }
+
+        let old = before.get(key).unwrap_or(&Value::Null);
+        let new = after.get(key).unwrap_or(&Value::Null);
+
+        if old != new {
+            changes.insert(key.clone(), json!({ "before": old, "after": new }));
+        }
+    }
+
+    Value::Object(changes)
+}
+
+/// Appends an entry to the audit log. The change it records has been made by then, so failing to
+/// record it is logged rather than failing the request.
+pub fn record(
+    actor: &Actor,
+    action: AuditAction,
+    store_id: Option<Uuid>,
+    subject_id: Option<Uuid>,
+    changes: Option<Value>,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = (), Error = Error> {
+    let mut payload = AuditLogPayload::new(action);
+    payload.store_id = store_id;
+    payload.user_id = actor.user_id;
+    payload.subject_id = subject_id;
+    payload.ip = actor.ip.clone();
+    payload.changes = changes;
+
+    AuditLog::insert(payload, postgres).then(move |res| -> Result<(), Error> {
+        if let Err(e) = res {
+            error!("Failed to record {} in the audit log: {:?}", action, e);
+        }
+
+        Ok(())
+    })
+}
+
+pub fn find_by_store(
+    store_id: Uuid,
+    limit: i64,
+    offset: i64,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Vec<AuditLog>, Error = Error> {
+    AuditLog::find_by_store(store_id, limit, offset, postgres).from_err()
+}
+
+pub fn find_by_user(
+    user_id: Uuid,
+    limit: i64,
+    offset: i64,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Vec<AuditLog>, Error = Error> {
+    AuditLog::find_by_user(user_id, limit, offset, postgres).from_err()
+}
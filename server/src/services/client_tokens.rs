@@ -1,25 +1,101 @@
+use bigdecimal::BigDecimal;
 use futures::future::Future;
+use serde_json::Value;
 use uuid::Uuid;
 
+use auth::Actor;
 use core::{
     client_token::{ClientToken, ClientTokenPayload},
     db::postgres::PgExecutorAddr,
 };
-use services::Error;
+use services::{audit, Error};
+use types::{
+    currency::{Crypto, Fiat},
+    AuditAction,
+};
+
+// The token itself is a credential, so the audit log only gets to see what identifies it.
+fn audit_export(client_token: &ClientToken) -> Value {
+    json!({
+        "name": client_token.name,
+        "typ": client_token.typ,
+        "allowed_origins": client_token.allowed_origins,
+        "allowed_cryptos": client_token.allowed_cryptos,
+        "allowed_fiats": client_token.allowed_fiats,
+        "max_price": client_token.max_price,
+        "max_price_fiat": client_token.max_price_fiat,
+    })
+}
 
 pub fn create(
     payload: ClientTokenPayload,
+    actor: Actor,
     postgres: &PgExecutorAddr,
 ) -> impl Future<Item = ClientToken, Error = Error> {
-    ClientToken::insert(payload, postgres).from_err()
+    let postgres = postgres.clone();
+
+    ClientToken::insert(payload, &postgres)
+        .from_err()
+        .and_then(move |client_token| {
+            audit::record(
+                &actor,
+                AuditAction::ClientTokenCreated,
+                Some(client_token.store_id),
+                Some(client_token.id),
+                Some(audit::diff(&Value::Null, &audit_export(&client_token))),
+                &postgres,
+            )
+            .map(move |_| client_token)
+        })
 }
 
-pub fn get_by_token_and_domain(
+/// Looks up the token for a request from `origin`, or from a native app when there's no
+/// `Origin` header.
+pub fn get_by_token_and_origin(
     token: Uuid,
-    domain: String,
+    origin: Option<String>,
     postgres: &PgExecutorAddr,
 ) -> impl Future<Item = ClientToken, Error = Error> {
-    ClientToken::find_by_token_and_domain(token, domain, postgres).from_err()
+    ClientToken::find_by_token(token, postgres)
+        .from_err()
+        .and_then(move |client_token| {
+            if client_token.allows_origin(origin.as_ref().map(|origin| origin.as_str())) {
+                Ok(client_token)
+            } else {
+                Err(Error::OriginNotAllowed)
+            }
+        })
+}
+
+/// Checks a payment or invoice against the token's restrictions. Invoices leave the crypto to
+/// the customer, so there's none to check yet.
+pub fn check_restrictions(
+    client_token: &ClientToken,
+    crypto: Option<&Crypto>,
+    fiat: &Fiat,
+    price: &BigDecimal,
+) -> Result<(), Error> {
+    if let Some(crypto) = crypto {
+        if !client_token.allows_crypto(crypto) {
+            return Err(Error::BadRequest(
+                "crypto is not allowed for this client token",
+            ));
+        }
+    }
+
+    if !client_token.allows_fiat(fiat) {
+        return Err(Error::BadRequest(
+            "fiat is not allowed for this client token",
+        ));
+    }
+
+    if !client_token.allows_price(price, fiat) {
+        return Err(Error::BadRequest(
+            "price is above this client token's maximum",
+        ));
+    }
+
+    Ok(())
 }
 
 pub fn find_by_store(
@@ -35,6 +111,24 @@ pub fn get(id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = ClientToke
     ClientToken::find_by_id(id, postgres).from_err()
 }
 
-pub fn delete(id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = usize, Error = Error> {
-    ClientToken::delete(id, postgres).from_err()
+pub fn delete(
+    client_token: ClientToken,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = usize, Error = Error> {
+    let postgres = postgres.clone();
+
+    ClientToken::delete(client_token.id, &postgres)
+        .from_err()
+        .and_then(move |deleted| {
+            audit::record(
+                &actor,
+                AuditAction::ClientTokenDeleted,
+                Some(client_token.store_id),
+                Some(client_token.id),
+                Some(audit::diff(&audit_export(&client_token), &Value::Null)),
+                &postgres,
+            )
+            .map(move |_| deleted)
+        })
 }
@@ -0,0 +1,122 @@
+use futures::future::{self, Future};
+use serde_json::Value;
+use uuid::Uuid;
+
+use auth::Actor;
+use core::{
+    db::postgres::PgExecutorAddr,
+    email_template::{EmailTemplate, EmailTemplatePayload},
+};
+use mailer::templates::{self, Template};
+use services::{audit, Error};
+use types::{AuditAction, NotificationKind};
+
+fn export(kind: NotificationKind, template: &Template, custom: bool) -> Value {
+    json!({
+        "kind": kind,
+        "subject": template.subject,
+        "html": template.html,
+        "text": template.text,
+        "custom": custom,
+        "placeholders": templates::placeholders(kind),
+    })
+}
+
+/// Every email the store sends, its own template where it has one and the default otherwise.
+pub fn list(store_id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = Value, Error = Error> {
+    EmailTemplate::find_by_store(store_id, postgres)
+        .from_err()
+        .map(|mut email_templates| {
+            let templates = NotificationKind::all()
+                .iter()
+                .map(
+                    |&kind| match email_templates.iter().position(|t| t.kind == kind) {
+                        Some(i) => export(kind, &Template::from(email_templates.remove(i)), true),
+                        None => export(kind, &templates::default(kind), false),
+                    },
+                )
+                .collect::<Vec<_>>();
+
+            json!(templates)
+        })
+}
+
+pub fn update(
+    store_id: Uuid,
+    kind: NotificationKind,
+    template: Template,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> Box<Future<Item = Value, Error = Error>> {
+    if let Err(e) = templates::validate(kind, &template) {
+        return Box::new(future::err(Error::BadRequest(e)));
+    }
+
+    let postgres = postgres.clone();
+    let payload = EmailTemplatePayload::new(
+        store_id,
+        kind,
+        template.subject,
+        template.html,
+        template.text,
+    );
+
+    Box::new(
+        EmailTemplate::find_by_store_and_kind(store_id, kind, &postgres)
+            .and_then(move |before| {
+                EmailTemplate::upsert(payload, &postgres)
+                    .map(move |after| (before, after, postgres))
+            })
+            .from_err()
+            .and_then(move |(before, after, postgres)| {
+                let before = before.map_or(Value::Null, |before| before.export());
+
+                audit::record(
+                    &actor,
+                    AuditAction::EmailTemplateUpdated,
+                    Some(store_id),
+                    Some(after.id),
+                    Some(audit::diff(&before, &after.export())),
+                    &postgres,
+                )
+                .map(move |_| export(kind, &Template::from(after), true))
+            }),
+    )
+}
+
+/// Goes back to the default template.
+pub fn remove(
+    store_id: Uuid,
+    kind: NotificationKind,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = usize, Error = Error> {
+    let postgres = postgres.clone();
+
+    EmailTemplate::find_by_store_and_kind(store_id, kind, &postgres)
+        .and_then(move |email_template| {
+            EmailTemplate::delete(store_id, kind, &postgres)
+                .map(move |deleted| (email_template, deleted, postgres))
+        })
+        .from_err()
+        .and_then(
+            move |(email_template, deleted, postgres)| -> Box<Future<Item = usize, Error = Error>> {
+                let email_template = match email_template {
+                    Some(email_template) => email_template,
+                    None => return Box::new(future::ok(deleted)),
+                };
+
+                Box::new(
+                    audit::record(
+                        &actor,
+                        AuditAction::EmailTemplateRemoved,
+                        Some(store_id),
+                        Some(email_template.id),
+                        Some(audit::diff(&email_template.export(), &Value::Null)),
+                        &postgres,
+                    )
+                    .map(move |_| deleted),
+                )
+            },
+        )
+}
@@ -9,7 +9,7 @@ use rustc_hex::FromHexError;
 use secp256k1::Error as Secp256k1Error;
 use serde_json::Error as SerdeError;
 
-use core::{db::Error as DbError, ModelError};
+use core::{crypto::Error as CryptoError, db::Error as DbError, ModelError};
 use currency_api_client::Error as CurrencyApiClientError;
 use hd_keyring::Error as KeyringError;
 use mailer::Error as MailerError;
@@ -24,6 +24,8 @@ pub enum Error {
     #[fail(display = "{}", _0)]
     KeyringError(#[cause] KeyringError),
     #[fail(display = "{}", _0)]
+    CryptoError(#[cause] CryptoError),
+    #[fail(display = "{}", _0)]
     DecodeError(#[cause] DecodeError),
     #[fail(display = "{}", _0)]
     JwtError(#[cause] JwtError),
@@ -37,8 +39,24 @@ pub enum Error {
     MailboxError(#[cause] MailboxError),
     #[fail(display = "incorrect password")]
     IncorrectPassword,
+    #[fail(display = "two-factor code required")]
+    TwoFactorRequired,
+    #[fail(display = "invalid two-factor code")]
+    InvalidTwoFactorCode,
+    #[fail(display = "invalid or expired session")]
+    InvalidSession,
+    #[fail(display = "too many requests, try again in {} seconds", retry_after)]
+    TooManyRequests { retry_after: u64 },
     #[fail(display = "invalid request account")]
     InvalidRequestAccount,
+    #[fail(display = "origin not allowed for this client token")]
+    OriginNotAllowed,
+    #[fail(display = "missing, invalid or expired checkout signature")]
+    InvalidCheckoutSignature,
+    #[fail(display = "idempotency key was already used for a different request")]
+    IdempotencyKeyReused,
+    #[fail(display = "a request with this idempotency key is still in progress")]
+    IdempotencyKeyInProgress,
     #[fail(display = "currency not supported")]
     CurrencyNotSupported,
     #[fail(display = "{}", _0)]
@@ -68,15 +86,38 @@ impl error::ResponseError for Error {
         let server_err_message = format!("{}", json!({ "message": "internal server error" }));
 
         match *self {
-            Error::BadRequest(_) | Error::IncorrectPassword | Error::CurrencyNotSupported => {
-                HttpResponse::build(http::StatusCode::BAD_REQUEST)
+            Error::BadRequest(_)
+            | Error::IncorrectPassword
+            | Error::InvalidTwoFactorCode
+            | Error::CurrencyNotSupported => HttpResponse::build(http::StatusCode::BAD_REQUEST)
+                .body(Body::from(user_err_message)),
+
+            Error::TwoFactorRequired | Error::InvalidSession => {
+                HttpResponse::build(http::StatusCode::UNAUTHORIZED)
+                    .body(Body::from(user_err_message))
+            }
+
+            Error::TooManyRequests { retry_after } => {
+                HttpResponse::build(http::StatusCode::TOO_MANY_REQUESTS)
+                    .header("Retry-After", retry_after.to_string())
                     .body(Body::from(user_err_message))
             }
 
-            Error::InvalidRequestAccount => {
+            Error::InvalidRequestAccount
+            | Error::OriginNotAllowed
+            | Error::InvalidCheckoutSignature => {
                 HttpResponse::build(http::StatusCode::FORBIDDEN).body(Body::from(user_err_message))
             }
 
+            Error::IdempotencyKeyReused => {
+                HttpResponse::build(http::StatusCode::UNPROCESSABLE_ENTITY)
+                    .body(Body::from(user_err_message))
+            }
+
+            Error::IdempotencyKeyInProgress => {
+                HttpResponse::build(http::StatusCode::CONFLICT).body(Body::from(user_err_message))
+            }
+
             Error::PaymentNotConfirmed => {
                 HttpResponse::build(http::StatusCode::NOT_FOUND).body(Body::from(user_err_message))
             }
@@ -127,6 +168,12 @@ impl From<KeyringError> for Error {
     }
 }
 
+impl From<CryptoError> for Error {
+    fn from(e: CryptoError) -> Error {
+        Error::CryptoError(e)
+    }
+}
+
 impl From<DecodeError> for Error {
     fn from(e: DecodeError) -> Error {
         Error::DecodeError(e)
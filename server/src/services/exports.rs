@@ -0,0 +1,146 @@
+//! Accounting exports of a store's payments and their payouts, as CSV or JSON lines. Payments are
+//! read a page at a time while the response is being sent, so exports of any size use about as
+//! much memory as one page.
+
+use bytes::Bytes;
+use chrono::prelude::*;
+use futures::{stream, Future, Stream};
+use serde_json;
+use uuid::Uuid;
+
+use core::{
+    db::postgres::PgExecutorAddr,
+    payment::{Payment, PaymentExport},
+};
+use services::Error;
+use types::PaymentStatus;
+
+const PAGE_SIZE: i64 = 500;
+
+const CSV_HEADER: &str = "payment_id,created_at,paid_at,status,identifier,fiat,price,crypto,\
+                          charge,amount_paid,transaction_hash,payout_status,payout_action,\
+                          payout_created_at,paid_out,fees,payout_transaction_hashes\r\n";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Csv,
+    Jsonl,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &str {
+        match *self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &str {
+        match *self {
+            Format::Csv => "csv",
+            Format::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// The store's payments created in `from..to`, oldest first, formatted as they're read.
+pub fn payments(
+    store_id: Uuid,
+    status: Option<PaymentStatus>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    format: Format,
+    postgres: &PgExecutorAddr,
+) -> impl Stream<Item = Bytes, Error = Error> {
+    let postgres = postgres.clone();
+    let header = match format {
+        Format::Csv => Some(Bytes::from_static(CSV_HEADER.as_bytes())),
+        Format::Jsonl => None,
+    };
+
+    // The next page starts after the last payment of the previous one, there's none left once a
+    // page comes back short.
+    let pages = stream::unfold(Some((from, Uuid::nil())), move |after| {
+        after.map(|after| {
+            Payment::find_for_export(store_id, status.clone(), to, after, PAGE_SIZE, &postgres)
+                .from_err()
+                .map(|rows| {
+                    let next = if rows.len() < PAGE_SIZE as usize {
+                        None
+                    } else {
+                        rows.last().map(|row| (row.created_at, row.id))
+                    };
+
+                    (rows, next)
+                })
+        })
+    });
+
+    stream::iter_ok(header).chain(pages.and_then(move |rows| -> Result<Bytes, Error> {
+        let mut page = String::new();
+
+        for row in rows.iter() {
+            match format {
+                Format::Csv => page.push_str(&csv_row(row)),
+                Format::Jsonl => {
+                    page.push_str(&serde_json::to_string(row)?);
+                    page.push('\n');
+                }
+            }
+        }
+
+        Ok(Bytes::from(page))
+    }))
+}
+
+fn csv_row(row: &PaymentExport) -> String {
+    let fields = [
+        row.id.to_string(),
+        row.created_at.to_rfc3339(),
+        optional(row.paid_at.map(|paid_at| paid_at.to_rfc3339())),
+        row.status.to_string(),
+        optional(row.identifier.as_ref().map(|identifier| defuse(identifier))),
+        row.fiat.to_str().to_owned(),
+        row.price.to_string(),
+        row.crypto.to_str().to_owned(),
+        row.charge.to_string(),
+        optional(row.amount_paid.as_ref().map(|amount| amount.to_string())),
+        optional(row.transaction_hash.clone()),
+        optional(row.payout_status.map(|status| status.to_string())),
+        optional(row.payout_action.map(|action| action.to_string())),
+        optional(
+            row.payout_created_at
+                .map(|created_at| created_at.to_rfc3339()),
+        ),
+        optional(row.paid_out.as_ref().map(|amount| amount.to_string())),
+        optional(row.fees.as_ref().map(|amount| amount.to_string())),
+        optional(row.payout_transaction_hashes.clone()),
+    ];
+    let escaped: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+
+    format!("{}\r\n", escaped.join(","))
+}
+
+fn optional(field: Option<String>) -> String {
+    field.unwrap_or_default()
+}
+
+/// Identifiers come from customers, one a spreadsheet would take for a formula (like `=1+1`) is
+/// prefixed with `'` so opening the export can't run anything.
+fn defuse(field: &str) -> String {
+    match field.chars().next() {
+        Some('=') | Some('+') | Some('-') | Some('@') | Some('\t') | Some('\r') => {
+            format!("'{}", field)
+        }
+        _ => field.to_owned(),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
@@ -0,0 +1,111 @@
+//! `Idempotency-Key` handling for endpoints a retry shouldn't run twice, like creating a payment.
+//! The first request with a key runs as usual and its response is kept; retries with the same
+//! key and parameters get that response back, with different parameters they're rejected. A
+//! request holds its key for `IDEMPOTENCY_KEY_LEASE_MINUTES`; if it crashed or timed out without
+//! a response by then, the next retry runs in its place.
+
+use chrono::prelude::*;
+use futures::future::{err, ok, Future};
+use ring::digest;
+use rustc_hex::ToHex;
+use serde::Serialize;
+use serde_json::{self, Value};
+use uuid::Uuid;
+
+use core::{
+    db::postgres::PgExecutorAddr,
+    idempotency_key::{IdempotencyKey, IdempotencyKeyPayload},
+};
+use services::Error;
+
+const MAX_KEY_LEN: usize = 255;
+
+/// Hash of the request's parameters, to tell a retry from a different request reusing the key.
+pub fn fingerprint<T: Serialize>(params: &T) -> Result<String, Error> {
+    let params = serde_json::to_vec(params)?;
+
+    Ok(digest::digest(&digest::SHA256, &params).as_ref().to_hex())
+}
+
+/// Runs `handle` unless the request was made before with the same key in `scope`, in which case
+/// the first response is returned instead. Without a key `handle` just runs. Failed requests
+/// aren't remembered, so they can be retried with the same key.
+pub fn run<F, R>(
+    scope: String,
+    key: Option<String>,
+    fingerprint: String,
+    postgres: &PgExecutorAddr,
+    handle: F,
+) -> Box<Future<Item = Value, Error = Error>>
+where
+    F: FnOnce() -> R + 'static,
+    R: Future<Item = Value, Error = Error> + 'static,
+{
+    let key = match key {
+        Some(key) => key,
+        None => return Box::new(handle()),
+    };
+
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Box::new(err(Error::BadRequest(
+            "Idempotency-Key must be 1 to 255 characters",
+        )));
+    }
+
+    let postgres = postgres.clone();
+
+    Box::new(
+        IdempotencyKey::claim(
+            IdempotencyKeyPayload::new(scope, key, fingerprint.clone()),
+            &postgres,
+        )
+        .from_err()
+        .and_then(
+            move |(idempotency_key, claimed)| -> Box<Future<Item = Value, Error = Error>> {
+                if !claimed {
+                    if idempotency_key.fingerprint != fingerprint {
+                        return Box::new(err(Error::IdempotencyKeyReused));
+                    }
+
+                    return match idempotency_key.response {
+                        Some(response) => Box::new(ok(response)),
+                        None => Box::new(err(Error::IdempotencyKeyInProgress)),
+                    };
+                }
+
+                let id = idempotency_key.id;
+                let locked_at = idempotency_key.locked_at;
+
+                Box::new(handle().then(move |res| finish(id, locked_at, res, postgres)))
+            },
+        ),
+    )
+}
+
+/// Keeps the response of the first request with a key. A failed request forgets the key instead.
+/// Neither happens if a retry took the key over in the meantime.
+fn finish(
+    id: Uuid,
+    locked_at: DateTime<Utc>,
+    res: Result<Value, Error>,
+    postgres: PgExecutorAddr,
+) -> Box<Future<Item = Value, Error = Error>> {
+    match res {
+        // The request went through either way. Failing to keep its response means retries get
+        // rejected as still in progress until the lease runs out, and then run again.
+        Ok(response) => Box::new(
+            IdempotencyKey::complete(id, locked_at, response.clone(), &postgres).then(move |res| {
+                if let Err(e) = res {
+                    error!("Failed to store idempotent response: {:?}", e);
+                }
+
+                Ok(response)
+            }),
+        ),
+        Err(e) => Box::new(IdempotencyKey::delete(id, locked_at, &postgres).then(move |_| Err(e))),
+    }
+}
+
+pub fn delete_expired(postgres: &PgExecutorAddr) -> impl Future<Item = usize, Error = Error> {
+    IdempotencyKey::delete_expired(postgres).from_err()
+}
@@ -0,0 +1,19 @@
+use futures::future::Future;
+use uuid::Uuid;
+
+use core::{
+    db::postgres::PgExecutorAddr,
+    invoice::{Invoice, InvoicePayload},
+};
+use services::Error;
+
+pub fn create(
+    payload: InvoicePayload,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Invoice, Error = Error> {
+    Invoice::insert(payload, postgres).from_err()
+}
+
+pub fn get(id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = Invoice, Error = Error> {
+    Invoice::find_by_id(id, postgres).from_err()
+}
@@ -1,8 +1,18 @@
 mod errors;
 
 pub use self::errors::Error;
+pub mod audit;
 pub mod client_tokens;
+pub mod email_templates;
+pub mod exports;
+pub mod idempotency;
+pub mod invoices;
+pub mod notifications;
 pub mod payments;
+pub mod payout_address_changes;
+pub mod sessions;
+pub mod stats;
+pub mod store_memberships;
 pub mod stores;
 pub mod users;
 pub mod vouchers;
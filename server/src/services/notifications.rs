@@ -0,0 +1,401 @@
+//! Emails about what happens to a store's payments: receipts for customers, and notifications of
+//! large payments, payments that fell short, failed payouts and a daily summary for the owner.
+//! Payments are confirmed and paid out by other processes, so the scheduler works out what's due
+//! from recent payments and payouts, and `Notification`s make sure each email only goes out once.
+
+use chrono::{prelude::*, Duration};
+use futures::{
+    future::{self, Future},
+    stream, Stream,
+};
+use serde_json::Value;
+use uuid::Uuid;
+
+use core::{
+    db::postgres::PgExecutorAddr,
+    email_template::EmailTemplate,
+    notification::{Notification, NotificationPayload},
+    payment::Payment,
+    payout::Payout,
+    store::Store,
+    user::User,
+};
+use mailer::{
+    templates::{self, Email, Template},
+    MailerAddr, SendMail,
+};
+use services::{
+    stats::{self, Bucket},
+    Error,
+};
+use types::NotificationKind;
+
+/// How far back payments and payouts are looked at. Older ones are never notified about, so
+/// turning notifications on doesn't send a flood of them.
+const WINDOW_HOURS: i64 = 24;
+
+/// Sends every notification that is due, returns how many went out.
+pub fn send_due(
+    mailer: MailerAddr,
+    postgres: &PgExecutorAddr,
+    mail_sender: String,
+) -> impl Future<Item = usize, Error = Error> {
+    let since = Utc::now() - Duration::hours(WINDOW_HOURS);
+    let sender = Sender {
+        mailer,
+        postgres: postgres.clone(),
+        mail_sender,
+    };
+
+    let receipts = Payment::find_receipts_due(since, postgres)
+        .from_err()
+        .map(|payments| (NotificationKind::Receipt, payments));
+    let large_payments = Payment::find_large_due(since, postgres)
+        .from_err()
+        .map(|payments| (NotificationKind::LargePayment, payments));
+    let insufficient_amounts = Payment::find_insufficient_amounts_due(since, postgres)
+        .from_err()
+        .map(|payments| (NotificationKind::InsufficientAmount, payments));
+
+    let payments = receipts
+        .join3(large_payments, insufficient_amounts)
+        .map(|(a, b, c)| vec![a, b, c])
+        .and_then({
+            let sender = sender.clone();
+            move |payments| {
+                stream::iter_ok(payments)
+                    .and_then(move |(kind, payments)| sender.payments(kind, payments))
+                    .fold(0, |total, sent| Ok::<_, Error>(total + sent))
+            }
+        });
+
+    let payouts = Payout::find_failed_due(since, postgres)
+        .from_err()
+        .and_then({
+            let sender = sender.clone();
+            move |payouts| sender.payouts(payouts)
+        });
+
+    payments
+        .join3(payouts, sender.daily_summaries())
+        .map(|(payments, payouts, summaries)| payments + payouts + summaries)
+}
+
+pub fn delete_expired(postgres: &PgExecutorAddr) -> impl Future<Item = usize, Error = Error> {
+    Notification::delete_expired(postgres).from_err()
+}
+
+#[derive(Clone)]
+struct Sender {
+    mailer: MailerAddr,
+    postgres: PgExecutorAddr,
+    mail_sender: String,
+}
+
+impl Sender {
+    fn payments(
+        &self,
+        kind: NotificationKind,
+        payments: Vec<Payment>,
+    ) -> Box<Future<Item = usize, Error = Error>> {
+        let sender = self.clone();
+
+        send_each(payments, move |payment| sender.payment(kind, payment))
+    }
+
+    fn payment(
+        &self,
+        kind: NotificationKind,
+        payment: Payment,
+    ) -> Box<Future<Item = bool, Error = Error>> {
+        let sender = self.clone();
+
+        Box::new(
+            Store::find_by_id_with_deleted(payment.store_id, &self.postgres)
+                .from_err()
+                .and_then(move |store| -> Box<Future<Item = bool, Error = Error>> {
+                    if store.deleted_at.is_some() {
+                        return Box::new(future::ok(false));
+                    }
+
+                    let values = payment_values(&store, &payment);
+                    let to = match kind {
+                        NotificationKind::Receipt => match payment.customer_email.clone() {
+                            Some(customer_email) => future::Either::A(future::ok(customer_email)),
+                            None => return Box::new(future::ok(false)),
+                        },
+                        _ => future::Either::B(sender.owner_email(&store)),
+                    };
+
+                    Box::new(to.and_then(move |to| {
+                        sender.send(store.id, kind, payment.id, None, to, values)
+                    }))
+                }),
+        )
+    }
+
+    fn payouts(&self, payouts: Vec<Payout>) -> Box<Future<Item = usize, Error = Error>> {
+        let sender = self.clone();
+
+        send_each(payouts, move |payout| sender.payout(payout))
+    }
+
+    fn payout(&self, payout: Payout) -> Box<Future<Item = bool, Error = Error>> {
+        let sender = self.clone();
+
+        Box::new(
+            Store::find_by_id_with_deleted(payout.store_id, &self.postgres)
+                .from_err()
+                .and_then(move |store| -> Box<Future<Item = bool, Error = Error>> {
+                    if store.deleted_at.is_some() {
+                        return Box::new(future::ok(false));
+                    }
+
+                    let values = vec![
+                        ("store_name", store.name.clone()),
+                        ("payment_id", payout.payment_id.to_string()),
+                        ("payout_id", payout.id.to_string()),
+                        ("action", payout.action.to_string()),
+                        ("crypto", payout.typ.to_str().to_uppercase()),
+                        ("status", payout.status.to_string()),
+                    ];
+
+                    Box::new(sender.owner_email(&store).and_then(move |to| {
+                        sender.send(
+                            store.id,
+                            NotificationKind::PayoutFailed,
+                            payout.id,
+                            None,
+                            to,
+                            values,
+                        )
+                    }))
+                }),
+        )
+    }
+
+    /// Summaries cover the previous day, in UTC.
+    fn daily_summaries(&self) -> Box<Future<Item = usize, Error = Error>> {
+        let sender = self.clone();
+        let postgres = self.postgres.clone();
+        let date = Utc::today().naive_utc() - Duration::days(1);
+
+        Box::new(
+            Store::find_all_with_daily_summary(&self.postgres)
+                .from_err()
+                .and_then(move |stores| {
+                    let ids = stores.iter().map(|store| store.id).collect();
+
+                    Notification::find_notified(
+                        NotificationKind::DailySummary,
+                        ids,
+                        Some(date),
+                        &postgres,
+                    )
+                    .from_err()
+                    .map(move |notified| {
+                        stores
+                            .into_iter()
+                            .filter(|store| !notified.contains(&store.id))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .and_then(move |due| {
+                    send_each(due, move |store| sender.daily_summary(store, date))
+                }),
+        )
+    }
+
+    fn daily_summary(
+        &self,
+        store: Store,
+        date: NaiveDate,
+    ) -> Box<Future<Item = bool, Error = Error>> {
+        let sender = self.clone();
+        let from = DateTime::<Utc>::from_utc(date.and_hms(0, 0, 0), Utc);
+        let to = from + Duration::days(1);
+        let owner_email = self.owner_email(&store);
+
+        Box::new(
+            stats::store(store.id, from, to, Bucket::Day, &self.postgres)
+                .join(owner_email)
+                .and_then(move |(stats, to)| {
+                    let totals = &stats["totals"];
+                    let values = vec![
+                        ("store_name", store.name.clone()),
+                        ("date", date.to_string()),
+                        ("payments", totals["payments"].to_string()),
+                        ("settled", totals["settled"].to_string()),
+                        ("revenue", amounts(&totals["revenue"])),
+                        ("received", amounts(&totals["received"])),
+                    ];
+
+                    sender.send(
+                        store.id,
+                        NotificationKind::DailySummary,
+                        store.id,
+                        Some(date),
+                        to,
+                        values,
+                    )
+                }),
+        )
+    }
+
+    fn owner_email(&self, store: &Store) -> impl Future<Item = String, Error = Error> {
+        User::find_by_id(store.owner_id, &self.postgres)
+            .from_err()
+            .map(|user| user.email)
+    }
+
+    /// Sends the email unless it went out already, returns whether it was sent. A failed email is
+    /// forgotten so it's tried again next time.
+    fn send(
+        &self,
+        store_id: Uuid,
+        kind: NotificationKind,
+        subject_id: Uuid,
+        period: Option<NaiveDate>,
+        to: String,
+        values: Vec<(&'static str, String)>,
+    ) -> Box<Future<Item = bool, Error = Error>> {
+        let sender = self.clone();
+        let postgres = self.postgres.clone();
+
+        Box::new(
+            EmailTemplate::find_by_store_and_kind(store_id, kind, &self.postgres)
+                .from_err()
+                .and_then(move |email_template| {
+                    let template = email_template
+                        .map(Template::from)
+                        .unwrap_or_else(|| templates::default(kind));
+                    let email = templates::render(&template, &values);
+
+                    Notification::claim(
+                        NotificationPayload::new(store_id, kind, subject_id, period),
+                        &postgres,
+                    )
+                    .from_err()
+                    .and_then(move |notification| match notification {
+                        Some(notification) => {
+                            future::Either::A(sender.deliver(notification, to, email))
+                        }
+                        None => future::Either::B(future::ok(false)),
+                    })
+                }),
+        )
+    }
+
+    fn deliver(
+        &self,
+        notification: Notification,
+        to: String,
+        email: Email,
+    ) -> Box<Future<Item = bool, Error = Error>> {
+        let postgres = self.postgres.clone();
+
+        Box::new(
+            self.mailer
+                .send(SendMail {
+                    subject: email.subject,
+                    from: self.mail_sender.clone(),
+                    to,
+                    html: email.html,
+                    text: email.text,
+                })
+                .from_err()
+                .and_then(|res| res.map_err(|e| Error::from(e)))
+                .then(move |res| -> Box<Future<Item = bool, Error = Error>> {
+                    match res {
+                        Ok(_) => Box::new(future::ok(true)),
+                        Err(e) => Box::new(
+                            Notification::delete(notification.id, &postgres).then(move |_| Err(e)),
+                        ),
+                    }
+                }),
+        )
+    }
+}
+
+/// Sends one at a time, a failure is logged and doesn't hold up the rest. Returns how many were
+/// sent.
+fn send_each<T, F>(items: Vec<T>, send: F) -> Box<Future<Item = usize, Error = Error>>
+where
+    T: 'static,
+    F: Fn(T) -> Box<Future<Item = bool, Error = Error>> + 'static,
+{
+    Box::new(
+        stream::iter_ok(items)
+            .and_then(move |item| {
+                send(item).then(|res| match res {
+                    Ok(sent) => Ok(sent),
+                    Err(e) => {
+                        error!("Failed to send notification: {:?}", e);
+                        Ok(false)
+                    }
+                })
+            })
+            .fold(0, |total, sent| {
+                Ok::<_, Error>(if sent { total + 1 } else { total })
+            }),
+    )
+}
+
+fn payment_values(store: &Store, payment: &Payment) -> Vec<(&'static str, String)> {
+    vec![
+        ("store_name", store.name.clone()),
+        ("payment_id", payment.id.to_string()),
+        ("identifier", payment.identifier.clone().unwrap_or_default()),
+        ("price", payment.price.to_string()),
+        ("fiat", payment.fiat.to_str().to_uppercase()),
+        ("charge", payment.charge.to_string()),
+        (
+            "amount_paid",
+            payment
+                .amount_paid
+                .as_ref()
+                .map_or_else(String::new, |amount_paid| amount_paid.to_string()),
+        ),
+        ("crypto", payment.crypto.to_str().to_uppercase()),
+        (
+            "transaction_hash",
+            payment
+                .transaction_hash
+                .as_ref()
+                .map_or_else(String::new, |transaction_hash| transaction_hash.hex()),
+        ),
+        (
+            "paid_at",
+            payment
+                .paid_at
+                .map_or_else(String::new, |paid_at| paid_at.to_rfc2822()),
+        ),
+    ]
+}
+
+/// `{"usd": "12.50"}` as `12.50 USD`, several currencies separated by commas.
+fn amounts(amounts: &Value) -> String {
+    let amounts = amounts
+        .as_object()
+        .map(|amounts| {
+            amounts
+                .iter()
+                .map(|(currency, amount)| {
+                    format!(
+                        "{} {}",
+                        amount
+                            .as_str()
+                            .map_or_else(|| amount.to_string(), String::from),
+                        currency.to_uppercase()
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if amounts.is_empty() {
+        String::from("0")
+    } else {
+        amounts.join(", ")
+    }
+}
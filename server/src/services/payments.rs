@@ -2,15 +2,24 @@ use bigdecimal::BigDecimal;
 use futures::future::{self, Future, IntoFuture};
 use uuid::Uuid;
 
+use auth::Actor;
 use core::{
+    bitcoin::BlockchainStatus as BtcBlockchainStatus,
+    crypto::Keychain,
     db::postgres::PgExecutorAddr,
+    ethereum::BlockchainStatus as EthBlockchainStatus,
     payment::{Payment, PaymentPayload},
+    payout::Payout,
     store::Store,
 };
 use currency_api_client::{CurrencyApiClientAddr, GetRate};
-use hd_keyring::HdKeyring;
-use services::Error;
-use types::{bitcoin::Network as BtcNetwork, currency::Crypto, PaymentStatus};
+use hd_keyring::{bip44_path, bip84_path, HdKeyring, WatchOnlyKeyring};
+use services::{audit, Error};
+use types::{
+    bitcoin::{AddressFormat, Network as BtcNetwork},
+    currency::Crypto,
+    AuditAction, PaymentStatus, PayoutAction, U128,
+};
 
 const BTC_SCALE: i64 = 8;
 const ETH_SCALE: i64 = 6;
@@ -21,66 +30,189 @@ pub fn create(
     postgres: &PgExecutorAddr,
     min_charge: Option<BigDecimal>,
     currency_api_client: CurrencyApiClientAddr,
+    keychain: &Keychain,
 ) -> impl Future<Item = Payment, Error = Error> {
     let postgres = postgres.clone();
     let store = store.to_owned();
+    let keychain = keychain.clone();
 
-    let index: u32 = 1;
-
-    payload.index = Some(index as i32);
     payload.status = Some(PaymentStatus::Pending);
     payload.set_created_at();
 
-    let mut path = store.hd_path.clone();
-
-    let created_at = payload.created_at.unwrap();
-
-    path.push_str("/");
-    path.push_str(&created_at.timestamp().to_string());
-    path.push_str("/");
-    path.push_str(&created_at.timestamp_subsec_micros().to_string());
-
-    HdKeyring::from_mnemonic(
-        &path,
-        &store.mnemonic.clone(),
-        0,
-        payload.btc_network.unwrap_or(BtcNetwork::Test),
-    )
-    .into_future()
-    .from_err()
-    .and_then(move |keyring| keyring.get_wallet_by_index(index).into_future().from_err())
-    .and_then(move |wallet| {
-        currency_api_client
-            .send(GetRate {
-                from: payload.fiat.unwrap(),
-                to: payload.crypto.unwrap(),
-            })
-            .from_err()
-            .and_then(move |res| res.map_err(|e| Error::from(e)))
-            .and_then(move |rate| -> Box<Future<Item = Payment, Error = Error>> {
-                let charge = match payload.crypto.unwrap() {
-                    Crypto::Btc => payload.clone().price.unwrap() * rate.with_scale(BTC_SCALE),
-                    Crypto::Eth => payload.clone().price.unwrap() * rate.with_scale(ETH_SCALE),
-                };
-
-                if let Some(min_charge) = min_charge {
-                    if charge < min_charge {
-                        return Box::new(future::err(Error::ChargeAmountTooLow {
-                            min: min_charge,
-                            unit: payload.crypto.unwrap(),
-                        }));
-                    }
+    currency_api_client
+        .send(GetRate {
+            from: payload.fiat.unwrap(),
+            to: payload.crypto.unwrap(),
+        })
+        .from_err()
+        .and_then(move |res| res.map_err(|e| Error::from(e)))
+        .and_then(move |rate| {
+            let charge = match payload.crypto.unwrap() {
+                Crypto::Btc => payload.clone().price.unwrap() * rate.with_scale(BTC_SCALE),
+                Crypto::Eth => payload.clone().price.unwrap() * rate.with_scale(ETH_SCALE),
+            };
+
+            if let Some(min_charge) = min_charge {
+                if charge < min_charge {
+                    return Err(Error::ChargeAmountTooLow {
+                        min: min_charge,
+                        unit: payload.crypto.unwrap(),
+                    });
                 }
+            }
 
-                payload.charge = Some(charge);
+            payload.charge = Some(charge);
 
-                payload.address = Some(wallet.get_address(&payload.crypto.unwrap()));
+            Ok(payload)
+        })
+        .and_then(move |mut payload| {
+            derive_address(&payload, &store, &keychain, &postgres).and_then(
+                move |(index, path, address)| {
+                    payload.index = Some(index);
+                    payload.hd_path = Some(path);
+                    payload.address = Some(address);
 
-                Box::new(Payment::insert(payload, &postgres).from_err())
-            })
-    })
+                    Payment::insert(payload, &postgres).from_err()
+                },
+            )
+        })
+}
+
+/// Derives the payment's deposit address at the next unused index of the store. Custodial stores
+/// use the standard BIP44 path of the coin, or BIP84 for Bitcoin on segwit stores, watch-only
+/// stores the receive chain of their xpub. Returns the index, the derivation path and the address.
+fn derive_address(
+    payload: &PaymentPayload,
+    store: &Store,
+    keychain: &Keychain,
+    postgres: &PgExecutorAddr,
+) -> Box<Future<Item = (i32, String, String), Error = Error>> {
+    let crypto = payload.crypto.unwrap();
+    let btc_network = payload.btc_network.unwrap_or(BtcNetwork::Test);
+    let btc_address_format = store.btc_address_format;
+
+    match store.decrypt_mnemonic(keychain) {
+        Ok(Some(mnemonic)) => {
+            let passphrase = match store.decrypt_passphrase(keychain) {
+                Ok(passphrase) => passphrase,
+                Err(e) => return Box::new(future::err(Error::from(e))),
+            };
+
+            Box::new(
+                Store::next_address_index(store.id, crypto, postgres)
+                    .from_err()
+                    .and_then(move |index| -> Result<(i32, String, String), Error> {
+                        let path = match (crypto, btc_address_format) {
+                            (Crypto::Btc, AddressFormat::Segwit) => {
+                                bip84_path(btc_network, index as u32 - 1)
+                            }
+                            _ => bip44_path(&crypto, btc_network, index as u32 - 1),
+                        };
+                        let wallet =
+                            HdKeyring::from_mnemonic("m", &mnemonic, &passphrase, 0, btc_network)?
+                                .get_wallet_by_path(&path)?;
+
+                        Ok((index, path, wallet.get_address(&crypto)))
+                    }),
+            )
+        }
+        Ok(None) => {
+            let xpub = match store.xpub(&crypto) {
+                Some(xpub) => xpub.to_owned(),
+                None => return Box::new(future::err(Error::CurrencyNotSupported)),
+            };
+            let root = store.hd_path.clone();
+
+            Box::new(
+                Store::next_address_index(store.id, crypto, postgres)
+                    .from_err()
+                    .and_then(move |index| -> Result<(i32, String, String), Error> {
+                        let keyring = WatchOnlyKeyring::from_xpub(&xpub, &root)?;
+                        let path = format!("{}/{}", root, index - 1);
+
+                        Ok((
+                            index,
+                            path,
+                            keyring.get_address_by_index(index as u32, &crypto)?,
+                        ))
+                    }),
+            )
+        }
+        Err(e) => Box::new(future::err(Error::from(e))),
+    }
 }
 
 pub fn get(id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = Payment, Error = Error> {
     Payment::find_by_id(id, postgres).from_err()
 }
+
+pub fn find_by_store(
+    store_id: Uuid,
+    limit: i64,
+    offset: i64,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Vec<Payment>, Error = Error> {
+    Payment::find_by_store(store_id, limit, offset, postgres).from_err()
+}
+
+/// Sends an ETH payment back to where it came from instead of paying it out. BTC payouts can't be
+/// refunded, and neither can payouts that have already been made.
+pub fn refund(
+    store_id: Uuid,
+    payment_id: Uuid,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Payout, Error = Error> {
+    let postgres = postgres.clone();
+    let _postgres = postgres.clone();
+
+    Payment::find_by_id(payment_id, &postgres)
+        .from_err()
+        .and_then(move |payment| -> Result<Uuid, Error> {
+            if payment.store_id != store_id {
+                return Err(Error::InvalidRequestAccount);
+            }
+
+            if payment.crypto != Crypto::Eth {
+                return Err(Error::BadRequest("only ETH payments can be refunded"));
+            }
+
+            Ok(payment.id)
+        })
+        .and_then(move |payment_id| Payout::refund_pending(payment_id, &postgres).from_err())
+        .and_then(|payout| payout.ok_or(Error::BadRequest("the payment has no pending payout")))
+        .and_then(move |payout| {
+            let changes = audit::diff(
+                &json!({ "action": PayoutAction::Payout }),
+                &json!({ "action": payout.action }),
+            );
+
+            audit::record(
+                &actor,
+                AuditAction::PaymentRefunded,
+                Some(store_id),
+                Some(payment_id),
+                Some(changes),
+                &_postgres,
+            )
+            .map(move |_| payout)
+        })
+}
+
+pub fn get_block_height(
+    payment: &Payment,
+    postgres: &PgExecutorAddr,
+) -> Box<Future<Item = U128, Error = Error>> {
+    match payment.crypto {
+        Crypto::Btc => Box::new(
+            BtcBlockchainStatus::find(payment.btc_network.unwrap(), postgres)
+                .from_err()
+                .map(move |status| status.block_height),
+        ),
+        Crypto::Eth => Box::new(
+            EthBlockchainStatus::find(payment.eth_network.unwrap(), postgres)
+                .from_err()
+                .map(move |status| status.block_height),
+        ),
+    }
+}
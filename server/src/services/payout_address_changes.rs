@@ -0,0 +1,354 @@
+use chrono::{prelude::*, Duration};
+use futures::{future, stream, Future, Stream};
+use serde_json::Value;
+use uuid::Uuid;
+
+use auth::Actor;
+use config::ServerConfig;
+use core::{
+    db::postgres::PgExecutorAddr,
+    payout_address_change::{PayoutAddressChange, PayoutAddressChangePayload},
+    store::Store,
+    user::User,
+};
+use mailer::{MailerAddr, SendMail};
+use services::{audit, Error};
+use types::{bitcoin::Address as BtcAddress, AuditAction, H160};
+
+const DEFAULT_DELAY_HOURS: i64 = 24;
+
+/// How long a confirmed change waits before it takes effect.
+pub fn delay(config: &ServerConfig) -> Duration {
+    Duration::hours(
+        config
+            .payout_address_change_delay
+            .unwrap_or(DEFAULT_DELAY_HOURS),
+    )
+}
+
+fn describe_addresses(addresses: Vec<String>, splits: Option<&Vec<i32>>) -> String {
+    match splits {
+        Some(splits) => addresses
+            .iter()
+            .zip(splits)
+            .map(|(address, split)| format!("{} ({}%)", address, split))
+            .collect::<Vec<_>>()
+            .join(", "),
+        None => addresses.join(", "),
+    }
+}
+
+fn describe(change: &PayoutAddressChange) -> String {
+    let mut lines = Vec::new();
+
+    if let Some(ref addresses) = change.eth_payout_addresses {
+        lines.push(format!(
+            "ETH: {}",
+            describe_addresses(
+                addresses.iter().map(H160::hex).collect(),
+                change.eth_payout_splits.as_ref()
+            )
+        ));
+    }
+
+    if let Some(ref addresses) = change.btc_payout_addresses {
+        lines.push(format!(
+            "BTC: {}",
+            describe_addresses(
+                addresses.iter().map(BtcAddress::to_string).collect(),
+                change.btc_payout_splits.as_ref()
+            )
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// The store's payout addresses and splits before and after the change, for the audit log.
+fn audit_changes(store: &Store, change: &PayoutAddressChange) -> Value {
+    let before = store.export();
+    let mut after = before.clone();
+
+    if let Some(ref addresses) = change.eth_payout_addresses {
+        after["eth_payout_addresses"] = json!(addresses);
+        after["eth_payout_splits"] = json!(change.eth_payout_splits);
+    }
+
+    if let Some(ref addresses) = change.btc_payout_addresses {
+        after["btc_payout_addresses"] = json!(addresses);
+        after["btc_payout_splits"] = json!(change.btc_payout_splits);
+    }
+
+    audit::diff(&before, &after)
+}
+
+fn record(
+    actor: &Actor,
+    action: AuditAction,
+    store: &Store,
+    change: &PayoutAddressChange,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = (), Error = Error> {
+    audit::record(
+        actor,
+        action,
+        Some(store.id),
+        Some(change.id),
+        Some(audit_changes(store, change)),
+        postgres,
+    )
+}
+
+fn cancel_url(change: &PayoutAddressChange, web_client_url: &str) -> String {
+    format!(
+        "{}/payout_address_changes/cancel?token={}",
+        web_client_url, change.cancellation_token
+    )
+}
+
+fn notify_owner(
+    store: &Store,
+    subject: String,
+    text: String,
+    mailer: MailerAddr,
+    postgres: &PgExecutorAddr,
+    mail_sender: String,
+) -> impl Future<Item = (), Error = Error> {
+    User::find_by_id(store.owner_id, postgres)
+        .from_err()
+        .and_then(move |user| {
+            let html = text
+                .lines()
+                .map(|line| format!("<p>{}</p>", line))
+                .collect::<String>();
+
+            mailer
+                .send(SendMail {
+                    subject,
+                    from: mail_sender,
+                    to: user.email,
+                    html,
+                    text,
+                })
+                .from_err()
+                .and_then(|res| res.map_err(|e| Error::from(e)))
+                .map(|_| ())
+        })
+}
+
+/// Holds new payout addresses until the owner confirms them from the emailed link. Any change
+/// still pending for the store is cancelled.
+pub fn request(
+    store: Store,
+    payload: PayoutAddressChangePayload,
+    actor: Actor,
+    mailer: MailerAddr,
+    postgres: &PgExecutorAddr,
+    web_client_url: String,
+    mail_sender: String,
+) -> impl Future<Item = PayoutAddressChange, Error = Error> {
+    let postgres = postgres.clone();
+
+    PayoutAddressChange::insert(payload, &postgres)
+        .from_err()
+        .and_then(move |change| {
+            record(
+                &actor,
+                AuditAction::PayoutAddressChangeRequested,
+                &store,
+                &change,
+                &postgres,
+            )
+            .map(move |_| (store, change, postgres))
+        })
+        .and_then(move |(store, change, postgres)| {
+            let text = format!(
+                "The payout addresses of {} are about to change to:\n{}\n\
+                 Please click the following link to confirm the change: \
+                 {}/payout_address_changes/confirm?token={}\n\
+                 If you didn't ask for this, cancel it here and change your password: {}",
+                store.name,
+                describe(&change),
+                web_client_url,
+                change.confirmation_token,
+                cancel_url(&change, &web_client_url),
+            );
+
+            notify_owner(
+                &store,
+                String::from("Please confirm your new payout addresses."),
+                text,
+                mailer,
+                &postgres,
+                mail_sender,
+            )
+            .map(move |_| change)
+        })
+}
+
+/// Confirms the change and lets the owner know when it takes effect.
+pub fn confirm(
+    token: Uuid,
+    delay: Duration,
+    actor: Actor,
+    mailer: MailerAddr,
+    postgres: &PgExecutorAddr,
+    web_client_url: String,
+    mail_sender: String,
+) -> impl Future<Item = PayoutAddressChange, Error = Error> {
+    let postgres = postgres.clone();
+
+    PayoutAddressChange::confirm(token, delay, &postgres)
+        .from_err()
+        .and_then(move |change| {
+            Store::find_by_id(change.store_id, &postgres)
+                .from_err()
+                .and_then(move |store| {
+                    record(
+                        &actor,
+                        AuditAction::PayoutAddressChangeConfirmed,
+                        &store,
+                        &change,
+                        &postgres,
+                    )
+                    .map(move |_| (store, change, postgres))
+                })
+                .and_then(move |(store, change, postgres)| {
+                    let effective_at = change.effective_at.unwrap_or_else(Utc::now);
+
+                    let text = format!(
+                        "The payout addresses of {} will change on {} to:\n{}\n\
+                         If you didn't ask for this, cancel it here and change your password: {}",
+                        store.name,
+                        effective_at.to_rfc2822(),
+                        describe(&change),
+                        cancel_url(&change, &web_client_url),
+                    );
+
+                    notify_owner(
+                        &store,
+                        String::from("Your payout addresses are about to change."),
+                        text,
+                        mailer,
+                        &postgres,
+                        mail_sender,
+                    )
+                    .map(move |_| change)
+                })
+        })
+}
+
+pub fn cancel(
+    token: Uuid,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = PayoutAddressChange, Error = Error> {
+    let postgres = postgres.clone();
+
+    PayoutAddressChange::cancel(token, &postgres)
+        .from_err()
+        .and_then(move |change| {
+            Store::find_by_id(change.store_id, &postgres)
+                .from_err()
+                .and_then(move |store| {
+                    record(
+                        &actor,
+                        AuditAction::PayoutAddressChangeCancelled,
+                        &store,
+                        &change,
+                        &postgres,
+                    )
+                    .map(move |_| change)
+                })
+        })
+}
+
+/// Returns the store's change waiting for confirmation or for its cool-down, if there is one.
+pub fn pending(
+    store_id: Uuid,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Option<PayoutAddressChange>, Error = Error> {
+    PayoutAddressChange::find_pending_by_store(store_id, postgres).from_err()
+}
+
+/// Applies the confirmed changes whose cool-down is over and tells the owners. Returns how many
+/// were applied, a change that fails is logged and retried on the next run.
+pub fn apply_due(
+    mailer: MailerAddr,
+    postgres: &PgExecutorAddr,
+    mail_sender: String,
+) -> impl Future<Item = usize, Error = Error> {
+    let postgres = postgres.clone();
+
+    PayoutAddressChange::find_due(&postgres)
+        .from_err()
+        .map(|changes| stream::iter_ok(changes))
+        .flatten_stream()
+        .and_then(move |change| {
+            let change_id = change.id;
+
+            apply(change, mailer.clone(), &postgres, mail_sender.clone()).then(
+                move |res| -> Result<bool, Error> {
+                    if let Err(ref e) = res {
+                        error!(
+                            "Failed to apply payout address change {}: {:?}",
+                            change_id, e
+                        );
+                    }
+
+                    Ok(res.is_ok())
+                },
+            )
+        })
+        .fold(0, |applied, ok| {
+            future::ok::<_, Error>(applied + ok as usize)
+        })
+}
+
+fn apply(
+    change: PayoutAddressChange,
+    mailer: MailerAddr,
+    postgres: &PgExecutorAddr,
+    mail_sender: String,
+) -> impl Future<Item = (), Error = Error> {
+    let postgres = postgres.clone();
+
+    Store::find_by_id(change.store_id, &postgres)
+        .and_then(move |before| {
+            PayoutAddressChange::apply(change.id, &postgres)
+                .map(move |(change, store)| (before, change, store, postgres))
+        })
+        .from_err()
+        .and_then(|(before, change, store, postgres)| {
+            audit::record(
+                &Actor::system(),
+                AuditAction::PayoutAddressChangeApplied,
+                Some(store.id),
+                Some(change.id),
+                Some(audit::diff(&before.export(), &store.export())),
+                &postgres,
+            )
+            .map(move |_| (change, store, postgres))
+        })
+        .and_then(move |(change, store, postgres)| {
+            let text = format!(
+                "The payout addresses of {} have changed to:\n{}",
+                store.name,
+                describe(&change),
+            );
+
+            // The addresses are in place either way, a failed notice shouldn't undo that.
+            notify_owner(
+                &store,
+                String::from("Your payout addresses have changed."),
+                text,
+                mailer,
+                &postgres,
+                mail_sender,
+            )
+            .or_else(|e| -> Result<(), Error> {
+                error!("Failed to send payout address change notice: {:?}", e);
+                Ok(())
+            })
+        })
+}
@@ -0,0 +1,140 @@
+use chrono::{prelude::*, Duration};
+use futures::future::Future;
+use uuid::Uuid;
+
+use auth::{Actor, AuthUser, JWTPayload};
+use core::{
+    db::postgres::PgExecutorAddr,
+    session::{Session, SessionPayload},
+    user::User,
+};
+use services::Error;
+use types::PrivateKey;
+
+/// How long an access token is good for. Clients get the next one with the session's refresh
+/// token.
+const ACCESS_TOKEN_MINUTES: i64 = 15;
+
+/// What signing in or refreshing hands out: an access token and the session's next refresh
+/// token. Only a hash of the refresh token is kept, it can't be shown again.
+pub struct Tokens {
+    pub access_token: String,
+    pub refresh_token: Uuid,
+}
+
+fn issue_access_token(session: &Session, jwt_private: &PrivateKey) -> Result<String, Error> {
+    let expires_at = Utc::now() + Duration::minutes(ACCESS_TOKEN_MINUTES);
+
+    let mut payload = JWTPayload::new(
+        Some(AuthUser {
+            id: session.user_id,
+        }),
+        None,
+        expires_at,
+    );
+    payload.sid = Some(session.id);
+
+    payload.encode(jwt_private).map_err(|e| Error::from(e))
+}
+
+/// Signs the user in on a new session. Returns an access token and the refresh token that gets
+/// the next one once it expires, along with the session.
+pub fn start(
+    user_id: Uuid,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+    jwt_private: PrivateKey,
+) -> impl Future<Item = (Tokens, Session), Error = Error> {
+    let refresh_token = Uuid::new_v4();
+
+    let mut payload = SessionPayload::new(user_id, &refresh_token);
+    payload.ip = actor.ip;
+    payload.user_agent = actor.user_agent;
+
+    Session::insert(payload, postgres)
+        .from_err()
+        .and_then(move |session| {
+            issue_access_token(&session, &jwt_private).map(|access_token| {
+                let tokens = Tokens {
+                    access_token,
+                    refresh_token,
+                };
+
+                (tokens, session)
+            })
+        })
+}
+
+/// Trades a refresh token for a new access token. The session gets a new refresh token too, the
+/// one passed in can't be used again. Passing it in again revokes the session, since whoever does
+/// has a copy of it.
+pub fn refresh(
+    token: Uuid,
+    postgres: &PgExecutorAddr,
+    jwt_private: PrivateKey,
+) -> impl Future<Item = (Tokens, Session), Error = Error> {
+    let refresh_token = Uuid::new_v4();
+
+    Session::refresh(&token, &refresh_token, postgres)
+        .from_err()
+        .and_then(|session| session.ok_or(Error::InvalidSession))
+        .and_then(move |session| {
+            issue_access_token(&session, &jwt_private).map(|access_token| {
+                let tokens = Tokens {
+                    access_token,
+                    refresh_token,
+                };
+
+                (tokens, session)
+            })
+        })
+}
+
+/// Checks a user's access token against the session it was issued for. The session has to be
+/// active still and the password unchanged since `issued_at`.
+pub fn validate(
+    id: Uuid,
+    user_id: Uuid,
+    issued_at: i64,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = (), Error = Error> {
+    let postgres = postgres.clone();
+
+    Session::find_active(id, user_id, &postgres)
+        .from_err()
+        .and_then(|session| session.ok_or(Error::InvalidSession))
+        .and_then(move |_| User::find_by_id(user_id, &postgres).from_err())
+        .and_then(move |user| match user.password_changed_at {
+            Some(changed_at) if changed_at.timestamp() > issued_at => Err(Error::InvalidSession),
+            _ => Ok(()),
+        })
+}
+
+pub fn list(
+    user_id: Uuid,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Vec<Session>, Error = Error> {
+    Session::find_by_user(user_id, postgres).from_err()
+}
+
+pub fn revoke(
+    id: Uuid,
+    user_id: Uuid,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = usize, Error = Error> {
+    Session::revoke(id, user_id, postgres).from_err()
+}
+
+/// Signs the user out everywhere.
+pub fn revoke_all(
+    user_id: Uuid,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = usize, Error = Error> {
+    Session::revoke_by_user(user_id, postgres).from_err()
+}
+
+pub fn delete_used_refresh_tokens(
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = usize, Error = Error> {
+    Session::delete_used_refresh_tokens(postgres).from_err()
+}
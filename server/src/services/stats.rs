@@ -0,0 +1,179 @@
+//! Reporting for the management console's dashboard. Payments are summed up by the database,
+//! fiat totals use the rate each payment was created with rather than the current one.
+
+use std::collections::BTreeMap;
+
+use bigdecimal::BigDecimal;
+use chrono::prelude::*;
+use futures::Future;
+use serde_json::Value;
+use uuid::Uuid;
+
+use core::{
+    db::postgres::PgExecutorAddr,
+    payment::{Payment, PaymentStats},
+    payout::{Payout, PayoutStats},
+};
+use services::Error;
+use types::PaymentStatus;
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Bucket {
+    Day,
+    Week,
+    Month,
+}
+
+impl Bucket {
+    /// The matching `date_trunc` field. Weeks start on Monday.
+    pub fn to_str(&self) -> &str {
+        match *self {
+            Bucket::Day => "day",
+            Bucket::Week => "week",
+            Bucket::Month => "month",
+        }
+    }
+}
+
+/// Payments summed up over some period. Only confirmed and completed payments count as settled
+/// and towards what was received.
+#[derive(Debug, Default)]
+struct Summary {
+    payments: i64,
+    settled: i64,
+    paid: i64,
+    seconds_to_paid: f64,
+    received: BTreeMap<String, BigDecimal>,
+    revenue: BTreeMap<String, BigDecimal>,
+}
+
+impl Summary {
+    fn add(&mut self, row: &PaymentStats) {
+        self.payments += row.count;
+        self.paid += row.paid_count;
+        self.seconds_to_paid += row.seconds_to_paid;
+
+        if row.status == PaymentStatus::Confirmed || row.status == PaymentStatus::Completed {
+            self.settled += row.count;
+            add_amount(
+                &mut self.received,
+                row.crypto.to_str(),
+                row.amount_paid.clone(),
+            );
+            add_amount(&mut self.revenue, row.fiat.to_str(), row.fiat_paid.clone());
+        }
+    }
+
+    fn export(&self) -> Value {
+        let conversion_rate = if self.payments > 0 {
+            self.settled as f64 / self.payments as f64
+        } else {
+            0.0
+        };
+        let average_seconds_to_paid = if self.paid > 0 {
+            Some(self.seconds_to_paid / self.paid as f64)
+        } else {
+            None
+        };
+        let revenue: BTreeMap<&String, BigDecimal> = self
+            .revenue
+            .iter()
+            .map(|(fiat, amount)| (fiat, amount.with_scale(2)))
+            .collect();
+
+        json!({
+            "payments": self.payments,
+            "settled": self.settled,
+            "conversion_rate": conversion_rate,
+            "average_seconds_to_paid": average_seconds_to_paid,
+            "received": self.received,
+            "revenue": revenue,
+        })
+    }
+}
+
+fn add_amount(amounts: &mut BTreeMap<String, BigDecimal>, currency: &str, amount: BigDecimal) {
+    let total = match amounts.remove(currency) {
+        Some(total) => total + amount,
+        None => amount,
+    };
+
+    amounts.insert(currency.to_owned(), total);
+}
+
+/// The store's payments and payouts created in `from..to`. Buckets without any payments are left
+/// out.
+pub fn store(
+    store_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    bucket: Bucket,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Value, Error = Error> {
+    Payment::stats(store_id, bucket.to_str().to_owned(), from, to, postgres)
+        .join(Payout::stats(store_id, from, to, postgres))
+        .from_err()
+        .map(move |(payments, payouts)| report(from, to, bucket, &payments, &payouts))
+}
+
+fn report(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    bucket: Bucket,
+    payments: &[PaymentStats],
+    payouts: &[PayoutStats],
+) -> Value {
+    let mut totals = Summary::default();
+    let mut by_status: BTreeMap<String, i64> = BTreeMap::new();
+    let mut by_crypto: BTreeMap<String, Summary> = BTreeMap::new();
+    let mut buckets: BTreeMap<NaiveDateTime, Summary> = BTreeMap::new();
+
+    for row in payments {
+        totals.add(row);
+        *by_status.entry(row.status.to_string()).or_insert(0) += row.count;
+        by_crypto
+            .entry(row.crypto.to_str().to_owned())
+            .or_insert_with(Summary::default)
+            .add(row);
+        buckets
+            .entry(row.bucket)
+            .or_insert_with(Summary::default)
+            .add(row);
+    }
+
+    let by_crypto: BTreeMap<String, Value> = by_crypto
+        .into_iter()
+        .map(|(crypto, summary)| (crypto, summary.export()))
+        .collect();
+    let buckets: Vec<Value> = buckets
+        .into_iter()
+        .map(|(start, summary)| {
+            let mut exported = summary.export();
+            exported["start"] = json!(start.timestamp());
+            exported
+        })
+        .collect();
+    let payouts: Vec<Value> = payouts
+        .iter()
+        .map(|row| {
+            json!({
+                "status": row.status,
+                "action": row.action,
+                "crypto": row.typ,
+                "count": row.count,
+            })
+        })
+        .collect();
+
+    json!({
+        "from": from.timestamp(),
+        "to": to.timestamp(),
+        "bucket": bucket.to_str(),
+        "totals": totals.export(),
+        "by_status": by_status,
+        "by_crypto": by_crypto,
+        "buckets": buckets,
+        "payouts": payouts,
+    })
+}
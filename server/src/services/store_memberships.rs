@@ -0,0 +1,239 @@
+use futures::future::{err, ok, Future, IntoFuture};
+use serde_json::Value;
+use uuid::Uuid;
+
+use auth::{
+    permissions::{self, Permission},
+    Actor,
+};
+use core::{
+    db::postgres::PgExecutorAddr,
+    store::Store,
+    store_membership::{StoreMembership, StoreMembershipPayload},
+    user::User,
+};
+use mailer::{MailerAddr, SendMail};
+use services::{audit, Error};
+use types::{AuditAction, Role};
+
+/// Returns the user's role in the store if it allows `permission`. The owner needs no
+/// membership, anyone else needs an accepted invitation.
+pub fn authorize(
+    store: &Store,
+    user_id: Uuid,
+    permission: Permission,
+    postgres: &PgExecutorAddr,
+) -> Box<Future<Item = Role, Error = Error>> {
+    if store.owner_id == user_id {
+        return Box::new(ok(Role::Owner));
+    }
+
+    Box::new(
+        StoreMembership::find_by_store_and_user(store.id, user_id, postgres)
+            .from_err()
+            .and_then(move |membership| match membership {
+                Some(ref membership) if permissions::allows(membership.role, permission) => {
+                    Ok(membership.role)
+                }
+                _ => Err(Error::InvalidRequestAccount),
+            }),
+    )
+}
+
+fn validate_role(role: Role) -> Result<Role, Error> {
+    match role {
+        Role::Owner => Err(Error::BadRequest("a store has only one owner")),
+        role => Ok(role),
+    }
+}
+
+fn audit_export(membership: &StoreMembership) -> Value {
+    json!({
+        "email": membership.email,
+        "role": membership.role,
+        "user_id": membership.user_id,
+        "accepted": membership.accepted_at.is_some(),
+    })
+}
+
+/// Invites `email` to the store and sends them a link to accept with. Inviting someone whose
+/// invitation is still pending sends a fresh one.
+pub fn invite(
+    store: Store,
+    email: String,
+    role: Role,
+    actor: Actor,
+    mailer: MailerAddr,
+    postgres: &PgExecutorAddr,
+    web_client_url: String,
+    mail_sender: String,
+) -> impl Future<Item = StoreMembership, Error = Error> {
+    let postgres = postgres.clone();
+
+    validate_role(role)
+        .into_future()
+        .and_then(move |role| {
+            StoreMembership::find_by_store(store.id, &postgres)
+                .from_err()
+                .and_then(move |memberships| -> Result<_, Error> {
+                    let is_member = memberships.iter().any(|membership| {
+                        membership.email == email && membership.accepted_at.is_some()
+                    });
+
+                    if is_member {
+                        return Err(Error::BadRequest("already a member of the store"));
+                    }
+
+                    let payload = StoreMembershipPayload::new(store.id, email, role);
+
+                    Ok((store, payload, postgres))
+                })
+        })
+        .and_then(move |(store, payload, postgres)| {
+            StoreMembership::insert(payload, &postgres)
+                .from_err()
+                .and_then(move |membership| {
+                    audit::record(
+                        &actor,
+                        AuditAction::MemberInvited,
+                        Some(store.id),
+                        Some(membership.id),
+                        Some(audit::diff(&Value::Null, &audit_export(&membership))),
+                        &postgres,
+                    )
+                    .map(move |_| (store, membership))
+                })
+        })
+        .and_then(move |(store, membership)| {
+            let url = format!(
+                "{}/store_memberships/accept?token={}",
+                web_client_url, membership.invitation_token
+            );
+
+            let html = format!(
+                "You have been invited to join {} as {}. \
+                 Please click the following link to accept: <a href=\"{}\">{}</a>.",
+                store.name, membership.role, url, url
+            );
+
+            let text = format!(
+                "You have been invited to join {} as {}. \
+                 Please click the following link to accept: {}",
+                store.name, membership.role, url
+            );
+
+            mailer
+                .send(SendMail {
+                    subject: format!("You have been invited to join {}.", store.name),
+                    from: mail_sender,
+                    to: membership.email.clone(),
+                    html,
+                    text,
+                })
+                .from_err()
+                .and_then(|res| res.map_err(|e| Error::from(e)))
+                .map(move |_| membership)
+        })
+}
+
+/// Accepts an invitation for the signed in user. Only the user it was sent to can accept it.
+pub fn accept(
+    token: Uuid,
+    user_id: Uuid,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = StoreMembership, Error = Error> {
+    let postgres = postgres.clone();
+    let _postgres = postgres.clone();
+
+    User::find_by_id(user_id, &postgres)
+        .and_then(move |user| StoreMembership::accept(token, user_id, user.email, &postgres))
+        .from_err()
+        .and_then(move |membership| {
+            let after = audit_export(&membership);
+            let mut before = after.clone();
+            before["user_id"] = Value::Null;
+            before["accepted"] = json!(false);
+
+            audit::record(
+                &actor,
+                AuditAction::MemberJoined,
+                Some(membership.store_id),
+                Some(membership.id),
+                Some(audit::diff(&before, &after)),
+                &_postgres,
+            )
+            .map(move |_| membership)
+        })
+}
+
+pub fn list(
+    store_id: Uuid,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Vec<StoreMembership>, Error = Error> {
+    StoreMembership::find_by_store(store_id, postgres).from_err()
+}
+
+pub fn update_role(
+    id: Uuid,
+    store_id: Uuid,
+    role: Role,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> Box<Future<Item = StoreMembership, Error = Error>> {
+    if let Err(e) = validate_role(role) {
+        return Box::new(err(e));
+    }
+
+    let postgres = postgres.clone();
+
+    Box::new(
+        StoreMembership::find_by_id(id, store_id, &postgres)
+            .and_then(move |before| {
+                StoreMembership::update_role(id, store_id, role, &postgres)
+                    .map(move |membership| (before, membership, postgres))
+            })
+            .from_err()
+            .and_then(move |(before, membership, postgres)| {
+                audit::record(
+                    &actor,
+                    AuditAction::MemberRoleChanged,
+                    Some(store_id),
+                    Some(id),
+                    Some(audit::diff(
+                        &audit_export(&before),
+                        &audit_export(&membership),
+                    )),
+                    &postgres,
+                )
+                .map(move |_| membership)
+            }),
+    )
+}
+
+pub fn remove(
+    id: Uuid,
+    store_id: Uuid,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = usize, Error = Error> {
+    let postgres = postgres.clone();
+
+    StoreMembership::find_by_id(id, store_id, &postgres)
+        .and_then(move |membership| {
+            StoreMembership::delete(id, store_id, &postgres)
+                .map(move |deleted| (membership, deleted, postgres))
+        })
+        .from_err()
+        .and_then(move |(membership, deleted, postgres)| {
+            audit::record(
+                &actor,
+                AuditAction::MemberRemoved,
+                Some(store_id),
+                Some(id),
+                Some(audit::diff(&audit_export(&membership), &Value::Null)),
+                &postgres,
+            )
+            .map(move |_| deleted)
+        })
+}
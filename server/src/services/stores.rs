@@ -1,14 +1,27 @@
-use futures::future::{Future, IntoFuture};
+use base64::{encode_config, URL_SAFE_NO_PAD};
+use futures::future::{ok, Future, IntoFuture};
 use openssl::rsa::Rsa;
+use rustc_hex::ToHex;
+use serde_json::Value;
 use uuid::Uuid;
 
+use auth::{signed_checkout, Actor};
 use core::{
+    crypto::Keychain,
     db::postgres::PgExecutorAddr,
+    payment::Payment,
+    retired_store_key::RetiredStoreKey,
     store::{Store, StorePayload},
 };
-use hd_keyring::HdKeyring;
-use services::Error;
-use types::{bitcoin::Network as BtcNetwork, PrivateKey, PublicKey};
+use hd_keyring::{HdKeyring, Keystore, Language, WatchOnlyKeyring};
+use services::{audit, Error};
+use types::{
+    bitcoin::Network as BtcNetwork, currency::Crypto, AuditAction, PaymentStatus, PrivateKey,
+    PublicKey,
+};
+
+// Receive chain of the merchant's account level xpub.
+const WATCH_ONLY_PATH: &str = "m/0";
 
 fn generate_rsa() -> Result<(PrivateKey, PublicKey), Error> {
     let rsa = Rsa::generate(2048)?;
@@ -18,54 +31,481 @@ fn generate_rsa() -> Result<(PrivateKey, PublicKey), Error> {
     Ok((private_key, public_key))
 }
 
+/// `language` and `passphrase` are for the generated BIP39 mnemonic, watch-only stores ignore
+/// them.
 pub fn create(
     mut payload: StorePayload,
+    language: Language,
+    passphrase: Option<String>,
     btc_network: BtcNetwork,
+    actor: Actor,
+    keychain: &Keychain,
     postgres: &PgExecutorAddr,
 ) -> impl Future<Item = Store, Error = Error> {
     let postgres = postgres.clone();
+    let keychain = keychain.clone();
 
     let kay_pair = generate_rsa().into_future();
 
-    let keyring = HdKeyring::new("m/44'/60'/0'/0", 1, btc_network)
-        .into_future()
-        .from_err();
+    // Stores given an xpub are watch-only and never get a mnemonic.
+    let keyring = validate_xpubs(&mut payload, btc_network)
+        .and_then(|watch_only| {
+            if watch_only {
+                return Ok(None);
+            }
+
+            Ok(Some(HdKeyring::new(
+                "m",
+                0,
+                language,
+                passphrase
+                    .as_ref()
+                    .map_or("", |passphrase| passphrase.as_str()),
+                btc_network,
+            )?))
+        })
+        .into_future();
 
     kay_pair
         .join(keyring)
         .and_then(move |((private_key, public_key), keyring)| {
-            payload.mnemonic = Some(keyring.mnemonic.phrase());
-            payload.hd_path = Some(keyring.hd_path.to_string());
-            payload.private_key = Some(private_key);
+            let mnemonic = keyring.as_ref().map(|keyring| keyring.mnemonic.phrase());
+
+            payload.hd_path = Some(keyring.map_or(String::from(WATCH_ONLY_PATH), |keyring| {
+                keyring.hd_path.to_string()
+            }));
             payload.public_key = Some(public_key);
 
-            Store::insert(payload, &postgres).from_err()
+            payload
+                .set_secrets(
+                    mnemonic.as_ref().map(|mnemonic| mnemonic.as_str()),
+                    passphrase.as_ref().map(|passphrase| passphrase.as_str()),
+                    None,
+                    &private_key,
+                    &keychain,
+                )
+                .into_future()
+                .from_err()
+                .and_then(move |_| {
+                    Store::insert(payload, &postgres)
+                        .from_err()
+                        .and_then(move |store| {
+                            audit::record(
+                                &actor,
+                                AuditAction::StoreCreated,
+                                Some(store.id),
+                                Some(store.id),
+                                Some(audit::diff(&Value::Null, &store.export())),
+                                &postgres,
+                            )
+                            .map(move |_| store)
+                        })
+                })
+        })
+}
+
+/// Checks the xpubs given for a watch-only store, returns whether there were any. The Bitcoin
+/// address format of watch-only stores follows the xpub's, zpubs and vpubs are segwit.
+fn validate_xpubs(payload: &mut StorePayload, btc_network: BtcNetwork) -> Result<bool, Error> {
+    if let Some(ref xpub) = payload.btc_xpub {
+        let keyring = WatchOnlyKeyring::from_xpub(xpub, WATCH_ONLY_PATH)
+            .map_err(|_| Error::BadRequest("invalid btc_xpub"))?;
+
+        if keyring.btc_network() != btc_network {
+            return Err(Error::BadRequest("btc_xpub is for a different network"));
+        }
+
+        let format = keyring.xpub.address_format();
+        if payload.btc_address_format.map_or(false, |f| f != format) {
+            return Err(Error::BadRequest(
+                "btc_address_format doesn't match the btc_xpub",
+            ));
+        }
+        payload.btc_address_format = Some(format);
+    }
+
+    if let Some(ref xpub) = payload.eth_xpub {
+        WatchOnlyKeyring::from_xpub(xpub, WATCH_ONLY_PATH)
+            .map_err(|_| Error::BadRequest("invalid eth_xpub"))?;
+    }
+
+    Ok(payload.btc_xpub.is_some() || payload.eth_xpub.is_some())
+}
+
+/// Replaces the voucher signing key. The secrets are re-encrypted under a fresh data key as well.
+pub fn rotate_key(
+    store: Store,
+    actor: Actor,
+    keychain: &Keychain,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Store, Error = Error> {
+    let postgres = postgres.clone();
+    let keychain = keychain.clone();
+    let id = store.id;
+    let before = store.export();
+
+    generate_rsa()
+        .into_future()
+        .and_then(
+            move |(private_key, public_key)| -> Result<StorePayload, Error> {
+                let mut payload = StorePayload::new();
+                let mnemonic = store.decrypt_mnemonic(&keychain)?;
+                let passphrase = store.decrypt_passphrase(&keychain)?;
+                let checkout_secret = store.decrypt_checkout_secret(&keychain)?;
+                payload.set_secrets(
+                    mnemonic.as_ref().map(|mnemonic| mnemonic.as_str()),
+                    Some(&passphrase),
+                    checkout_secret.as_ref().map(|secret| secret.as_str()),
+                    &private_key,
+                    &keychain,
+                )?;
+                payload.public_key = Some(public_key);
+
+                Ok(payload)
+            },
+        )
+        .and_then(move |payload| {
+            Store::rotate_key(id, payload, &postgres)
+                .from_err()
+                .and_then(move |store| {
+                    audit::record(
+                        &actor,
+                        AuditAction::StoreKeyRotated,
+                        Some(store.id),
+                        Some(store.id),
+                        Some(audit::diff(&before, &store.export())),
+                        &postgres,
+                    )
+                    .map(move |_| store)
+                })
+        })
+}
+
+/// Sets a new checkout secret, from then on the store only takes signed checkouts. Returns the
+/// secret along with the store, it can't be read back later.
+pub fn rotate_checkout_secret(
+    store: Store,
+    actor: Actor,
+    keychain: &Keychain,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = (Store, String), Error = Error> {
+    let postgres = postgres.clone();
+    let id = store.id;
+    let before = store.export();
+
+    signed_checkout::generate_secret()
+        .and_then(|checkout_secret| -> Result<(StorePayload, String), Error> {
+            let mut payload = StorePayload::new();
+            let mnemonic = store.decrypt_mnemonic(keychain)?;
+            let passphrase = store.decrypt_passphrase(keychain)?;
+            let private_key = store.decrypt_private_key(keychain)?;
+            payload.set_secrets(
+                mnemonic.as_ref().map(|mnemonic| mnemonic.as_str()),
+                Some(&passphrase),
+                Some(&checkout_secret),
+                &private_key,
+                keychain,
+            )?;
+
+            Ok((payload, checkout_secret))
+        })
+        .into_future()
+        .and_then(move |(payload, checkout_secret)| {
+            Store::update(id, payload, &postgres)
+                .from_err()
+                .and_then(move |store| {
+                    audit::record(
+                        &actor,
+                        AuditAction::CheckoutSecretRotated,
+                        Some(store.id),
+                        Some(store.id),
+                        Some(audit::diff(&before, &store.export())),
+                        &postgres,
+                    )
+                    .map(move |_| (store, checkout_secret))
+                })
+        })
+}
+
+/// Goes back to taking unsigned checkouts.
+pub fn remove_checkout_secret(
+    store: Store,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Store, Error = Error> {
+    let postgres = postgres.clone();
+    let before = store.export();
+
+    let mut payload = StorePayload::new();
+    payload.checkout_secret = Some(None);
+
+    Store::update(store.id, payload, &postgres)
+        .from_err()
+        .and_then(move |store| {
+            audit::record(
+                &actor,
+                AuditAction::CheckoutSecretRemoved,
+                Some(store.id),
+                Some(store.id),
+                Some(audit::diff(&before, &store.export())),
+                &postgres,
+            )
+            .map(move |_| store)
+        })
+}
+
+/// Recovery material for a store: every deposit address with its derivation path, plus either
+/// the mnemonic itself (and its BIP39 passphrase, if any) or, given a passphrase to encrypt
+/// with, each key that may still hold funds as BIP38 (BTC) or a version 3 keystore (ETH).
+pub fn export_recovery(
+    store: Store,
+    passphrase: Option<String>,
+    btc_network: BtcNetwork,
+    actor: Actor,
+    keychain: &Keychain,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Value, Error = Error> {
+    let postgres = postgres.clone();
+    let _postgres = postgres.clone();
+    let id = store.id;
+    let encrypted = passphrase.is_some();
+
+    let bip39_passphrase = store
+        .decrypt_passphrase(keychain)
+        .map_err(|e| Error::from(e));
+
+    store
+        .decrypt_mnemonic(keychain)
+        .map_err(|e| Error::from(e))
+        .and_then(|mnemonic| mnemonic.ok_or(Error::BadRequest("watch-only stores hold no keys")))
+        .and_then(|mnemonic| bip39_passphrase.map(|bip39_passphrase| (mnemonic, bip39_passphrase)))
+        .into_future()
+        .and_then(move |(mnemonic, bip39_passphrase)| {
+            Payment::find_all_by_store(store.id, &postgres)
+                .from_err()
+                .and_then(move |payments| -> Result<Value, Error> {
+                    let mut export = json!({
+                        "store_id": store.id,
+                        "btc_address_index": store.btc_address_index,
+                        "eth_address_index": store.eth_address_index,
+                    });
+
+                    match passphrase {
+                        Some(passphrase) => {
+                            let keys = encrypt_keys(
+                                &mnemonic,
+                                &bip39_passphrase,
+                                &payments,
+                                &passphrase,
+                                btc_network,
+                            )?;
+
+                            export["addresses"] = json!(keys);
+                        }
+                        None => {
+                            let addresses: Vec<Value> = payments
+                                .iter()
+                                .map(|payment| {
+                                    json!({
+                                        "crypto": payment.crypto,
+                                        "hd_path": payment.hd_path,
+                                        "address": payment.address,
+                                    })
+                                })
+                                .collect();
+
+                            if !bip39_passphrase.is_empty() {
+                                export["bip39_passphrase"] = json!(bip39_passphrase);
+                            }
+
+                            export["mnemonic"] = json!(mnemonic);
+                            export["addresses"] = json!(addresses);
+                        }
+                    }
+
+                    Ok(export)
+                })
+        })
+        .and_then(move |export| {
+            audit::record(
+                &actor,
+                AuditAction::RecoveryExported,
+                Some(id),
+                Some(id),
+                Some(json!({ "encrypted": encrypted })),
+                &_postgres,
+            )
+            .map(move |_| export)
+        })
+}
+
+// Completed payments have already been paid out, so their keys are left out.
+fn encrypt_keys(
+    mnemonic: &str,
+    bip39_passphrase: &str,
+    payments: &[Payment],
+    passphrase: &str,
+    btc_network: BtcNetwork,
+) -> Result<Vec<Value>, Error> {
+    let keyring = HdKeyring::from_mnemonic("m", mnemonic, bip39_passphrase, 0, btc_network)?;
+
+    payments
+        .iter()
+        .filter(|payment| payment.status != PaymentStatus::Completed)
+        .map(|payment| -> Result<Value, Error> {
+            let wallet = keyring.get_wallet_by_path(&payment.hd_path)?;
+
+            let key = match payment.crypto {
+                Crypto::Btc => json!(wallet.to_bip38(passphrase)?),
+                Crypto::Eth => keystore(wallet.to_keystore(passphrase)?),
+            };
+
+            Ok(json!({
+                "crypto": payment.crypto,
+                "hd_path": payment.hd_path,
+                "address": payment.address,
+                "key": key,
+            }))
+        })
+        .collect()
+}
+
+fn keystore(keystore: Keystore) -> Value {
+    json!({
+        "version": 3,
+        "id": Uuid::new_v4(),
+        "address": keystore.address.to_string(),
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": keystore.iv.to_hex() },
+            "ciphertext": keystore.ciphertext.to_hex(),
+            "kdf": "scrypt",
+            "kdfparams": {
+                "dklen": keystore.dklen,
+                "n": keystore.n,
+                "r": keystore.r,
+                "p": keystore.p,
+                "salt": keystore.salt.to_hex(),
+            },
+            "mac": keystore.mac.to_hex(),
+        },
+    })
+}
+
+fn jwk(key_id: Uuid, public_key: &PublicKey) -> Result<Value, Error> {
+    let rsa = Rsa::public_key_from_pem_pkcs1(public_key)?;
+
+    Ok(json!({
+        "kty": "RSA",
+        "alg": "RS256",
+        "use": "sig",
+        "kid": key_id,
+        "n": encode_config(&rsa.n().to_vec(), URL_SAFE_NO_PAD),
+        "e": encode_config(&rsa.e().to_vec(), URL_SAFE_NO_PAD),
+    }))
+}
+
+/// JWKS document with the store's current voucher signing key followed by recently retired ones.
+pub fn jwks(id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = Value, Error = Error> {
+    let retired_keys = RetiredStoreKey::find_published_by_store(id, postgres).from_err();
+
+    Store::find_by_id(id, postgres)
+        .from_err()
+        .join(retired_keys)
+        .and_then(|(store, retired_keys)| -> Result<Value, Error> {
+            let mut keys = vec![jwk(store.key_id, &store.public_key)?];
+
+            for key in retired_keys {
+                keys.push(jwk(key.id, &key.public_key)?);
+            }
+
+            Ok(json!({ "keys": keys }))
         })
 }
 
+/// Looks up the public key a voucher was signed with by its `kid`, whether it's a store's current
+/// key or a retired one. Returns the store id along with the key.
+pub fn find_public_key(
+    key_id: Uuid,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = (Uuid, PublicKey), Error = Error> {
+    let postgres = postgres.clone();
+
+    Store::find_by_key_id(key_id, &postgres)
+        .map(|store| (store.id, store.public_key))
+        .or_else(move |_| {
+            RetiredStoreKey::find_by_id(key_id, &postgres).map(|key| (key.store_id, key.public_key))
+        })
+        .from_err()
+}
+
 pub fn patch(
-    id: Uuid,
+    store: Store,
     payload: StorePayload,
+    actor: Actor,
     postgres: &PgExecutorAddr,
 ) -> impl Future<Item = Store, Error = Error> {
     let postgres = postgres.clone();
 
-    Store::update(id, payload, &postgres).from_err()
+    Store::update(store.id, payload, &postgres)
+        .from_err()
+        .and_then(move |updated| -> Box<Future<Item = Store, Error = Error>> {
+            let changes = audit::diff(&store.export(), &updated.export());
+            let unchanged = changes
+                .as_object()
+                .map_or(true, |changes| changes.is_empty());
+
+            // Payout address changes are recorded on their own, a patch may change nothing else.
+            if unchanged {
+                return Box::new(ok(updated));
+            }
+
+            Box::new(
+                audit::record(
+                    &actor,
+                    AuditAction::StoreUpdated,
+                    Some(updated.id),
+                    Some(updated.id),
+                    Some(changes),
+                    &postgres,
+                )
+                .map(move |_| updated),
+            )
+        })
 }
 
-pub fn find_by_owner(
-    owner_id: Uuid,
+/// Stores the user owns or is a member of.
+pub fn find_by_member(
+    user_id: Uuid,
     limit: i64,
     offset: i64,
     postgres: &PgExecutorAddr,
 ) -> impl Future<Item = Vec<Store>, Error = Error> {
-    Store::find_by_owner(owner_id, limit, offset, postgres).from_err()
+    Store::find_by_member(user_id, limit, offset, postgres).from_err()
 }
 
 pub fn get(id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = Store, Error = Error> {
     Store::find_by_id(id, postgres).from_err()
 }
 
-pub fn delete(id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = usize, Error = Error> {
-    Store::soft_delete(id, postgres).from_err()
+pub fn delete(
+    store: Store,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = usize, Error = Error> {
+    let postgres = postgres.clone();
+
+    Store::soft_delete(store.id, &postgres)
+        .from_err()
+        .and_then(move |deleted| {
+            audit::record(
+                &actor,
+                AuditAction::StoreDeleted,
+                Some(store.id),
+                Some(store.id),
+                Some(audit::diff(&store.export(), &Value::Null)),
+                &postgres,
+            )
+            .map(move |_| deleted)
+        })
 }
@@ -1,17 +1,24 @@
-use chrono::{prelude::*, Duration};
+use chrono::prelude::*;
 use data_encoding::BASE64;
-use futures::future::{err, Future, IntoFuture};
+use futures::future::{err, ok, Future, IntoFuture};
 use ring::{digest, pbkdf2, rand, rand::SecureRandom};
 use uuid::Uuid;
 
-use auth::{AuthUser, JWTPayload};
+use auth::{two_factor, Actor};
 use core::{
+    crypto::Keychain,
     db::postgres::PgExecutorAddr,
+    session::Session,
     user::{User, UserPayload},
 };
 use mailer::{MailerAddr, SendMail};
-use services::Error;
-use types::PrivateKey;
+use rate_limit::RateLimiter;
+use services::{
+    audit,
+    sessions::{self, Tokens},
+    Error,
+};
+use types::{AuditAction, PrivateKey};
 
 const CREDENTIAL_LEN: usize = digest::SHA512_OUTPUT_LEN;
 const N_ITER: u32 = 100_000;
@@ -86,62 +93,304 @@ pub fn register(
         })
 }
 
+fn verify_password(user: &User, password: &str) -> Result<(), Error> {
+    let salt = BASE64.decode(&user.salt.as_bytes())?;
+    let password_hash = BASE64.decode(&user.password.as_bytes())?;
+
+    pbkdf2::verify(
+        &digest::SHA512,
+        N_ITER,
+        &salt,
+        password.as_bytes(),
+        &password_hash,
+    )
+    .map_err(|_| Error::IncorrectPassword)
+}
+
+/// Checks the second factor of users with two-factor authentication enabled and lets everyone
+/// else through. Accepted codes are used up: the TOTP time step is remembered and recovery codes
+/// are removed. Of two requests with the same code only the first one passes. Every code tried
+/// takes from the user's bucket, so codes can't be guessed with a stolen session either.
+fn verify_second_factor(
+    user: User,
+    code: Option<String>,
+    rate_limiter: &RateLimiter,
+    keychain: &Keychain,
+    postgres: &PgExecutorAddr,
+) -> Box<Future<Item = User, Error = Error>> {
+    if !user.two_factor_enabled() {
+        return Box::new(ok(user));
+    }
+
+    let code = match code {
+        Some(code) => code,
+        None => return Box::new(err(Error::TwoFactorRequired)),
+    };
+
+    let keychain = keychain.clone();
+    let postgres = postgres.clone();
+
+    Box::new(
+        rate_limiter
+            .take(
+                format!("two_factor:user:{}", user.id),
+                &rate_limiter.limits.two_factor,
+            )
+            .and_then(move |_| check_second_factor(user, code, &keychain, &postgres)),
+    )
+}
+
+fn check_second_factor(
+    user: User,
+    code: String,
+    keychain: &Keychain,
+    postgres: &PgExecutorAddr,
+) -> Box<Future<Item = User, Error = Error>> {
+    let secret = match user.decrypt_totp_secret(keychain) {
+        Ok(Some(secret)) => secret,
+        Ok(None) => return Box::new(err(Error::InternalServerError)),
+        Err(e) => return Box::new(err(Error::from(e))),
+    };
+
+    if let Some(step) = two_factor::verify_code(&secret, &code, user.totp_last_step) {
+        return Box::new(
+            User::use_totp_step(user.id, step, postgres)
+                .from_err()
+                .and_then(|user| user.ok_or(Error::InvalidTwoFactorCode)),
+        );
+    }
+
+    let recovery_codes = user.recovery_codes.clone().unwrap_or_default();
+
+    match two_factor::find_recovery_code(&code, &recovery_codes) {
+        Some(index) => {
+            let mut remaining = recovery_codes.clone();
+            remaining.remove(index);
+
+            Box::new(
+                User::use_recovery_code(user.id, recovery_codes, remaining, postgres)
+                    .from_err()
+                    .and_then(|user| user.ok_or(Error::InvalidTwoFactorCode)),
+            )
+        }
+        None => Box::new(err(Error::InvalidTwoFactorCode)),
+    }
+}
+
 pub fn authenticate(
     email: String,
     password: String,
+    code: Option<String>,
+    actor: Actor,
+    rate_limiter: &RateLimiter,
+    keychain: &Keychain,
     postgres: &PgExecutorAddr,
     jwt_private: PrivateKey,
-) -> impl Future<Item = (String, User), Error = Error> {
-    User::find_by_email(email, postgres)
+) -> impl Future<Item = (Tokens, Session, User), Error = Error> {
+    let rate_limiter = rate_limiter.clone();
+    let keychain = keychain.clone();
+    let postgres = postgres.clone();
+    let _postgres = postgres.clone();
+
+    User::find_by_email(email, &postgres)
         .from_err()
         .and_then(move |user| {
-            let salt = BASE64
-                .decode(&user.salt.as_bytes())
-                .map_err(|e| Error::from(e))
-                .into_future();
-
-            let password_hash = BASE64
-                .decode(&user.password.as_bytes())
-                .map_err(|e| Error::from(e))
-                .into_future();
-
-            salt.join(password_hash)
-                .and_then(move |(salt, password_hash)| {
-                    pbkdf2::verify(
-                        &digest::SHA512,
-                        N_ITER,
-                        &salt,
-                        password.as_bytes(),
-                        &password_hash,
+            verify_password(&user, &password)
+                .into_future()
+                .and_then(move |_| {
+                    verify_second_factor(user, code, &rate_limiter, &keychain, &postgres)
+                })
+                .and_then(move |user| {
+                    sessions::start(user.id, actor, &_postgres, jwt_private)
+                        .map(move |(token, session)| (token, session, user))
+                })
+        })
+}
+
+/// Asks an already signed in user for their password again before sensitive operations.
+pub fn reauthenticate(
+    id: Uuid,
+    password: String,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = User, Error = Error> {
+    User::find_by_id(id, postgres)
+        .from_err()
+        .and_then(move |user| verify_password(&user, &password).map(|_| user))
+}
+
+/// Asks an already signed in user for their second factor before sensitive operations. Users
+/// without two-factor authentication pass without a code.
+pub fn verify_two_factor(
+    id: Uuid,
+    code: Option<String>,
+    rate_limiter: &RateLimiter,
+    keychain: &Keychain,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = User, Error = Error> {
+    let rate_limiter = rate_limiter.clone();
+    let keychain = keychain.clone();
+    let postgres = postgres.clone();
+
+    User::find_by_id(id, &postgres)
+        .from_err()
+        .and_then(move |user| verify_second_factor(user, code, &rate_limiter, &keychain, &postgres))
+}
+
+/// Starts enrolling in two-factor authentication with a new TOTP secret. Returns the secret and
+/// its provisioning URL; nothing is enforced until a code is confirmed.
+pub fn enroll_two_factor(
+    id: Uuid,
+    password: String,
+    actor: Actor,
+    keychain: &Keychain,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = (String, String), Error = Error> {
+    let keychain = keychain.clone();
+    let postgres = postgres.clone();
+
+    reauthenticate(id, password, &postgres)
+        .and_then(move |user| -> Result<_, Error> {
+            if user.two_factor_enabled() {
+                return Err(Error::BadRequest(
+                    "two-factor authentication is already enabled",
+                ));
+            }
+
+            let secret = two_factor::generate_secret()?;
+
+            let mut payload = UserPayload::new();
+            payload.set_totp_secret(&secret, &keychain)?;
+
+            let url = two_factor::provisioning_url(&secret, &user.email);
+
+            Ok((user.id, payload, two_factor::encode_secret(&secret), url))
+        })
+        .and_then(move |(id, payload, secret, url)| {
+            User::update(id, payload, &postgres)
+                .from_err()
+                .and_then(move |_| {
+                    audit::record(
+                        &actor,
+                        AuditAction::TwoFactorEnrolled,
+                        None,
+                        Some(id),
+                        None,
+                        &postgres,
                     )
-                    .map_err(|_| Error::IncorrectPassword)
-                    .into_future()
                 })
+                .map(move |_| (secret, url))
+        })
+}
+
+/// Enables two-factor authentication once the user proves their app generates valid codes.
+/// Returns the recovery codes, which are only stored hashed and can't be shown again.
+pub fn confirm_two_factor(
+    id: Uuid,
+    code: String,
+    actor: Actor,
+    rate_limiter: &RateLimiter,
+    keychain: &Keychain,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = Vec<String>, Error = Error> {
+    let keychain = keychain.clone();
+    let postgres = postgres.clone();
+
+    rate_limiter
+        .take(
+            format!("two_factor:user:{}", id),
+            &rate_limiter.limits.two_factor,
+        )
+        .and_then({
+            let postgres = postgres.clone();
+            move |_| User::find_by_id(id, &postgres).from_err()
+        })
+        .and_then(move |user| -> Result<_, Error> {
+            if user.two_factor_enabled() {
+                return Err(Error::BadRequest(
+                    "two-factor authentication is already enabled",
+                ));
+            }
+
+            let secret = user
+                .decrypt_totp_secret(&keychain)?
+                .ok_or(Error::BadRequest("two-factor authentication isn't set up"))?;
+            let step =
+                two_factor::verify_code(&secret, &code, None).ok_or(Error::InvalidTwoFactorCode)?;
+            let (codes, hashes) = two_factor::generate_recovery_codes()?;
+
+            let mut payload = UserPayload::new();
+            payload.totp_enabled_at = Some(Some(Utc::now()));
+            payload.totp_last_step = Some(Some(step));
+            payload.recovery_codes = Some(Some(hashes));
+
+            Ok((user.id, payload, codes))
+        })
+        .and_then(move |(id, payload, codes)| {
+            User::update(id, payload, &postgres)
+                .from_err()
                 .and_then(move |_| {
-                    let expires_at = Utc::now() + Duration::days(1);
+                    audit::record(
+                        &actor,
+                        AuditAction::TwoFactorEnabled,
+                        None,
+                        Some(id),
+                        None,
+                        &postgres,
+                    )
+                })
+                .map(move |_| codes)
+        })
+}
 
-                    JWTPayload::new(Some(AuthUser { id: user.id }), None, expires_at)
-                        .encode(&jwt_private)
-                        .map_err(|e| Error::from(e))
-                        .and_then(|token| Ok((token, user)))
+pub fn disable_two_factor(
+    id: Uuid,
+    password: String,
+    code: Option<String>,
+    actor: Actor,
+    rate_limiter: &RateLimiter,
+    keychain: &Keychain,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = User, Error = Error> {
+    let rate_limiter = rate_limiter.clone();
+    let keychain = keychain.clone();
+    let postgres = postgres.clone();
+    let _postgres = postgres.clone();
+
+    reauthenticate(id, password, &postgres)
+        .and_then(move |user| verify_second_factor(user, code, &rate_limiter, &keychain, &postgres))
+        .and_then(move |user| {
+            let mut payload = UserPayload::new();
+            payload.clear_two_factor();
+
+            User::update(user.id, payload, &_postgres)
+                .from_err()
+                .and_then(move |user| {
+                    audit::record(
+                        &actor,
+                        AuditAction::TwoFactorDisabled,
+                        None,
+                        Some(user.id),
+                        None,
+                        &_postgres,
+                    )
+                    .map(move |_| user)
                 })
         })
 }
 
 pub fn activate(
     token: Uuid,
+    actor: Actor,
     postgres: &PgExecutorAddr,
     jwt_private: PrivateKey,
-) -> impl Future<Item = (String, User), Error = Error> {
-    User::activate(token, postgres)
+) -> impl Future<Item = (Tokens, Session, User), Error = Error> {
+    let postgres = postgres.clone();
+
+    User::activate(token, &postgres)
         .from_err()
         .and_then(move |user| {
-            let expires_at = Utc::now() + Duration::days(1);
-
-            JWTPayload::new(Some(AuthUser { id: user.id }), None, expires_at)
-                .encode(&jwt_private)
-                .map_err(|e| Error::from(e))
-                .and_then(|token| Ok((token, user)))
+            sessions::start(user.id, actor, &postgres, jwt_private)
+                .map(move |(token, session)| (token, session, user))
         })
 }
 
@@ -205,16 +454,29 @@ pub fn reset_password(
         })
 }
 
+/// Sets a new password for the holder of a reset token and signs them in. The reset link only
+/// proves access to the inbox, so users with two-factor authentication need their second factor
+/// as well.
 pub fn change_password(
     token: Uuid,
     password: String,
+    code: Option<String>,
+    actor: Actor,
+    rate_limiter: &RateLimiter,
+    keychain: &Keychain,
     postgres: &PgExecutorAddr,
     jwt_private: PrivateKey,
-) -> impl Future<Item = (String, User), Error = Error> {
+) -> impl Future<Item = (Tokens, Session, User), Error = Error> {
+    let rate_limiter = rate_limiter.clone();
+    let keychain = keychain.clone();
     let postgres = postgres.clone();
 
     User::find_by_reset_token(token, &postgres)
         .from_err()
+        .and_then({
+            let postgres = postgres.clone();
+            move |user| verify_second_factor(user, code, &rate_limiter, &keychain, &postgres)
+        })
         .and_then(move |user| {
             let mut payload = UserPayload::from(user.clone());
 
@@ -234,16 +496,36 @@ pub fn change_password(
 
             payload.password = Some(BASE64.encode(&pbkdf2_hash));
             payload.salt = Some(BASE64.encode(&salt));
+            // Access tokens issued before now stop working.
+            payload.password_changed_at = Some(Some(Utc::now()));
+            // The link works once, a replayed one mustn't start another session.
+            payload.reset_token = Some(None);
+            payload.reset_token_expires_at = Some(None);
 
             User::update(user.id, payload, &postgres)
                 .from_err()
                 .and_then(move |user| {
-                    let expires_at = Utc::now() + Duration::days(1);
-
-                    JWTPayload::new(Some(AuthUser { id: user.id }), None, expires_at)
-                        .encode(&jwt_private)
-                        .map_err(|e| Error::from(e))
-                        .and_then(|token| Ok((token, user)))
+                    // Whoever holds the reset token acts as the user.
+                    let actor = Actor {
+                        user_id: Some(user.id),
+                        ..actor
+                    };
+
+                    audit::record(
+                        &actor,
+                        AuditAction::PasswordReset,
+                        None,
+                        Some(user.id),
+                        None,
+                        &postgres,
+                    )
+                    .and_then(move |_| {
+                        sessions::revoke_all(user.id, &postgres).map(move |_| (user, postgres))
+                    })
+                    .and_then(move |(user, postgres)| {
+                        sessions::start(user.id, actor, &postgres, jwt_private)
+                            .map(move |(token, session)| (token, session, user))
+                    })
                 })
         })
 }
@@ -252,6 +534,24 @@ pub fn get(id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = User, Erro
     User::find_by_id(id, postgres).from_err()
 }
 
-pub fn delete(id: Uuid, postgres: &PgExecutorAddr) -> impl Future<Item = usize, Error = Error> {
-    User::delete(id, postgres).from_err()
+pub fn delete(
+    id: Uuid,
+    actor: Actor,
+    postgres: &PgExecutorAddr,
+) -> impl Future<Item = usize, Error = Error> {
+    let postgres = postgres.clone();
+
+    User::delete(id, &postgres)
+        .from_err()
+        .and_then(move |deleted| {
+            audit::record(
+                &actor,
+                AuditAction::UserDeleted,
+                None,
+                Some(id),
+                None,
+                &postgres,
+            )
+            .map(move |_| deleted)
+        })
 }
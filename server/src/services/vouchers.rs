@@ -1,40 +1,87 @@
 use chrono::{prelude::*, Duration};
+use diesel::result::Error as DieselError;
 use futures::future::{err, ok, Future, IntoFuture};
+use jwt;
+use openssl::rsa::Rsa;
+use uuid::Uuid;
 
-use core::{db::postgres::PgExecutorAddr, payment::Payment, voucher::Voucher};
-use services::Error;
-use types::PaymentStatus;
+use core::{
+    crypto::Keychain,
+    db::{postgres::PgExecutorAddr, Error as DbError},
+    payment::Payment,
+    voucher::Voucher,
+    ModelError,
+};
+use services::{self, Error};
 
 pub fn create(
     payment: Payment,
+    keychain: &Keychain,
     postgres: &PgExecutorAddr,
 ) -> impl Future<Item = String, Error = Error> {
     let postgres = postgres.clone();
+    let keychain = keychain.clone();
 
     payment
         .store(&postgres)
         .from_err()
         .and_then(move |store| {
-            let zero_confirmation_payment = payment.confirmations_required == 0;
-
-            match payment.status {
-                PaymentStatus::Pending => return err(Error::PaymentNotConfirmed),
-                PaymentStatus::Paid => {
-                    if zero_confirmation_payment {
-                        ok((payment, store))
-                    } else {
-                        return err(Error::PaymentNotConfirmed);
-                    }
-                }
-                PaymentStatus::Confirmed | PaymentStatus::Completed => ok((payment, store)),
-                _ => err(Error::PaymentNotConfirmed),
+            if !payment.is_settled() {
+                return err(Error::PaymentNotConfirmed);
             }
+
+            ok((payment, store))
         })
-        .and_then(move |(payment, store)| {
+        .and_then(move |(payment, store)| -> Result<String, Error> {
+            let private_key = store.decrypt_private_key(&keychain)?;
+
             // Voucher JWT expires in 1 minute.
-            Voucher::from_payment(payment, Utc::now() + Duration::minutes(1))
-                .encode(&store.private_key)
-                .into_future()
-                .from_err()
+            Ok(
+                Voucher::from_payment(payment, Utc::now() + Duration::minutes(1))
+                    .encode(&private_key, store.key_id)?,
+            )
         })
 }
+
+/// Checks a voucher against the key named in its `kid` header. Vouchers that are expired, were
+/// tampered with or name an unknown key come back as `None`.
+pub fn verify(
+    token: String,
+    postgres: &PgExecutorAddr,
+) -> Box<Future<Item = Option<Voucher>, Error = Error>> {
+    let key_id = match jwt::decode_header(&token)
+        .ok()
+        .and_then(|header| header.kid)
+        .and_then(|kid| Uuid::parse_str(&kid).ok())
+    {
+        Some(key_id) => key_id,
+        None => return Box::new(ok(None)),
+    };
+
+    Box::new(
+        services::stores::find_public_key(key_id, postgres).then(move |res| {
+            let (store_id, public_key) = match res {
+                Ok(key) => key,
+                Err(Error::ModelError(ModelError::DbError(DbError::DieselError(
+                    DieselError::NotFound,
+                )))) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+
+            let public_key =
+                Rsa::public_key_from_pem_pkcs1(&public_key)?.public_key_to_der_pkcs1()?;
+            let validation = jwt::Validation::new(jwt::Algorithm::RS256);
+
+            match jwt::decode::<Voucher>(&token, &public_key, &validation) {
+                Ok(data) => {
+                    if data.claims.store_id != store_id {
+                        return Ok(None);
+                    }
+
+                    Ok(Some(data.claims))
+                }
+                Err(_) => Ok(None),
+            }
+        }),
+    )
+}
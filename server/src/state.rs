@@ -1,7 +1,9 @@
 use config::{BtcConfig, EthConfig, ServerConfig};
-use core::db::postgres::PgExecutorAddr;
+use core::{crypto::Keychain, db::postgres::PgExecutorAddr};
 use currency_api_client::CurrencyApiClientAddr;
+use hub::HubAddr;
 use mailer::MailerAddr;
+use rate_limit::RateLimiter;
 use types::{currency::Crypto, PrivateKey, PublicKey};
 
 #[derive(Clone)]
@@ -14,6 +16,9 @@ pub struct AppState {
     pub btc_config: Option<BtcConfig>,
     pub eth_config: Option<EthConfig>,
     pub currency_api_client: CurrencyApiClientAddr,
+    pub hub: HubAddr,
+    pub keychain: Keychain,
+    pub rate_limiter: RateLimiter,
 }
 
 impl AppState {
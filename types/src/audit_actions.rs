@@ -0,0 +1,122 @@
+use std::{fmt, io::Write};
+
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Text,
+    types::VarChar,
+};
+
+/// What an audit log entry records.
+#[derive(FromSqlRow, AsExpression, Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "VarChar"]
+pub enum AuditAction {
+    StoreCreated,
+    StoreUpdated,
+    StoreDeleted,
+    StoreKeyRotated,
+    CheckoutSecretRotated,
+    CheckoutSecretRemoved,
+    EmailTemplateUpdated,
+    EmailTemplateRemoved,
+    RecoveryExported,
+    PayoutAddressChangeRequested,
+    PayoutAddressChangeConfirmed,
+    PayoutAddressChangeCancelled,
+    PayoutAddressChangeApplied,
+    ClientTokenCreated,
+    ClientTokenDeleted,
+    MemberInvited,
+    MemberJoined,
+    MemberRoleChanged,
+    MemberRemoved,
+    PaymentRefunded,
+    PasswordReset,
+    TwoFactorEnrolled,
+    TwoFactorEnabled,
+    TwoFactorDisabled,
+    UserDeleted,
+}
+
+impl AuditAction {
+    pub fn to_str(&self) -> &str {
+        match *self {
+            AuditAction::StoreCreated => "store_created",
+            AuditAction::StoreUpdated => "store_updated",
+            AuditAction::StoreDeleted => "store_deleted",
+            AuditAction::StoreKeyRotated => "store_key_rotated",
+            AuditAction::CheckoutSecretRotated => "checkout_secret_rotated",
+            AuditAction::CheckoutSecretRemoved => "checkout_secret_removed",
+            AuditAction::EmailTemplateUpdated => "email_template_updated",
+            AuditAction::EmailTemplateRemoved => "email_template_removed",
+            AuditAction::RecoveryExported => "recovery_exported",
+            AuditAction::PayoutAddressChangeRequested => "payout_address_change_requested",
+            AuditAction::PayoutAddressChangeConfirmed => "payout_address_change_confirmed",
+            AuditAction::PayoutAddressChangeCancelled => "payout_address_change_cancelled",
+            AuditAction::PayoutAddressChangeApplied => "payout_address_change_applied",
+            AuditAction::ClientTokenCreated => "client_token_created",
+            AuditAction::ClientTokenDeleted => "client_token_deleted",
+            AuditAction::MemberInvited => "member_invited",
+            AuditAction::MemberJoined => "member_joined",
+            AuditAction::MemberRoleChanged => "member_role_changed",
+            AuditAction::MemberRemoved => "member_removed",
+            AuditAction::PaymentRefunded => "payment_refunded",
+            AuditAction::PasswordReset => "password_reset",
+            AuditAction::TwoFactorEnrolled => "two_factor_enrolled",
+            AuditAction::TwoFactorEnabled => "two_factor_enabled",
+            AuditAction::TwoFactorDisabled => "two_factor_disabled",
+            AuditAction::UserDeleted => "user_deleted",
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl ToSql<Text, Pg> for AuditAction {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let text = self.to_str();
+
+        ToSql::<Text, Pg>::to_sql(&text, out)
+    }
+}
+
+impl FromSql<Text, Pg> for AuditAction {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let text: String = FromSql::<Text, Pg>::from_sql(bytes)?;
+
+        match text.as_ref() {
+            "store_created" => Ok(AuditAction::StoreCreated),
+            "store_updated" => Ok(AuditAction::StoreUpdated),
+            "store_deleted" => Ok(AuditAction::StoreDeleted),
+            "store_key_rotated" => Ok(AuditAction::StoreKeyRotated),
+            "checkout_secret_rotated" => Ok(AuditAction::CheckoutSecretRotated),
+            "checkout_secret_removed" => Ok(AuditAction::CheckoutSecretRemoved),
+            "email_template_updated" => Ok(AuditAction::EmailTemplateUpdated),
+            "email_template_removed" => Ok(AuditAction::EmailTemplateRemoved),
+            "recovery_exported" => Ok(AuditAction::RecoveryExported),
+            "payout_address_change_requested" => Ok(AuditAction::PayoutAddressChangeRequested),
+            "payout_address_change_confirmed" => Ok(AuditAction::PayoutAddressChangeConfirmed),
+            "payout_address_change_cancelled" => Ok(AuditAction::PayoutAddressChangeCancelled),
+            "payout_address_change_applied" => Ok(AuditAction::PayoutAddressChangeApplied),
+            "client_token_created" => Ok(AuditAction::ClientTokenCreated),
+            "client_token_deleted" => Ok(AuditAction::ClientTokenDeleted),
+            "member_invited" => Ok(AuditAction::MemberInvited),
+            "member_joined" => Ok(AuditAction::MemberJoined),
+            "member_role_changed" => Ok(AuditAction::MemberRoleChanged),
+            "member_removed" => Ok(AuditAction::MemberRemoved),
+            "payment_refunded" => Ok(AuditAction::PaymentRefunded),
+            "password_reset" => Ok(AuditAction::PasswordReset),
+            "two_factor_enrolled" => Ok(AuditAction::TwoFactorEnrolled),
+            "two_factor_enabled" => Ok(AuditAction::TwoFactorEnabled),
+            "two_factor_disabled" => Ok(AuditAction::TwoFactorDisabled),
+            "user_deleted" => Ok(AuditAction::UserDeleted),
+            v => Err(format!("unknown value {} for AuditAction found", v).into()),
+        }
+    }
+}
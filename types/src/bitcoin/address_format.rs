@@ -0,0 +1,71 @@
+use std::{fmt, io::Write, str::FromStr};
+
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    types::VarChar,
+};
+
+/// The kind of deposit addresses a store hands out. Legacy addresses are P2PKH under the BIP44
+/// purpose, segwit ones native P2WPKH (bech32) under BIP84.
+#[derive(FromSqlRow, AsExpression, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+#[sql_type = "VarChar"]
+pub enum AddressFormat {
+    Legacy,
+    Segwit,
+}
+
+impl AddressFormat {
+    pub fn to_str(&self) -> &str {
+        match *self {
+            AddressFormat::Legacy => "legacy",
+            AddressFormat::Segwit => "segwit",
+        }
+    }
+
+    /// The format of the address at `path`, going by its purpose. Anything but BIP84 is legacy,
+    /// including the paths of payments made before standard paths were used.
+    pub fn from_path(path: &str) -> Self {
+        if path.starts_with("m/84'/") {
+            AddressFormat::Segwit
+        } else {
+            AddressFormat::Legacy
+        }
+    }
+}
+
+impl fmt::Display for AddressFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl ToSql<VarChar, Pg> for AddressFormat {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let text = self.to_str();
+
+        ToSql::<VarChar, Pg>::to_sql(&text, out)
+    }
+}
+
+impl FromSql<VarChar, Pg> for AddressFormat {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let s: String = FromSql::<VarChar, Pg>::from_sql(bytes)?;
+
+        AddressFormat::from_str(&s).map_err(|e| e.into())
+    }
+}
+
+impl FromStr for AddressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AddressFormat, Self::Err> {
+        match s {
+            "legacy" => Ok(AddressFormat::Legacy),
+            "segwit" => Ok(AddressFormat::Segwit),
+            _ => Err(String::from("invalid value for bitcoin address format")),
+        }
+    }
+}
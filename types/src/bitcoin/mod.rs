@@ -1,7 +1,9 @@
 pub mod address;
+pub mod address_format;
 pub mod network;
 pub mod var_int;
 
 pub use self::address::Address;
+pub use self::address_format::AddressFormat;
 pub use self::network::Network;
 pub use self::var_int::VarInt;
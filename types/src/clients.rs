@@ -13,12 +13,15 @@ use diesel::{
 #[sql_type = "VarChar"]
 pub enum Client {
     Web,
+    /// Mobile and desktop apps, which send no `Origin` header.
+    Native,
 }
 
 impl Client {
     pub fn to_str(&self) -> &str {
         match *self {
             Client::Web => "web",
+            Client::Native => "native",
         }
     }
 }
@@ -43,6 +46,7 @@ impl FromSql<VarChar, Pg> for Client {
 
         match text.as_ref() {
             "web" => Ok(Client::Web),
+            "native" => Ok(Client::Native),
             v => Err(format!("unknown value {} for client found", v).into()),
         }
     }
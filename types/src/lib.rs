@@ -24,26 +24,32 @@ extern crate sha2;
 #[macro_use]
 extern crate uint;
 
+mod audit_actions;
 pub mod bitcoin;
 mod clients;
 pub mod currency;
 pub mod ethereum;
 mod h160;
 mod h256;
+mod notification_kinds;
 mod payment_status;
 mod payout_actions;
 mod payout_status;
+mod roles;
 mod u128;
 mod u256;
 
 pub type PrivateKey = Vec<u8>;
 pub type PublicKey = Vec<u8>;
 
+pub use self::audit_actions::AuditAction;
 pub use self::clients::Client;
 pub use self::h160::H160;
 pub use self::h256::H256;
+pub use self::notification_kinds::NotificationKind;
 pub use self::payment_status::PaymentStatus;
 pub use self::payout_actions::PayoutAction;
 pub use self::payout_status::PayoutStatus;
+pub use self::roles::Role;
 pub use self::u128::U128;
 pub use self::u256::U256;
@@ -0,0 +1,74 @@
+use std::fmt;
+use std::io::Write;
+
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    types::VarChar,
+};
+
+/// The emails a store sends. Receipts go to the customer, the others to the store's owner.
+#[derive(
+    FromSqlRow, AsExpression, Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Hash,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "VarChar"]
+pub enum NotificationKind {
+    Receipt,
+    LargePayment,
+    InsufficientAmount,
+    PayoutFailed,
+    DailySummary,
+}
+
+impl NotificationKind {
+    pub fn all() -> [NotificationKind; 5] {
+        [
+            NotificationKind::Receipt,
+            NotificationKind::LargePayment,
+            NotificationKind::InsufficientAmount,
+            NotificationKind::PayoutFailed,
+            NotificationKind::DailySummary,
+        ]
+    }
+
+    pub fn to_str(&self) -> &str {
+        match *self {
+            NotificationKind::Receipt => "receipt",
+            NotificationKind::LargePayment => "large_payment",
+            NotificationKind::InsufficientAmount => "insufficient_amount",
+            NotificationKind::PayoutFailed => "payout_failed",
+            NotificationKind::DailySummary => "daily_summary",
+        }
+    }
+}
+
+impl fmt::Display for NotificationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl ToSql<VarChar, Pg> for NotificationKind {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let text = self.to_str();
+
+        ToSql::<VarChar, Pg>::to_sql(&text, out)
+    }
+}
+
+impl FromSql<VarChar, Pg> for NotificationKind {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let text: String = FromSql::<VarChar, Pg>::from_sql(bytes)?;
+
+        match text.as_ref() {
+            "receipt" => Ok(NotificationKind::Receipt),
+            "large_payment" => Ok(NotificationKind::LargePayment),
+            "insufficient_amount" => Ok(NotificationKind::InsufficientAmount),
+            "payout_failed" => Ok(NotificationKind::PayoutFailed),
+            "daily_summary" => Ok(NotificationKind::DailySummary),
+            v => Err(format!("unknown value {} for notification kind found", v).into()),
+        }
+    }
+}
@@ -16,6 +16,7 @@ pub enum PayoutStatus {
     PaidOut,
     Refunded,
     InsufficientFunds,
+    Skipped,
 }
 
 impl fmt::Display for PayoutStatus {
@@ -28,6 +29,7 @@ impl fmt::Display for PayoutStatus {
                 PayoutStatus::PaidOut => "paid_out",
                 PayoutStatus::Refunded => "refunded",
                 PayoutStatus::InsufficientFunds => "insufficient_funds",
+                PayoutStatus::Skipped => "skipped",
             }
         )
     }
@@ -40,6 +42,7 @@ impl ToSql<Text, Pg> for PayoutStatus {
             PayoutStatus::PaidOut => "paid_out",
             PayoutStatus::Refunded => "refunded",
             PayoutStatus::InsufficientFunds => "insufficient_funds",
+            PayoutStatus::Skipped => "skipped",
         };
 
         ToSql::<Text, Pg>::to_sql(&text, out)
@@ -56,6 +59,7 @@ impl FromSql<Text, Pg> for PayoutStatus {
             "paid_out" => Ok(PayoutStatus::PaidOut),
             "refunded" => Ok(PayoutStatus::Refunded),
             "insufficient_funds" => Ok(PayoutStatus::InsufficientFunds),
+            "skipped" => Ok(PayoutStatus::Skipped),
             v => Err(format!("unknown value {} for Currency found", v).into()),
         }
     }
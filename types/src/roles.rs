@@ -0,0 +1,63 @@
+use std::fmt;
+use std::io::Write;
+
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    types::VarChar,
+};
+
+/// What a user may do in a store they're a member of. The owner of a store holds `Owner`
+/// without a membership.
+#[derive(FromSqlRow, AsExpression, Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "VarChar"]
+pub enum Role {
+    Owner,
+    Admin,
+    Developer,
+    Finance,
+    ReadOnly,
+}
+
+impl Role {
+    pub fn to_str(&self) -> &str {
+        match *self {
+            Role::Owner => "owner",
+            Role::Admin => "admin",
+            Role::Developer => "developer",
+            Role::Finance => "finance",
+            Role::ReadOnly => "read_only",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+impl ToSql<VarChar, Pg> for Role {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let text = self.to_str();
+
+        ToSql::<VarChar, Pg>::to_sql(&text, out)
+    }
+}
+
+impl FromSql<VarChar, Pg> for Role {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let text: String = FromSql::<VarChar, Pg>::from_sql(bytes)?;
+
+        match text.as_ref() {
+            "owner" => Ok(Role::Owner),
+            "admin" => Ok(Role::Admin),
+            "developer" => Ok(Role::Developer),
+            "finance" => Ok(Role::Finance),
+            "read_only" => Ok(Role::ReadOnly),
+            v => Err(format!("unknown value {} for role found", v).into()),
+        }
+    }
+}